@@ -73,6 +73,9 @@ pub enum Command {
     /// Prints the metadata attached to an attribute, given as a path
     Query(QueryCommand),
     /// Typechecks the program but do not run it
+    ///
+    /// No value is ever forced, so this returns promptly even if the evaluation of the program
+    /// would not terminate.
     Typecheck(TypecheckCommand),
     /// Starts a REPL session
     #[cfg(feature = "repl")]
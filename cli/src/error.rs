@@ -7,6 +7,7 @@ use nickel_lang_core::{
     },
     eval::cache::lazy::CBNCache,
     program::{FieldOverride, FieldPath, Program},
+    serialize::ExportFormat,
 };
 
 /// Data about an unknown field error.
@@ -29,6 +30,11 @@ pub enum CliUsageError {
     AssignmentParseError { error: ParseError },
     /// A parse error occurred when trying to parse a field path.
     FieldPathParseError { error: ParseError },
+    /// Tried to use an export option which isn't supported by the chosen export format.
+    UnsupportedExportOption {
+        option: &'static str,
+        format: ExportFormat,
+    },
 }
 
 pub enum Error {
@@ -151,6 +157,13 @@ impl IntoDiagnostics<FileId> for CliUsageError {
                 );
                 diags
             }
+            CliUsageError::UnsupportedExportOption { option, format } => {
+                vec![Diagnostic::error()
+                    .with_message(format!("`{option}` isn't supported for {format} export"))
+                    .with_notes(vec![format!(
+                        "`{option}` is only supported for JSON export."
+                    )])]
+            }
         }
     }
 }
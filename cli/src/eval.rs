@@ -2,7 +2,7 @@ use crate::{
     cli::GlobalOptions,
     customize::CustomizeMode,
     error::{CliResult, ResultErrorExt},
-    input::{InputOptions, Prepare},
+    input::{report_profile, report_stats, InputOptions, Prepare},
 };
 
 #[derive(clap::Parser, Debug)]
@@ -15,9 +15,9 @@ impl EvalCommand {
     pub fn run(self, global: GlobalOptions) -> CliResult<()> {
         let mut program = self.input.prepare(&global)?;
 
-        program
-            .eval_full()
-            .map(|t| println!("{t}"))
-            .report_with_program(program)
+        let result = program.eval_full().map(|t| println!("{t}"));
+        report_stats(&program);
+        report_profile(&program);
+        result.report_with_program(program)
     }
 }
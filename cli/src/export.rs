@@ -3,15 +3,18 @@ use std::{fs, io::Write, path::PathBuf};
 use nickel_lang_core::{
     error::{Error, IOError},
     eval::cache::lazy::CBNCache,
+    identifier::LocIdent,
+    json_schema::SCHEMA_DIALECT,
     program::Program,
-    serialize::{self, ExportFormat},
+    serialize::{self, ExportFormat, Indentation},
+    term::{record::Field, RichTerm, Term},
 };
 
 use crate::{
     cli::GlobalOptions,
     customize::CustomizeMode,
-    error::{CliResult, ResultErrorExt},
-    input::{InputOptions, Prepare},
+    error::{CliResult, CliUsageError, Error as CliError, ResultErrorExt},
+    input::{report_profile, report_stats, InputOptions, Prepare},
 };
 
 #[derive(clap::Parser, Debug)]
@@ -19,10 +22,34 @@ pub struct ExportCommand {
     #[arg(long, short, value_enum, default_value_t)]
     pub format: ExportFormat,
 
+    /// Number of spaces used to indent nested values. Only supported for JSON export
+    #[arg(long, value_name = "N", conflicts_with = "compact")]
+    pub indent: Option<usize>,
+
+    /// Output minified JSON, without any whitespace. Only supported for JSON export
+    #[arg(long)]
+    pub compact: bool,
+
     /// Output file. Standard output by default
     #[arg(short, long)]
     pub output: Option<PathBuf>,
 
+    /// Adds a `$schema` field referring to the given URL at the root of the output, so that
+    /// editors can validate and complete the exported configuration. Only supported for JSON
+    /// export, and only when the configuration is a record
+    #[arg(long, value_name = "URL")]
+    pub emit_schema_ref: Option<String>,
+
+    /// Writes a JSON Schema generated from the contracts annotating the root of the configuration
+    /// to the given file.
+    ///
+    /// Builtin types, record contracts and some contracts of the standard library are translated
+    /// to their JSON Schema equivalent. Other contracts, such as custom contracts, accept any
+    /// value in the generated schema. If the root of the configuration isn't annotated, the schema
+    /// accepts any value.
+    #[arg(long, value_name = "FILE")]
+    pub schema_out: Option<PathBuf>,
+
     #[command(flatten)]
     pub input: InputOptions<CustomizeMode>,
 }
@@ -31,27 +58,64 @@ impl ExportCommand {
     pub fn run(self, global: GlobalOptions) -> CliResult<()> {
         let mut program = self.input.prepare(&global)?;
 
-        self.export(&mut program).report_with_program(program)
+        if self.emit_schema_ref.is_some() && self.format != ExportFormat::Json {
+            return Err(CliError::CliUsage {
+                program,
+                error: CliUsageError::UnsupportedExportOption {
+                    option: "--emit-schema-ref",
+                    format: self.format,
+                },
+            });
+        }
+
+        let result = self.export(&mut program);
+        report_stats(&program);
+        report_profile(&program);
+        result.report_with_program(program)
     }
 
     fn export(self, program: &mut Program<CBNCache>) -> Result<(), Error> {
-        let rt = program.eval_full_for_export()?;
+        let mut rt = program.eval_full_for_export()?;
+
+        if let Some(path) = &self.schema_out {
+            let schema = program
+                .root_contract_schema()?
+                .unwrap_or_else(|| serde_json::json!({ "$schema": SCHEMA_DIALECT }));
+            let file = fs::File::create(path).map_err(IOError::from)?;
+            serde_json::to_writer_pretty(file, &schema)
+                .map_err(|err| IOError(format!("{}: {err}", path.display())))?;
+        }
+
+        if let Some(url) = &self.emit_schema_ref {
+            rt = with_schema_ref(rt, url);
+        }
 
         // We only add a trailing newline for JSON exports. Both YAML and TOML
         // exporters already append a trailing newline by default.
         let trailing_newline = self.format == ExportFormat::Json;
 
+        let indentation = match (self.compact, self.indent) {
+            (true, _) => Indentation::Compact,
+            (false, Some(width)) => Indentation::Spaces(width),
+            (false, None) => Indentation::default(),
+        };
+
         serialize::validate(self.format, &rt)?;
 
         if let Some(file) = self.output {
             let mut file = fs::File::create(file).map_err(IOError::from)?;
-            serialize::to_writer(&mut file, self.format, &rt)?;
+            serialize::to_writer_with_indentation(&mut file, self.format, indentation, &rt)?;
 
             if trailing_newline {
                 writeln!(file).map_err(IOError::from)?;
             }
         } else {
-            serialize::to_writer(std::io::stdout(), self.format, &rt)?;
+            serialize::to_writer_with_indentation(
+                std::io::stdout(),
+                self.format,
+                indentation,
+                &rt,
+            )?;
 
             if trailing_newline {
                 println!();
@@ -61,3 +125,18 @@ impl ExportCommand {
         Ok(())
     }
 }
+
+/// Add a `$schema` field set to `url` to `rt`, if it's a record.
+fn with_schema_ref(rt: RichTerm, url: &str) -> RichTerm {
+    let Term::Record(data) = rt.as_ref() else {
+        return rt;
+    };
+
+    let mut data = data.clone();
+    data.fields.insert(
+        LocIdent::from("$schema"),
+        Field::from(RichTerm::from(Term::Str(url.into()))),
+    );
+
+    RichTerm::new(Term::Record(data), rt.pos)
+}
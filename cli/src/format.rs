@@ -89,8 +89,8 @@ impl FormatCommand {
             output.persist();
             Ok(())
         }
-        if self.input.files.is_empty() {
-            return format(stdin(), Output::Stdout);
+        if self.input.files.is_empty() || self.input.stdin {
+            format(stdin(), Output::Stdout)?;
         }
 
         for file in self.input.files.iter() {
@@ -1,6 +1,9 @@
-use std::path::PathBuf;
+use std::{ffi::OsString, path::PathBuf, time::Duration};
 
-use nickel_lang_core::{eval::cache::lazy::CBNCache, program::Program};
+use nickel_lang_core::{
+    eval::cache::lazy::CBNCache,
+    program::{Input, Program, STDIN_SOURCE_NAME},
+};
 
 use crate::{cli::GlobalOptions, customize::Customize, error::CliResult};
 
@@ -9,6 +12,13 @@ pub struct InputOptions<Customize: clap::Args> {
     /// Input files, omit to read from stdin
     pub files: Vec<PathBuf>,
 
+    /// Reads the program from stdin, even when input files are given.
+    ///
+    /// The program read from stdin is then merged with the input files. In error messages, it's
+    /// referred to as `<stdin>`.
+    #[arg(long)]
+    pub stdin: bool,
+
     #[cfg(debug_assertions)]
     /// Skips the standard library import. For debugging only
     #[arg(long, global = true)]
@@ -23,6 +33,55 @@ pub struct InputOptions<Customize: clap::Args> {
     #[arg(long, short = 'I', global = true)]
     pub import_path: Vec<PathBuf>,
 
+    /// Restricts imports to files located inside the given directory.
+    ///
+    /// Any import resolving to a file outside of this directory, for example
+    /// through `..` or a symbolic link, is rejected.
+    #[arg(long, value_name = "DIR", global = true)]
+    pub sandbox_root: Option<PathBuf>,
+
+    /// Caches the parsed programs and imports in the given directory.
+    ///
+    /// Later runs with the same cache directory skip the parsing of the sources which haven't
+    /// changed.
+    #[arg(long, value_name = "DIR", global = true)]
+    pub ast_cache: Option<PathBuf>,
+
+    /// Aborts the evaluation if it takes longer than the given number of seconds.
+    ///
+    /// Fractional values are accepted, e.g. `--max-eval-time 0.5`. The time spent parsing and
+    /// typechecking the program isn't taken into account.
+    #[arg(long, value_name = "SECONDS", value_parser = parse_duration, global = true)]
+    pub max_eval_time: Option<Duration>,
+
+    /// Seeds the pseudo-random number generator used by the standard library, e.g. by
+    /// `std.array.shuffle`.
+    ///
+    /// Two evaluations of the same program with the same seed give the same result. Without this
+    /// option, a different seed is picked for each run.
+    #[arg(long, value_name = "SEED", global = true)]
+    pub seed: Option<u64>,
+
+    /// Prints statistics about the evaluation on the standard error stream once it's done, such
+    /// as the number of merges performed and of contracts applied.
+    #[arg(long, global = true)]
+    pub stats: bool,
+
+    /// Times the evaluation of each field of the result, and prints the timings on the standard
+    /// error stream once the evaluation is done, slowest first.
+    ///
+    /// Only the top-level fields are timed by default. Nested fields are timed as well up to the
+    /// given depth, e.g. `--profile=2` times the top-level fields and their direct subfields.
+    #[arg(
+        long,
+        value_name = "DEPTH",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "1",
+        global = true
+    )]
+    pub profile: Option<usize>,
+
     #[command(flatten)]
     pub customize_mode: Customize,
 }
@@ -35,6 +94,16 @@ impl<C: clap::Args + Customize> Prepare for InputOptions<C> {
     fn prepare(&self, global: &GlobalOptions) -> CliResult<Program<CBNCache>> {
         let mut program = match self.files.as_slice() {
             [] => Program::new_from_stdin(std::io::stderr()),
+            files if self.stdin => {
+                let inputs = files
+                    .iter()
+                    .map(|p| Input::Path(OsString::from(p)))
+                    .chain(std::iter::once(Input::Source(
+                        std::io::stdin(),
+                        OsString::from(STDIN_SOURCE_NAME),
+                    )));
+                Program::new_from_inputs(inputs, std::io::stderr())
+            }
             [p] => Program::new_from_file(p, std::io::stderr()),
             files => Program::new_from_files(files, std::io::stderr()),
         }?;
@@ -42,6 +111,18 @@ impl<C: clap::Args + Customize> Prepare for InputOptions<C> {
         program.color_opt = global.color.into();
 
         program.add_import_paths(self.import_path.iter());
+        program.set_max_eval_time(self.max_eval_time);
+        program.set_seed(self.seed);
+        program.set_stats(self.stats);
+        program.set_profile(self.profile);
+
+        if let Some(root) = &self.sandbox_root {
+            program.set_sandbox_root(root)?;
+        }
+
+        if let Some(dir) = &self.ast_cache {
+            program.set_ast_cache(dir);
+        }
 
         if let Ok(nickel_path) = std::env::var("NICKEL_IMPORT_PATH") {
             program.add_import_paths(nickel_path.split(':'));
@@ -55,3 +136,27 @@ impl<C: clap::Args + Customize> Prepare for InputOptions<C> {
         self.customize_mode.customize(program)
     }
 }
+
+/// Print the evaluation statistics of `program` on the standard error stream, if they have been
+/// enabled with `--stats`.
+pub fn report_stats(program: &Program<CBNCache>) {
+    if let Some(stats) = program.stats() {
+        eprintln!("{stats}");
+    }
+}
+
+/// Print the field timings of `program` on the standard error stream, if they have been enabled
+/// with `--profile`.
+pub fn report_profile(program: &Program<CBNCache>) {
+    if let Some(profile) = program.profile() {
+        eprintln!("{profile}");
+    }
+}
+
+fn parse_duration(s: &str) -> Result<Duration, String> {
+    let secs: f64 = s
+        .parse()
+        .map_err(|_| format!("expected a number of seconds, got `{s}`"))?;
+
+    Duration::try_from_secs_f64(secs).map_err(|err| err.to_string())
+}
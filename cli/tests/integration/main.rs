@@ -73,3 +73,113 @@ fn automatic_color_on_non_tty() {
         );
     }
 }
+
+#[test]
+fn export_emits_schema_ref_and_schema() {
+    let nickel_bin = env!("CARGO_BIN_EXE_nickel");
+    let tmpdir = tempdir().expect("Failed to create temporary directory");
+    let schema_path = tmpdir.path().join("schema.json");
+
+    let mut nickel = Command::new(nickel_bin)
+        .args([
+            "export",
+            "--emit-schema-ref",
+            "https://example.com/schema.json",
+        ])
+        .arg("--schema-out")
+        .arg(&schema_path)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .expect("Nickel should be runnable");
+    let mut stdin = nickel
+        .stdin
+        .take()
+        .expect("couldn't retrieve stdin handle to Nickel");
+    stdin
+        .write_all(b"{ port = 80 } | { port | Number }")
+        .expect("writing into Nickel stdin should work");
+    drop(stdin);
+    let output = nickel
+        .wait_with_output()
+        .expect("couldn't retrieve stdout handle to Nickel");
+    assert!(output.status.success());
+
+    let stdout =
+        String::from_utf8(output.stdout).expect("The result of Nickel should be valid utf8");
+    assert!(stdout.contains(r#""$schema": "https://example.com/schema.json""#));
+
+    let schema = std::fs::read_to_string(&schema_path).expect("the schema should be written");
+    assert!(schema.contains(r#""port""#));
+}
+
+#[test]
+fn export_rejects_schema_ref_for_non_json_formats() {
+    let nickel_bin = env!("CARGO_BIN_EXE_nickel");
+
+    let mut nickel = Command::new(nickel_bin)
+        .args([
+            "export",
+            "--format",
+            "yaml",
+            "--emit-schema-ref",
+            "https://example.com/schema.json",
+        ])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("Nickel should be runnable");
+    let mut stdin = nickel
+        .stdin
+        .take()
+        .expect("couldn't retrieve stdin handle to Nickel");
+    stdin
+        .write_all(b"{ port = 80 }")
+        .expect("writing into Nickel stdin should work");
+    drop(stdin);
+    let output = nickel
+        .wait_with_output()
+        .expect("couldn't retrieve stdout handle to Nickel");
+    assert!(!output.status.success());
+
+    let stderr =
+        String::from_utf8(output.stderr).expect("The error of Nickel should be valid utf8");
+    assert!(stderr.contains("`--emit-schema-ref` isn't supported for yaml export"));
+}
+
+#[test]
+fn ast_cache_is_reused_across_runs() {
+    let nickel_bin = env!("CARGO_BIN_EXE_nickel");
+    let tmpdir = tempdir().expect("Failed to create temporary directory");
+    let main = tmpdir.path().join("main.ncl");
+    let cache_dir = tmpdir.path().join("cache");
+    std::fs::write(
+        &main,
+        "{ server.port | Number = 80, url = \"localhost:%{std.to_string server.port}\" }",
+    )
+    .expect("writing the input file should work");
+
+    let export = || {
+        let output = Command::new(nickel_bin)
+            .arg("export")
+            .arg(&main)
+            .arg("--ast-cache")
+            .arg(&cache_dir)
+            .output()
+            .expect("Nickel should be runnable");
+        assert!(output.status.success());
+        String::from_utf8(output.stdout).expect("The result of Nickel should be valid utf8")
+    };
+
+    let first = export();
+    let entries = std::fs::read_dir(&cache_dir)
+        .expect("the cache directory should be created")
+        .filter_map(Result::ok)
+        .filter(|entry| entry.path().extension().is_some_and(|ext| ext == "ast"))
+        .count();
+    assert!(entries > 0);
+
+    assert_eq!(export(), first);
+    assert!(first.contains(r#""url": "localhost:80""#));
+}
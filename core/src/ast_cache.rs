@@ -0,0 +1,1236 @@
+//! On-disk cache of parsed terms.
+//!
+//! Parsing large Nickel libraries over and over can be costly. This module provides a compact
+//! serialized representation of the terms produced by the parser, which can be stored on disk
+//! (using `bincode`) and loaded back by a later run, skipping parsing altogether when the source
+//! hasn't changed. Cache entries are keyed by a hash of the source.
+//!
+//! [RichTerm] can't be serialized as it is: its `Serialize` implementation is dedicated to the
+//! export of Nickel values, positions refer to a [FileId] which is only meaningful within one
+//! session, and identifiers are interned. The parsed term is thus first converted to an owned
+//! mirror representation, where positions are stored as plain byte offsets and identifiers as
+//! strings. Upon loading, positions are attached back to the `FileId` of the current session.
+//!
+//! Only the nodes that the parser can produce are supported. Encoding a term which contains
+//! runtime-only nodes (closures, sealed terms, etc.) fails, and such a term is simply not cached.
+use crate::{
+    identifier::{Ident, LocIdent},
+    label::{Label, MergeKind, MergeLabel},
+    position::{RawSpan, TermPos},
+    term::{
+        array::ArrayAttrs,
+        pattern::{
+            ArrayPattern, ConstantPattern, ConstantPatternData, EnumPattern, FieldPattern,
+            OrPattern, Pattern, PatternData, RecordPattern, TailPattern,
+        },
+        record::{Field, FieldMetadata, RecordAttrs, RecordData},
+        string::NickelString,
+        BinaryOp, BindingType, EnumVariantAttrs, LabeledType, LetAttrs, MatchBranch, MatchData,
+        MergePriority, NAryOp, Number, RecordExtKind, RecordOpKind, RichTerm, StrChunk, Term,
+        TypeAnnotation, UnaryOp,
+    },
+    typ::{
+        DictTypeFlavour, EnumRow, EnumRows, EnumRowsF, RecordRow, RecordRows, RecordRowsF, Type,
+        TypeF, VarKind,
+    },
+};
+
+use codespan::{ByteIndex, FileId};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use std::{
+    ffi::OsString,
+    fs, io,
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+
+/// The version of the serialized format. Must be bumped each time the representation below
+/// changes, so that stale entries are ignored.
+const FORMAT_VERSION: u32 = 3;
+
+/// An on-disk cache of parsed terms, stored in a directory as one file per entry.
+#[derive(Debug, Clone)]
+pub struct AstCache {
+    dir: PathBuf,
+    hits: usize,
+    misses: usize,
+}
+
+impl AstCache {
+    /// Create a new cache storing its entries in `dir`. The directory is created on the first
+    /// write if it doesn't exist.
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        AstCache {
+            dir: dir.into(),
+            hits: 0,
+            misses: 0,
+        }
+    }
+
+    /// The directory where entries are stored.
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// The number of successful lookups since the creation of this cache.
+    pub fn hits(&self) -> usize {
+        self.hits
+    }
+
+    /// The number of failed lookups since the creation of this cache.
+    pub fn misses(&self) -> usize {
+        self.misses
+    }
+
+    /// Compute the path of the entry corresponding to `source`. The key includes the version of
+    /// Nickel and of the format, as the AST may change between releases.
+    fn entry_path(&self, source: &str) -> PathBuf {
+        let mut hasher = Sha256::new();
+        hasher.update(env!("CARGO_PKG_VERSION"));
+        hasher.update(FORMAT_VERSION.to_le_bytes());
+        hasher.update(source);
+        self.dir.join(format!("{:x}.ast", hasher.finalize()))
+    }
+
+    /// Look up the parsed term corresponding to `source`. The positions of the returned term
+    /// point to `file_id`, which must be the id of `source`.
+    pub fn load(&mut self, file_id: FileId, source: &str) -> Option<RichTerm> {
+        let result = fs::read(self.entry_path(source))
+            .ok()
+            .and_then(|bytes| decode(&bytes, file_id));
+
+        if result.is_some() {
+            self.hits += 1;
+        } else {
+            self.misses += 1;
+        }
+
+        result
+    }
+
+    /// Store the parsed term corresponding to `source`, which must be the source of `file_id`.
+    ///
+    /// The entry is first written to a temporary file which is then renamed, so that concurrent
+    /// processes never observe a partially written entry.
+    pub fn store(&self, file_id: FileId, source: &str, term: &RichTerm) -> io::Result<()> {
+        let bytes = encode(term, file_id).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "the term contains nodes which can't be cached",
+            )
+        })?;
+
+        fs::create_dir_all(&self.dir)?;
+        let path = self.entry_path(source);
+        let tmp_path = path.with_extension(format!("tmp{}", std::process::id()));
+        fs::write(&tmp_path, bytes)?;
+        fs::rename(tmp_path, path)
+    }
+}
+
+/// Serialize a parsed term originating from `file_id`. Return `None` if the term contains nodes
+/// that can't be produced by the parser, or positions pointing to other files.
+pub fn encode(term: &RichTerm, file_id: FileId) -> Option<Vec<u8>> {
+    let cached = Encoder { file_id }.term(term).ok()?;
+    bincode::serialize(&cached).ok()
+}
+
+/// Deserialize a term previously serialized with [encode]. The positions of the resulting term
+/// point to `file_id`.
+pub fn decode(bytes: &[u8], file_id: FileId) -> Option<RichTerm> {
+    let cached: CachedTerm = bincode::deserialize(bytes).ok()?;
+    Some(Decoder { file_id }.term(cached))
+}
+
+#[derive(Serialize, Deserialize)]
+struct CachedSpan(u32, u32);
+
+#[derive(Serialize, Deserialize)]
+enum CachedPos {
+    Original(CachedSpan),
+    Inherited(CachedSpan),
+    None,
+}
+
+#[derive(Serialize, Deserialize)]
+struct CachedIdent {
+    label: String,
+    pos: CachedPos,
+}
+
+#[derive(Serialize, Deserialize)]
+struct CachedTerm {
+    node: Box<CachedNode>,
+    pos: CachedPos,
+}
+
+#[derive(Serialize, Deserialize)]
+enum CachedChunk {
+    Literal(String),
+    Expr(CachedTerm, usize),
+}
+
+#[derive(Serialize, Deserialize)]
+enum CachedNode {
+    Null,
+    Bool(bool),
+    Num(Number),
+    Str(NickelString),
+    StrChunks(Vec<CachedChunk>),
+    Fun(CachedIdent, CachedTerm),
+    FunPattern(CachedPattern, CachedTerm),
+    Let {
+        id: CachedIdent,
+        bound: CachedTerm,
+        body: CachedTerm,
+        rec: bool,
+    },
+    LetPattern(CachedPattern, CachedTerm, CachedTerm),
+    App(CachedTerm, CachedTerm),
+    Var(CachedIdent),
+    Enum(CachedIdent),
+    EnumVariant {
+        tag: CachedIdent,
+        arg: CachedTerm,
+    },
+    Record(CachedRecord),
+    RecRecord(CachedRecord, Vec<(CachedTerm, CachedField)>),
+    Match(Vec<CachedBranch>),
+    Array(Vec<CachedTerm>),
+    Op1(CachedUnaryOp, CachedTerm),
+    Op2(CachedBinaryOp, CachedTerm, CachedTerm),
+    OpN(NAryOp, Vec<CachedTerm>),
+    Annotated(CachedAnnotation, CachedTerm),
+    Import(OsString),
+    Type(CachedType),
+}
+
+/// Operators carrying identifiers or spans need a dedicated representation, as their positions
+/// must be rebased on load.
+#[derive(Serialize, Deserialize)]
+enum CachedUnaryOp {
+    Op(UnaryOp),
+    EnumEmbed(CachedIdent),
+    RecordAccess(CachedIdent),
+}
+
+#[derive(Serialize, Deserialize)]
+enum CachedBinaryOp {
+    Op(BinaryOp),
+    Merge {
+        span: CachedSpan,
+        kind: MergeKind,
+    },
+    RecordInsert {
+        metadata: CachedMetadata,
+        ext_kind: RecordExtKind,
+        op_kind: RecordOpKind,
+    },
+}
+
+#[derive(Serialize, Deserialize)]
+struct CachedRecord {
+    fields: Vec<(CachedIdent, CachedField)>,
+    open: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+struct CachedField {
+    value: Option<CachedTerm>,
+    metadata: CachedMetadata,
+}
+
+#[derive(Serialize, Deserialize)]
+enum CachedPriority {
+    Bottom,
+    Neutral,
+    Numeral(Number),
+    Relative(Number),
+    Top,
+}
+
+#[derive(Serialize, Deserialize)]
+struct CachedMetadata {
+    doc: Option<String>,
+    annotation: CachedAnnotation,
+    opt: bool,
+    not_exported: bool,
+    priority: CachedPriority,
+}
+
+#[derive(Serialize, Deserialize)]
+struct CachedAnnotation {
+    typ: Option<CachedLabeledType>,
+    contracts: Vec<CachedLabeledType>,
+}
+
+/// The parser only sets the type, the span and the field path of a label, the other fields being
+/// filled at runtime, so we don't need to store more.
+#[derive(Serialize, Deserialize)]
+struct CachedLabeledType {
+    typ: CachedType,
+    span: CachedSpan,
+    field_path: Vec<CachedIdent>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct CachedBranch {
+    pattern: CachedPattern,
+    guard: Option<CachedTerm>,
+    body: CachedTerm,
+}
+
+#[derive(Serialize, Deserialize)]
+struct CachedPattern {
+    data: CachedPatternData,
+    alias: Option<CachedIdent>,
+    pos: CachedPos,
+}
+
+#[derive(Serialize, Deserialize)]
+enum CachedPatternData {
+    Wildcard,
+    Any(CachedIdent),
+    Record {
+        patterns: Vec<CachedFieldPattern>,
+        tail: CachedTailPattern,
+        pos: CachedPos,
+    },
+    Array {
+        patterns: Vec<CachedPattern>,
+        tail: CachedTailPattern,
+        pos: CachedPos,
+    },
+    Enum {
+        tag: CachedIdent,
+        pattern: Option<Box<CachedPattern>>,
+        pos: CachedPos,
+    },
+    Constant {
+        data: CachedConstant,
+        pos: CachedPos,
+    },
+    Or {
+        patterns: Vec<CachedPattern>,
+        pos: CachedPos,
+    },
+}
+
+#[derive(Serialize, Deserialize)]
+struct CachedFieldPattern {
+    matched_id: CachedIdent,
+    annotation: CachedAnnotation,
+    default: Option<CachedTerm>,
+    pattern: CachedPattern,
+    pos: CachedPos,
+}
+
+#[derive(Serialize, Deserialize)]
+enum CachedTailPattern {
+    Empty,
+    Open,
+    Capture(CachedIdent),
+}
+
+#[derive(Serialize, Deserialize)]
+enum CachedConstant {
+    Bool(bool),
+    Number(Number),
+    String(NickelString),
+    Null,
+}
+
+#[derive(Serialize, Deserialize)]
+struct CachedType {
+    typ: CachedTypeF,
+    pos: CachedPos,
+}
+
+#[derive(Serialize, Deserialize)]
+enum CachedTypeF {
+    Dyn,
+    Number,
+    Bool,
+    String,
+    Symbol,
+    ForeignId,
+    Flat(CachedTerm),
+    Arrow(Box<CachedType>, Box<CachedType>),
+    Var(String),
+    Forall {
+        var: CachedIdent,
+        var_kind: CachedVarKind,
+        body: Box<CachedType>,
+    },
+    Enum(CachedEnumRows),
+    Record(CachedRecordRows),
+    Dict {
+        type_fields: Box<CachedType>,
+        flavour: DictTypeFlavour,
+    },
+    Array(Box<CachedType>),
+    Wildcard(usize),
+}
+
+#[derive(Serialize, Deserialize)]
+enum CachedVarKind {
+    Type,
+    EnumRows(Vec<String>),
+    RecordRows(Vec<String>),
+}
+
+#[derive(Serialize, Deserialize)]
+enum CachedRecordRows {
+    Empty,
+    Extend {
+        id: CachedIdent,
+        typ: Box<CachedType>,
+        tail: Box<CachedRecordRows>,
+    },
+    TailVar(CachedIdent),
+    TailDyn,
+}
+
+#[derive(Serialize, Deserialize)]
+enum CachedEnumRows {
+    Empty,
+    Extend {
+        id: CachedIdent,
+        typ: Option<Box<CachedType>>,
+        tail: Box<CachedEnumRows>,
+    },
+    TailVar(CachedIdent),
+}
+
+/// Error raised when encountering a node that can't be cached.
+struct Unsupported;
+
+type EncodeResult<T> = Result<T, Unsupported>;
+
+/// Convert terms to their cached representation.
+struct Encoder {
+    file_id: FileId,
+}
+
+impl Encoder {
+    fn span(&self, span: &RawSpan) -> EncodeResult<CachedSpan> {
+        if span.src_id == self.file_id {
+            Ok(CachedSpan(span.start.0, span.end.0))
+        } else {
+            Err(Unsupported)
+        }
+    }
+
+    fn pos(&self, pos: &TermPos) -> EncodeResult<CachedPos> {
+        Ok(match pos {
+            TermPos::Original(span) => CachedPos::Original(self.span(span)?),
+            TermPos::Inherited(span) => CachedPos::Inherited(self.span(span)?),
+            TermPos::None => CachedPos::None,
+        })
+    }
+
+    fn ident(&self, id: &LocIdent) -> EncodeResult<CachedIdent> {
+        // Generated identifiers are only guaranteed to be unique within one session.
+        if id.is_generated() {
+            return Err(Unsupported);
+        }
+
+        Ok(CachedIdent {
+            label: id.label().to_owned(),
+            pos: self.pos(&id.pos)?,
+        })
+    }
+
+    fn opt_term(&self, term: &Option<RichTerm>) -> EncodeResult<Option<CachedTerm>> {
+        term.as_ref().map(|t| self.term(t)).transpose()
+    }
+
+    fn terms<'a>(
+        &self,
+        terms: impl IntoIterator<Item = &'a RichTerm>,
+    ) -> EncodeResult<Vec<CachedTerm>> {
+        terms.into_iter().map(|t| self.term(t)).collect()
+    }
+
+    fn term(&self, rt: &RichTerm) -> EncodeResult<CachedTerm> {
+        let node = match rt.as_ref() {
+            Term::Null => CachedNode::Null,
+            Term::Bool(b) => CachedNode::Bool(*b),
+            Term::Num(n) => CachedNode::Num(n.clone()),
+            Term::Str(s) => CachedNode::Str(s.clone()),
+            Term::StrChunks(chunks) => CachedNode::StrChunks(
+                chunks
+                    .iter()
+                    .map(|chunk| match chunk {
+                        StrChunk::Literal(s) => Ok(CachedChunk::Literal(s.clone())),
+                        StrChunk::Expr(e, indent) => Ok(CachedChunk::Expr(self.term(e)?, *indent)),
+                    })
+                    .collect::<EncodeResult<_>>()?,
+            ),
+            Term::Fun(id, body) => CachedNode::Fun(self.ident(id)?, self.term(body)?),
+            Term::FunPattern(pat, body) => {
+                CachedNode::FunPattern(self.pattern(pat)?, self.term(body)?)
+            }
+            Term::Let(id, bound, body, attrs) => {
+                // Revertible bindings are introduced by program transformations, not the parser.
+                if !matches!(attrs.binding_type, BindingType::Normal) {
+                    return Err(Unsupported);
+                }
+
+                CachedNode::Let {
+                    id: self.ident(id)?,
+                    bound: self.term(bound)?,
+                    body: self.term(body)?,
+                    rec: attrs.rec,
+                }
+            }
+            Term::LetPattern(pat, bound, body) => {
+                CachedNode::LetPattern(self.pattern(pat)?, self.term(bound)?, self.term(body)?)
+            }
+            Term::App(t1, t2) => CachedNode::App(self.term(t1)?, self.term(t2)?),
+            Term::Var(id) => CachedNode::Var(self.ident(id)?),
+            Term::Enum(id) => CachedNode::Enum(self.ident(id)?),
+            Term::EnumVariant { tag, arg, attrs } if !attrs.closurized => {
+                CachedNode::EnumVariant {
+                    tag: self.ident(tag)?,
+                    arg: self.term(arg)?,
+                }
+            }
+            Term::Record(data) => CachedNode::Record(self.record(data)?),
+            Term::RecRecord(data, dyn_fields, None) => CachedNode::RecRecord(
+                self.record(data)?,
+                dyn_fields
+                    .iter()
+                    .map(|(name, field)| Ok((self.term(name)?, self.field(field)?)))
+                    .collect::<EncodeResult<_>>()?,
+            ),
+            Term::Match(data) => CachedNode::Match(
+                data.branches
+                    .iter()
+                    .map(|branch| {
+                        Ok(CachedBranch {
+                            pattern: self.pattern(&branch.pattern)?,
+                            guard: self.opt_term(&branch.guard)?,
+                            body: self.term(&branch.body)?,
+                        })
+                    })
+                    .collect::<EncodeResult<_>>()?,
+            ),
+            Term::Array(array, attrs)
+                if !attrs.closurized && attrs.pending_contracts.is_empty() =>
+            {
+                CachedNode::Array(self.terms(array.iter())?)
+            }
+            Term::Op1(op, t) => CachedNode::Op1(self.unary_op(op)?, self.term(t)?),
+            Term::Op2(op, t1, t2) => {
+                CachedNode::Op2(self.binary_op(op)?, self.term(t1)?, self.term(t2)?)
+            }
+            Term::OpN(op, args) => CachedNode::OpN(op.clone(), self.terms(args)?),
+            Term::Annotated(annot, t) => {
+                CachedNode::Annotated(self.annotation(annot)?, self.term(t)?)
+            }
+            Term::Import(path) => CachedNode::Import(path.clone()),
+            Term::Type(typ) => CachedNode::Type(self.typ(typ)?),
+            _ => return Err(Unsupported),
+        };
+
+        Ok(CachedTerm {
+            node: Box::new(node),
+            pos: self.pos(&rt.pos)?,
+        })
+    }
+
+    fn unary_op(&self, op: &UnaryOp) -> EncodeResult<CachedUnaryOp> {
+        Ok(match op {
+            UnaryOp::EnumEmbed(id) => CachedUnaryOp::EnumEmbed(self.ident(id)?),
+            UnaryOp::RecordAccess(id) => CachedUnaryOp::RecordAccess(self.ident(id)?),
+            UnaryOp::StringIsMatchCompiled(_)
+            | UnaryOp::StringFindCompiled(_)
+            | UnaryOp::StringFindAllCompiled(_)
+            | UnaryOp::Profile(_) => return Err(Unsupported),
+            op => CachedUnaryOp::Op(op.clone()),
+        })
+    }
+
+    fn binary_op(&self, op: &BinaryOp) -> EncodeResult<CachedBinaryOp> {
+        Ok(match op {
+            BinaryOp::Merge(label) if !label.field_path.is_empty() => return Err(Unsupported),
+            BinaryOp::Merge(label) => CachedBinaryOp::Merge {
+                span: self.span(&label.span)?,
+                kind: label.kind,
+            },
+            BinaryOp::RecordInsert {
+                metadata,
+                pending_contracts,
+                ext_kind,
+                op_kind,
+            } if pending_contracts.is_empty() => CachedBinaryOp::RecordInsert {
+                metadata: self.metadata(metadata)?,
+                ext_kind: *ext_kind,
+                op_kind: *op_kind,
+            },
+            BinaryOp::RecordInsert { .. } => return Err(Unsupported),
+            op => CachedBinaryOp::Op(op.clone()),
+        })
+    }
+
+    fn record(&self, data: &RecordData) -> EncodeResult<CachedRecord> {
+        if data.attrs.closurized || data.sealed_tail.is_some() {
+            return Err(Unsupported);
+        }
+
+        Ok(CachedRecord {
+            fields: data
+                .fields
+                .iter()
+                .map(|(id, field)| Ok((self.ident(id)?, self.field(field)?)))
+                .collect::<EncodeResult<_>>()?,
+            open: data.attrs.open,
+        })
+    }
+
+    fn field(&self, field: &Field) -> EncodeResult<CachedField> {
+        if !field.pending_contracts.is_empty() {
+            return Err(Unsupported);
+        }
+
+        Ok(CachedField {
+            value: self.opt_term(&field.value)?,
+            metadata: self.metadata(&field.metadata)?,
+        })
+    }
+
+    fn metadata(&self, metadata: &FieldMetadata) -> EncodeResult<CachedMetadata> {
+        Ok(CachedMetadata {
+            doc: metadata.doc.clone(),
+            annotation: self.annotation(&metadata.annotation)?,
+            opt: metadata.opt,
+            not_exported: metadata.not_exported,
+            priority: match &metadata.priority {
+                MergePriority::Bottom => CachedPriority::Bottom,
+                MergePriority::Neutral => CachedPriority::Neutral,
+                MergePriority::Numeral(n) => CachedPriority::Numeral(n.clone()),
+                MergePriority::Relative(n) => CachedPriority::Relative(n.clone()),
+                MergePriority::Top => CachedPriority::Top,
+            },
+        })
+    }
+
+    fn annotation(&self, annot: &TypeAnnotation) -> EncodeResult<CachedAnnotation> {
+        Ok(CachedAnnotation {
+            typ: annot
+                .typ
+                .as_ref()
+                .map(|labeled_ty| self.labeled_type(labeled_ty))
+                .transpose()?,
+            contracts: annot
+                .contracts
+                .iter()
+                .map(|labeled_ty| self.labeled_type(labeled_ty))
+                .collect::<EncodeResult<_>>()?,
+        })
+    }
+
+    fn labeled_type(&self, labeled_ty: &LabeledType) -> EncodeResult<CachedLabeledType> {
+        let label = &labeled_ty.label;
+        let parsed_label = Label {
+            typ: label.typ.clone(),
+            span: label.span,
+            field_path: label.field_path.clone(),
+            ..Default::default()
+        };
+
+        if *label != parsed_label || *label.typ != labeled_ty.typ {
+            return Err(Unsupported);
+        }
+
+        Ok(CachedLabeledType {
+            typ: self.typ(&labeled_ty.typ)?,
+            span: self.span(&label.span)?,
+            field_path: label
+                .field_path
+                .iter()
+                .map(|id| self.ident(id))
+                .collect::<EncodeResult<_>>()?,
+        })
+    }
+
+    fn patterns(&self, patterns: &[Pattern]) -> EncodeResult<Vec<CachedPattern>> {
+        patterns.iter().map(|pat| self.pattern(pat)).collect()
+    }
+
+    fn tail_pattern(&self, tail: &TailPattern) -> EncodeResult<CachedTailPattern> {
+        Ok(match tail {
+            TailPattern::Empty => CachedTailPattern::Empty,
+            TailPattern::Open => CachedTailPattern::Open,
+            TailPattern::Capture(id) => CachedTailPattern::Capture(self.ident(id)?),
+        })
+    }
+
+    fn pattern(&self, pat: &Pattern) -> EncodeResult<CachedPattern> {
+        let data = match &pat.data {
+            PatternData::Wildcard => CachedPatternData::Wildcard,
+            PatternData::Any(id) => CachedPatternData::Any(self.ident(id)?),
+            PatternData::Record(record_pat) => CachedPatternData::Record {
+                patterns: record_pat
+                    .patterns
+                    .iter()
+                    .map(|field_pat| {
+                        Ok(CachedFieldPattern {
+                            matched_id: self.ident(&field_pat.matched_id)?,
+                            annotation: self.annotation(&field_pat.annotation)?,
+                            default: self.opt_term(&field_pat.default)?,
+                            pattern: self.pattern(&field_pat.pattern)?,
+                            pos: self.pos(&field_pat.pos)?,
+                        })
+                    })
+                    .collect::<EncodeResult<_>>()?,
+                tail: self.tail_pattern(&record_pat.tail)?,
+                pos: self.pos(&record_pat.pos)?,
+            },
+            PatternData::Array(array_pat) => CachedPatternData::Array {
+                patterns: self.patterns(&array_pat.patterns)?,
+                tail: self.tail_pattern(&array_pat.tail)?,
+                pos: self.pos(&array_pat.pos)?,
+            },
+            PatternData::Enum(enum_pat) => CachedPatternData::Enum {
+                tag: self.ident(&enum_pat.tag)?,
+                pattern: enum_pat
+                    .pattern
+                    .as_deref()
+                    .map(|pat| self.pattern(pat).map(Box::new))
+                    .transpose()?,
+                pos: self.pos(&enum_pat.pos)?,
+            },
+            PatternData::Constant(constant_pat) => CachedPatternData::Constant {
+                data: match &constant_pat.data {
+                    ConstantPatternData::Bool(b) => CachedConstant::Bool(*b),
+                    ConstantPatternData::Number(n) => CachedConstant::Number(n.clone()),
+                    ConstantPatternData::String(s) => CachedConstant::String(s.clone()),
+                    ConstantPatternData::Null => CachedConstant::Null,
+                },
+                pos: self.pos(&constant_pat.pos)?,
+            },
+            PatternData::Or(or_pat) => CachedPatternData::Or {
+                patterns: self.patterns(&or_pat.patterns)?,
+                pos: self.pos(&or_pat.pos)?,
+            },
+        };
+
+        Ok(CachedPattern {
+            data,
+            alias: pat.alias.as_ref().map(|id| self.ident(id)).transpose()?,
+            pos: self.pos(&pat.pos)?,
+        })
+    }
+
+    fn boxed_typ(&self, typ: &Type) -> EncodeResult<Box<CachedType>> {
+        self.typ(typ).map(Box::new)
+    }
+
+    fn typ(&self, typ: &Type) -> EncodeResult<CachedType> {
+        let excluded = |idents: &std::collections::HashSet<Ident>| {
+            idents.iter().map(|id| id.label().to_owned()).collect()
+        };
+
+        let cached = match &typ.typ {
+            TypeF::Dyn => CachedTypeF::Dyn,
+            TypeF::Number => CachedTypeF::Number,
+            TypeF::Bool => CachedTypeF::Bool,
+            TypeF::String => CachedTypeF::String,
+            TypeF::Symbol => CachedTypeF::Symbol,
+            TypeF::ForeignId => CachedTypeF::ForeignId,
+            TypeF::Flat(t) => CachedTypeF::Flat(self.term(t)?),
+            TypeF::Arrow(dom, codom) => {
+                CachedTypeF::Arrow(self.boxed_typ(dom)?, self.boxed_typ(codom)?)
+            }
+            TypeF::Var(id) => CachedTypeF::Var(id.label().to_owned()),
+            TypeF::Forall {
+                var,
+                var_kind,
+                body,
+            } => CachedTypeF::Forall {
+                var: self.ident(var)?,
+                var_kind: match var_kind {
+                    VarKind::Type => CachedVarKind::Type,
+                    VarKind::EnumRows { excluded: ids } => CachedVarKind::EnumRows(excluded(ids)),
+                    VarKind::RecordRows { excluded: ids } => {
+                        CachedVarKind::RecordRows(excluded(ids))
+                    }
+                },
+                body: self.boxed_typ(body)?,
+            },
+            TypeF::Enum(erows) => CachedTypeF::Enum(self.enum_rows(erows)?),
+            TypeF::Record(rrows) => CachedTypeF::Record(self.record_rows(rrows)?),
+            TypeF::Dict {
+                type_fields,
+                flavour,
+            } => CachedTypeF::Dict {
+                type_fields: self.boxed_typ(type_fields)?,
+                flavour: *flavour,
+            },
+            TypeF::Array(elt) => CachedTypeF::Array(self.boxed_typ(elt)?),
+            TypeF::Wildcard(id) => CachedTypeF::Wildcard(*id),
+        };
+
+        Ok(CachedType {
+            typ: cached,
+            pos: self.pos(&typ.pos)?,
+        })
+    }
+
+    fn record_rows(&self, rrows: &RecordRows) -> EncodeResult<CachedRecordRows> {
+        Ok(match &rrows.0 {
+            RecordRowsF::Empty => CachedRecordRows::Empty,
+            RecordRowsF::Extend { row, tail } => CachedRecordRows::Extend {
+                id: self.ident(&row.id)?,
+                typ: self.boxed_typ(&row.typ)?,
+                tail: Box::new(self.record_rows(tail)?),
+            },
+            RecordRowsF::TailVar(id) => CachedRecordRows::TailVar(self.ident(id)?),
+            RecordRowsF::TailDyn => CachedRecordRows::TailDyn,
+        })
+    }
+
+    fn enum_rows(&self, erows: &EnumRows) -> EncodeResult<CachedEnumRows> {
+        Ok(match &erows.0 {
+            EnumRowsF::Empty => CachedEnumRows::Empty,
+            EnumRowsF::Extend { row, tail } => CachedEnumRows::Extend {
+                id: self.ident(&row.id)?,
+                typ: row
+                    .typ
+                    .as_ref()
+                    .map(|typ| self.boxed_typ(typ))
+                    .transpose()?,
+                tail: Box::new(self.enum_rows(tail)?),
+            },
+            EnumRowsF::TailVar(id) => CachedEnumRows::TailVar(self.ident(id)?),
+        })
+    }
+}
+
+/// Convert cached terms back to terms, attaching positions to the file being loaded.
+struct Decoder {
+    file_id: FileId,
+}
+
+impl Decoder {
+    fn span(&self, CachedSpan(start, end): CachedSpan) -> RawSpan {
+        RawSpan {
+            src_id: self.file_id,
+            start: ByteIndex(start),
+            end: ByteIndex(end),
+        }
+    }
+
+    fn pos(&self, pos: CachedPos) -> TermPos {
+        match pos {
+            CachedPos::Original(span) => TermPos::Original(self.span(span)),
+            CachedPos::Inherited(span) => TermPos::Inherited(self.span(span)),
+            CachedPos::None => TermPos::None,
+        }
+    }
+
+    fn ident(&self, id: CachedIdent) -> LocIdent {
+        LocIdent::new_with_pos(id.label, self.pos(id.pos))
+    }
+
+    fn terms(&self, terms: Vec<CachedTerm>) -> Vec<RichTerm> {
+        terms.into_iter().map(|t| self.term(t)).collect()
+    }
+
+    fn term(&self, cached: CachedTerm) -> RichTerm {
+        let term = match *cached.node {
+            CachedNode::Null => Term::Null,
+            CachedNode::Bool(b) => Term::Bool(b),
+            CachedNode::Num(n) => Term::Num(n),
+            CachedNode::Str(s) => Term::Str(s),
+            CachedNode::StrChunks(chunks) => Term::StrChunks(
+                chunks
+                    .into_iter()
+                    .map(|chunk| match chunk {
+                        CachedChunk::Literal(s) => StrChunk::Literal(s),
+                        CachedChunk::Expr(e, indent) => StrChunk::Expr(self.term(e), indent),
+                    })
+                    .collect(),
+            ),
+            CachedNode::Fun(id, body) => Term::Fun(self.ident(id), self.term(body)),
+            CachedNode::FunPattern(pat, body) => {
+                Term::FunPattern(self.pattern(pat), self.term(body))
+            }
+            CachedNode::Let {
+                id,
+                bound,
+                body,
+                rec,
+            } => Term::Let(
+                self.ident(id),
+                self.term(bound),
+                self.term(body),
+                LetAttrs {
+                    binding_type: BindingType::Normal,
+                    rec,
+                },
+            ),
+            CachedNode::LetPattern(pat, bound, body) => {
+                Term::LetPattern(self.pattern(pat), self.term(bound), self.term(body))
+            }
+            CachedNode::App(t1, t2) => Term::App(self.term(t1), self.term(t2)),
+            CachedNode::Var(id) => Term::Var(self.ident(id)),
+            CachedNode::Enum(id) => Term::Enum(self.ident(id)),
+            CachedNode::EnumVariant { tag, arg } => Term::EnumVariant {
+                tag: self.ident(tag),
+                arg: self.term(arg),
+                attrs: EnumVariantAttrs::new(),
+            },
+            CachedNode::Record(record) => Term::Record(self.record(record)),
+            CachedNode::RecRecord(record, dyn_fields) => Term::RecRecord(
+                self.record(record),
+                dyn_fields
+                    .into_iter()
+                    .map(|(name, field)| (self.term(name), self.field(field)))
+                    .collect(),
+                None,
+            ),
+            CachedNode::Match(branches) => Term::Match(MatchData {
+                branches: branches
+                    .into_iter()
+                    .map(|branch| MatchBranch {
+                        pattern: self.pattern(branch.pattern),
+                        guard: branch.guard.map(|guard| self.term(guard)),
+                        body: self.term(branch.body),
+                    })
+                    .collect(),
+            }),
+            CachedNode::Array(elts) => Term::Array(
+                elts.into_iter().map(|t| self.term(t)).collect(),
+                ArrayAttrs::new(),
+            ),
+            CachedNode::Op1(op, t) => Term::Op1(self.unary_op(op), self.term(t)),
+            CachedNode::Op2(op, t1, t2) => {
+                Term::Op2(self.binary_op(op), self.term(t1), self.term(t2))
+            }
+            CachedNode::OpN(op, args) => Term::OpN(op, self.terms(args)),
+            CachedNode::Annotated(annot, t) => {
+                Term::Annotated(self.annotation(annot), self.term(t))
+            }
+            CachedNode::Import(path) => Term::Import(path),
+            CachedNode::Type(typ) => Term::Type(self.typ(typ)),
+        };
+
+        RichTerm::new(term, self.pos(cached.pos))
+    }
+
+    fn unary_op(&self, op: CachedUnaryOp) -> UnaryOp {
+        match op {
+            CachedUnaryOp::Op(op) => op,
+            CachedUnaryOp::EnumEmbed(id) => UnaryOp::EnumEmbed(self.ident(id)),
+            CachedUnaryOp::RecordAccess(id) => UnaryOp::RecordAccess(self.ident(id)),
+        }
+    }
+
+    fn binary_op(&self, op: CachedBinaryOp) -> BinaryOp {
+        match op {
+            CachedBinaryOp::Op(op) => op,
+            CachedBinaryOp::Merge { span, kind } => BinaryOp::Merge(MergeLabel {
+                span: self.span(span),
+                kind,
+                field_path: Vec::new(),
+            }),
+            CachedBinaryOp::RecordInsert {
+                metadata,
+                ext_kind,
+                op_kind,
+            } => BinaryOp::RecordInsert {
+                metadata: self.metadata(metadata),
+                pending_contracts: Vec::new(),
+                ext_kind,
+                op_kind,
+            },
+        }
+    }
+
+    fn record(&self, record: CachedRecord) -> RecordData {
+        RecordData::new(
+            record
+                .fields
+                .into_iter()
+                .map(|(id, field)| (self.ident(id), self.field(field)))
+                .collect(),
+            RecordAttrs {
+                open: record.open,
+                closurized: false,
+            },
+            None,
+        )
+    }
+
+    fn field(&self, field: CachedField) -> Field {
+        Field {
+            value: field.value.map(|t| self.term(t)),
+            metadata: self.metadata(field.metadata),
+            pending_contracts: Vec::new(),
+        }
+    }
+
+    fn metadata(&self, metadata: CachedMetadata) -> FieldMetadata {
+        FieldMetadata {
+            doc: metadata.doc,
+            annotation: self.annotation(metadata.annotation),
+            opt: metadata.opt,
+            not_exported: metadata.not_exported,
+            priority: match metadata.priority {
+                CachedPriority::Bottom => MergePriority::Bottom,
+                CachedPriority::Neutral => MergePriority::Neutral,
+                CachedPriority::Numeral(n) => MergePriority::Numeral(n),
+                CachedPriority::Relative(n) => MergePriority::Relative(n),
+                CachedPriority::Top => MergePriority::Top,
+            },
+        }
+    }
+
+    fn annotation(&self, annot: CachedAnnotation) -> TypeAnnotation {
+        TypeAnnotation {
+            typ: annot.typ.map(|labeled_ty| self.labeled_type(labeled_ty)),
+            contracts: annot
+                .contracts
+                .into_iter()
+                .map(|labeled_ty| self.labeled_type(labeled_ty))
+                .collect(),
+        }
+    }
+
+    fn labeled_type(&self, labeled_ty: CachedLabeledType) -> LabeledType {
+        let typ = self.typ(labeled_ty.typ);
+
+        LabeledType {
+            typ: typ.clone(),
+            label: Label {
+                typ: Rc::new(typ),
+                span: self.span(labeled_ty.span),
+                field_path: labeled_ty
+                    .field_path
+                    .into_iter()
+                    .map(|id| self.ident(id))
+                    .collect(),
+                ..Default::default()
+            },
+        }
+    }
+
+    fn patterns(&self, patterns: Vec<CachedPattern>) -> Vec<Pattern> {
+        patterns.into_iter().map(|pat| self.pattern(pat)).collect()
+    }
+
+    fn tail_pattern(&self, tail: CachedTailPattern) -> TailPattern {
+        match tail {
+            CachedTailPattern::Empty => TailPattern::Empty,
+            CachedTailPattern::Open => TailPattern::Open,
+            CachedTailPattern::Capture(id) => TailPattern::Capture(self.ident(id)),
+        }
+    }
+
+    fn pattern(&self, pat: CachedPattern) -> Pattern {
+        let data = match pat.data {
+            CachedPatternData::Wildcard => PatternData::Wildcard,
+            CachedPatternData::Any(id) => PatternData::Any(self.ident(id)),
+            CachedPatternData::Record {
+                patterns,
+                tail,
+                pos,
+            } => PatternData::Record(RecordPattern {
+                patterns: patterns
+                    .into_iter()
+                    .map(|field_pat| FieldPattern {
+                        matched_id: self.ident(field_pat.matched_id),
+                        annotation: self.annotation(field_pat.annotation),
+                        default: field_pat.default.map(|t| self.term(t)),
+                        pattern: self.pattern(field_pat.pattern),
+                        pos: self.pos(field_pat.pos),
+                    })
+                    .collect(),
+                tail: self.tail_pattern(tail),
+                pos: self.pos(pos),
+            }),
+            CachedPatternData::Array {
+                patterns,
+                tail,
+                pos,
+            } => PatternData::Array(ArrayPattern {
+                patterns: self.patterns(patterns),
+                tail: self.tail_pattern(tail),
+                pos: self.pos(pos),
+            }),
+            CachedPatternData::Enum { tag, pattern, pos } => PatternData::Enum(EnumPattern {
+                tag: self.ident(tag),
+                pattern: pattern.map(|pat| Box::new(self.pattern(*pat))),
+                pos: self.pos(pos),
+            }),
+            CachedPatternData::Constant { data, pos } => PatternData::Constant(ConstantPattern {
+                data: match data {
+                    CachedConstant::Bool(b) => ConstantPatternData::Bool(b),
+                    CachedConstant::Number(n) => ConstantPatternData::Number(n),
+                    CachedConstant::String(s) => ConstantPatternData::String(s),
+                    CachedConstant::Null => ConstantPatternData::Null,
+                },
+                pos: self.pos(pos),
+            }),
+            CachedPatternData::Or { patterns, pos } => PatternData::Or(OrPattern {
+                patterns: self.patterns(patterns),
+                pos: self.pos(pos),
+            }),
+        };
+
+        Pattern {
+            data,
+            alias: pat.alias.map(|id| self.ident(id)),
+            pos: self.pos(pat.pos),
+        }
+    }
+
+    fn boxed_typ(&self, typ: Box<CachedType>) -> Box<Type> {
+        Box::new(self.typ(*typ))
+    }
+
+    fn typ(&self, cached: CachedType) -> Type {
+        let excluded = |labels: Vec<String>| labels.into_iter().map(Ident::new).collect();
+
+        let typ = match cached.typ {
+            CachedTypeF::Dyn => TypeF::Dyn,
+            CachedTypeF::Number => TypeF::Number,
+            CachedTypeF::Bool => TypeF::Bool,
+            CachedTypeF::String => TypeF::String,
+            CachedTypeF::Symbol => TypeF::Symbol,
+            CachedTypeF::ForeignId => TypeF::ForeignId,
+            CachedTypeF::Flat(t) => TypeF::Flat(self.term(t)),
+            CachedTypeF::Arrow(dom, codom) => {
+                TypeF::Arrow(self.boxed_typ(dom), self.boxed_typ(codom))
+            }
+            CachedTypeF::Var(label) => TypeF::Var(Ident::new(label)),
+            CachedTypeF::Forall {
+                var,
+                var_kind,
+                body,
+            } => TypeF::Forall {
+                var: self.ident(var),
+                var_kind: match var_kind {
+                    CachedVarKind::Type => VarKind::Type,
+                    CachedVarKind::EnumRows(labels) => VarKind::EnumRows {
+                        excluded: excluded(labels),
+                    },
+                    CachedVarKind::RecordRows(labels) => VarKind::RecordRows {
+                        excluded: excluded(labels),
+                    },
+                },
+                body: self.boxed_typ(body),
+            },
+            CachedTypeF::Enum(erows) => TypeF::Enum(self.enum_rows(erows)),
+            CachedTypeF::Record(rrows) => TypeF::Record(self.record_rows(rrows)),
+            CachedTypeF::Dict {
+                type_fields,
+                flavour,
+            } => TypeF::Dict {
+                type_fields: self.boxed_typ(type_fields),
+                flavour,
+            },
+            CachedTypeF::Array(elt) => TypeF::Array(self.boxed_typ(elt)),
+            CachedTypeF::Wildcard(id) => TypeF::Wildcard(id),
+        };
+
+        Type {
+            typ,
+            pos: self.pos(cached.pos),
+        }
+    }
+
+    fn record_rows(&self, rrows: CachedRecordRows) -> RecordRows {
+        RecordRows(match rrows {
+            CachedRecordRows::Empty => RecordRowsF::Empty,
+            CachedRecordRows::Extend { id, typ, tail } => RecordRowsF::Extend {
+                row: RecordRow {
+                    id: self.ident(id),
+                    typ: self.boxed_typ(typ),
+                },
+                tail: Box::new(self.record_rows(*tail)),
+            },
+            CachedRecordRows::TailVar(id) => RecordRowsF::TailVar(self.ident(id)),
+            CachedRecordRows::TailDyn => RecordRowsF::TailDyn,
+        })
+    }
+
+    fn enum_rows(&self, erows: CachedEnumRows) -> EnumRows {
+        EnumRows(match erows {
+            CachedEnumRows::Empty => EnumRowsF::Empty,
+            CachedEnumRows::Extend { id, typ, tail } => EnumRowsF::Extend {
+                row: EnumRow {
+                    id: self.ident(id),
+                    typ: typ.map(|typ| self.boxed_typ(typ)),
+                },
+                tail: Box::new(self.enum_rows(*tail)),
+            },
+            CachedEnumRows::TailVar(id) => EnumRowsF::TailVar(self.ident(id)),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cache::{Cache, ErrorTolerance, InputFormat};
+
+    #[test]
+    fn encode_decode_roundtrip() {
+        let source = r#"
+            let rec f | forall a. a -> Array a = fun x => [x] in
+            let { foo, bar ? 1, ..rest } = { foo = 1, "%{"ba" ++ "z"}" = 2, qux | String = "a" } in
+            {
+              a = f foo |> std.array.length,
+              b = match { 'Some x if x > 0 => x, _ => bar },
+              c = { x | default = 1 } & { x = 2 },
+              d = m%"interpolated %{std.to_string bar}"%,
+              e : { x : Number, y : [| 'A, 'B Number |] } = { x = 1, y = 'A },
+              f = import "lib.ncl",
+              g.h | Number = 1,
+            }
+        "#;
+
+        let mut cache = Cache::new(ErrorTolerance::Strict);
+        let file_id = cache.add_string(
+            crate::cache::SourcePath::Generated("test".into()),
+            source.to_owned(),
+        );
+        let (term, errs) = cache.parse_nocache(file_id).unwrap();
+        assert!(errs.no_errors());
+
+        let bytes = encode(&term, file_id).expect("parsed terms should be cacheable");
+        assert_eq!(decode(&bytes, file_id), Some(term));
+    }
+
+    #[test]
+    fn second_load_hits_cache() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("main.ncl");
+        fs::write(&path, "{ foo | Number = 1, bar = foo + 1 }").unwrap();
+
+        let parse = || {
+            let mut cache = Cache::new(ErrorTolerance::Strict);
+            cache.set_ast_cache(AstCache::new(dir.path().join("cache")));
+            let file_id = cache.add_file(&path).unwrap();
+            cache.parse(file_id, InputFormat::Nickel).unwrap();
+            let ast_cache = cache.ast_cache().unwrap();
+            (
+                cache.get_owned(file_id).unwrap(),
+                ast_cache.hits(),
+                ast_cache.misses(),
+            )
+        };
+
+        let (first, hits, misses) = parse();
+        assert_eq!((hits, misses), (0, 1));
+        let (second, hits, misses) = parse();
+        assert_eq!((hits, misses), (1, 0));
+        assert_eq!(first, second);
+    }
+}
@@ -0,0 +1,343 @@
+//! Structural comparison of terms, for regression tests.
+//!
+//! Comparing two large terms with `assert_eq!` dumps their whole debug representation, which
+//! makes it hard to tell where they actually differ. [ast_diff] instead walks both terms in
+//! parallel and returns the first node (in pre-order) which differs, together with the path
+//! leading to it from the root.
+//!
+//! Positions are ignored by default, so that a term can be compared with the same term parsed from
+//! a slightly different source. This includes the spans recorded in the labels of annotations. Use
+//! [ast_diff_with] and [DiffOptions] to take them into account.
+use std::{convert::Infallible, fmt};
+
+use crate::{
+    identifier::LocIdent,
+    label::Label,
+    term::{
+        record::RecordData, MatchBranch, MatchData, RichTerm, StrChunk, Term, Traverse,
+        TraverseOrder, TypeAnnotation,
+    },
+};
+
+/// Options of [ast_diff_with].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct DiffOptions {
+    /// Consider two nodes with different positions to be different.
+    pub compare_positions: bool,
+}
+
+/// A step of the path from the root of a term to one of its subterms.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PathElem {
+    /// The definition of a record field.
+    Field(LocIdent),
+    /// An element of an array, an argument of a primitive operator, an interpolated chunk of a
+    /// string, or the body of a branch of a match expression.
+    Index(usize),
+    /// A named child of a node, such as the function (`fun`) or the argument (`arg`) of an
+    /// application.
+    Child(&'static str),
+}
+
+impl fmt::Display for PathElem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PathElem::Field(id) => write!(f, ".{id}"),
+            PathElem::Index(idx) => write!(f, "[{idx}]"),
+            PathElem::Child(name) => write!(f, "/{name}"),
+        }
+    }
+}
+
+/// The first difference between two terms.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AstDiff {
+    /// The path from the root to the differing nodes. It's empty if the roots themselves differ.
+    pub path: Vec<PathElem>,
+    /// The differing node of the first term.
+    pub left: RichTerm,
+    /// The differing node of the second term.
+    pub right: RichTerm,
+}
+
+impl fmt::Display for AstDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "terms differ at <root>")?;
+
+        for elem in self.path.iter() {
+            write!(f, "{elem}")?;
+        }
+
+        write!(f, ":\n  left: {}\n right: {}", self.left, self.right)
+    }
+}
+
+/// Compare two terms, ignoring positions, and return their first difference, if any.
+pub fn ast_diff(left: &RichTerm, right: &RichTerm) -> Option<AstDiff> {
+    ast_diff_with(left, right, DiffOptions::default())
+}
+
+/// Compare two terms according to `options`, and return their first difference, if any.
+pub fn ast_diff_with(left: &RichTerm, right: &RichTerm, options: DiffOptions) -> Option<AstDiff> {
+    Differ { options }.diff(left, right, &mut Vec::new())
+}
+
+struct Differ {
+    options: DiffOptions,
+}
+
+impl Differ {
+    fn same(&self, left: &RichTerm, right: &RichTerm) -> bool {
+        if self.options.compare_positions {
+            left == right
+        } else {
+            without_pos(left.clone()) == without_pos(right.clone())
+        }
+    }
+
+    fn diff(&self, left: &RichTerm, right: &RichTerm, path: &mut Vec<PathElem>) -> Option<AstDiff> {
+        if self.same(left, right) {
+            return None;
+        }
+
+        let same_node = (!self.options.compare_positions || left.pos == right.pos)
+            && self.same(&skeleton(left).into(), &skeleton(right).into());
+
+        if same_node {
+            // The skeletons are the same, so the children match pairwise.
+            for ((elem, left_child), (_, right_child)) in
+                children(left).into_iter().zip(children(right))
+            {
+                path.push(elem);
+
+                if let Some(diff) = self.diff(left_child, right_child, path) {
+                    return Some(diff);
+                }
+
+                path.pop();
+            }
+        }
+
+        Some(AstDiff {
+            path: path.clone(),
+            left: left.clone(),
+            right: right.clone(),
+        })
+    }
+}
+
+/// Erase the positions of a term, as [RichTerm::without_pos], as well as the labels of its
+/// annotations, which are generated by the parser from the position of the annotation.
+fn without_pos(rt: RichTerm) -> RichTerm {
+    fn erase_labels(annot: &mut TypeAnnotation) {
+        for labeled_ty in annot.iter_mut() {
+            labeled_ty.label = Label::default();
+        }
+    }
+
+    fn erase_record_labels(data: &mut RecordData) {
+        for field in data.fields.values_mut() {
+            erase_labels(&mut field.metadata.annotation);
+        }
+    }
+
+    rt.without_pos()
+        .traverse(
+            &mut |rt: RichTerm| -> Result<_, Infallible> {
+                if !matches!(
+                    rt.as_ref(),
+                    Term::Annotated(..) | Term::Record(..) | Term::RecRecord(..)
+                ) {
+                    return Ok(rt);
+                }
+
+                let term = match rt.term.into_owned() {
+                    Term::Annotated(mut annot, inner) => {
+                        erase_labels(&mut annot);
+                        Term::Annotated(annot, inner)
+                    }
+                    Term::Record(mut data) => {
+                        erase_record_labels(&mut data);
+                        Term::Record(data)
+                    }
+                    Term::RecRecord(mut data, mut dyn_fields, deps) => {
+                        erase_record_labels(&mut data);
+
+                        for (_, field) in dyn_fields.iter_mut() {
+                            erase_labels(&mut field.metadata.annotation);
+                        }
+
+                        Term::RecRecord(data, dyn_fields, deps)
+                    }
+                    t => t,
+                };
+
+                Ok(RichTerm::new(term, rt.pos))
+            },
+            TraverseOrder::TopDown,
+        )
+        .unwrap()
+}
+
+/// The children of a node which are compared recursively, in order.
+fn children(rt: &RichTerm) -> Vec<(PathElem, &RichTerm)> {
+    match rt.as_ref() {
+        Term::Fun(_, body) | Term::FunPattern(_, body) => vec![(PathElem::Child("body"), body)],
+        Term::Let(_, bound, body, _) | Term::LetPattern(_, bound, body) => vec![
+            (PathElem::Child("bound"), bound),
+            (PathElem::Child("body"), body),
+        ],
+        Term::App(fun, arg) => vec![(PathElem::Child("fun"), fun), (PathElem::Child("arg"), arg)],
+        Term::Op1(_, arg) => vec![(PathElem::Index(0), arg)],
+        Term::Op2(_, arg1, arg2) => vec![(PathElem::Index(0), arg1), (PathElem::Index(1), arg2)],
+        Term::OpN(_, args) => indexed(args.iter()),
+        Term::Array(ts, _) => indexed(ts.iter()),
+        Term::Record(data) | Term::RecRecord(data, ..) => data
+            .fields
+            .iter()
+            .filter_map(|(id, field)| Some((PathElem::Field(*id), field.value.as_ref()?)))
+            .collect(),
+        Term::EnumVariant { arg, .. } => vec![(PathElem::Child("arg"), arg)],
+        Term::Annotated(_, inner) => vec![(PathElem::Child("inner"), inner)],
+        Term::StrChunks(chunks) => chunks
+            .iter()
+            .enumerate()
+            .filter_map(|(idx, chunk)| match chunk {
+                StrChunk::Expr(expr, _) => Some((PathElem::Index(idx), expr)),
+                StrChunk::Literal(_) => None,
+            })
+            .collect(),
+        Term::Match(data) => indexed(data.branches.iter().map(|branch| &branch.body)),
+        _ => Vec::new(),
+    }
+}
+
+fn indexed<'a>(ts: impl Iterator<Item = &'a RichTerm>) -> Vec<(PathElem, &'a RichTerm)> {
+    ts.enumerate()
+        .map(|(idx, t)| (PathElem::Index(idx), t))
+        .collect()
+}
+
+/// A copy of a node where the [children] are replaced with `null`. Two nodes with the same
+/// skeleton only differ in their children.
+fn skeleton(rt: &RichTerm) -> Term {
+    let hole = || RichTerm::from(Term::Null);
+
+    let record_skeleton = |data: &RecordData| {
+        let mut data = data.clone();
+
+        for field in data.fields.values_mut() {
+            field.value = field.value.as_ref().map(|_| hole());
+        }
+
+        data
+    };
+
+    match rt.as_ref() {
+        Term::Fun(id, _) => Term::Fun(*id, hole()),
+        Term::FunPattern(pat, _) => Term::FunPattern(pat.clone(), hole()),
+        Term::Let(id, _, _, attrs) => Term::Let(*id, hole(), hole(), attrs.clone()),
+        Term::LetPattern(pat, _, _) => Term::LetPattern(pat.clone(), hole(), hole()),
+        Term::App(..) => Term::App(hole(), hole()),
+        Term::Op1(op, _) => Term::Op1(op.clone(), hole()),
+        Term::Op2(op, _, _) => Term::Op2(op.clone(), hole(), hole()),
+        Term::OpN(op, args) => Term::OpN(op.clone(), args.iter().map(|_| hole()).collect()),
+        Term::Array(ts, attrs) => Term::Array(ts.iter().map(|_| hole()).collect(), attrs.clone()),
+        Term::Record(data) => Term::Record(record_skeleton(data)),
+        Term::RecRecord(data, dyn_fields, deps) => {
+            Term::RecRecord(record_skeleton(data), dyn_fields.clone(), deps.clone())
+        }
+        Term::EnumVariant { tag, attrs, .. } => Term::EnumVariant {
+            tag: *tag,
+            arg: hole(),
+            attrs: attrs.clone(),
+        },
+        Term::Annotated(annot, _) => Term::Annotated(annot.clone(), hole()),
+        Term::StrChunks(chunks) => Term::StrChunks(
+            chunks
+                .iter()
+                .map(|chunk| match chunk {
+                    StrChunk::Literal(s) => StrChunk::Literal(s.clone()),
+                    StrChunk::Expr(_, indent) => StrChunk::Expr(hole(), *indent),
+                })
+                .collect(),
+        ),
+        Term::Match(data) => Term::Match(MatchData {
+            branches: data
+                .branches
+                .iter()
+                .map(|branch| MatchBranch {
+                    pattern: branch.pattern.clone(),
+                    guard: branch.guard.clone(),
+                    body: hole(),
+                })
+                .collect(),
+        }),
+        t => t.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        parser::{grammar::TermParser, lexer::Lexer, ErrorTolerantParser},
+        term::make,
+    };
+    use codespan::Files;
+
+    fn parse(s: &str) -> RichTerm {
+        let id = Files::new().add("<test>", String::from(s));
+
+        TermParser::new().parse_strict(id, Lexer::new(s)).unwrap()
+    }
+
+    #[test]
+    fn reports_path_to_differing_leaf() {
+        let left = parse("{ foo = { bar = [1, 2, fun x => x + 1] }, baz = 'Tag }");
+        let right = parse("{ foo = { bar = [1, 2, fun x => x + 2] }, baz = 'Tag }");
+
+        let diff = ast_diff(&left, &right).unwrap();
+
+        assert_eq!(
+            diff.path,
+            [
+                PathElem::Field("foo".into()),
+                PathElem::Field("bar".into()),
+                PathElem::Index(2),
+                PathElem::Child("body"),
+                PathElem::Index(1),
+            ]
+        );
+        assert_eq!(diff.left.without_pos(), make::integer(1));
+        assert_eq!(diff.right.without_pos(), make::integer(2));
+    }
+
+    #[test]
+    fn reports_differing_node() {
+        // The operators differ, so the node itself is reported even if its arguments also differ.
+        let diff = ast_diff(&parse("let x = 1 in x + 1"), &parse("let x = 1 in x - 2")).unwrap();
+        assert_eq!(diff.path, [PathElem::Child("body")]);
+
+        let diff = ast_diff(&parse("{ a = 1 }"), &parse("{ b = 1 }")).unwrap();
+        assert!(diff.path.is_empty());
+    }
+
+    #[test]
+    fn positions() {
+        let left = parse("{ foo | Number = 1 }");
+        let right = parse("{foo|Number=1}");
+
+        assert_eq!(ast_diff(&left, &right), None);
+
+        let diff = ast_diff_with(
+            &left,
+            &right,
+            DiffOptions {
+                compare_positions: true,
+            },
+        )
+        .unwrap();
+        assert!(diff.path.is_empty());
+    }
+}
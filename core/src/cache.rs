@@ -1,13 +1,16 @@
 //! Source cache.
 
+use crate::ast_cache::AstCache;
 use crate::error::{Error, ImportError, ParseError, ParseErrors, TypecheckError};
 use crate::eval::cache::Cache as EvalCache;
 use crate::eval::Closure;
+use crate::identifier::FieldPath;
 #[cfg(feature = "nix-experimental")]
 use crate::nix_ffi;
 use crate::parser::{lexer::Lexer, ErrorTolerantParser};
 use crate::position::TermPos;
-use crate::program::FieldPath;
+#[cfg(feature = "remote-imports")]
+use crate::remote_import::{self, RemoteImports};
 use crate::stdlib::{self as nickel_stdlib, StdlibModule};
 use crate::term::array::Array;
 use crate::term::record::{Field, RecordData};
@@ -101,6 +104,19 @@ pub struct Cache {
     /// Whether processing should try to continue even in case of errors. Needed by the NLS.
     error_tolerance: ErrorTolerance,
     import_paths: Vec<PathBuf>,
+    /// If set, imports may only resolve to files located inside this directory. The path is
+    /// canonicalized.
+    sandbox_root: Option<PathBuf>,
+    /// An optional on-disk cache of parsed terms, used to skip parsing unchanged sources across
+    /// runs.
+    ast_cache: Option<AstCache>,
+    /// The configuration of remote imports.
+    #[cfg(feature = "remote-imports")]
+    remote_imports: RemoteImports,
+    /// The URL of each file which was imported remotely, used to resolve the relative imports
+    /// of these files.
+    #[cfg(feature = "remote-imports")]
+    remote_origins: HashMap<FileId, String>,
 
     #[cfg(debug_assertions)]
     /// Skip loading the stdlib, used for debugging purpose
@@ -349,6 +365,12 @@ impl Cache {
             stdlib_ids: None,
             error_tolerance,
             import_paths: Vec::new(),
+            sandbox_root: None,
+            ast_cache: None,
+            #[cfg(feature = "remote-imports")]
+            remote_imports: RemoteImports::from_env(),
+            #[cfg(feature = "remote-imports")]
+            remote_origins: HashMap::new(),
 
             #[cfg(debug_assertions)]
             skip_stdlib: false,
@@ -362,6 +384,126 @@ impl Cache {
         self.import_paths.extend(paths.map(PathBuf::from));
     }
 
+    /// Restrict imports to files located inside `root`. Any import resolving to a file outside of
+    /// this directory, once symbolic links and `..` components have been resolved, fails with
+    /// [ImportError::OutsideSandbox]. Remote imports are rejected as well. The root of the sandbox
+    /// must exist.
+    pub fn set_sandbox_root(&mut self, root: impl AsRef<Path>) -> io::Result<()> {
+        self.sandbox_root = Some(root.as_ref().canonicalize()?);
+        Ok(())
+    }
+
+    /// Override the configuration of remote imports, which is read from the environment by
+    /// default. See [crate::remote_import].
+    #[cfg(feature = "remote-imports")]
+    pub fn set_remote_imports(&mut self, remote_imports: RemoteImports) {
+        self.remote_imports = remote_imports;
+    }
+
+    /// The URL to fetch when importing `path` from `parent`, if this import is remote: either
+    /// `path` is a URL, or `parent` has been imported remotely and `path` is relative.
+    ///
+    /// A file imported remotely can't import local files: importing an absolute path from such a
+    /// file is an error, instead of falling back to the local filesystem.
+    #[cfg(feature = "remote-imports")]
+    fn remote_url(
+        &self,
+        path: &OsStr,
+        parent: Option<FileId>,
+        pos: &TermPos,
+    ) -> Result<Option<String>, ImportError> {
+        let Some(origin) = parent.and_then(|parent| self.remote_origins.get(&parent)) else {
+            return Ok(path
+                .to_str()
+                .filter(|path| remote_import::is_url(path))
+                .map(str::to_owned));
+        };
+
+        match path.to_str() {
+            Some(path) if remote_import::is_url(path) => Ok(Some(path.to_owned())),
+            Some(path) if Path::new(path).is_relative() => {
+                Ok(Some(remote_import::join(origin, path)))
+            }
+            _ => Err(ImportError::IOError(
+                path.to_string_lossy().into_owned(),
+                format!("a file imported from {origin} can only import URLs or relative paths"),
+                *pos,
+            )),
+        }
+    }
+
+    /// Import the remote file at `url`, by fetching a local copy and importing it instead.
+    #[cfg(feature = "remote-imports")]
+    fn resolve_remote(
+        &mut self,
+        path: &OsStr,
+        url: String,
+        parent: Option<FileId>,
+        pos: &TermPos,
+    ) -> Result<(ResolvedTerm, FileId), ImportError> {
+        let io_error =
+            |msg: String| ImportError::IOError(path.to_string_lossy().into_owned(), msg, *pos);
+
+        let local = self
+            .remote_imports
+            .fetch(&url)
+            .map_err(|err| io_error(err.to_string()))?;
+        let id_op = self
+            .get_or_add_file(&local)
+            .map_err(|err| io_error(err.to_string()))?;
+
+        let (result, file_id) = self.register_import(id_op, local, parent, pos)?;
+        self.remote_origins.insert(file_id, url);
+
+        Ok((result, file_id))
+    }
+
+    /// Record that `parent` imports the file added to the cache by `id_op`, and parse this file.
+    fn register_import(
+        &mut self,
+        id_op: CacheOp<FileId>,
+        path_buf: PathBuf,
+        parent: Option<FileId>,
+        pos: &TermPos,
+    ) -> Result<(ResolvedTerm, FileId), ImportError> {
+        let format = InputFormat::from_path(&path_buf).unwrap_or_default();
+        let (result, file_id) = match id_op {
+            CacheOp::Cached(id) => (ResolvedTerm::FromCache, id),
+            CacheOp::Done(id) => (ResolvedTerm::FromFile { path: path_buf }, id),
+        };
+
+        if let Some(parent) = parent {
+            self.imports.entry(parent).or_default().insert(file_id);
+            self.rev_imports.entry(file_id).or_default().insert(parent);
+        }
+
+        self.parse(file_id, format)
+            .map_err(|err| ImportError::ParseErrors(err, *pos))?;
+
+        Ok((result, file_id))
+    }
+
+    /// Check that `path` is inside the sandbox, if any. A path which can't be canonicalized,
+    /// typically because it doesn't exist, is accepted: it can't be read either, and the import
+    /// fails with the usual IO error.
+    fn is_in_sandbox(&self, path: &Path) -> bool {
+        match (&self.sandbox_root, path.canonicalize()) {
+            (Some(root), Ok(path)) => path.starts_with(root),
+            _ => true,
+        }
+    }
+
+    /// Enable the on-disk cache of parsed terms. Nickel sources (including imports) are then
+    /// looked up in this cache before being parsed, and stored there after a successful parse.
+    pub fn set_ast_cache(&mut self, ast_cache: AstCache) {
+        self.ast_cache = Some(ast_cache);
+    }
+
+    /// Return the on-disk cache of parsed terms, if enabled.
+    pub fn ast_cache(&self) -> Option<&AstCache> {
+        self.ast_cache.as_ref()
+    }
+
     /// Same as [Self::add_file], but assume that the path is already normalized, and take the
     /// timestamp as a parameter.
     fn add_file_(&mut self, path: PathBuf, timestamp: SystemTime) -> io::Result<FileId> {
@@ -475,7 +617,7 @@ impl Cache {
         if let Some(TermEntry { parse_errs, .. }) = self.terms.get(&file_id) {
             Ok(CacheOp::Cached(parse_errs.clone()))
         } else {
-            let (term, parse_errs) = self.parse_nocache_multi(file_id, format)?;
+            let (term, parse_errs) = self.parse_with_ast_cache(file_id, format)?;
             self.terms.insert(
                 file_id,
                 TermEntry {
@@ -508,6 +650,37 @@ impl Cache {
         }
     }
 
+    /// Parse a source, going through the on-disk cache of parsed terms if it is enabled and the
+    /// source is a Nickel source. Only sources which parsed without errors are stored.
+    fn parse_with_ast_cache(
+        &mut self,
+        file_id: FileId,
+        format: InputFormat,
+    ) -> Result<(RichTerm, ParseErrors), ParseError> {
+        if format != InputFormat::Nickel || self.ast_cache.is_none() {
+            return self.parse_nocache_multi(file_id, format);
+        }
+
+        let source = self.files.source(file_id);
+
+        if let Some(term) = self
+            .ast_cache
+            .as_mut()
+            .and_then(|ast_cache| ast_cache.load(file_id, source))
+        {
+            return Ok((term, ParseErrors::none()));
+        }
+
+        let (term, parse_errs) = self.parse_nocache_multi(file_id, format)?;
+
+        if let Some(ast_cache) = self.ast_cache.as_ref().filter(|_| parse_errs.no_errors()) {
+            // The AST cache is only an optimization: failing to write an entry isn't an error.
+            let _ = ast_cache.store(file_id, self.files.source(file_id), &term);
+        }
+
+        Ok((term, parse_errs))
+    }
+
     /// Parse a source without querying nor populating the cache.
     pub fn parse_nocache(&self, file_id: FileId) -> Result<(RichTerm, ParseErrors), ParseError> {
         self.parse_nocache_multi(file_id, InputFormat::default())
@@ -1336,6 +1509,36 @@ impl ImportResolver for Cache {
         parent: Option<FileId>,
         pos: &TermPos,
     ) -> Result<(ResolvedTerm, FileId), ImportError> {
+        let is_url = path
+            .to_str()
+            .is_some_and(|path| path.starts_with("http://") || path.starts_with("https://"));
+
+        // A remote file can't be checked to be inside the sandbox, so remote imports are
+        // disabled altogether in a sandbox.
+        if is_url && self.sandbox_root.is_some() {
+            return Err(ImportError::IOError(
+                path.to_string_lossy().into_owned(),
+                String::from("remote imports are disabled when imports are sandboxed"),
+                *pos,
+            ));
+        }
+
+        #[cfg(feature = "remote-imports")]
+        if let Some(url) = self.remote_url(path, parent, pos)? {
+            return self.resolve_remote(path, url, parent, pos);
+        }
+
+        #[cfg(not(feature = "remote-imports"))]
+        if is_url {
+            return Err(ImportError::IOError(
+                path.to_string_lossy().into_owned(),
+                String::from(
+                    "importing a URL requires Nickel to be built with the `remote-imports` feature",
+                ),
+                *pos,
+            ));
+        }
+
         // `parent` is the file that did the import. We first look in its containing directory.
         let mut parent_path = parent
             .and_then(|p| self.get_path(p))
@@ -1347,15 +1550,33 @@ impl ImportResolver for Cache {
             .chain(self.import_paths.iter().cloned())
             .collect();
 
+        // The first candidate which was rejected because it's outside the sandbox, if any.
+        let mut outside_sandbox = None;
+
         // Try to import from all possibilities, taking the first one that succeeds.
         let (id_op, path_buf) = possible_parents
             .iter()
             .find_map(|parent| {
                 let mut path_buf = parent.clone();
                 path_buf.push(path);
+
+                if !self.is_in_sandbox(&path_buf) {
+                    outside_sandbox
+                        .get_or_insert_with(|| path_buf.canonicalize().unwrap_or(path_buf));
+                    return None;
+                }
+
                 self.get_or_add_file(&path_buf).ok().map(|x| (x, path_buf))
             })
             .ok_or_else(|| {
+                if let Some(resolved) = outside_sandbox {
+                    return ImportError::OutsideSandbox {
+                        path: path.to_string_lossy().into_owned(),
+                        resolved,
+                        pos: *pos,
+                    };
+                }
+
                 let parents = possible_parents
                     .iter()
                     .map(|p| p.to_string_lossy())
@@ -1367,21 +1588,7 @@ impl ImportResolver for Cache {
                 )
             })?;
 
-        let format = InputFormat::from_path(&path_buf).unwrap_or_default();
-        let (result, file_id) = match id_op {
-            CacheOp::Cached(id) => (ResolvedTerm::FromCache, id),
-            CacheOp::Done(id) => (ResolvedTerm::FromFile { path: path_buf }, id),
-        };
-
-        if let Some(parent) = parent {
-            self.imports.entry(parent).or_default().insert(file_id);
-            self.rev_imports.entry(file_id).or_default().insert(parent);
-        }
-
-        self.parse(file_id, format)
-            .map_err(|err| ImportError::ParseErrors(err, *pos))?;
-
-        Ok((result, file_id))
+        self.register_import(id_op, path_buf, parent, pos)
     }
 
     fn get(&self, file_id: FileId) -> Option<RichTerm> {
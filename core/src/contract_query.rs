@@ -0,0 +1,206 @@
+//! Find the contracts that a value satisfies.
+//!
+//! This is used by tooling to suggest annotations, for example when completing the contract of a
+//! field whose value is already known. Only builtin types (including record shapes) and the
+//! scalar contracts of the standard library are considered for now.
+use std::fmt;
+
+use malachite::num::{basic::traits::Zero, conversion::traits::IsInteger};
+
+use crate::{
+    identifier::LocIdent,
+    term::{record::RecordData, Number, RichTerm, Term},
+    typ::{
+        DictTypeFlavour, EnumRow, EnumRows, EnumRowsF, RecordRow, RecordRows, RecordRowsF, Type,
+        TypeF,
+    },
+};
+
+/// A contract that a value satisfies.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ContractInfo {
+    /// A type used as a contract, such as `Number` or `{ foo : String }`.
+    Type(Type),
+    /// A contract of the standard library, given by its path, such as `std.number.Nat`.
+    Stdlib(&'static str),
+}
+
+impl fmt::Display for ContractInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ContractInfo::Type(typ) => write!(f, "{typ}"),
+            ContractInfo::Stdlib(path) => write!(f, "{path}"),
+        }
+    }
+}
+
+/// Return the contracts that `value` would satisfy, from the most specific to the least specific.
+///
+/// `value` is expected to be fully evaluated: the parts of the value that aren't (such as a field
+/// whose value is an unevaluated expression) are only known to satisfy `Dyn`. The first contract
+/// returned is always the most precise type of `value`, and the last one is always `Dyn`.
+pub fn applicable_contracts(value: &RichTerm) -> Vec<ContractInfo> {
+    let typ = principal_type(value);
+    let is_dyn = matches!(typ.typ, TypeF::Dyn);
+    let mut contracts = vec![ContractInfo::Type(typ)];
+
+    match value.as_ref() {
+        Term::Num(n) if n.is_integer() => {
+            contracts.push(ContractInfo::Stdlib("std.number.Integer"));
+
+            if n >= &Number::ZERO {
+                contracts.push(ContractInfo::Stdlib("std.number.Nat"));
+            }
+
+            if n > &Number::ZERO {
+                contracts.push(ContractInfo::Stdlib("std.number.PosNat"));
+            }
+        }
+        Term::Str(s) if !s.is_empty() => {
+            contracts.push(ContractInfo::Stdlib("std.string.NonEmpty"));
+        }
+        Term::Enum(_) => {
+            contracts.push(ContractInfo::Stdlib("std.enum.Tag"));
+        }
+        Term::Array(elts, _) => {
+            if !elts.is_empty() {
+                contracts.push(ContractInfo::Stdlib("std.array.NonEmpty"));
+            }
+        }
+        Term::Record(data) | Term::RecRecord(data, ..) => {
+            if let Some(typ) = dict_type(data) {
+                contracts.push(ContractInfo::Type(typ));
+            }
+        }
+        _ => (),
+    }
+
+    if !is_dyn {
+        contracts.push(ContractInfo::Type(Type::from(TypeF::Dyn)));
+    }
+
+    contracts
+}
+
+/// The most precise type of a value that is expressible as a type. For example, the principal
+/// type of `{ foo = 1, bar = ['Baz] }` is `{ foo : Number, bar : Array [| 'Baz |] }`.
+fn principal_type(value: &RichTerm) -> Type {
+    let typ = match value.as_ref() {
+        Term::Num(_) => TypeF::Number,
+        Term::Str(_) | Term::StrChunks(_) => TypeF::String,
+        Term::Bool(_) => TypeF::Bool,
+        Term::Enum(tag) => TypeF::Enum(single_enum_row(*tag, None)),
+        Term::EnumVariant { tag, arg, .. } => {
+            TypeF::Enum(single_enum_row(*tag, Some(principal_type(arg))))
+        }
+        Term::Array(elts, _) => TypeF::Array(Box::new(common_type(elts.iter()))),
+        Term::Record(data) | Term::RecRecord(data, ..) => TypeF::Record(record_shape(data)),
+        _ => TypeF::Dyn,
+    };
+
+    Type::from(typ)
+}
+
+/// The principal type shared by all the given values, or `Dyn` if they don't have the same
+/// principal type or if there are no values at all.
+fn common_type<'a>(mut values: impl Iterator<Item = &'a RichTerm>) -> Type {
+    let Some(first) = values.next().map(principal_type) else {
+        return Type::from(TypeF::Dyn);
+    };
+
+    if values.all(|value| principal_type(value) == first) {
+        first
+    } else {
+        Type::from(TypeF::Dyn)
+    }
+}
+
+fn single_enum_row(id: LocIdent, typ: Option<Type>) -> EnumRows {
+    EnumRows(EnumRowsF::Extend {
+        row: EnumRow {
+            id,
+            typ: typ.map(Box::new),
+        },
+        tail: Box::new(EnumRows(EnumRowsF::Empty)),
+    })
+}
+
+/// The closed record type with one row per defined field of `data`. Fields without a value are
+/// ignored.
+fn record_shape(data: &RecordData) -> RecordRows {
+    data.fields
+        .iter()
+        .rev()
+        .filter_map(|(id, field)| Some((*id, principal_type(field.value.as_ref()?))))
+        .fold(RecordRows(RecordRowsF::Empty), |tail, (id, typ)| {
+            RecordRows(RecordRowsF::Extend {
+                row: RecordRow {
+                    id,
+                    typ: Box::new(typ),
+                },
+                tail: Box::new(tail),
+            })
+        })
+}
+
+/// The dictionary type `{ _ : T }` satisfied by `data`, if all of its fields have the same
+/// principal type `T`. Return `None` if `data` has no field with a value.
+fn dict_type(data: &RecordData) -> Option<Type> {
+    let mut values = data.fields.values().filter_map(|field| field.value.as_ref());
+    let first = principal_type(values.next()?);
+
+    values
+        .all(|value| principal_type(value) == first)
+        .then(|| {
+            Type::from(TypeF::Dict {
+                type_fields: Box::new(first),
+                flavour: DictTypeFlavour::Type,
+            })
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{grammar::TermParser, lexer::Lexer, ErrorTolerantParser};
+    use codespan::Files;
+
+    fn contracts(s: &str) -> Vec<String> {
+        let id = Files::new().add("<test>", s);
+        let value = TermParser::new().parse_strict(id, Lexer::new(s)).unwrap();
+
+        applicable_contracts(&value)
+            .iter()
+            .map(ToString::to_string)
+            .collect()
+    }
+
+    #[test]
+    fn scalars() {
+        let number = contracts("5");
+        assert_eq!(number[0], "Number");
+        assert!(number.contains(&String::from("std.number.PosNat")));
+        assert!(!number.contains(&String::from("String")));
+        assert_eq!(number.last().unwrap(), "Dyn");
+
+        assert_eq!(contracts("0.5"), ["Number", "Dyn"]);
+        assert_eq!(contracts("\"\""), ["String", "Dyn"]);
+        assert_eq!(contracts("true"), ["Bool", "Dyn"]);
+        assert_eq!(contracts("null"), ["Dyn"]);
+    }
+
+    #[test]
+    fn record_shapes() {
+        assert_eq!(
+            contracts("{ foo = 1, bar = { baz = ['A, 'A], qux = [1, \"a\"] } }"),
+            [
+                "{ foo : Number, bar : { baz : Array [| 'A |], qux : Array Dyn } }",
+                "Dyn"
+            ]
+        );
+        assert_eq!(
+            contracts("{ foo = 1, bar = 2 }"),
+            ["{ foo : Number, bar : Number }", "{ _ : Number }", "Dyn"]
+        );
+    }
+}
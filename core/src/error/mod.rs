@@ -9,6 +9,8 @@ use codespan_reporting::term::termcolor::{ColorChoice, StandardStream, WriteColo
 use lalrpop_util::ErrorRecovery;
 use malachite::num::conversion::traits::ToSci;
 
+use std::{path::PathBuf, time::Duration};
+
 use crate::{
     cache::Cache,
     eval::callstack::CallStack,
@@ -126,18 +128,48 @@ pub enum EvalError {
         /// Additional error-reporting data.
         merge_label: MergeLabel,
     },
+    /// Attempted to merge a record with a value which isn't a record, such as a number or a
+    /// string.
+    MergeRecordWithNonRecord {
+        /// The operand of the merge which is a record.
+        record: RichTerm,
+        /// The operand of the merge which isn't a record.
+        non_record: RichTerm,
+        /// Additional error-reporting data.
+        merge_label: MergeLabel,
+    },
+    /// Attempted to merge two arrays of different lengths element-wise, with the
+    /// [crate::eval::merge::ArrayMergeMode::Zip] array merge mode.
+    MergeArrayLengthMismatch {
+        /// The length of the left operand of the merge.
+        left_length: usize,
+        /// The position of the left operand of the merge.
+        left_pos: TermPos,
+        /// The length of the right operand of the merge.
+        right_length: usize,
+        /// The position of the right operand of the merge.
+        right_pos: TermPos,
+        /// Additional error-reporting data.
+        merge_label: MergeLabel,
+    },
     /// An unbound identifier was referenced.
     UnboundIdentifier(LocIdent, TermPos),
-    /// An element in the evaluation Cache was entered during its own update.
-    InfiniteRecursion(CallStack, TermPos),
+    /// An element in the evaluation Cache was entered during its own update. The identifiers are
+    /// the bindings involved in the cycle, starting from the one that was entered again, if they
+    /// are known.
+    InfiniteRecursion(CallStack, TermPos, Vec<LocIdent>),
     /// A serialization error occurred during a call to the builtin `serialize`.
     SerializationError(ExportError),
     /// A parse error occurred during a call to the builtin `deserialize`.
-    DeserializationError(
-        String,  /* format */
-        String,  /* error message */
-        TermPos, /* position of the call to deserialize */
-    ),
+    DeserializationError {
+        /// The format of the input, such as `json`.
+        format: String,
+        message: String,
+        /// The position of the call to `deserialize`.
+        pos: TermPos,
+        /// The location of the error in the deserialized string, if the parser reported one.
+        location: Option<DeserializationLocation>,
+    },
     /// A polymorphic record contract was broken somewhere.
     IllegalPolymorphicTailAccess {
         action: IllegalPolymorphicTailAction,
@@ -168,6 +200,16 @@ pub enum EvalError {
         /// The position of the `match` expression
         pos: TermPos,
     },
+    /// An array was indexed with an integer which isn't a valid index, that is an integer which is
+    /// either negative or greater than or equal to the length of the array.
+    ArrayOutOfBounds {
+        /// The offending index.
+        index: Number,
+        /// The length of the indexed array.
+        length: usize,
+        /// The position of the indexing operation.
+        pos: TermPos,
+    },
     /// Tried to query a field of something that wasn't a record.
     QueryNonRecord {
         /// Position of the original unevaluated expression.
@@ -179,10 +221,63 @@ pub enum EvalError {
     },
     /// An unexpected internal error.
     InternalError(String, TermPos),
+    /// Evaluation took longer than the maximum time allowed.
+    TimeLimitExceeded(Duration),
     /// Errors occurring rarely enough to not deserve a dedicated variant.
     Other(String, TermPos),
 }
 
+/// The location of a deserialization error in the deserialized string.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct DeserializationLocation {
+    /// The offset of the error in the input, in bytes.
+    pub offset: usize,
+    /// The line of the error, starting at 1.
+    pub line: usize,
+    /// The column of the error, in characters, starting at 1.
+    pub column: usize,
+    /// The position of the error in the Nickel source, if the input is a string literal written
+    /// verbatim in the source.
+    pub span: Option<RawSpan>,
+}
+
+impl DeserializationLocation {
+    /// Locate the byte `offset` of `input`, which has the position `input_pos`.
+    ///
+    /// The offset can only be mapped back to the source when `input` comes straight from a
+    /// double-quoted string literal without escape sequences, whose span is then exactly two bytes
+    /// longer than the string itself. Interpolated strings don't have an original position, and
+    /// escape sequences or multiline string delimiters always make the span longer.
+    pub fn new(input: &str, mut offset: usize, input_pos: TermPos) -> Self {
+        offset = offset.min(input.len());
+        while !input.is_char_boundary(offset) {
+            offset -= 1;
+        }
+
+        let before = &input[..offset];
+        let line_start = before.rfind('\n').map_or(0, |idx| idx + 1);
+
+        let span = match input_pos {
+            TermPos::Original(span)
+                if span.end.to_usize() - span.start.to_usize() == input.len() + 2 =>
+            {
+                // An error at the end of the input points to the closing delimiter.
+                let start = span.start.to_usize() + 1 + offset;
+                let len = input[offset..].chars().next().map_or(1, char::len_utf8);
+                Some(RawSpan::from_range(span.src_id, start..(start + len)))
+            }
+            _ => None,
+        };
+
+        DeserializationLocation {
+            offset,
+            line: before.matches('\n').count() + 1,
+            column: before[line_start..].chars().count() + 1,
+            span,
+        }
+    }
+}
+
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub enum IllegalPolymorphicTailAction {
     FieldAccess { field: String },
@@ -340,6 +435,18 @@ pub enum TypecheckError {
         cause: Box<TypecheckError>,
         pos: TermPos,
     },
+    /// The two branches of an if-then-else expression have incompatible types, as in `if c then
+    /// 1 else "x"`. Both branches must have the same type, which is the type of the whole
+    /// expression.
+    IfBranchesMismatch {
+        then_type: Type,
+        else_type: Type,
+        /// The error raised when unifying the type of the `else` branch with the type of the
+        /// `then` branch.
+        cause: Box<TypecheckError>,
+        /// The position of the `else` branch.
+        pos: TermPos,
+    },
     /// This error should mostly not happen: contracts (flat types) are now properly checked for
     /// equality. This error is raised when flat types are encountered during unification, but flat
     /// types should all have been converted to `typecheck::UnifType::Contract` at this point.
@@ -484,6 +591,8 @@ pub enum ParseError {
     ),
     /// Superfluous, unexpected token.
     ExtraToken(RawSpan),
+    /// A C-style comment `//` was used instead of a Nickel comment `#`.
+    CStyleComment(RawSpan),
     /// A closing brace '}' does not match an opening brace '{'. This rather precise error is
     /// detected because of how interpolated strings are lexed.
     UnmatchedCloseBrace(RawSpan),
@@ -584,6 +693,16 @@ pub enum ImportError {
         /* error */ ParseErrors,
         /* import position */ TermPos,
     ),
+    /// An import resolved to a file outside of the sandbox root. See
+    /// [crate::cache::Cache::set_sandbox_root].
+    OutsideSandbox {
+        /// The path, as written in the import.
+        path: String,
+        /// The path of the file that the import resolved to.
+        resolved: PathBuf,
+        /// The position of the import.
+        pos: TermPos,
+    },
 }
 
 #[derive(Debug, PartialEq, Clone)]
@@ -602,6 +721,10 @@ pub enum ExportErrorData {
     UnsupportedNull(ExportFormat, RichTerm),
     /// Tried exporting something else than a `String` to raw format.
     NotAString(RichTerm),
+    /// Tried exporting something else than a record to a format that only supports records.
+    NotARecord(ExportFormat, RichTerm),
+    /// Encountered a value which isn't a scalar (nor a record) for a flat format.
+    NotAScalar(ExportFormat, RichTerm),
     /// A term contains constructs that cannot be serialized.
     NonSerializable(RichTerm),
     /// No exportable documentation was found when requested.
@@ -734,6 +857,9 @@ impl ParseError {
                         Vec::new(),
                     )
                 }
+                InternalParseError::Lexical(LexicalError::CStyleComment(range)) => {
+                    ParseError::CStyleComment(mk_span(file_id, range.start, range.end))
+                }
                 InternalParseError::Lexical(LexicalError::UnmatchedCloseBrace(location)) => {
                     ParseError::UnmatchedCloseBrace(mk_span(file_id, location, location + 1))
                 }
@@ -896,6 +1022,13 @@ pub trait IntoDiagnostics<FileId> {
         files: &mut Files<String>,
         stdlib_ids: Option<&Vec<FileId>>,
     ) -> Vec<Diagnostic<FileId>>;
+
+    /// Structured data attached to the error, if any. This data can't be represented in a
+    /// codespan diagnostic, but is included alongside the diagnostics when they are serialized.
+    /// See [crate::label::ContractDiagnostic::data].
+    fn error_data(&self) -> Option<serde_json::Value> {
+        None
+    }
 }
 
 // Allow the use of a single `Diagnostic` directly as an error that can be reported by Nickel.
@@ -1022,6 +1155,26 @@ fn secondary_term(term: &RichTerm, files: &mut Files<String>) -> Label<FileId> {
     secondary_alt(term.pos, term.to_string(), files)
 }
 
+/// Generate a secondary label pointing to the origin of a failed merge, as recorded in its merge
+/// label.
+fn merge_origin_label(merge_label: &MergeLabel) -> Label<FileId> {
+    let msg = match merge_label.kind {
+        // For a standard merge, the span of the label indicates the position of the
+        // original merge expression
+        MergeKind::Standard => "originally merged here".to_owned(),
+        // For a piecewise definition, there isn't such merge expression (the merge has
+        // been generated by the parser). The spans thus point to the corresponding
+        // field identifier
+        MergeKind::PiecewiseDef => "when combining the definitions of this field".to_owned(),
+        // For `std.record.merge_all`, the span points to the offending array element
+        MergeKind::ArrayElement { index } => {
+            format!("when merging the array element at index {index}")
+        }
+    };
+
+    secondary(&merge_label.span).with_message(msg)
+}
+
 fn cardinal(number: usize) -> String {
     let suffix = if number % 10 == 1 {
         "st"
@@ -1055,6 +1208,13 @@ impl IntoDiagnostics<FileId> for Error {
             Error::ReplError(err) => err.into_diagnostics(files, stdlib_ids),
         }
     }
+
+    fn error_data(&self) -> Option<serde_json::Value> {
+        match self {
+            Error::EvalError(err) => err.error_data(),
+            _ => None,
+        }
+    }
 }
 
 impl IntoDiagnostics<FileId> for EvalError {
@@ -1222,17 +1382,7 @@ impl IntoDiagnostics<FileId> for EvalError {
                     primary_term(&right_arg, files).with_message("with this expression"),
                 ];
 
-                let span_label = match merge_label.kind {
-                    // For a standard merge, the span of the label indicates the position of the
-                    // original merge expression
-                    MergeKind::Standard => "originally merged here",
-                    // For a piecewise definition, there isn't such merge expression (the merge has
-                    // been generated by the parser). The spans thus point to the corresponding
-                    // field identifier
-                    MergeKind::PiecewiseDef => "when combining the definitions of this field",
-                };
-
-                labels.push(secondary(&merge_label.span).with_message(span_label));
+                labels.push(merge_origin_label(&merge_label));
 
                 fn push_merge_note(notes: &mut Vec<String>, typ: &str) {
                     notes.push(format!(
@@ -1321,6 +1471,69 @@ impl IntoDiagnostics<FileId> for EvalError {
                     .with_labels(labels)
                     .with_notes(notes)]
             }
+            EvalError::MergeArrayLengthMismatch {
+                left_length,
+                left_pos,
+                right_length,
+                right_pos,
+                merge_label,
+            } => {
+                let describe = |length: usize| {
+                    let plural = if length == 1 { "" } else { "s" };
+                    format!("this array has {length} element{plural}")
+                };
+
+                let labels = vec![
+                    primary_alt(left_pos.into_opt(), String::from("[ ... ]"), files)
+                        .with_message(describe(left_length)),
+                    secondary_alt(right_pos, String::from("[ ... ]"), files)
+                        .with_message(describe(right_length)),
+                    merge_origin_label(&merge_label),
+                ];
+
+                vec![Diagnostic::error()
+                    .with_message("cannot merge arrays of different lengths")
+                    .with_labels(labels)
+                    .with_notes(vec![
+                        "Arrays are merged element-wise, which requires them to have the same \
+                        length."
+                            .to_owned(),
+                    ])]
+            }
+            EvalError::MergeRecordWithNonRecord {
+                record,
+                non_record,
+                merge_label,
+            } => {
+                let kind = match non_record.as_ref().type_of().as_deref() {
+                    Some("Null") => "null".to_owned(),
+                    Some("Bool") => "a boolean".to_owned(),
+                    Some("Number") => "a number".to_owned(),
+                    Some("String") => "a string".to_owned(),
+                    Some("Array") => "an array".to_owned(),
+                    Some("Function" | "MatchExpression") => "a function".to_owned(),
+                    Some("EnumTag") => "an enum tag".to_owned(),
+                    Some("EnumVariant") => "an enum variant".to_owned(),
+                    Some(other) => format!("a value of type {other}"),
+                    None => "a value which isn't a record".to_owned(),
+                };
+
+                let labels = vec![
+                    primary_term(&non_record, files).with_message(format!("this is {kind}")),
+                    secondary_term(&record, files).with_message("this is a record"),
+                    merge_origin_label(&merge_label),
+                ];
+
+                vec![Diagnostic::error()
+                    .with_message(format!("cannot merge a record with {kind}"))
+                    .with_labels(labels)
+                    .with_notes(vec![
+                        "Records can only be merged with other records.".to_owned(),
+                        "To set the value of a field, merge with a record defining this field, \
+                        as in `{ field = value }`, rather than with the value itself."
+                            .to_owned(),
+                    ])]
+            }
             EvalError::UnboundIdentifier(ident, span_opt) => vec![Diagnostic::error()
                 .with_message(format!("unbound identifier `{ident}`"))
                 .with_labels(vec![primary_alt(
@@ -1329,16 +1542,36 @@ impl IntoDiagnostics<FileId> for EvalError {
                     files,
                 )
                 .with_message("this identifier is unbound")])],
-            EvalError::InfiniteRecursion(_call_stack, span_opt) => {
+            EvalError::InfiniteRecursion(_call_stack, span_opt, bindings) => {
                 let labels = span_opt
                     .as_opt_ref()
                     .map(|span| vec![primary(span).with_message("recursive reference")])
                     .unwrap_or_default();
 
+                let notes = match bindings.first() {
+                    Some(first) => {
+                        let cycle = bindings
+                            .iter()
+                            .chain(std::iter::once(first))
+                            .map(LocIdent::to_string)
+                            .collect::<Vec<_>>()
+                            .join(" -> ");
+                        vec![format!("The values of these bindings depend on each other: {cycle}")]
+                    }
+                    None => Vec::new(),
+                };
+
                 vec![Diagnostic::error()
                     .with_message("infinite recursion")
-                    .with_labels(labels)]
+                    .with_labels(labels)
+                    .with_notes(notes)]
             }
+            EvalError::TimeLimitExceeded(limit) => vec![Diagnostic::error()
+                .with_message("evaluation time limit exceeded")
+                .with_notes(vec![format!(
+                    "The evaluation was aborted because it took longer than the maximum \
+                    allowed time of {limit:?}."
+                )])],
             EvalError::Other(msg, span_opt) => {
                 let labels = span_opt
                     .as_opt_ref()
@@ -1359,15 +1592,40 @@ impl IntoDiagnostics<FileId> for EvalError {
                     .with_notes(vec![String::from(INTERNAL_ERROR_MSG)])]
             }
             EvalError::SerializationError(err) => err.into_diagnostics(files, stdlib_ids),
-            EvalError::DeserializationError(format, msg, span_opt) => {
-                let labels = span_opt
-                    .as_opt_ref()
-                    .map(|span| vec![primary(span).with_message("here")])
-                    .unwrap_or_default();
+            EvalError::DeserializationError {
+                format,
+                message,
+                pos,
+                location,
+            } => {
+                let mut labels = Vec::new();
+                let mut notes = Vec::new();
+
+                if let Some(DeserializationLocation {
+                    line, column, span, ..
+                }) = location
+                {
+                    if let Some(span) = span {
+                        labels.push(primary(&span).with_message("here"));
+                    }
+
+                    notes.push(format!(
+                        "The error is located at line {line}, column {column} of the input."
+                    ));
+                }
+
+                if let Some(span) = pos.as_opt_ref() {
+                    labels.push(if labels.is_empty() {
+                        primary(span).with_message("here")
+                    } else {
+                        secondary(span).with_message("while deserializing this")
+                    });
+                }
 
                 vec![Diagnostic::error()
-                    .with_message(format!("{format} parse error: {msg}"))
-                    .with_labels(labels)]
+                    .with_message(format!("{format} parse error: {message}"))
+                    .with_labels(labels)
+                    .with_notes(notes)]
             }
             EvalError::IncomparableValues {
                 eq_pos,
@@ -1516,6 +1774,37 @@ impl IntoDiagnostics<FileId> for EvalError {
                     .with_message("tried to query field of a non-record")
                     .with_labels(vec![label])]
             }
+            EvalError::ArrayOutOfBounds { index, length, pos } => {
+                let labels = pos
+                    .as_opt_ref()
+                    .map(|span| vec![primary(span).with_message("this indexing operation")])
+                    .unwrap_or_default();
+
+                let note = if length == 0 {
+                    String::from("the array is empty")
+                } else {
+                    format!("valid indices are between 0 and {}", length - 1)
+                };
+
+                vec![Diagnostic::error()
+                    .with_message(format!(
+                        "index out of bounds: the index is {index} but the length is {length}"
+                    ))
+                    .with_labels(labels)
+                    .with_notes(vec![note])]
+            }
+        }
+    }
+
+    fn error_data(&self) -> Option<serde_json::Value> {
+        match self {
+            // The most precise diagnostic comes last.
+            EvalError::BlameError { label, .. } => label
+                .diagnostics
+                .iter()
+                .rev()
+                .find_map(|diag| diag.data.clone()),
+            _ => None,
         }
     }
 }
@@ -1542,22 +1831,24 @@ mod blame_error {
     /// Returns a title to be used by blame errors based on the `path` and `polarity`
     /// of the label.
     pub fn title(l: &label::Label) -> String {
+        let field = l.qualified_field_name();
+
         if ty_path::has_no_arrow(&l.path) {
             // An empty path or a path that contains only fields necessarily corresponds to
             // a positive blame
             assert_eq!(l.polarity, Polarity::Positive);
-            match l.field_name {
-                Some(ident) => format!("contract broken by the value of `{ident}`"),
+            match field {
+                Some(path) => format!("contract broken by the value of `{path}`"),
                 None => "contract broken by a value".to_owned(),
             }
         } else if l.polarity == Polarity::Positive {
-            match l.field_name {
-                Some(ident) => format!("contract broken by the function `{ident}`"),
+            match field {
+                Some(path) => format!("contract broken by the function `{path}`"),
                 None => "contract broken by a function".to_owned(),
             }
         } else {
-            match l.field_name {
-                Some(ident) => format!("contract broken by the caller of `{ident}`"),
+            match field {
+                Some(path) => format!("contract broken by the caller of `{path}`"),
                 None => "contract broken by the caller".to_owned(),
             }
         }
@@ -1646,6 +1937,17 @@ mod blame_error {
             }
         }
 
+        // Show the definition of a user-defined contract, unless it's written inline in the
+        // annotation, as in `value | std.contract.from_predicate (fun x => x > 0)`, and is thus
+        // already part of the excerpt.
+        if let Some(ctr_span) = blame_label.contract_pos.into_opt() {
+            let in_stdlib = stdlib_ids.is_some_and(|ids| ids.contains(&ctr_span.src_id));
+
+            if !in_stdlib && !blame_label.span.contains(ctr_span.start_pos()) {
+                labels.push(secondary(&ctr_span).with_message("contract defined here"));
+            }
+        }
+
         labels
     }
 
@@ -1896,6 +2198,12 @@ impl IntoDiagnostics<FileId> for ParseError {
             ParseError::ExtraToken(span) => Diagnostic::error()
                 .with_message("superfluous unexpected token")
                 .with_labels(vec![primary(&span)]),
+            ParseError::CStyleComment(span) => Diagnostic::error()
+                .with_message("unexpected token `//`")
+                .with_labels(vec![primary(&span).with_message("this looks like a C-style comment")])
+                .with_notes(vec![
+                    "Nickel comments start with `#`. Try replacing `//` with `#`.".into(),
+                ]),
             ParseError::UnmatchedCloseBrace(span) => Diagnostic::error()
                 .with_message("unmatched closing brace \'}\'")
                 .with_labels(vec![primary(&span)]),
@@ -2467,6 +2775,44 @@ impl IntoDiagnostics<FileId> for TypecheckError {
 
                 diags
             }
+            TypecheckError::IfBranchesMismatch {
+                then_type,
+                else_type,
+                cause,
+                pos,
+            } => {
+                let mut diags = vec![Diagnostic::error()
+                    .with_message("incompatible types for the branches of an if-then-else")
+                    .with_labels(
+                        pos.as_opt_ref()
+                            .map(|span| {
+                                vec![primary(span).with_message("this branch has a different type")]
+                            })
+                            .unwrap_or_default(),
+                    )
+                    .with_notes(vec![
+                        format!("The `then` branch has type `{then_type}`"),
+                        format!("The `else` branch has type `{else_type}`"),
+                        String::from("Both branches of an if-then-else must have the same type"),
+                    ])];
+
+                // As for function types, a plain type mismatch is already described by the types
+                // of the branches. Other causes, such as row mismatches, are more precise.
+                match *cause {
+                    TypecheckError::TypeMismatch { .. } => (),
+                    err => {
+                        diags.extend(err.into_diagnostics(files, stdlib_ids).into_iter().map(
+                            |mut diag| {
+                                diag.message =
+                                    format!("while matching the branches: {}", diag.message);
+                                diag
+                            },
+                        ));
+                    }
+                }
+
+                diags
+            }
             TypecheckError::IncomparableFlatTypes {
                 expected,
                 inferred,
@@ -2597,6 +2943,23 @@ impl IntoDiagnostics<FileId> for ImportError {
 
                 diagnostic
             }
+            ImportError::OutsideSandbox {
+                path,
+                resolved,
+                pos,
+            } => {
+                let labels = pos
+                    .as_opt_ref()
+                    .map(|span| vec![primary(span).with_message("imported here")])
+                    .unwrap_or_default();
+
+                vec![Diagnostic::error()
+                    .with_message(format!(
+                        "import of {path} failed: the file is outside of the sandbox"
+                    ))
+                    .with_labels(labels)
+                    .with_notes(vec![format!("{path} resolves to {}", resolved.display())])]
+            }
         }
     }
 }
@@ -2623,6 +2986,31 @@ impl IntoDiagnostics<FileId> for ExportError {
                 ))
                 .with_labels(vec![primary_term(&rt, files)])
                 .with_notes(notes)],
+            ExportErrorData::NotARecord(format, rt) => vec![Diagnostic::error()
+                .with_message(format!(
+                    "{format} export expects a record, but got {}",
+                    rt.as_ref()
+                        .type_of()
+                        .unwrap_or_else(|| String::from("<unevaluated>"))
+                ))
+                .with_labels(vec![primary_term(&rt, files)])
+                .with_notes(notes)],
+            ExportErrorData::NotAScalar(format, rt) => {
+                notes.push(format!(
+                    "The {format} format only supports strings, booleans, numbers and enum tags, \
+                    possibly nested in records. Arrays aren't supported."
+                ));
+
+                vec![Diagnostic::error()
+                    .with_message(format!(
+                        "{format} format doesn't support {} values",
+                        rt.as_ref()
+                            .type_of()
+                            .unwrap_or_else(|| String::from("<unevaluated>"))
+                    ))
+                    .with_labels(vec![primary_term(&rt, files)])
+                    .with_notes(notes)]
+            }
             ExportErrorData::UnsupportedNull(format, rt) => vec![Diagnostic::error()
                 .with_message(format!("{format} format doesn't support null values"))
                 .with_labels(vec![primary_term(&rt, files)])
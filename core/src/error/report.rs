@@ -1,15 +1,24 @@
 //! Error diagnostics reporting and serialization.
 use super::*;
 
+use codespan_reporting::term::termcolor::{Ansi, NoColor};
+use std::io::Write;
+
 /// Serializable wrapper type to export diagnostics with a top-level attribute.
 #[derive(serde::Serialize)]
 pub struct DiagnosticsWrapper {
     pub diagnostics: Vec<Diagnostic<FileId>>,
+    /// Structured data attached to the error. See [IntoDiagnostics::error_data].
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data: Option<serde_json::Value>,
 }
 
 impl From<Vec<Diagnostic<FileId>>> for DiagnosticsWrapper {
     fn from(diagnostics: Vec<Diagnostic<FileId>>) -> Self {
-        Self { diagnostics }
+        Self {
+            diagnostics,
+            data: None,
+        }
     }
 }
 
@@ -78,7 +87,47 @@ pub fn report<E: IntoDiagnostics<FileId>>(
     )
 }
 
-/// Report an error on `stderr`, provided a file database and a list of stdlib file ids.
+/// Render an error as text, in a string.
+///
+/// ANSI color codes are included unless `color_opt` is `never`. Since the output isn't written to
+/// a terminal, `auto` behaves like `always`, leaving it up to the consumer of the string to strip
+/// the colors if needed.
+pub fn report_as_str<E: IntoDiagnostics<FileId>>(
+    cache: &mut Cache,
+    error: E,
+    color_opt: ColorOpt,
+) -> String {
+    let stdlib_ids = cache.get_all_stdlib_modules_file_id();
+    let diagnostics = error.into_diagnostics(cache.files_mut(), stdlib_ids.as_ref());
+    let config = codespan_reporting::term::Config::default();
+    let files = cache.files();
+
+    let emit = |writer: &mut dyn WriteColor| {
+        diagnostics
+            .iter()
+            .try_for_each(|d| codespan_reporting::term::emit(writer, &config, files, d))
+            // safe because writing to a buffer in memory
+            .unwrap()
+    };
+
+    let bytes = match color_opt.0 {
+        clap::ColorChoice::Never => {
+            let mut buffer = NoColor::new(Vec::new());
+            emit(&mut buffer);
+            buffer.into_inner()
+        }
+        clap::ColorChoice::Auto | clap::ColorChoice::Always => {
+            let mut buffer = Ansi::new(Vec::new());
+            emit(&mut buffer);
+            buffer.into_inner()
+        }
+    };
+
+    // unwrap(): emit() should only print valid utf8 to the the buffer
+    String::from_utf8(bytes).unwrap()
+}
+
+/// Report an error on `writer`, provided a file database and a list of stdlib file ids.
 pub fn report_with<E: IntoDiagnostics<FileId>>(
     writer: &mut dyn WriteColor,
     files: &mut Files<String>,
@@ -87,21 +136,23 @@ pub fn report_with<E: IntoDiagnostics<FileId>>(
     format: ErrorFormat,
 ) {
     let config = codespan_reporting::term::Config::default();
+    let data = error.error_data();
     let diagnostics = error.into_diagnostics(files, stdlib_ids);
-    let stderr = std::io::stderr();
+    let wrapper = DiagnosticsWrapper { diagnostics, data };
 
     let result = match format {
-        ErrorFormat::Text => diagnostics.iter().try_for_each(|d| {
+        ErrorFormat::Text => wrapper.diagnostics.iter().try_for_each(|d| {
             codespan_reporting::term::emit(writer, &config, files, d).map_err(|err| err.to_string())
         }),
-        ErrorFormat::Json => serde_json::to_writer(stderr, &DiagnosticsWrapper::from(diagnostics))
-            .map(|_| eprintln!())
-            .map_err(|err| err.to_string()),
-        ErrorFormat::Yaml => serde_yaml::to_writer(stderr, &DiagnosticsWrapper::from(diagnostics))
-            .map_err(|err| err.to_string()),
-        ErrorFormat::Toml => toml::to_string(&DiagnosticsWrapper::from(diagnostics))
-            .map(|repr| eprint!("{}", repr))
-            .map_err(|err| err.to_string()),
+        ErrorFormat::Json => serde_json::to_writer(&mut *writer, &wrapper)
+            .map_err(|err| err.to_string())
+            .and_then(|_| writeln!(writer).map_err(|err| err.to_string())),
+        ErrorFormat::Yaml => {
+            serde_yaml::to_writer(&mut *writer, &wrapper).map_err(|err| err.to_string())
+        }
+        ErrorFormat::Toml => toml::to_string(&wrapper)
+            .map_err(|err| err.to_string())
+            .and_then(|repr| write!(writer, "{repr}").map_err(|err| err.to_string())),
     };
 
     match result {
@@ -42,6 +42,29 @@ impl CallStack {
         CallStack(Vec::new())
     }
 
+    /// Return the variables and fields entered since the last time a variable or a field named
+    /// `id` was entered, starting with `id` itself. When evaluation loops on `id`, these are the
+    /// bindings involved in the cycle. If `id` doesn't appear in the call stack, return `[id]`.
+    pub fn cycle_from(&self, id: LocIdent) -> Vec<LocIdent> {
+        let entered: Vec<LocIdent> = self
+            .0
+            .iter()
+            .filter_map(|elem| match elem {
+                StackElem::Var { id, .. } | StackElem::Field { id, .. } => Some(*id),
+                _ => None,
+            })
+            .collect();
+
+        match entered.iter().rposition(|other| other.ident() == id.ident()) {
+            Some(start) => {
+                let mut cycle = entered[start..].to_vec();
+                cycle.dedup_by_key(|id| id.ident());
+                cycle
+            }
+            None => vec![id],
+        }
+    }
+
     /// Push a marker to indicate that a var was entered.
     pub fn enter_var(&mut self, id: LocIdent, pos: TermPos) {
         self.0.push(StackElem::Var { id, pos });
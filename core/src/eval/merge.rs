@@ -29,13 +29,18 @@ use super::*;
 use crate::closurize::Closurize;
 use crate::combine::Combine;
 use crate::error::{EvalError, IllegalPolymorphicTailAction};
+use crate::identifier::FieldPath;
 use crate::label::{Label, MergeLabel};
-use crate::position::TermPos;
+use crate::position::{RawSpan, TermPos};
 use crate::term::{
+    array::Array,
     record::{self, Field, FieldDeps, FieldMetadata, RecordAttrs, RecordData},
-    BinaryOp, EnumVariantAttrs, IndexMap, RichTerm, Term, TypeAnnotation,
+    BinaryOp, EnumVariantAttrs, IndexMap, MergePriority, RichTerm, Term, TypeAnnotation,
 };
 
+use indexmap::IndexSet;
+use std::collections::HashMap;
+
 /// Merging mode. Merging is used both to combine standard data and to apply contracts defined as
 /// records.
 #[derive(Clone, PartialEq, Debug)]
@@ -57,6 +62,212 @@ impl From<MergeMode> for MergeLabel {
     }
 }
 
+/// How arrays are merged in [`MergeMode::Standard`] mode. See
+/// [VirtualMachine::set_array_merge_mode].
+///
+/// Record contracts aren't concerned: an array merged with another array in
+/// [`MergeMode::Contract`] mode must always be equal to it.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ArrayMergeMode {
+    /// Arrays can only be merged if they are equal. This is the only mode which keeps merge
+    /// idempotent, and the default.
+    #[default]
+    Equal,
+    /// Merging two arrays concatenates them: `array1 & array2` is `array1 @ array2`.
+    Concat,
+    /// Arrays must have the same length, and are merged element-wise: the element `i` of
+    /// `array1 & array2` is `array1.i & array2.i`.
+    Zip,
+}
+
+/// How the documentation of two merged fields is combined. See
+/// [VirtualMachine::set_doc_merge_mode].
+///
+/// The mode only applies to merges performed during evaluation. The piecewise definitions of a
+/// field in a record literal, as in `{ foo | doc "a" = 1, foo | doc "b" = 2 }`, are combined by
+/// the parser, which always keeps the first documentation, whatever the mode.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DocMergeMode {
+    /// Both documentations are kept, the left one first, separated by an empty line. Identical
+    /// documentations are only kept once. This is the default.
+    #[default]
+    Concat,
+    /// Only the documentation of the left field is kept, or the one of the right field if the
+    /// left one isn't documented.
+    First,
+}
+
+/// The options of merging which can be set by embedders.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct MergeOptions {
+    /// See [VirtualMachine::set_array_merge_mode].
+    pub array_mode: ArrayMergeMode,
+    /// See [VirtualMachine::set_doc_merge_mode].
+    pub doc_mode: DocMergeMode,
+}
+
+/// The state of the virtual machine that [merge] has access to.
+pub struct MergeContext<'a, C: Cache> {
+    pub cache: &'a mut C,
+    pub call_stack: &'a mut CallStack,
+    pub messages: &'a dyn MergeMessages,
+    /// Where to record the fields checked by record contracts, in [`MergeMode::Contract`] mode.
+    pub coverage: Option<&'a mut ContractCoverage>,
+    /// Where to record the definitions of merged fields, in [`MergeMode::Standard`] mode.
+    pub provenance: Option<&'a mut MergeProvenance>,
+    pub options: MergeOptions,
+}
+
+/// The text of the explanations attached to merge errors. Embedders can provide their own
+/// implementation through [VirtualMachine::set_merge_messages], for example to localize them.
+pub trait MergeMessages {
+    /// The notes of the error reported when a closed record contract is applied to a record with
+    /// the extra fields `fields`.
+    fn extra_field_notes(&self, fields: &[LocIdent]) -> Vec<String>;
+}
+
+/// The default merge messages, in English.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct DefaultMergeMessages;
+
+impl MergeMessages for DefaultMergeMessages {
+    fn extra_field_notes(&self, _fields: &[LocIdent]) -> Vec<String> {
+        vec![
+            String::from("Have you misspelled a field?"),
+            String::from(
+                "The record contract might also be too strict. By default, \
+                record contracts exclude any field which is not listed.\n\
+                Append `, ..` at the end of the record contract, as in \
+                `{some_field | SomeContract, ..}`, to make it accept extra fields.",
+            ),
+        ]
+    }
+}
+
+/// The fields of record contracts which have been checked during evaluation, to find the parts
+/// of a schema that aren't exercised by a configuration. See
+/// [VirtualMachine::set_contract_coverage].
+///
+/// A field of a record contract is checked when the contract is applied to a record which defines
+/// this field. Fields are identified by their path from the outermost record contract: the fields
+/// of a record contract annotating a checked field `foo` are reported as `foo.<field>`.
+#[derive(Clone, Debug, Default)]
+pub struct ContractCoverage {
+    /// The paths of the record contracts annotating checked fields, indexed by the span of their
+    /// label.
+    prefixes: HashMap<RawSpan, Vec<LocIdent>>,
+    /// The checked fields, in the order in which they were first checked.
+    checked: IndexSet<FieldPath>,
+}
+
+impl ContractCoverage {
+    /// The checked fields, in the order in which they were first checked.
+    pub fn checked_fields(&self) -> impl Iterator<Item = &FieldPath> {
+        self.checked.iter()
+    }
+
+    /// Record the fields checked by the application of the record contract with label `label`,
+    /// given the fields defined both by the value and by the contract.
+    fn record(&mut self, label: &Label, fields: &IndexMap<LocIdent, (Field, Field)>) {
+        let prefix = self.prefixes.get(&label.span).cloned().unwrap_or_default();
+
+        for (id, (_, contract_field)) in fields {
+            let mut path = prefix.clone();
+            path.push(*id);
+
+            let nested_spans = contract_field
+                .metadata
+                .annotation
+                .iter()
+                .map(|labeled_ty| labeled_ty.label.span)
+                .chain(
+                    contract_field
+                        .pending_contracts
+                        .iter()
+                        .map(|ctr| ctr.label.span),
+                );
+
+            for span in nested_spans {
+                self.prefixes.entry(span).or_insert_with(|| path.clone());
+            }
+
+            self.checked.insert(FieldPath(path));
+        }
+    }
+}
+
+/// The definitions which have been merged to compute each field of the records merged during
+/// evaluation, to find out where the value of a field comes from. See
+/// [VirtualMachine::set_merge_provenance].
+///
+/// Fields are identified by their path from the outermost merged record: when the subrecords
+/// defined by two layers for a field `foo` are merged in turn, their fields are reported as
+/// `foo.<field>`. Records merged elsewhere, for example inside a function, are considered to be
+/// outermost records as well.
+///
+/// All the definitions of a field are listed, including the ones which are overridden because
+/// of a lower priority.
+#[derive(Clone, Debug, Default)]
+pub struct MergeProvenance {
+    /// The spans of the definitions of each field, in the order in which they were merged.
+    sources: IndexMap<FieldPath, Vec<RawSpan>>,
+}
+
+impl MergeProvenance {
+    /// The spans of the definitions of each merged field, in the order in which the fields were
+    /// first merged.
+    pub fn sources(&self) -> impl Iterator<Item = (&FieldPath, &[RawSpan])> {
+        self.sources
+            .iter()
+            .map(|(path, spans)| (path, spans.as_slice()))
+    }
+
+    /// The spans of the definitions of the field at `path`, or `None` if this field hasn't been
+    /// merged.
+    pub fn get(&self, path: &FieldPath) -> Option<&[RawSpan]> {
+        self.sources.get(path).map(Vec::as_slice)
+    }
+
+    /// Record the definitions of the fields of the merge of two records, which are the values of
+    /// the field at `prefix`. The prefix is the field path of the label of the merge.
+    fn record<'a, C: Cache>(
+        &mut self,
+        cache: &C,
+        prefix: &[LocIdent],
+        fields: impl Iterator<Item = (&'a LocIdent, &'a Field)>,
+    ) {
+        for (id, field) in fields {
+            // A definition without value, such as an annotation alone, doesn't contribute.
+            let Some(span) = field
+                .value
+                .as_ref()
+                .and_then(|value| value_span(cache, value))
+            else {
+                continue;
+            };
+
+            let mut path = prefix.to_vec();
+            path.push(*id);
+
+            let spans = self.sources.entry(FieldPath(path)).or_default();
+
+            if !spans.contains(&span) {
+                spans.push(span);
+            }
+        }
+    }
+}
+
+/// The original position of the definition of a field, looking through the closure of the value
+/// if it's been closurized without a position.
+fn value_span<C: Cache>(cache: &C, value: &RichTerm) -> Option<RawSpan> {
+    match (value.pos.into_opt(), value.as_ref()) {
+        (Some(span), _) => Some(span),
+        (None, Term::Closure(idx)) => cache.get(idx.clone()).body.pos.into_opt(),
+        (None, _) => None,
+    }
+}
+
 /// Compute the merge of two evaluated operands. Support both standard merging and record contract
 /// application.
 ///
@@ -64,17 +275,37 @@ impl From<MergeMode> for MergeLabel {
 ///
 /// In [`MergeMode::Contract`] mode, `t1` must be the value and `t2` must be the contract. It is
 /// important as `merge` is not commutative in this mode.
-#[allow(clippy::too_many_arguments)] // TODO: Is it worth to pack the inputs in an ad-hoc struct?
+///
+/// # Coverage
+///
+/// In [`MergeMode::Contract`] mode, the fields checked by the contract are recorded in
+/// `ctxt.coverage`, if provided.
+///
+/// # Provenance
+///
+/// In [`MergeMode::Standard`] mode, the definitions of the fields of merged records are recorded
+/// in `ctxt.provenance`, if provided.
+///
+/// # Arrays
+///
+/// In [`MergeMode::Standard`] mode, arrays are merged according to `ctxt.options.array_mode`.
 pub fn merge<C: Cache>(
-    cache: &mut C,
+    ctxt: MergeContext<'_, C>,
     t1: RichTerm,
     env1: Environment,
     t2: RichTerm,
     env2: Environment,
     pos_op: TermPos,
     mode: MergeMode,
-    call_stack: &mut CallStack,
 ) -> Result<Closure, EvalError> {
+    let MergeContext {
+        cache,
+        call_stack,
+        messages,
+        coverage,
+        provenance,
+        options,
+    } = ctxt;
     let RichTerm {
         term: t1,
         pos: pos1,
@@ -187,11 +418,77 @@ pub fn merge<C: Cache>(
                 pos_op.into_inherited(),
             )))
         }
+        (t1 @ Term::Array(..), t2 @ Term::Array(..))
+            if options.array_mode == ArrayMergeMode::Concat
+                && matches!(mode, MergeMode::Standard(_)) =>
+        {
+            let t1 = RichTerm::new(t1, pos1).closurize(cache, env1);
+            let t2 = RichTerm::new(t2, pos2).closurize(cache, env2);
+
+            Ok(Closure {
+                body: mk_term::op2(BinaryOp::ArrayConcat, t1, t2).with_pos(pos_op),
+                env: Environment::new(),
+            })
+        }
+        (Term::Array(ts1, attrs1), Term::Array(ts2, attrs2))
+            if options.array_mode == ArrayMergeMode::Zip
+                && matches!(mode, MergeMode::Standard(_)) =>
+        {
+            if ts1.len() != ts2.len() {
+                return Err(EvalError::MergeArrayLengthMismatch {
+                    left_length: ts1.len(),
+                    left_pos: pos1,
+                    right_length: ts2.len(),
+                    right_pos: pos2,
+                    merge_label: mode.into(),
+                });
+            }
+
+            let merge_label = MergeLabel::from(mode);
+
+            // The pending contracts of each array are applied to its elements before merging
+            // them. Each merged element is a new thunk, so that merging the elements stays lazy
+            // and is shared, as for the values of merged record fields.
+            let ts: Array = ts1
+                .into_iter()
+                .zip(ts2)
+                .map(|(elt1, elt2)| {
+                    let pos1 = elt1.pos;
+                    let pos2 = elt2.pos;
+                    let elt1 = RuntimeContract::apply_all(
+                        elt1,
+                        attrs1.pending_contracts.iter().cloned(),
+                        pos1,
+                    )
+                    .closurize(cache, env1.clone());
+                    let elt2 = RuntimeContract::apply_all(
+                        elt2,
+                        attrs2.pending_contracts.iter().cloned(),
+                        pos2,
+                    )
+                    .closurize(cache, env2.clone());
+
+                    mk_term::op2(BinaryOp::Merge(merge_label.clone()), elt1, elt2)
+                        .closurize(cache, Environment::new())
+                })
+                .collect();
+
+            Ok(Closure::atomic_closure(RichTerm::new(
+                Term::Array(ts, ArrayAttrs::new().closurized()),
+                pos_op.into_inherited(),
+            )))
+        }
         // There are several different (and valid) ways of merging arrays. We don't want to choose
-        // for the user, so future custom merge functions will provide a way to overload the native
-        // merging function. For the time being, we still need to be idempotent: thus we rewrite
+        // for the user by default, but embedders can select another way through `options`.
+        // By default, we still need to be idempotent: thus we rewrite
         // `array1 & array2` to `contract.Equal array1 array2`, so that we extend merge in the
         // minimum way such that it is idempotent.
+        //
+        // The pending contracts of both arrays are preserved, and stay lazy: `contract.Equal`
+        // builds the merged array element by element, comparing `array2.i`, with the element
+        // contracts of `array2` applied, to `array1.i`, with the element contracts of `array1`
+        // applied. Thus, forcing an element of the result checks it against the contracts of both
+        // sides, but forcing the array itself doesn't check anything.
         (t1 @ Term::Array(..), t2 @ Term::Array(..)) => {
             use crate::{mk_app, stdlib, typ::TypeF};
             use std::rc::Rc;
@@ -268,22 +565,17 @@ pub fn merge<C: Cache>(
 
             match mode {
                 MergeMode::Contract(label) if !r2.attrs.open && !left.is_empty() => {
-                    let fields: Vec<String> =
-                        left.keys().map(|field| format!("`{field}`")).collect();
+                    let extra_fields: Vec<LocIdent> = left.keys().copied().collect();
+                    let fields: Vec<String> = extra_fields
+                        .iter()
+                        .map(|field| format!("`{field}`"))
+                        .collect();
                     let plural = if fields.len() == 1 { "" } else { "s" };
                     let fields_list = fields.join(", ");
 
                     let label = label
                         .with_diagnostic_message(format!("extra field{plural} {fields_list}"))
-                        .with_diagnostic_notes(vec![
-                            String::from("Have you misspelled a field?"),
-                            String::from(
-                                "The record contract might also be too strict. By default, \
-                                record contracts exclude any field which is not listed.\n\
-                                Append `, ..` at the end of the record contract, as in \
-                                `{some_field | SomeContract, ..}`, to make it accept extra fields.",
-                            ),
-                        ]);
+                        .with_diagnostic_notes(messages.extra_field_notes(&extra_fields));
 
                     return Err(EvalError::BlameError {
                         evaluated_arg: label.get_evaluated_arg(cache),
@@ -294,13 +586,43 @@ pub fn merge<C: Cache>(
                 _ => (),
             };
 
+            if let (MergeMode::Contract(label), Some(coverage)) = (&mode, coverage) {
+                coverage.record(label, &center);
+            }
+
+            if let (MergeMode::Standard(label), Some(provenance)) = (&mode, provenance) {
+                let fields = left
+                    .iter()
+                    .chain(
+                        center
+                            .iter()
+                            .flat_map(|(id, (field1, field2))| [(id, field1), (id, field2)]),
+                    )
+                    .chain(right.iter());
+
+                provenance.record(cache, &label.field_path, fields);
+            }
+
             let final_pos = if let MergeMode::Standard(_) = mode {
                 pos_op.into_inherited()
             } else {
                 pos1.into_inherited()
             };
 
+            // The path of the merged records: the path of the field checked by the contract in
+            // contract mode, and the path of the field whose values are merged in standard mode.
+            // The labels of the fields are updated with this path, and the values of the fields
+            // defined on both sides are merged with this path extended with the name of the field.
+            // This way, blame errors report the full path of a field nested in several records,
+            // whether it's defined in a record contract or in a value.
             let merge_label = MergeLabel::from(mode);
+            let with_path = |field: Field| {
+                if merge_label.field_path.is_empty() {
+                    field
+                } else {
+                    field.with_field_path(&merge_label.field_path)
+                }
+            };
 
             let field_names: Vec<_> = left
                 .keys()
@@ -321,19 +643,29 @@ pub fn merge<C: Cache>(
             // [crate::eval::cache::Cache::saturate()].
             m.extend(
                 left.into_iter()
-                    .map(|(id, field)| (id, field.revert_closurize(cache))),
+                    .map(|(id, field)| (id, with_path(field.revert_closurize(cache)))),
             );
 
             m.extend(
                 right
                     .into_iter()
-                    .map(|(id, field)| (id, field.revert_closurize(cache))),
+                    .map(|(id, field)| (id, with_path(field.revert_closurize(cache)))),
             );
 
             for (id, (field1, field2)) in center.into_iter() {
+                let mut field_label = merge_label.clone();
+                field_label.field_path.push(id);
+
                 m.insert(
                     id,
-                    merge_fields(cache, merge_label, field1, field2, field_names.iter())?,
+                    merge_fields(
+                        cache,
+                        field_label,
+                        options.doc_mode,
+                        with_path(field1),
+                        with_path(field2),
+                        field_names.iter(),
+                    )?,
                 );
             }
 
@@ -361,6 +693,22 @@ pub fn merge<C: Cache>(
                 label,
                 call_stack: call_stack.clone(),
             }),
+            // A record merged with something else is a common mistake, which deserves a more
+            // specific error
+            (MergeMode::Standard(merge_label), Term::Record(..)) => {
+                Err(EvalError::MergeRecordWithNonRecord {
+                    record: RichTerm::new(t2_, pos2),
+                    non_record: RichTerm::new(t1_, pos1),
+                    merge_label,
+                })
+            }
+            (MergeMode::Standard(merge_label), _) if matches!(t1_, Term::Record(..)) => {
+                Err(EvalError::MergeRecordWithNonRecord {
+                    record: RichTerm::new(t1_, pos1),
+                    non_record: RichTerm::new(t2_, pos2),
+                    merge_label,
+                })
+            }
             // The following cases are either errors or not yet implemented
             (mode, _) => Err(EvalError::MergeIncompatibleArgs {
                 left_arg: RichTerm::new(t1_, pos1),
@@ -378,6 +726,7 @@ pub fn merge<C: Cache>(
 fn merge_fields<'a, C: Cache, I: DoubleEndedIterator<Item = &'a LocIdent> + Clone>(
     cache: &mut C,
     merge_label: MergeLabel,
+    doc_mode: DocMergeMode,
     field1: Field,
     field2: Field,
     fields: I,
@@ -393,21 +742,25 @@ fn merge_fields<'a, C: Cache, I: DoubleEndedIterator<Item = &'a LocIdent> + Clon
         pending_contracts: pending_contracts2,
     } = field2;
 
+    // A relative priority is relative to the value it's merged over, so it's only resolved when
+    // both fields have a value.
+    let (priority1, priority2) = if value1.is_some() && value2.is_some() {
+        MergePriority::resolve_relative(metadata1.priority, metadata2.priority)
+    } else {
+        (metadata1.priority, metadata2.priority)
+    };
+
     // Selecting either meta1's value, meta2's value, or the merge of the two values,
     // depending on which is defined and respective priorities.
     let (value, priority) = match (value1, value2) {
-        (Some(t1), Some(t2)) if metadata1.priority == metadata2.priority => (
+        (Some(t1), Some(t2)) if priority1 == priority2 => (
             Some(fields_merge_closurize(cache, merge_label, t1, t2, fields).unwrap()),
-            metadata1.priority,
+            priority1,
         ),
-        (Some(t1), _) if metadata1.priority > metadata2.priority => {
-            (Some(t1.revert_closurize(cache)), metadata1.priority)
-        }
-        (Some(t1), None) => (Some(t1.revert_closurize(cache)), metadata1.priority),
-        (_, Some(t2)) if metadata2.priority > metadata1.priority => {
-            (Some(t2.revert_closurize(cache)), metadata2.priority)
-        }
-        (None, Some(t2)) => (Some(t2.revert_closurize(cache)), metadata2.priority),
+        (Some(t1), _) if priority1 > priority2 => (Some(t1.revert_closurize(cache)), priority1),
+        (Some(t1), None) => (Some(t1.revert_closurize(cache)), priority1),
+        (_, Some(t2)) if priority2 > priority1 => (Some(t2.revert_closurize(cache)), priority2),
+        (None, Some(t2)) => (Some(t2.revert_closurize(cache)), priority2),
         (None, None) => (None, Default::default()),
         _ => unreachable!(),
     };
@@ -423,7 +776,7 @@ fn merge_fields<'a, C: Cache, I: DoubleEndedIterator<Item = &'a LocIdent> + Clon
 
     Ok(Field {
         metadata: FieldMetadata {
-            doc: merge_doc(metadata1.doc, metadata2.doc),
+            doc: merge_doc(metadata1.doc, metadata2.doc, doc_mode),
             annotation: TypeAnnotation::combine_dedup(metadata1.annotation, metadata2.annotation),
             // If one of the record requires this field, then it musn't be optional. The
             // resulting field is optional iff both are.
@@ -436,10 +789,18 @@ fn merge_fields<'a, C: Cache, I: DoubleEndedIterator<Item = &'a LocIdent> + Clon
     })
 }
 
-/// Merge two optional documentations.
-pub(crate) fn merge_doc(doc1: Option<String>, doc2: Option<String>) -> Option<String> {
-    //FIXME: how to merge documentation? Just concatenate?
-    doc1.or(doc2)
+/// Merge two optional documentations according to `mode`.
+pub(crate) fn merge_doc(
+    doc1: Option<String>,
+    doc2: Option<String>,
+    mode: DocMergeMode,
+) -> Option<String> {
+    match (doc1, doc2) {
+        (Some(doc1), Some(doc2)) if mode == DocMergeMode::Concat && doc1 != doc2 => {
+            Some(format!("{doc1}\n\n{doc2}"))
+        }
+        (doc1, doc2) => doc1.or(doc2),
+    }
 }
 
 /// See [crate::eval::cache::Cache::saturate]. Saturation is a transformation on recursive cache
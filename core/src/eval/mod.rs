@@ -79,11 +79,13 @@ use crate::{
     closurize::{closurize_rec_record, Closurize},
     environment::Environment as GenericEnvironment,
     error::{Error, EvalError},
+    identifier::FieldPath,
+    identifier::FreshIdentGen,
     identifier::Ident,
     identifier::LocIdent,
+    label::{Label, MergeKind, MergeLabel},
     match_sharedterm,
     position::TermPos,
-    program::FieldPath,
     term::{
         array::ArrayAttrs,
         make as mk_term,
@@ -95,19 +97,35 @@ use crate::{
     },
 };
 
-use std::io::Write;
+use std::{
+    io::Write,
+    rc::Rc,
+    time::{Duration, Instant},
+};
 
 pub mod cache;
 pub mod callstack;
 pub mod fixpoint;
 pub mod merge;
 pub mod operation;
+pub mod profile;
+pub mod rng;
 pub mod stack;
+pub mod stats;
+pub mod trace;
 
 use callstack::*;
 use codespan::FileId;
+use merge::{
+    ArrayMergeMode, ContractCoverage, DefaultMergeMessages, DocMergeMode, MergeContext,
+    MergeMessages, MergeOptions, MergeProvenance,
+};
 use operation::OperationCont;
+use profile::Profile;
+use rng::Rng;
 use stack::{Stack, StrAccData};
+use stats::EvalStats;
+use trace::{TraceSink, WriteTraceSink};
 
 use self::cache::{Cache, CacheIndex};
 
@@ -129,10 +147,40 @@ pub struct VirtualMachine<R: ImportResolver, C: Cache> {
     pub cache: C,
     // The initial environment containing stdlib and builtin functions accessible from anywhere
     initial_env: Environment,
-    // The stream for writing trace output.
-    trace: Box<dyn Write>,
+    // The destination of trace output.
+    trace: Box<dyn TraceSink>,
+    // The maximum wall-clock time that an evaluation may take, if any.
+    time_limit: Option<Duration>,
+    // The instant at which evaluation must be aborted. Set from `time_limit` by
+    // [VirtualMachine::start_time_limit].
+    deadline: Option<Instant>,
+    // The number of evaluation steps remaining before we check the deadline again. Querying the
+    // system clock isn't free, so we only do it every `TIME_CHECK_PERIOD` steps.
+    steps_before_time_check: u32,
+    // The text of the explanations attached to merge errors.
+    merge_messages: Rc<dyn MergeMessages>,
+    // The generator of the fresh identifiers introduced by primitive operators. It's reset along
+    // with the machine, so that evaluating the same program twice yields the same intermediate
+    // terms.
+    fresh_idents: FreshIdentGen,
+    // The fields of record contracts checked so far, if contract coverage is enabled.
+    contract_coverage: Option<ContractCoverage>,
+    // The definitions of the fields merged so far, if merge provenance is enabled.
+    merge_provenance: Option<MergeProvenance>,
+    // How arrays and documentations are merged.
+    merge_options: MergeOptions,
+    // The source of randomness of primitive operators. As for `fresh_idents`, it's reset along
+    // with the machine.
+    rng: Rng,
+    // The counters of the operations performed so far, if statistics are enabled.
+    stats: Option<EvalStats>,
+    // The timings of the fields forced so far, if profiling is enabled.
+    profile: Option<Profile>,
 }
 
+/// The number of steps of the main evaluation loop between two checks of the evaluation deadline.
+const TIME_CHECK_PERIOD: u32 = 1024;
+
 impl<R: ImportResolver, C: Cache> VirtualMachine<R, C> {
     pub fn new(import_resolver: R, trace: impl Write + 'static) -> Self {
         VirtualMachine {
@@ -141,7 +189,18 @@ impl<R: ImportResolver, C: Cache> VirtualMachine<R, C> {
             stack: Stack::new(),
             cache: Cache::new(),
             initial_env: Environment::new(),
-            trace: Box::new(trace),
+            trace: Box::new(WriteTraceSink(trace)),
+            time_limit: None,
+            deadline: None,
+            steps_before_time_check: TIME_CHECK_PERIOD,
+            merge_messages: Rc::new(DefaultMergeMessages),
+            fresh_idents: FreshIdentGen::new(),
+            contract_coverage: None,
+            merge_provenance: None,
+            merge_options: MergeOptions::default(),
+            rng: Rng::from_entropy(),
+            stats: None,
+            profile: None,
         }
     }
 
@@ -151,8 +210,19 @@ impl<R: ImportResolver, C: Cache> VirtualMachine<R, C> {
             call_stack: Default::default(),
             stack: Stack::new(),
             cache,
-            trace: Box::new(trace),
+            trace: Box::new(WriteTraceSink(trace)),
             initial_env: Environment::new(),
+            time_limit: None,
+            deadline: None,
+            steps_before_time_check: TIME_CHECK_PERIOD,
+            merge_messages: Rc::new(DefaultMergeMessages),
+            fresh_idents: FreshIdentGen::new(),
+            contract_coverage: None,
+            merge_provenance: None,
+            merge_options: MergeOptions::default(),
+            rng: Rng::from_entropy(),
+            stats: None,
+            profile: None,
         }
     }
 
@@ -161,6 +231,144 @@ impl<R: ImportResolver, C: Cache> VirtualMachine<R, C> {
     pub fn reset(&mut self) {
         self.call_stack.0.clear();
         self.stack.reset(&mut self.cache);
+        self.fresh_idents.reset();
+        self.rng.reset();
+
+        if let Some(profile) = &mut self.profile {
+            profile.reset();
+        }
+    }
+
+    /// Set the seed of the pseudo-random number generator used by primitive operators such as
+    /// `%array/shuffle%`. Evaluating the same program with the same seed gives the same result.
+    /// `None` picks a seed which is different for each process, which is the default.
+    pub fn set_seed(&mut self, seed: Option<u64>) {
+        self.rng = seed.map(Rng::new).unwrap_or_else(Rng::from_entropy);
+    }
+
+    /// Set the maximum wall-clock time that evaluation may take. Once the limit is exceeded,
+    /// evaluation is aborted with [EvalError::TimeLimitExceeded]. `None` removes the limit.
+    ///
+    /// The limit only applies once the clock has been started with
+    /// [VirtualMachine::start_time_limit].
+    pub fn set_time_limit(&mut self, limit: Option<Duration>) {
+        self.time_limit = limit;
+        self.deadline = None;
+    }
+
+    /// Set the provider of the explanations attached to merge errors, such as the notes of the
+    /// error reported when a record contract gets extra fields. The default is
+    /// [DefaultMergeMessages].
+    pub fn set_merge_messages(&mut self, messages: impl MergeMessages + 'static) {
+        self.merge_messages = Rc::new(messages);
+    }
+
+    /// Enable or disable the recording of the fields checked by record contracts. Recording has
+    /// an overhead, and is disabled by default. Enabling it starts a new, empty record. See
+    /// [ContractCoverage].
+    pub fn set_contract_coverage(&mut self, enabled: bool) {
+        self.contract_coverage = enabled.then(ContractCoverage::default);
+    }
+
+    /// The fields checked by record contracts since contract coverage has been enabled, or `None`
+    /// if it's disabled.
+    pub fn contract_coverage(&self) -> Option<&ContractCoverage> {
+        self.contract_coverage.as_ref()
+    }
+
+    /// Enable or disable the recording of the definitions of merged fields. As for contract
+    /// coverage, recording has an overhead and is disabled by default, and enabling it starts a
+    /// new, empty record. See [MergeProvenance].
+    pub fn set_merge_provenance(&mut self, enabled: bool) {
+        self.merge_provenance = enabled.then(MergeProvenance::default);
+    }
+
+    /// The definitions of the fields merged since merge provenance has been enabled, or `None` if
+    /// it's disabled.
+    pub fn merge_provenance(&self) -> Option<&MergeProvenance> {
+        self.merge_provenance.as_ref()
+    }
+
+    /// Set how arrays are merged. The default, [ArrayMergeMode::Equal], only merges equal arrays.
+    /// See [ArrayMergeMode].
+    pub fn set_array_merge_mode(&mut self, mode: ArrayMergeMode) {
+        self.merge_options.array_mode = mode;
+    }
+
+    /// Set how the documentation of merged fields is combined. The default,
+    /// [DocMergeMode::Concat], keeps both. See [DocMergeMode].
+    pub fn set_doc_merge_mode(&mut self, mode: DocMergeMode) {
+        self.merge_options.doc_mode = mode;
+    }
+
+    /// Borrow the state of the machine needed by [merge::merge].
+    fn merge_context(&mut self) -> MergeContext<'_, C> {
+        MergeContext {
+            cache: &mut self.cache,
+            call_stack: &mut self.call_stack,
+            messages: self.merge_messages.as_ref(),
+            coverage: self.contract_coverage.as_mut(),
+            provenance: self.merge_provenance.as_mut(),
+            options: self.merge_options,
+        }
+    }
+
+    /// Set the receiver of the messages traced with `std.trace`. By default, they are written to
+    /// the stream given to the constructor of the machine.
+    pub fn set_trace_sink(&mut self, sink: impl TraceSink + 'static) {
+        self.trace = Box::new(sink);
+    }
+
+    /// Enable or disable the collection of evaluation statistics. Statistics aren't collected by
+    /// default. Enabling them resets all the counters to zero. See [EvalStats].
+    pub fn set_stats(&mut self, enabled: bool) {
+        self.stats = enabled.then(EvalStats::default);
+    }
+
+    /// The statistics collected since they have been enabled, or `None` if they are disabled.
+    pub fn stats(&self) -> Option<&EvalStats> {
+        self.stats.as_ref()
+    }
+
+    /// Enable profiling, timing the forcing of the fields of the result of the next deep
+    /// evaluations up to `depth` levels of nesting, or disable it if `depth` is `None`. Profiling
+    /// is disabled by default. See [Profile].
+    pub fn set_profile(&mut self, depth: Option<usize>) {
+        self.profile = depth.map(Profile::new);
+    }
+
+    /// The timings recorded since profiling has been enabled, or `None` if it's disabled.
+    pub fn profile(&self) -> Option<&Profile> {
+        self.profile.as_ref()
+    }
+
+    /// Start the clock for the time limit set by [VirtualMachine::set_time_limit], if any. The
+    /// deadline is computed from the current instant.
+    pub fn start_time_limit(&mut self) {
+        self.deadline = self.time_limit.map(|limit| Instant::now() + limit);
+        self.steps_before_time_check = TIME_CHECK_PERIOD;
+    }
+
+    /// Abort evaluation if the deadline has passed. To keep the overhead low, the clock is only
+    /// actually checked every [TIME_CHECK_PERIOD] calls.
+    fn check_time_limit(&mut self) -> Result<(), EvalError> {
+        let (Some(deadline), Some(limit)) = (self.deadline, self.time_limit) else {
+            return Ok(());
+        };
+
+        self.steps_before_time_check -= 1;
+
+        if self.steps_before_time_check > 0 {
+            return Ok(());
+        }
+
+        self.steps_before_time_check = TIME_CHECK_PERIOD;
+
+        if Instant::now() >= deadline {
+            Err(EvalError::TimeLimitExceeded(limit))
+        } else {
+            Ok(())
+        }
     }
 
     pub fn import_resolver(&self) -> &R {
@@ -221,6 +429,30 @@ impl<R: ImportResolver, C: Cache> VirtualMachine<R, C> {
             .map(|result| result.body)
     }
 
+    /// Merge two terms, as `t1 & t2` would, and evaluate the result to a weak head normal form.
+    /// Terms which have already been evaluated, such as the results of previous calls to
+    /// [Self::eval] on this machine, can be merged as well.
+    ///
+    /// Merge errors point to the span of both terms if they come from the same source, or to the
+    /// span of either of them otherwise.
+    pub fn merge_terms(&mut self, t1: RichTerm, t2: RichTerm) -> Result<RichTerm, EvalError> {
+        let span = match (t1.pos.into_opt(), t2.pos.into_opt()) {
+            (Some(span1), Some(span2)) => span1.fuse(span2).or(Some(span1)),
+            (span1, span2) => span1.or(span2),
+        };
+        let merge_label = match span {
+            Some(span) => MergeLabel {
+                span,
+                kind: MergeKind::Standard,
+                field_path: Vec::new(),
+            },
+            None => Label::default().into(),
+        };
+        let pos = t1.pos.fuse(t2.pos);
+
+        self.eval(mk_term::op2(BinaryOp::Merge(merge_label), t1, t2).with_pos(pos))
+    }
+
     /// Use a specific initial environment for evaluation. Usually, [VirtualMachine::prepare_eval]
     /// is populating the initial environment. But in some cases, such as testing or benchmarks, we
     /// might want to use a different one.
@@ -243,6 +475,12 @@ impl<R: ImportResolver, C: Cache> VirtualMachine<R, C> {
             closure.body,
         );
 
+        // The root of the result is profiled under the empty path, which isn't recorded, so that
+        // the forcing of its fields is profiled as well.
+        if self.profile.is_some() {
+            closure.body = mk_term::op1(UnaryOp::Profile(Vec::new()), closure.body);
+        }
+
         self.eval_closure(closure)
     }
 
@@ -430,11 +668,31 @@ impl<R: ImportResolver, C: Cache> VirtualMachine<R, C> {
         // is going to be discarded anyway
         std::mem::drop(env);
 
-        match self.cache.get_update_index(&mut idx) {
+        let update_index = self.cache.get_update_index(&mut idx);
+
+        if let Some(stats) = &mut self.stats {
+            stats.cache_lookups += 1;
+
+            match update_index {
+                Ok(Some(_)) => stats.cache_misses += 1,
+                Ok(None) => stats.cache_hits += 1,
+                Err(_) => {}
+            }
+        }
+
+        match update_index {
             Ok(Some(idx_upd)) => self.stack.push_update_index(idx_upd),
             Ok(None) => {}
             Err(_blackholed_error) => {
-                return Err(EvalError::InfiniteRecursion(self.call_stack.clone(), pos))
+                let bindings = var
+                    .map(|var| self.call_stack.cycle_from(var))
+                    .unwrap_or_default();
+
+                return Err(EvalError::InfiniteRecursion(
+                    self.call_stack.clone(),
+                    pos,
+                    bindings,
+                ));
             }
         }
 
@@ -487,6 +745,8 @@ impl<R: ImportResolver, C: Cache> VirtualMachine<R, C> {
     ///  - the evaluated term with its final environment
     pub fn eval_closure(&mut self, mut clos: Closure) -> Result<Closure, EvalError> {
         loop {
+            self.check_time_limit()?;
+
             let Closure {
                 body:
                     RichTerm {
@@ -588,6 +848,10 @@ impl<R: ImportResolver, C: Cache> VirtualMachine<R, C> {
                     Closure { body, env }
                 }
                 Term::Op1(op, arg) => {
+                    if let (UnaryOp::Profile(_), Some(profile)) = (&op, &mut self.profile) {
+                        profile.start();
+                    }
+
                     self.stack.push_op_cont(
                         OperationCont::Op1(op, arg.pos),
                         self.call_stack.len(),
@@ -9,8 +9,9 @@
 use super::{
     cache::lazy::Thunk,
     merge::{self, MergeMode},
+    profile::Profile,
     stack::StrAccData,
-    subst, Cache, Closure, Environment, ImportResolver, VirtualMachine,
+    subst, Cache, CacheIndex, Closure, Environment, ImportResolver, VirtualMachine,
 };
 
 #[cfg(feature = "nix-experimental")]
@@ -18,9 +19,9 @@ use crate::nix_ffi;
 
 use crate::{
     closurize::Closurize,
-    error::{EvalError, IllegalPolymorphicTailAction},
-    identifier::LocIdent,
-    label::{ty_path, Polarity, TypeVarData},
+    error::{DeserializationLocation, EvalError, ExportErrorData, IllegalPolymorphicTailAction},
+    identifier::{FreshIdentGen, Ident, LocIdent},
+    label::{ty_path, Label, MergeKind, MergeLabel, Polarity, TypeVarData},
     match_sharedterm, mk_app, mk_fun, mk_opn, mk_record,
     parser::utils::parse_number_sci,
     position::TermPos,
@@ -30,10 +31,11 @@ use crate::{
     term::{
         array::{Array, ArrayAttrs, OutOfBoundError},
         make as mk_term,
-        record::{self, Field, FieldMetadata, RecordData},
+        record::{self, Field, FieldMetadata, RecordAttrs, RecordData},
         string::NickelString,
         *,
     },
+    typ::{EnumRowsIteratorItem, Type, TypeF},
     typecheck::eq::contract_eq,
 };
 
@@ -41,7 +43,7 @@ use malachite::{
     num::{
         arithmetic::traits::Pow,
         basic::traits::Zero,
-        conversion::traits::{RoundingFrom, ToSci},
+        conversion::traits::{IsInteger, RoundingFrom, ToSci},
     },
     rounding_modes::RoundingMode,
     Integer,
@@ -51,7 +53,12 @@ use md5::digest::Digest;
 use simple_counter::*;
 use unicode_segmentation::UnicodeSegmentation;
 
-use std::{convert::TryFrom, iter::Extend, rc::Rc};
+use std::{
+    collections::{HashMap, HashSet},
+    convert::TryFrom,
+    iter::Extend,
+    rc::Rc,
+};
 
 generate_counter!(FreshVariableCounter, usize);
 
@@ -511,6 +518,123 @@ impl<R: ImportResolver, C: Cache> VirtualMachine<R, C> {
                 }
                 _ => Err(mk_type_error!("values", "Record")),
             }),
+            UnaryOp::RecordToEntries => match_sharedterm!(match (t) {
+                Term::Record(record) => {
+                    let mut values = record
+                        .into_iter_without_opts()
+                        .collect::<Result<Vec<_>, _>>()
+                        .map_err(|missing_def_err| missing_def_err.into_eval_err(pos, pos_op))?;
+
+                    // Sort in the same order as `record/fields`, so that the result is
+                    // deterministic.
+                    values.sort_by(|(id1, _), (id2, _)| id1.label().cmp(id2.label()));
+
+                    let entries = values
+                        .into_iter()
+                        .map(|(id, value)| {
+                            mk_record!(("field", mk_term::string(id.label())), ("value", value))
+                                .with_pos(pos_op_inh)
+                        })
+                        .collect();
+
+                    Ok(Closure {
+                        body: RichTerm::new(
+                            Term::Array(entries, ArrayAttrs::default()),
+                            pos_op_inh,
+                        ),
+                        env,
+                    })
+                }
+                _ => Err(mk_type_error!("record/to_entries", "Record")),
+            }),
+            UnaryOp::RecordFromEntries => match_sharedterm!(match (t) {
+                Term::Array(ts, attrs) => {
+                    // The field names are only known once the entries are evaluated, so we build
+                    // a chain of insertions instead, starting from the empty record. Inserting a
+                    // field which already exists fails with an error naming the field. Each entry
+                    // is accessed twice, hence closurized first to share its evaluation.
+                    let insert = BinaryOp::RecordInsert {
+                        ext_kind: RecordExtKind::WithValue,
+                        metadata: Default::default(),
+                        pending_contracts: Default::default(),
+                        op_kind: RecordOpKind::IgnoreEmptyOpt,
+                    };
+
+                    let record = ts.into_iter().fold(
+                        RichTerm::new(Term::Record(RecordData::empty()), pos_op_inh),
+                        |acc, entry| {
+                            let entry = RuntimeContract::apply_all(
+                                entry,
+                                attrs.pending_contracts.iter().cloned(),
+                                pos.into_inherited(),
+                            )
+                            .closurize(&mut self.cache, env.clone());
+
+                            mk_app!(
+                                mk_term::op2(
+                                    insert.clone(),
+                                    mk_term::op1(
+                                        UnaryOp::RecordAccess("field".into()),
+                                        entry.clone()
+                                    ),
+                                    acc
+                                ),
+                                mk_term::op1(UnaryOp::RecordAccess("value".into()), entry)
+                            )
+                            .with_pos(pos_op_inh)
+                        },
+                    );
+
+                    Ok(Closure {
+                        body: record,
+                        env: Environment::new(),
+                    })
+                }
+                _ => Err(mk_type_error!("record/from_entries", "Array")),
+            }),
+            UnaryOp::RecordMergeAll => match_sharedterm!(match (t) {
+                Term::Array(ts, attrs) => {
+                    // Each merge is labeled with the index of the element being merged, so that a
+                    // conflict points to the offending element rather than to the standard
+                    // library.
+                    let merged = ts.into_iter().enumerate().try_fold(
+                        RichTerm::new(Term::Record(RecordData::empty()), pos_op_inh),
+                        |acc, (index, elt)| {
+                            let span = elt
+                                .pos
+                                .into_opt()
+                                .or(pos.into_opt())
+                                .or(pos_op.into_opt())
+                                .ok_or_else(|| {
+                                    EvalError::InternalError(
+                                        String::from("merge_all: no position for the merge label"),
+                                        pos_op,
+                                    )
+                                })?;
+
+                            let elt_with_ctrs = RuntimeContract::apply_all(
+                                elt,
+                                attrs.pending_contracts.iter().cloned(),
+                                pos.into_inherited(),
+                            );
+
+                            Ok(mk_term::op2(
+                                BinaryOp::Merge(MergeLabel {
+                                    span,
+                                    kind: MergeKind::ArrayElement { index },
+                                    field_path: Vec::new(),
+                                }),
+                                acc,
+                                elt_with_ctrs,
+                            )
+                            .with_pos(pos_op_inh))
+                        },
+                    )?;
+
+                    Ok(Closure { body: merged, env })
+                }
+                _ => Err(mk_type_error!("merge_all", "Array")),
+            }),
             UnaryOp::ArrayMap => {
                 let (f, ..) = self
                     .stack
@@ -739,6 +863,69 @@ impl<R: ImportResolver, C: Cache> VirtualMachine<R, C> {
                     Err(mk_type_error!("length", "Array"))
                 }
             }
+            UnaryOp::ArrayShuffle => {
+                let Term::Array(ts, attrs) = &*t else {
+                    return Err(mk_type_error!("array/shuffle", "Array"));
+                };
+
+                let mut elts: Vec<_> = ts.iter().cloned().collect();
+                self.rng.shuffle(&mut elts);
+
+                Ok(Closure {
+                    body: RichTerm::new(
+                        Term::Array(elts.into_iter().collect(), attrs.clone()),
+                        pos_op_inh,
+                    ),
+                    env,
+                })
+            }
+            UnaryOp::ArrayDedup => {
+                let Term::Array(ts, attrs) = &*t else {
+                    return Err(mk_type_error!("array/dedup", "Array"));
+                };
+
+                let mut seen = HashSet::new();
+                let mut elts = Vec::with_capacity(ts.len());
+                // The first element which can't be compared for equality, such as a function. As
+                // with `==`, it's different from all the elements which can be compared, but
+                // comparing it to another such element is an error.
+                let mut incomparable: Option<&RichTerm> = None;
+
+                for elt in ts.iter() {
+                    match DedupKey::new(&self.cache, elt, &env) {
+                        Some(key) => {
+                            if seen.insert(key) {
+                                elts.push(elt.clone());
+                            }
+                        }
+                        None => {
+                            if let Some(first) = incomparable {
+                                let value = |rt: &RichTerm| match rt.as_ref() {
+                                    Term::Closure(idx) => self.cache.get(idx.clone()).body,
+                                    _ => rt.clone(),
+                                };
+
+                                return Err(EvalError::IncomparableValues {
+                                    eq_pos: pos_op,
+                                    left: value(first),
+                                    right: value(elt),
+                                });
+                            }
+
+                            incomparable = Some(elt);
+                            elts.push(elt.clone());
+                        }
+                    }
+                }
+
+                Ok(Closure {
+                    body: RichTerm::new(
+                        Term::Array(elts.into_iter().collect(), attrs.clone()),
+                        pos_op_inh,
+                    ),
+                    env,
+                })
+            }
             UnaryOp::ChunksConcat => {
                 let StrAccData {
                     mut acc,
@@ -808,6 +995,16 @@ impl<R: ImportResolver, C: Cache> VirtualMachine<R, C> {
                     Err(mk_type_error!("str_trim", "String"))
                 }
             }
+            UnaryOp::StringDedent => {
+                if let Term::Str(s) = &*t {
+                    Ok(Closure::atomic_closure(RichTerm::new(
+                        Term::Str(s.dedent()),
+                        pos_op_inh,
+                    )))
+                } else {
+                    Err(mk_type_error!("string/dedent", "String"))
+                }
+            }
             UnaryOp::StringChars => {
                 if let Term::Str(s) = &*t {
                     let ts = s.characters();
@@ -819,6 +1016,16 @@ impl<R: ImportResolver, C: Cache> VirtualMachine<R, C> {
                     Err(mk_type_error!("str_chars", "String"))
                 }
             }
+            UnaryOp::StringSplitLines => {
+                if let Term::Str(s) = &*t {
+                    Ok(Closure::atomic_closure(RichTerm::new(
+                        Term::Array(s.split_lines(), ArrayAttrs::new().closurized()),
+                        pos_op_inh,
+                    )))
+                } else {
+                    Err(mk_type_error!("string/split_lines", "String"))
+                }
+            }
             UnaryOp::StringUppercase => {
                 if let Term::Str(s) = &*t {
                     Ok(Closure::atomic_closure(RichTerm::new(
@@ -894,12 +1101,52 @@ impl<R: ImportResolver, C: Cache> VirtualMachine<R, C> {
                     Err(mk_type_error!("enum_from_str", "String"))
                 }
             }
+            UnaryOp::DurationFromString => {
+                let Term::Str(ref s) = *t else {
+                    return Err(mk_type_error!("duration/from_string", "String"));
+                };
+
+                let secs = parse_duration(s.as_str()).map_err(|msg| {
+                    EvalError::Other(
+                        format!(
+                            "duration/from_string: invalid duration `{}`: {msg}",
+                            s.as_str()
+                        ),
+                        pos,
+                    )
+                })?;
+
+                Ok(Closure::atomic_closure(RichTerm::new(
+                    Term::Num(secs),
+                    pos_op_inh,
+                )))
+            }
+            UnaryOp::DurationToString => {
+                let Term::Num(ref n) = *t else {
+                    return Err(mk_type_error!("duration/to_string", "Number"));
+                };
+
+                if n < &Number::ZERO {
+                    return Err(EvalError::Other(
+                        format!(
+                            "duration/to_string: expected a non-negative number of seconds, got {}",
+                            n.to_sci()
+                        ),
+                        pos,
+                    ));
+                }
+
+                Ok(Closure::atomic_closure(RichTerm::new(
+                    Term::Str(format_duration(n).into()),
+                    pos_op_inh,
+                )))
+            }
             UnaryOp::StringIsMatch => {
                 if let Term::Str(s) = &*t {
                     let re = regex::Regex::new(s)
                         .map_err(|err| EvalError::Other(err.to_string(), pos_op))?;
 
-                    let param = LocIdent::fresh();
+                    let param = self.fresh_idents.fresh();
                     let matcher = Term::Fun(
                         param,
                         RichTerm::new(
@@ -921,7 +1168,7 @@ impl<R: ImportResolver, C: Cache> VirtualMachine<R, C> {
                     let re = regex::Regex::new(s)
                         .map_err(|err| EvalError::Other(err.to_string(), pos_op))?;
 
-                    let param = LocIdent::fresh();
+                    let param = self.fresh_idents.fresh();
                     let matcher = Term::Fun(
                         param,
                         RichTerm::new(
@@ -943,7 +1190,7 @@ impl<R: ImportResolver, C: Cache> VirtualMachine<R, C> {
                     let re = regex::Regex::new(s)
                         .map_err(|err| EvalError::Other(err.to_string(), pos_op))?;
 
-                    let param = LocIdent::fresh();
+                    let param = self.fresh_idents.fresh();
                     let matcher = Term::Fun(
                         param,
                         RichTerm::new(
@@ -1058,6 +1305,21 @@ impl<R: ImportResolver, C: Cache> VirtualMachine<R, C> {
 
                 match_sharedterm!(match (t) {
                     Term::Record(record) if !record.fields.is_empty() => {
+                        // If this record is being profiled, that is if the continuation is
+                        // `Profile`, we profile its fields as well, up to the depth of the
+                        // profile.
+                        let profiled_path = match self.profile.as_ref().map(Profile::depth) {
+                            Some(depth) => match self.stack.peek_op_cont() {
+                                Some(OperationCont::Op1(UnaryOp::Profile(path), _))
+                                    if path.len() < depth =>
+                                {
+                                    Some(path)
+                                }
+                                _ => None,
+                            },
+                            None => None,
+                        };
+
                         let fields = record
                             .fields
                             .into_iter()
@@ -1065,13 +1327,22 @@ impl<R: ImportResolver, C: Cache> VirtualMachine<R, C> {
                                 !(field.is_empty_optional()
                                     || (ignore_not_exported && field.metadata.not_exported))
                             })
-                            .map_values_closurize(&mut self.cache, &env, |_, value| {
-                                mk_term::op1(
+                            .map_values_closurize(&mut self.cache, &env, |id, value| {
+                                let forced = mk_term::op1(
                                     UnaryOp::Force {
                                         ignore_not_exported,
                                     },
                                     value,
-                                )
+                                );
+
+                                match &profiled_path {
+                                    Some(path) => {
+                                        let mut path = path.clone();
+                                        path.push(id);
+                                        mk_term::op1(UnaryOp::Profile(path), forced)
+                                    }
+                                    None => forced,
+                                }
                             })
                             .map_err(|e| e.into_eval_err(pos, pos_op))?;
 
@@ -1152,12 +1423,30 @@ impl<R: ImportResolver, C: Cache> VirtualMachine<R, C> {
                     }),
                 })
             }
-            UnaryOp::RecDefault => {
-                Ok(RecPriority::Bottom.propagate_in_term(&mut self.cache, t, env, pos))
-            }
-            UnaryOp::RecForce => {
-                Ok(RecPriority::Top.propagate_in_term(&mut self.cache, t, env, pos))
+            UnaryOp::Profile(path) => {
+                if let Some(profile) = &mut self.profile {
+                    profile.stop(path);
+                }
+
+                Ok(Closure {
+                    body: RichTerm { term: t, pos },
+                    env,
+                })
             }
+            UnaryOp::RecDefault => Ok(RecPriority::Bottom.propagate_in_term(
+                &mut self.cache,
+                &mut self.fresh_idents,
+                t,
+                env,
+                pos,
+            )),
+            UnaryOp::RecForce => Ok(RecPriority::Top.propagate_in_term(
+                &mut self.cache,
+                &mut self.fresh_idents,
+                t,
+                env,
+                pos,
+            )),
             UnaryOp::RecordEmptyWithTail => match_sharedterm!(match (t) {
                 Term::Record(r) => {
                     let mut empty = RecordData::empty();
@@ -1170,8 +1459,11 @@ impl<R: ImportResolver, C: Cache> VirtualMachine<R, C> {
                 _ => Err(mk_type_error!("record_empty_with_tail", "Record")),
             }),
             UnaryOp::Trace => {
-                if let Term::Str(s) = &*t {
-                    let _ = writeln!(self.trace, "std.trace: {s}");
+                if let Term::Str(_) = &*t {
+                    self.trace.trace(&RichTerm {
+                        term: t.clone(),
+                        pos,
+                    });
                     Ok(())
                 } else {
                     Err(mk_type_error!("trace", "String"))
@@ -1320,6 +1612,104 @@ impl<R: ImportResolver, C: Cache> VirtualMachine<R, C> {
                     pos_op_inh,
                 )))
             }
+            UnaryOp::EnumToRecord => {
+                let (tag, arg) = match &*t {
+                    Term::Enum(tag) => (*tag, None),
+                    Term::EnumVariant { tag, arg, .. } => (*tag, Some(arg.clone())),
+                    _ => return Err(mk_type_error!("enum/to_record", "Enum")),
+                };
+
+                let tag = RichTerm::new(Term::Str(tag.into()), pos_op_inh);
+                let fields = std::iter::once((LocIdent::from("tag"), tag))
+                    .chain(arg.map(|arg| (LocIdent::from("arg"), arg)));
+
+                // The argument of the variant might not be closurized, so we keep the original
+                // environment around.
+                Ok(Closure {
+                    body: RichTerm::new(
+                        Term::Record(RecordData::with_field_values(fields)),
+                        pos_op_inh,
+                    ),
+                    env,
+                })
+            }
+            UnaryOp::EnumFromRecord => {
+                let Term::Record(record) = &*t else {
+                    return Err(mk_type_error!("enum/from_record", "Record"));
+                };
+
+                let field_names = record.field_names(RecordOpKind::IgnoreEmptyOpt);
+
+                if let Some(extra) = field_names
+                    .iter()
+                    .find(|id| !matches!(id.label(), "tag" | "arg"))
+                {
+                    return Err(EvalError::Other(
+                        format!(
+                            "enum/from_record: unexpected field `{extra}`, \
+                            the record can only have the fields `tag` and `arg`"
+                        ),
+                        pos,
+                    ));
+                }
+
+                let tag_id = LocIdent::from("tag");
+                let tag = record
+                    .get_value_with_ctrs(&tag_id)
+                    .map_err(|err| err.into_eval_err(pos, pos_op))?
+                    .ok_or_else(|| EvalError::FieldMissing {
+                        id: tag_id,
+                        field_names,
+                        operator: String::from("enum/from_record"),
+                        pos_record: pos,
+                        pos_op,
+                    })?;
+                let arg = record
+                    .get_value_with_ctrs(&LocIdent::from("arg"))
+                    .map_err(|err| err.into_eval_err(pos, pos_op))?;
+
+                // The tag isn't evaluated yet: we delegate its conversion to the operators that
+                // build enums from strings, which will force it.
+                let body = match arg {
+                    Some(arg) => mk_app!(mk_term::op1(UnaryOp::EnumMakeVariant, tag), arg),
+                    None => mk_term::op1(UnaryOp::EnumFromString, tag),
+                };
+
+                Ok(Closure {
+                    body: body.with_pos(pos_op_inh),
+                    env,
+                })
+            }
+            UnaryOp::EnumVariants => {
+                let Term::Type(ty) = &*t else {
+                    return Err(mk_type_error!("enum/variants", "Type"));
+                };
+
+                let TypeF::Enum(erows) = &ty.typ else {
+                    return Err(EvalError::Other(
+                        format!(
+                            "enum/variants: expected an enum contract, such as `[| 'A, 'B |]`, \
+                            got the contract `{ty}`"
+                        ),
+                        pos,
+                    ));
+                };
+
+                let tags = erows
+                    .iter()
+                    .filter_map(|item| match item {
+                        EnumRowsIteratorItem::Row(row) => {
+                            Some(RichTerm::from(Term::Str(row.id.label().into())))
+                        }
+                        EnumRowsIteratorItem::TailVar(_) => None,
+                    })
+                    .collect();
+
+                Ok(Closure::atomic_closure(RichTerm::new(
+                    Term::Array(tags, ArrayAttrs::new().closurized()),
+                    pos_op_inh,
+                )))
+            }
             UnaryOp::PatternBranch => {
                 // The continuation, that we must evaluate in the augmented environment.
                 let (mut cont, _) = self
@@ -1555,6 +1945,26 @@ impl<R: ImportResolver, C: Cache> VirtualMachine<R, C> {
                     Err(mk_type_error!("pow", "Number", 1, t1, pos1))
                 }
             }
+            BinaryOp::NumberMin | BinaryOp::NumberMax => {
+                let Term::Num(ref n1) = *t1 else {
+                    return Err(mk_type_error!(b_op.to_string(), "Number", 1, t1, pos1));
+                };
+
+                let Term::Num(ref n2) = *t2 else {
+                    return Err(mk_type_error!(b_op.to_string(), "Number", 2, t2, pos2));
+                };
+
+                let result = if matches!(b_op, BinaryOp::NumberMin) {
+                    n1.min(n2)
+                } else {
+                    n1.max(n2)
+                };
+
+                Ok(Closure::atomic_closure(RichTerm::new(
+                    Term::Num(result.clone()),
+                    pos_op_inh,
+                )))
+            }
             BinaryOp::StringConcat => {
                 if let Term::Str(s1) = &*t1 {
                     if let Term::Str(s2) = &*t2 {
@@ -1571,6 +1981,10 @@ impl<R: ImportResolver, C: Cache> VirtualMachine<R, C> {
                 }
             }
             BinaryOp::ContractApply => {
+                if let Some(stats) = &mut self.stats {
+                    stats.contracts_applied += 1;
+                }
+
                 if let Term::Lbl(l) = &*t2 {
                     // Track the contract argument for better error reporting, and push back the
                     // label on the stack, so that it becomes the first argument of the contract.
@@ -1580,6 +1994,11 @@ impl<R: ImportResolver, C: Cache> VirtualMachine<R, C> {
                     let mut l = l.clone();
                     l.arg_pos = self.cache.get_then(idx.clone(), |c| c.body.pos);
                     l.arg_idx = Some(idx);
+                    l.contract_pos = if matches!(&*t1, Term::Type(_)) {
+                        TermPos::None
+                    } else {
+                        pos1
+                    };
 
                     self.stack.push_arg(
                         Closure::atomic_closure(RichTerm::new(Term::Lbl(l), pos2.into_inherited())),
@@ -1961,53 +2380,282 @@ impl<R: ImportResolver, C: Cache> VirtualMachine<R, C> {
                 }),
                 _ => Err(mk_type_error!("record_remove", "String", 1, t1, pos1)),
             }),
-            BinaryOp::RecordHasField(op_kind) => match_sharedterm!(match (t1) {
-                Term::Str(id) => {
-                    if let Term::Record(record) = &*t2 {
-                        Ok(Closure::atomic_closure(RichTerm::new(
-                            Term::Bool(matches!(
-                                record.fields.get(&LocIdent::from(id.into_inner())),
-                                Some(field) if matches!(op_kind, RecordOpKind::ConsiderAllFields) || !field.is_empty_optional()
-                            )),
-                            pos_op_inh,
-                        )))
-                    } else {
-                        Err(mk_type_error!("has_field", "Record", 2, t2, pos2))
-                    }
-                }
-                _ => Err(mk_type_error!("has_field", "String", 1, t1, pos1)),
-            }),
-            BinaryOp::RecordFieldIsDefined(op_kind) => match_sharedterm!(match (t1) {
-                Term::Str(id) => {
-                    if let Term::Record(record) = &*t2 {
-                        Ok(Closure::atomic_closure(RichTerm::new(
-                            Term::Bool(matches!(
-                                record.fields.get(&LocIdent::from(id.into_inner())),
-                                Some(field @ Field { value: Some(_), ..}) if matches!(op_kind, RecordOpKind::ConsiderAllFields) || !field.is_empty_optional()
-                            )),
-                            pos_op_inh,
-                        )))
-                    } else {
-                        Err(mk_type_error!("field_is_defined", "Record", 2, t2, pos2))
+            BinaryOp::RecordFilterKeys => match_sharedterm!(match (t2) {
+                Term::Record(record) => {
+                    // We can't evaluate the predicate from within a primop. Instead, we build an
+                    // array with the result of the predicate for each field, force it with
+                    // `deep_seq`, and hand it over to `RecordFilterKeysMasked`, which does the
+                    // actual filtering in one pass.
+                    let f_closure = RichTerm {
+                        term: t1,
+                        pos: pos1,
                     }
+                    .closurize(&mut self.cache, env1);
+
+                    let mask: Array = record
+                        .fields
+                        .keys()
+                        .map(|id| {
+                            mk_app!(f_closure.clone(), mk_term::string(id.label()))
+                                .with_pos(pos_op_inh)
+                                .closurize(&mut self.cache, Environment::new())
+                        })
+                        .collect();
+
+                    let mask = RichTerm::new(
+                        Term::Array(mask, ArrayAttrs::new().closurized()),
+                        pos_op_inh,
+                    )
+                    .closurize(&mut self.cache, Environment::new());
+                    let record = RichTerm::new(Term::Record(record), pos2)
+                        .closurize(&mut self.cache, env2);
+
+                    Ok(Closure {
+                        body: mk_app!(
+                            mk_term::op1(UnaryOp::DeepSeq, mask.clone()),
+                            mk_term::op2(BinaryOp::RecordFilterKeysMasked, mask, record)
+                        )
+                        .with_pos(pos_op_inh),
+                        env: Environment::new(),
+                    })
                 }
-                _ => Err(mk_type_error!("field_is_defined", "String", 1, t1, pos1)),
+                _ => Err(mk_type_error!("record/filter_keys", "Record", 2, t2, pos2)),
             }),
-            BinaryOp::ArrayConcat => match_sharedterm!(match (t1) {
-                Term::Array(ts1, attrs1) => match_sharedterm!(match (t2) {
-                    Term::Array(ts2, attrs2) => {
-                        // NOTE: the [eval_closure] function in [eval] should've made sure
-                        // that the array is closurized. We leave a debug_assert! here just
-                        // in case something goes wrong in the future. If the assert failed,
-                        // you may need to map closurize over `ts1` and `ts2`.
-                        debug_assert!(
-                            attrs1.closurized,
-                            "the left-hand side of ArrayConcat (@) is not closurized."
-                        );
-                        debug_assert!(
-                            attrs2.closurized,
-                            "the right-hand side of ArrayConcat (@) is not closurized."
-                        );
+            BinaryOp::RecordFilterKeysMasked => {
+                let Term::Array(mask, _) = &*t1 else {
+                    return Err(mk_type_error!("record/filter_keys_masked", "Array", 1, t1, pos1));
+                };
+
+                let mask = mask
+                    .iter()
+                    .map(|elt| {
+                        let value = match elt.as_ref() {
+                            Term::Closure(idx) => self.cache.get(idx.clone()).body,
+                            _ => elt.clone(),
+                        };
+
+                        match value.as_ref() {
+                            Term::Bool(keep) => Ok(*keep),
+                            _ => Err(EvalError::TypeError(
+                                String::from("Bool"),
+                                String::from("the predicate must return a Bool"),
+                                pos_op,
+                                value,
+                            )),
+                        }
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+
+                match_sharedterm!(match (t2) {
+                    Term::Record(record) => {
+                        let fields = record
+                            .fields
+                            .into_iter()
+                            .zip(mask)
+                            .filter_map(|(entry, keep)| keep.then_some(entry))
+                            .collect();
+
+                        Ok(Closure {
+                            body: RichTerm::new(
+                                Term::Record(RecordData { fields, ..record }),
+                                pos_op_inh,
+                            ),
+                            env: env2,
+                        })
+                    }
+                    _ => Err(mk_type_error!("record/filter_keys_masked", "Record", 2, t2, pos2)),
+                })
+            }
+            BinaryOp::RecordDifference | BinaryOp::RecordIntersection => {
+                let is_difference = matches!(b_op, BinaryOp::RecordDifference);
+                let op_name = b_op.to_string();
+
+                match_sharedterm!(match (t1) {
+                    Term::Record(record1) => {
+                        let Term::Record(record2) = &*t2 else {
+                            return Err(mk_type_error!(op_name, "Record", 2, t2, pos2));
+                        };
+
+                        // Only the names of the fields of the second record matter.
+                        let names2: IndexMap<LocIdent, ()> = record2
+                            .field_names(RecordOpKind::IgnoreEmptyOpt)
+                            .into_iter()
+                            .map(|id| (id, ()))
+                            .collect();
+                        let merge::split::SplitResult { left, center, .. } =
+                            merge::split::split(record1.fields, names2);
+
+                        let fields = if is_difference {
+                            left
+                        } else {
+                            center
+                                .into_iter()
+                                .map(|(id, (field, ()))| (id, field))
+                                .collect()
+                        };
+
+                        Ok(Closure {
+                            body: RichTerm::new(
+                                Term::Record(RecordData { fields, ..record1 }),
+                                pos_op_inh,
+                            ),
+                            env: env1,
+                        })
+                    }
+                    _ => Err(mk_type_error!(op_name, "Record", 1, t1, pos1)),
+                })
+            }
+            BinaryOp::RecordRenameKeys => match_sharedterm!(match (t1) {
+                Term::Record(mapping) => {
+                    if !matches!(&*t2, Term::Record(_)) {
+                        return Err(mk_type_error!("record/rename_keys", "Record", 2, t2, pos2));
+                    }
+
+                    // As for `RecordFilterKeys`, the new names must be evaluated before we can
+                    // rename anything. We force them with `deep_seq` and hand them over to
+                    // `RecordRenameAll`.
+                    let (olds, news): (Array, Array) = mapping
+                        .into_iter_without_opts()
+                        .map(|entry| {
+                            entry.map(|(old, new)| {
+                                (
+                                    mk_term::string(old.label())
+                                        .closurize(&mut self.cache, Environment::new()),
+                                    new.closurize(&mut self.cache, env1.clone()),
+                                )
+                            })
+                        })
+                        .collect::<Result<Vec<_>, _>>()
+                        .map_err(|missing_def_err| missing_def_err.into_eval_err(pos1, pos_op))?
+                        .into_iter()
+                        .unzip();
+
+                    let olds = RichTerm::new(
+                        Term::Array(olds, ArrayAttrs::new().closurized()),
+                        pos_op_inh,
+                    );
+                    let news = RichTerm::new(
+                        Term::Array(news, ArrayAttrs::new().closurized()),
+                        pos_op_inh,
+                    )
+                    .closurize(&mut self.cache, Environment::new());
+                    let record = RichTerm {
+                        term: t2,
+                        pos: pos2,
+                    }
+                    .closurize(&mut self.cache, env2);
+
+                    Ok(Closure {
+                        body: mk_app!(
+                            mk_term::op1(UnaryOp::DeepSeq, news.clone()),
+                            mk_opn!(NAryOp::RecordRenameAll, olds, news, record)
+                        )
+                        .with_pos(pos_op_inh),
+                        env: Environment::new(),
+                    })
+                }
+                _ => Err(mk_type_error!("record/rename_keys", "Record", 1, t1, pos1)),
+            }),
+            BinaryOp::RecordMapKeys => {
+                if !matches!(&*t1, Term::Fun(..) | Term::FunPattern(..) | Term::Match(_)) {
+                    return Err(mk_type_error!("record/map_keys", "Function", 1, t1, pos1));
+                }
+
+                let Term::Record(record) = &*t2 else {
+                    return Err(mk_type_error!("record/map_keys", "Record", 2, t2, pos2));
+                };
+
+                let f = RichTerm {
+                    term: t1,
+                    pos: pos1,
+                }
+                .closurize(&mut self.cache, env1);
+
+                // We compute the new names and hand them over to `RecordRenameAll`, as done for
+                // `RecordRenameKeys`.
+                let (olds, news): (Array, Array) = record
+                    .fields
+                    .keys()
+                    .map(|id| {
+                        let old = mk_term::string(id.label());
+                        (
+                            old.clone(),
+                            mk_app!(f.clone(), old).closurize(&mut self.cache, Environment::new()),
+                        )
+                    })
+                    .unzip();
+
+                let olds = RichTerm::new(
+                    Term::Array(olds, ArrayAttrs::new().closurized()),
+                    pos_op_inh,
+                );
+                let news = RichTerm::new(
+                    Term::Array(news, ArrayAttrs::new().closurized()),
+                    pos_op_inh,
+                )
+                .closurize(&mut self.cache, Environment::new());
+                let record = RichTerm {
+                    term: t2,
+                    pos: pos2,
+                }
+                .closurize(&mut self.cache, env2);
+
+                Ok(Closure {
+                    body: mk_app!(
+                        mk_term::op1(UnaryOp::DeepSeq, news.clone()),
+                        mk_opn!(NAryOp::RecordRenameAll, olds, news, record)
+                    )
+                    .with_pos(pos_op_inh),
+                    env: Environment::new(),
+                })
+            }
+            BinaryOp::RecordHasField(op_kind) => match_sharedterm!(match (t1) {
+                Term::Str(id) => {
+                    if let Term::Record(record) = &*t2 {
+                        Ok(Closure::atomic_closure(RichTerm::new(
+                            Term::Bool(matches!(
+                                record.fields.get(&LocIdent::from(id.into_inner())),
+                                Some(field) if matches!(op_kind, RecordOpKind::ConsiderAllFields) || !field.is_empty_optional()
+                            )),
+                            pos_op_inh,
+                        )))
+                    } else {
+                        Err(mk_type_error!("has_field", "Record", 2, t2, pos2))
+                    }
+                }
+                _ => Err(mk_type_error!("has_field", "String", 1, t1, pos1)),
+            }),
+            BinaryOp::RecordFieldIsDefined(op_kind) => match_sharedterm!(match (t1) {
+                Term::Str(id) => {
+                    if let Term::Record(record) = &*t2 {
+                        Ok(Closure::atomic_closure(RichTerm::new(
+                            Term::Bool(matches!(
+                                record.fields.get(&LocIdent::from(id.into_inner())),
+                                Some(field @ Field { value: Some(_), ..}) if matches!(op_kind, RecordOpKind::ConsiderAllFields) || !field.is_empty_optional()
+                            )),
+                            pos_op_inh,
+                        )))
+                    } else {
+                        Err(mk_type_error!("field_is_defined", "Record", 2, t2, pos2))
+                    }
+                }
+                _ => Err(mk_type_error!("field_is_defined", "String", 1, t1, pos1)),
+            }),
+            BinaryOp::ArrayConcat => match_sharedterm!(match (t1) {
+                Term::Array(ts1, attrs1) => match_sharedterm!(match (t2) {
+                    Term::Array(ts2, attrs2) => {
+                        // NOTE: the [eval_closure] function in [eval] should've made sure
+                        // that the array is closurized. We leave a debug_assert! here just
+                        // in case something goes wrong in the future. If the assert failed,
+                        // you may need to map closurize over `ts1` and `ts2`.
+                        debug_assert!(
+                            attrs1.closurized,
+                            "the left-hand side of ArrayConcat (@) is not closurized."
+                        );
+                        debug_assert!(
+                            attrs2.closurized,
+                            "the right-hand side of ArrayConcat (@) is not closurized."
+                        );
 
                         // NOTE: To avoid the extra Vec allocation, we could use
                         // Rc<[T]>::new_uninit_slice() and fill up the slice manually, but
@@ -2117,29 +2765,26 @@ impl<R: ImportResolver, C: Cache> VirtualMachine<R, C> {
             }),
             BinaryOp::ArrayAt => match (&*t1, &*t2) {
                 (Term::Array(ts, attrs), Term::Num(n)) => {
-                    let Ok(n_as_usize) = usize::try_from(n) else {
-                        return Err(EvalError::Other(
-                            format!(
-                                "elem_at expects its second argument to be a \
-                                positive integer smaller than {}, got {n}",
-                                usize::MAX
-                            ),
-                            pos_op,
-                        ));
-                    };
-
-                    if n_as_usize >= ts.len() {
+                    if !n.is_integer() {
                         return Err(EvalError::Other(
-                            format!(
-                                "elem_at: index out of bounds. \
-                                Expected an index between 0 and {}, got {}",
-                                ts.len(),
-                                n
-                            ),
+                            format!("elem_at expects its second argument to be an integer, got {n}"),
                             pos_op,
                         ));
                     }
 
+                    // A negative index, or an index too large to fit in a `usize`, is out of
+                    // bounds as well.
+                    let n_as_usize = match usize::try_from(n) {
+                        Ok(n_as_usize) if n_as_usize < ts.len() => n_as_usize,
+                        _ => {
+                            return Err(EvalError::ArrayOutOfBounds {
+                                index: n.clone(),
+                                length: ts.len(),
+                                pos: pos_op,
+                            })
+                        }
+                    };
+
                     let elem_with_ctr = RuntimeContract::apply_all(
                         ts.get(n_as_usize).unwrap().clone(),
                         attrs.pending_contracts.iter().cloned(),
@@ -2154,103 +2799,429 @@ impl<R: ImportResolver, C: Cache> VirtualMachine<R, C> {
                 (Term::Array(..), _) => Err(mk_type_error!("elem_at", "Number", 2, t2, pos2)),
                 (_, _) => Err(mk_type_error!("elem_at", "Array", 1, t1, pos1)),
             },
-            BinaryOp::Merge(merge_label) => merge::merge(
-                &mut self.cache,
-                RichTerm {
+            BinaryOp::FunctionCompose => {
+                let is_fun =
+                    |t: &Term| matches!(t, Term::Fun(..) | Term::FunPattern(..) | Term::Match(_));
+
+                if !is_fun(&t1) {
+                    return Err(mk_type_error!("function/compose", "Function", 1, t1, pos1));
+                }
+
+                if !is_fun(&t2) {
+                    return Err(mk_type_error!("function/compose", "Function", 2, t2, pos2));
+                }
+
+                let f = RichTerm {
                     term: t1,
                     pos: pos1,
-                },
-                env1,
-                RichTerm {
+                }
+                .closurize(&mut self.cache, env1);
+                let g = RichTerm {
                     term: t2,
                     pos: pos2,
-                },
-                env2,
-                pos_op,
-                MergeMode::Standard(merge_label),
-                &mut self.call_stack,
-            ),
-            BinaryOp::Hash => {
-                let mk_err_fst = |t1| {
-                    Err(mk_type_error!(
-                        "hash",
-                        "[| 'Md5, 'Sha1, 'Sha256, 'Sha512 |]",
-                        1,
-                        t1,
-                        pos1
-                    ))
-                };
+                }
+                .closurize(&mut self.cache, env2);
+
+                // The inner application is an argument of `f`: it's put in a thunk when `f` is
+                // applied, and is only evaluated if `f` uses its argument.
+                let param = self.fresh_idents.fresh();
+                let composed = Term::Fun(
+                    param,
+                    mk_app!(
+                        f,
+                        mk_app!(g, RichTerm::new(Term::Var(param), pos_op_inh))
+                            .with_pos(pos_op_inh)
+                    )
+                    .with_pos(pos_op_inh),
+                );
 
-                if let Term::Enum(id) = &*t1 {
-                    if let Term::Str(s) = &*t2 {
-                        let result = match id.as_ref() {
-                            "Md5" => {
-                                let mut hasher = md5::Md5::new();
-                                hasher.update(s.as_ref());
-                                format!("{:x}", hasher.finalize())
-                            }
-                            "Sha1" => {
-                                let mut hasher = sha1::Sha1::new();
-                                hasher.update(s.as_ref());
-                                format!("{:x}", hasher.finalize())
-                            }
-                            "Sha256" => {
-                                let mut hasher = sha2::Sha256::new();
-                                hasher.update(s.as_ref());
-                                format!("{:x}", hasher.finalize())
-                            }
-                            "Sha512" => {
-                                let mut hasher = sha2::Sha512::new();
-                                hasher.update(s.as_ref());
-                                format!("{:x}", hasher.finalize())
-                            }
-                            _ => return mk_err_fst(t1),
-                        };
+                Ok(Closure::atomic_closure(RichTerm::new(composed, pos_op_inh)))
+            }
+            BinaryOp::ArrayZip => match (&*t1, &*t2) {
+                (Term::Array(ts1, attrs1), Term::Array(ts2, attrs2)) => {
+                    // If the arrays have different lengths, we truncate the result to the
+                    // shortest one instead of erroring out, as `zip` usually does in other
+                    // functional languages.
+                    let ts = ts1
+                        .iter()
+                        .zip(ts2.iter())
+                        .map(|(fst, snd)| {
+                            let fst = RuntimeContract::apply_all(
+                                fst.clone(),
+                                attrs1.pending_contracts.iter().cloned(),
+                                pos1.into_inherited(),
+                            )
+                            .closurize(&mut self.cache, env1.clone());
+                            let snd = RuntimeContract::apply_all(
+                                snd.clone(),
+                                attrs2.pending_contracts.iter().cloned(),
+                                pos2.into_inherited(),
+                            )
+                            .closurize(&mut self.cache, env2.clone());
+
+                            let pair = RecordData {
+                                attrs: RecordAttrs::new().closurized(),
+                                ..RecordData::with_field_values([
+                                    (LocIdent::from("_1"), fst),
+                                    (LocIdent::from("_2"), snd),
+                                ])
+                            };
 
-                        Ok(Closure::atomic_closure(RichTerm::new(
-                            Term::Str(result.into()),
+                            RichTerm::new(Term::Record(pair), pos_op_inh)
+                                .closurize(&mut self.cache, Environment::new())
+                        })
+                        .collect();
+
+                    Ok(Closure {
+                        body: RichTerm::new(
+                            Term::Array(ts, ArrayAttrs::new().closurized()),
                             pos_op_inh,
-                        )))
-                    } else {
-                        Err(mk_type_error!("hash", "String", 2, t2, pos2))
-                    }
-                } else {
-                    mk_err_fst(t1)
+                        ),
+                        env: Environment::new(),
+                    })
                 }
-            }
-            BinaryOp::Serialize => {
-                let mk_err_fst = |t1| {
-                    Err(mk_type_error!(
-                        "serialize",
-                        "[| 'Json, 'Yaml, 'Toml |]",
-                        1,
-                        t1,
-                        pos1
-                    ))
-                };
+                (Term::Array(..), _) => Err(mk_type_error!("array/zip", "Array", 2, t2, pos2)),
+                (_, _) => Err(mk_type_error!("array/zip", "Array", 1, t1, pos1)),
+            },
+            BinaryOp::ArrayChunk => match (&*t1, &*t2) {
+                (Term::Num(size), Term::Array(ts, attrs)) => {
+                    if !size.is_integer() || size <= &Number::ZERO {
+                        return Err(EvalError::Other(
+                            format!(
+                                "array/chunk expects its first argument to be a positive \
+                                integer, got {size}"
+                            ),
+                            pos_op,
+                        ));
+                    }
 
-                if let Term::Enum(ref id) = t1.as_ref() {
-                    // Serialization needs all variables term to be fully substituted
-                    let initial_env = Environment::new();
-                    let rt2 = subst(
-                        &self.cache,
-                        RichTerm {
-                            term: t2,
-                            pos: pos2,
-                        },
-                        &initial_env,
-                        &env2,
-                    );
+                    // A size too large to fit in a `usize` is larger than any array anyway.
+                    let size = usize::try_from(size).unwrap_or(usize::MAX);
 
-                    let format = match id.to_string().as_str() {
-                        "Json" => ExportFormat::Json,
-                        "Yaml" => ExportFormat::Yaml,
-                        "Toml" => ExportFormat::Toml,
-                        _ => return mk_err_fst(t1),
-                    };
+                    // The chunks are views over the original array, and keep its pending
+                    // contracts, which thus stay lazy.
+                    let chunks = (0..ts.len())
+                        .step_by(size)
+                        .map(|start| {
+                            let mut chunk = ts.clone();
+                            chunk
+                                .slice(start, start.saturating_add(size).min(ts.len()))
+                                .unwrap();
 
-                    serialize::validate(format, &rt2)?;
-                    Ok(Closure::atomic_closure(RichTerm::new(
+                            RichTerm::new(Term::Array(chunk, attrs.clone()), pos2.into_inherited())
+                                .closurize(&mut self.cache, env2.clone())
+                        })
+                        .collect();
+
+                    Ok(Closure::atomic_closure(RichTerm::new(
+                        Term::Array(chunks, ArrayAttrs::new().closurized()),
+                        pos_op_inh,
+                    )))
+                }
+                (Term::Num(_), _) => Err(mk_type_error!("array/chunk", "Array", 2, t2, pos2)),
+                (_, _) => Err(mk_type_error!("array/chunk", "Number", 1, t1, pos1)),
+            },
+            BinaryOp::ArrayFlatten { nested } => {
+                let Term::Num(depth) = &*t1 else {
+                    return Err(mk_type_error!("array/flatten", "Number", 1, t1, pos1));
+                };
+
+                let Term::Array(ts, attrs) = &*t2 else {
+                    return Err(mk_type_error!("array/flatten", "Array", 2, t2, pos2));
+                };
+
+                if !depth.is_integer() || depth < &Number::ZERO {
+                    return Err(EvalError::Other(
+                        format!(
+                            "array/flatten expects its first argument to be a non-negative \
+                            integer, got {depth}"
+                        ),
+                        pos_op,
+                    ));
+                }
+
+                if depth == &Number::ZERO || ts.is_empty() {
+                    return Ok(Closure {
+                        body: RichTerm {
+                            term: t2,
+                            pos: pos2,
+                        },
+                        env: env2,
+                    });
+                }
+
+                let elems: Array = ts
+                    .iter()
+                    .map(|t| {
+                        RuntimeContract::apply_all(
+                            t.clone(),
+                            attrs.pending_contracts.iter().cloned(),
+                            pos2.into_inherited(),
+                        )
+                        .closurize(&mut self.cache, env2.clone())
+                    })
+                    .collect();
+
+                // The elements must be evaluated to know whether they are arrays. If some of them
+                // haven't been evaluated yet, we do it first and try again: the cache then holds
+                // their value.
+                let mut unevaluated = Vec::new();
+                let mut has_array = false;
+                let mut non_array = None;
+
+                for (index, elem) in elems.iter().enumerate() {
+                    let value = match elem.as_ref() {
+                        Term::Closure(idx) => self.cache.get(idx.clone()).body,
+                        _ => elem.clone(),
+                    };
+
+                    if !value.as_ref().is_whnf() {
+                        unevaluated.push(elem.clone());
+                    } else if matches!(value.as_ref(), Term::Array(..)) {
+                        has_array = true;
+                    } else if non_array.is_none() {
+                        non_array = Some((index, value));
+                    }
+                }
+
+                if !unevaluated.is_empty() {
+                    let array = RichTerm::new(
+                        Term::Array(elems, ArrayAttrs::new().closurized()),
+                        pos2.into_inherited(),
+                    );
+                    let retry = mk_term::op2(
+                        BinaryOp::ArrayFlatten { nested },
+                        Term::Num(depth.clone()),
+                        array,
+                    );
+
+                    return Ok(Closure {
+                        body: unevaluated
+                            .into_iter()
+                            .fold(retry, |acc, t| mk_app!(mk_term::op1(UnaryOp::Seq, t), acc))
+                            .with_pos(pos_op_inh),
+                        env: Environment::new(),
+                    });
+                }
+
+                match non_array {
+                    // This level is already flat.
+                    Some(_) if nested && !has_array => Ok(Closure::atomic_closure(RichTerm::new(
+                        Term::Array(elems, ArrayAttrs::new().closurized()),
+                        pos_op_inh,
+                    ))),
+                    Some((index, value)) => Err(EvalError::Other(
+                        format!(
+                            "array/flatten expects the elements being flattened to be arrays, \
+                            but the element at index {index} is a {}",
+                            value
+                                .as_ref()
+                                .type_of()
+                                .unwrap_or_else(|| String::from("value"))
+                        ),
+                        value.pos.into_opt().map_or(pos_op, TermPos::Original),
+                    )),
+                    None => {
+                        let concat = elems
+                            .into_iter()
+                            .reduce(|acc, t| mk_term::op2(BinaryOp::ArrayConcat, acc, t))
+                            .unwrap();
+
+                        Ok(Closure {
+                            body: mk_term::op2(
+                                BinaryOp::ArrayFlatten { nested: true },
+                                Term::Num(depth.clone() - Number::from(1)),
+                                concat,
+                            )
+                            .with_pos(pos_op_inh),
+                            env: Environment::new(),
+                        })
+                    }
+                }
+            }
+            BinaryOp::ArrayFindMap => {
+                if !matches!(&*t1, Term::Fun(..) | Term::FunPattern(..) | Term::Match(_)) {
+                    return Err(mk_type_error!("array/find_map", "Function", 1, t1, pos1));
+                }
+
+                let Term::Array(ts, attrs) = &*t2 else {
+                    return Err(mk_type_error!("array/find_map", "Array", 2, t2, pos2));
+                };
+
+                if ts.is_empty() {
+                    return Ok(Closure::atomic_closure(RichTerm::new(
+                        Term::Enum("None".into()),
+                        pos_op_inh,
+                    )));
+                }
+
+                let f = RichTerm {
+                    term: t1,
+                    pos: pos1,
+                }
+                .closurize(&mut self.cache, env1);
+                let first = RuntimeContract::apply_all(
+                    ts.get(0).unwrap().clone(),
+                    attrs.pending_contracts.iter().cloned(),
+                    pos2.into_inherited(),
+                )
+                .closurize(&mut self.cache, env2.clone());
+                let rest = RichTerm::new(
+                    Term::Array(ts.clone().advance_by(1), attrs.clone()),
+                    pos2.into_inherited(),
+                )
+                .closurize(&mut self.cache, env2);
+                let result = self.fresh_idents.fresh();
+
+                // We only apply `f` to the first element here. The search goes on with the rest
+                // of the array only if the result is `'None`, so that the elements after the
+                // first match are never evaluated.
+                Ok(Closure {
+                    body: mk_term::let_in(
+                        result,
+                        mk_app!(f.clone(), first),
+                        mk_term::if_then_else(
+                            mk_term::op2(
+                                BinaryOp::Eq,
+                                mk_term::op1(UnaryOp::EnumGetTag, mk_term::var(result)),
+                                Term::Enum("None".into()),
+                            ),
+                            mk_term::op2(BinaryOp::ArrayFindMap, f, rest),
+                            mk_term::var(result),
+                        ),
+                    )
+                    .with_pos(pos_op_inh),
+                    env: Environment::new(),
+                })
+            }
+            BinaryOp::ArrayFindIndex => {
+                if !matches!(&*t1, Term::Fun(..) | Term::FunPattern(..) | Term::Match(_)) {
+                    return Err(mk_type_error!("array/find_index", "Function", 1, t1, pos1));
+                }
+
+                if !matches!(&*t2, Term::Array(..)) {
+                    return Err(mk_type_error!("array/find_index", "Array", 2, t2, pos2));
+                }
+
+                let pred = RichTerm {
+                    term: t1,
+                    pos: pos1,
+                }
+                .closurize(&mut self.cache, env1);
+                let array = RichTerm {
+                    term: t2,
+                    pos: pos2,
+                }
+                .closurize(&mut self.cache, env2);
+
+                Ok(Closure {
+                    body: mk_opn!(NAryOp::ArrayFindIndexFrom, pred, array, mk_term::integer(0))
+                        .with_pos(pos_op_inh),
+                    env: Environment::new(),
+                })
+            }
+            BinaryOp::Merge(merge_label) => {
+                if let Some(stats) = &mut self.stats {
+                    stats.merges += 1;
+                }
+
+                merge::merge(
+                    self.merge_context(),
+                    RichTerm {
+                        term: t1,
+                        pos: pos1,
+                    },
+                    env1,
+                    RichTerm {
+                        term: t2,
+                        pos: pos2,
+                    },
+                    env2,
+                    pos_op,
+                    MergeMode::Standard(merge_label),
+                )
+            }
+            BinaryOp::Hash => {
+                let mk_err_fst = |t1| {
+                    Err(mk_type_error!(
+                        "hash",
+                        "[| 'Md5, 'Sha1, 'Sha256, 'Sha512 |]",
+                        1,
+                        t1,
+                        pos1
+                    ))
+                };
+
+                if let Term::Enum(id) = &*t1 {
+                    if let Term::Str(s) = &*t2 {
+                        let result = match id.as_ref() {
+                            "Md5" => {
+                                let mut hasher = md5::Md5::new();
+                                hasher.update(s.as_ref());
+                                format!("{:x}", hasher.finalize())
+                            }
+                            "Sha1" => {
+                                let mut hasher = sha1::Sha1::new();
+                                hasher.update(s.as_ref());
+                                format!("{:x}", hasher.finalize())
+                            }
+                            "Sha256" => {
+                                let mut hasher = sha2::Sha256::new();
+                                hasher.update(s.as_ref());
+                                format!("{:x}", hasher.finalize())
+                            }
+                            "Sha512" => {
+                                let mut hasher = sha2::Sha512::new();
+                                hasher.update(s.as_ref());
+                                format!("{:x}", hasher.finalize())
+                            }
+                            _ => return mk_err_fst(t1),
+                        };
+
+                        Ok(Closure::atomic_closure(RichTerm::new(
+                            Term::Str(result.into()),
+                            pos_op_inh,
+                        )))
+                    } else {
+                        Err(mk_type_error!("hash", "String", 2, t2, pos2))
+                    }
+                } else {
+                    mk_err_fst(t1)
+                }
+            }
+            BinaryOp::Serialize => {
+                let mk_err_fst = |t1| {
+                    Err(mk_type_error!(
+                        "serialize",
+                        "[| 'Json, 'Yaml, 'Toml |]",
+                        1,
+                        t1,
+                        pos1
+                    ))
+                };
+
+                if let Term::Enum(ref id) = t1.as_ref() {
+                    // Serialization needs all variables term to be fully substituted
+                    let initial_env = Environment::new();
+                    let rt2 = subst(
+                        &self.cache,
+                        RichTerm {
+                            term: t2,
+                            pos: pos2,
+                        },
+                        &initial_env,
+                        &env2,
+                    );
+
+                    let format = match id.to_string().as_str() {
+                        "Json" => ExportFormat::Json,
+                        "Yaml" => ExportFormat::Yaml,
+                        "Toml" => ExportFormat::Toml,
+                        _ => return mk_err_fst(t1),
+                    };
+
+                    serialize::validate(format, &rt2)?;
+                    Ok(Closure::atomic_closure(RichTerm::new(
                         Term::Str(serialize::to_string(format, &rt2)?.into()),
                         pos_op_inh,
                     )))
@@ -2271,27 +3242,39 @@ impl<R: ImportResolver, C: Cache> VirtualMachine<R, C> {
 
                 if let Term::Enum(id) = &*t1 {
                     if let Term::Str(s) = &*t2 {
+                        let mk_err =
+                            |format: &str, err: &dyn std::fmt::Display, offset: Option<usize>| {
+                                EvalError::DeserializationError {
+                                    format: String::from(format),
+                                    message: format!("{err}"),
+                                    pos: pos_op,
+                                    location: offset.map(|offset| {
+                                        DeserializationLocation::new(s.as_str(), offset, pos2)
+                                    }),
+                                }
+                            };
+
                         let rt: RichTerm = match id.as_ref() {
                             "Json" => serde_json::from_str(s).map_err(|err| {
-                                EvalError::DeserializationError(
-                                    String::from("json"),
-                                    format!("{err}"),
-                                    pos_op,
-                                )
+                                // serde_json reports a line of 0 for errors without a position.
+                                let offset = (err.line() > 0).then(|| {
+                                    let line_start: usize = s
+                                        .as_str()
+                                        .split_inclusive('\n')
+                                        .take(err.line() - 1)
+                                        .map(str::len)
+                                        .sum();
+                                    line_start + err.column().saturating_sub(1)
+                                });
+                                mk_err("json", &err, offset)
                             })?,
                             "Yaml" => serde_yaml::from_str(s).map_err(|err| {
-                                EvalError::DeserializationError(
-                                    String::from("yaml"),
-                                    format!("{err}"),
-                                    pos_op,
-                                )
+                                let offset = err.location().map(|loc| loc.index());
+                                mk_err("yaml", &err, offset)
                             })?,
                             "Toml" => toml::from_str(s).map_err(|err| {
-                                EvalError::DeserializationError(
-                                    String::from("toml"),
-                                    format!("{err}"),
-                                    pos_op,
-                                )
+                                let offset = err.span().map(|span| span.start);
+                                mk_err("toml", &err, offset)
                             })?,
                             _ => return mk_err_fst(t1),
                         };
@@ -2326,50 +3309,274 @@ impl<R: ImportResolver, C: Cache> VirtualMachine<R, C> {
                 (Term::Str(_), _) => Err(mk_type_error!("str_contains", "String", 2, t2, pos2)),
                 (_, _) => Err(mk_type_error!("str_contains", "String", 1, t1, pos1)),
             },
-            BinaryOp::ContractArrayLazyApp => {
-                let (ctr, _) = self.stack.pop_arg(&self.cache).ok_or_else(|| {
-                    EvalError::NotEnoughArgs(3, String::from("array_lazy_app_ctr"), pos_op)
-                })?;
+            BinaryOp::StringIndent => match (&*t1, &*t2) {
+                (Term::Str(s), Term::Str(prefix)) => Ok(Closure::atomic_closure(RichTerm::new(
+                    Term::Str(s.indent(prefix)),
+                    pos_op_inh,
+                ))),
+                (Term::Str(_), _) => Err(mk_type_error!("string/indent", "String", 2, t2, pos2)),
+                (_, _) => Err(mk_type_error!("string/indent", "String", 1, t1, pos1)),
+            },
+            BinaryOp::StringWrap => match (&*t1, &*t2) {
+                (Term::Str(s), Term::Num(width)) => {
+                    let width = usize::try_from(width)
+                        .ok()
+                        .filter(|width| *width > 0)
+                        .ok_or_else(|| {
+                            EvalError::Other(
+                                format!(
+                                    "string/wrap: expected the width to be a positive integer, \
+                                    got {width}"
+                                ),
+                                pos_op,
+                            )
+                        })?;
 
-                let Closure {
-                    body: rt3,
-                    env: env3,
-                } = ctr;
+                    Ok(Closure::atomic_closure(RichTerm::new(
+                        Term::Str(s.wrap(width)),
+                        pos_op_inh,
+                    )))
+                }
+                (Term::Str(_), _) => Err(mk_type_error!("string/wrap", "Number", 2, t2, pos2)),
+                (_, _) => Err(mk_type_error!("string/wrap", "String", 1, t1, pos1)),
+            },
+            BinaryOp::StringFormat => {
+                let Term::Str(template) = &*t1 else {
+                    return Err(mk_type_error!("string/format", "String", 1, t1, pos1));
+                };
+                let Term::Record(record) = &*t2 else {
+                    return Err(mk_type_error!("string/format", "Record", 2, t2, pos2));
+                };
 
-                // FIXME: use match?
-                let lbl = match_sharedterm!(match (t1) {
-                    Term::Lbl(lbl) => lbl,
-                    _ => return Err(mk_type_error!("array_lazy_app_ctr", "Label", 1, t1, pos1)),
-                });
+                let pieces = parse_format_template(template.as_str())
+                    .map_err(|msg| EvalError::Other(format!("string/format: {msg}"), pos1))?;
 
-                match_sharedterm!(match (t2) {
-                    Term::Array(ts, attrs) => {
-                        let mut attrs = attrs;
-                        let mut final_env = env2;
+                for piece in pieces.iter() {
+                    if let FormatPiece::Placeholder(name) = piece {
+                        let id = LocIdent::from(name.as_str());
 
-                        // Preserve the environment of the contract in the resulting array.
-                        let contract = rt3.closurize(&mut self.cache, env3);
-                        RuntimeContract::push_dedup(
-                            &mut attrs.pending_contracts,
-                            &final_env,
-                            RuntimeContract::new(contract, lbl),
-                            &final_env,
-                        );
+                        if !record.fields.contains_key(&id) {
+                            return Err(EvalError::FieldMissing {
+                                id,
+                                field_names: record.field_names(RecordOpKind::IgnoreEmptyOpt),
+                                operator: String::from("string/format"),
+                                pos_record: pos2,
+                                pos_op,
+                            });
+                        }
+                    }
+                }
 
-                        let array_with_ctr = Closure {
-                            body: RichTerm::new(Term::Array(ts, attrs), pos2),
-                            env: final_env,
-                        };
+                let values = RichTerm {
+                    term: t2,
+                    pos: pos2,
+                }
+                .closurize(&mut self.cache, env2);
 
-                        Ok(array_with_ctr)
-                    }
-                    _ => Err(mk_type_error!("array_lazy_app_ctr", "Array", 2, t2, pos2)),
-                })
-            }
-            BinaryOp::ContractRecordLazyApp => {
-                // The contract is expected to be of type `String -> Contract`: it takes the name
-                // of the field as a parameter, and returns a contract.
-                let (
+                // The values are converted and concatenated by evaluating a string with
+                // interpolated expressions. String chunks are stored in reverse order.
+                let chunks = pieces
+                    .into_iter()
+                    .rev()
+                    .map(|piece| match piece {
+                        FormatPiece::Literal(s) => StrChunk::Literal(s),
+                        FormatPiece::Placeholder(name) => StrChunk::Expr(
+                            mk_term::op1(
+                                UnaryOp::ToString,
+                                mk_term::op1(
+                                    UnaryOp::RecordAccess(name.as_str().into()),
+                                    values.clone(),
+                                ),
+                            ),
+                            0,
+                        ),
+                    })
+                    .collect();
+
+                Ok(Closure {
+                    body: RichTerm::new(Term::StrChunks(chunks), pos_op_inh),
+                    env: Environment::new(),
+                })
+            }
+            BinaryOp::StringToBytes => {
+                let Some(encoding) = TextEncoding::from_term(&t1) else {
+                    return Err(mk_type_error!(
+                        "string/to_bytes",
+                        "[| 'Utf8, 'Utf16, 'Latin1 |]",
+                        1,
+                        t1,
+                        pos1
+                    ));
+                };
+                let Term::Str(s) = &*t2 else {
+                    return Err(mk_type_error!("string/to_bytes", "String", 2, t2, pos2));
+                };
+
+                let bytes = encoding.encode(s.as_str()).map_err(|offset| {
+                    EvalError::Other(
+                        format!(
+                            "string/to_bytes: the character at offset {offset} can't be encoded \
+                            in {encoding}"
+                        ),
+                        pos2,
+                    )
+                })?;
+
+                let ts = bytes
+                    .into_iter()
+                    .map(|b| RichTerm::from(Term::Num(Number::from(b))))
+                    .collect();
+
+                Ok(Closure::atomic_closure(RichTerm::new(
+                    Term::Array(ts, ArrayAttrs::new().closurized()),
+                    pos_op_inh,
+                )))
+            }
+            BinaryOp::StringFromBytes => {
+                let Some(encoding) = TextEncoding::from_term(&t1) else {
+                    return Err(mk_type_error!(
+                        "string/from_bytes",
+                        "[| 'Utf8, 'Utf16, 'Latin1 |]",
+                        1,
+                        t1,
+                        pos1
+                    ));
+                };
+                let Term::Array(ts, attrs) = &*t2 else {
+                    return Err(mk_type_error!("string/from_bytes", "Array", 2, t2, pos2));
+                };
+
+                let elems: Array = ts
+                    .iter()
+                    .map(|t| {
+                        RuntimeContract::apply_all(
+                            t.clone(),
+                            attrs.pending_contracts.iter().cloned(),
+                            pos2.into_inherited(),
+                        )
+                        .closurize(&mut self.cache, env2.clone())
+                    })
+                    .collect();
+
+                let mut unevaluated = Vec::new();
+                let mut bytes = Vec::with_capacity(elems.len());
+
+                for (index, elem) in elems.iter().enumerate() {
+                    let value = match elem.as_ref() {
+                        Term::Closure(idx) => self.cache.get(idx.clone()).body,
+                        _ => elem.clone(),
+                    };
+
+                    if !value.as_ref().is_whnf() {
+                        unevaluated.push(elem.clone());
+                        continue;
+                    }
+
+                    match value.as_ref() {
+                        Term::Num(n) => match u8::try_from(n) {
+                            Ok(b) => bytes.push(b),
+                            Err(_) => {
+                                return Err(EvalError::Other(
+                                    format!(
+                                        "string/from_bytes: expected a byte, that is an integer \
+                                        between 0 and 255, at index {index}, got {n}"
+                                    ),
+                                    value.pos.into_opt().map_or(pos_op, TermPos::Original),
+                                ))
+                            }
+                        },
+                        _ => {
+                            return Err(EvalError::Other(
+                                format!(
+                                    "string/from_bytes: expected a byte at index {index}, got a {}",
+                                    value
+                                        .as_ref()
+                                        .type_of()
+                                        .unwrap_or_else(|| String::from("value"))
+                                ),
+                                value.pos.into_opt().map_or(pos_op, TermPos::Original),
+                            ))
+                        }
+                    }
+                }
+
+                // As for `array/flatten`, unevaluated elements are evaluated first, and we try
+                // again.
+                if !unevaluated.is_empty() {
+                    let array = RichTerm::new(
+                        Term::Array(elems, ArrayAttrs::new().closurized()),
+                        pos2.into_inherited(),
+                    );
+                    let retry = mk_term::op2(BinaryOp::StringFromBytes, (*t1).clone(), array);
+
+                    return Ok(Closure {
+                        body: unevaluated
+                            .into_iter()
+                            .fold(retry, |acc, t| mk_app!(mk_term::op1(UnaryOp::Seq, t), acc))
+                            .with_pos(pos_op_inh),
+                        env: Environment::new(),
+                    });
+                }
+
+                let s = encoding.decode(&bytes).map_err(|offset| {
+                    EvalError::Other(
+                        format!(
+                            "string/from_bytes: invalid {encoding} byte sequence at offset \
+                            {offset}"
+                        ),
+                        pos2,
+                    )
+                })?;
+
+                Ok(Closure::atomic_closure(RichTerm::new(
+                    Term::Str(s.into()),
+                    pos_op_inh,
+                )))
+            }
+            BinaryOp::ContractArrayLazyApp => {
+                let (ctr, _) = self.stack.pop_arg(&self.cache).ok_or_else(|| {
+                    EvalError::NotEnoughArgs(3, String::from("array_lazy_app_ctr"), pos_op)
+                })?;
+
+                let Closure {
+                    body: rt3,
+                    env: env3,
+                } = ctr;
+
+                // FIXME: use match?
+                let lbl = match_sharedterm!(match (t1) {
+                    Term::Lbl(lbl) => lbl,
+                    _ => return Err(mk_type_error!("array_lazy_app_ctr", "Label", 1, t1, pos1)),
+                });
+
+                match_sharedterm!(match (t2) {
+                    Term::Array(ts, attrs) => {
+                        let mut attrs = attrs;
+                        let mut final_env = env2;
+
+                        // Preserve the environment of the contract in the resulting array.
+                        let contract = rt3.closurize(&mut self.cache, env3);
+                        RuntimeContract::push_dedup(
+                            &mut attrs.pending_contracts,
+                            &final_env,
+                            RuntimeContract::new(contract, lbl),
+                            &final_env,
+                        );
+
+                        let array_with_ctr = Closure {
+                            body: RichTerm::new(Term::Array(ts, attrs), pos2),
+                            env: final_env,
+                        };
+
+                        Ok(array_with_ctr)
+                    }
+                    _ => Err(mk_type_error!("array_lazy_app_ctr", "Array", 2, t2, pos2)),
+                })
+            }
+            BinaryOp::ContractRecordLazyApp => {
+                // The contract is expected to be of type `String -> Contract`: it takes the name
+                // of the field as a parameter, and returns a contract.
+                let (
                     Closure {
                         body: contract_term,
                         env: contract_env,
@@ -2522,6 +3729,41 @@ impl<R: ImportResolver, C: Cache> VirtualMachine<R, C> {
                     pos_op_inh,
                 )))
             }
+            BinaryOp::LabelWithErrorData => {
+                let t2 = t2.into_owned();
+
+                // As for `LabelWithNotes`, the data most likely contains generated variables, which
+                // we substitute before serializing it.
+                let data = subst(
+                    &self.cache,
+                    RichTerm {
+                        term: t1,
+                        pos: pos1,
+                    },
+                    &Environment::new(),
+                    &env1,
+                );
+
+                serialize::validate(ExportFormat::Json, &data)?;
+                let data = serde_json::to_value(&data).map_err(|err| {
+                    EvalError::SerializationError(ExportErrorData::Other(err.to_string()).into())
+                })?;
+
+                let Term::Lbl(label) = t2 else {
+                    return Err(mk_type_error!(
+                        "label_with_error_data",
+                        "Label",
+                        2,
+                        t2.into(),
+                        pos2
+                    ));
+                };
+
+                Ok(Closure::atomic_closure(RichTerm::new(
+                    Term::Lbl(label.with_diagnostic_data(data)),
+                    pos_op_inh,
+                )))
+            }
             BinaryOp::LabelAppendNote => {
                 let t1 = t1.into_owned();
                 let t2 = t2.into_owned();
@@ -2703,6 +3945,84 @@ impl<R: ImportResolver, C: Cache> VirtualMachine<R, C> {
                     }),
                 }
             }
+            NAryOp::StringPadStart | NAryOp::StringPadEnd => {
+                let mut args_wo_env = args
+                    .into_iter()
+                    .map(|(clos, pos)| (clos.body.term, clos.body.pos, pos));
+                let (fst, pos1, fst_pos) = args_wo_env.next().unwrap();
+                let (snd, pos2, snd_pos) = args_wo_env.next().unwrap();
+                let (thd, pos3, thd_pos) = args_wo_env.next().unwrap();
+                debug_assert!(args_wo_env.next().is_none());
+
+                match (&*fst, &*snd, &*thd) {
+                    (Term::Str(s), Term::Num(width), Term::Str(pad)) => {
+                        let width = usize::try_from(width).map_err(|_| {
+                            EvalError::Other(
+                                format!(
+                                    "{n_op}: expected the width to be a natural number, \
+                                    got {width}"
+                                ),
+                                pos_op,
+                            )
+                        })?;
+
+                        let mut pad_chars = pad.chars();
+                        let pad_char = match (pad_chars.next(), pad_chars.next()) {
+                            (Some(c), None) => c,
+                            _ => {
+                                return Err(EvalError::Other(
+                                    format!(
+                                        "{n_op}: expected the padding to be a single \
+                                        character, got \"{pad}\""
+                                    ),
+                                    pos_op,
+                                ))
+                            }
+                        };
+
+                        let result = if let NAryOp::StringPadStart = n_op {
+                            s.pad_start(width, pad_char)
+                        } else {
+                            s.pad_end(width, pad_char)
+                        };
+
+                        Ok(Closure::atomic_closure(RichTerm::new(
+                            Term::Str(result),
+                            pos_op_inh,
+                        )))
+                    }
+                    (Term::Str(_), Term::Num(_), _) => Err(EvalError::NAryPrimopTypeError {
+                        primop: format!("{n_op}"),
+                        expected: String::from("String"),
+                        arg_number: 3,
+                        arg_pos: thd_pos,
+                        arg_evaluated: RichTerm {
+                            term: thd,
+                            pos: pos3,
+                        },
+                    }),
+                    (Term::Str(_), _, _) => Err(EvalError::NAryPrimopTypeError {
+                        primop: format!("{n_op}"),
+                        expected: String::from("Number"),
+                        arg_number: 2,
+                        arg_pos: snd_pos,
+                        arg_evaluated: RichTerm {
+                            term: snd,
+                            pos: pos2,
+                        },
+                    }),
+                    (_, _, _) => Err(EvalError::NAryPrimopTypeError {
+                        primop: format!("{n_op}"),
+                        expected: String::from("String"),
+                        arg_number: 1,
+                        arg_pos: fst_pos,
+                        arg_evaluated: RichTerm {
+                            term: fst,
+                            pos: pos1,
+                        },
+                    }),
+                }
+            }
             NAryOp::MergeContract => {
                 let mut args_iter = args.into_iter();
                 let (
@@ -2739,7 +4059,7 @@ impl<R: ImportResolver, C: Cache> VirtualMachine<R, C> {
                 match_sharedterm!(match (t1) {
                     Term::Lbl(lbl) => {
                         merge::merge(
-                            &mut self.cache,
+                            self.merge_context(),
                             RichTerm {
                                 term: t2,
                                 pos: pos2,
@@ -2752,7 +4072,6 @@ impl<R: ImportResolver, C: Cache> VirtualMachine<R, C> {
                             env3,
                             pos_op,
                             MergeMode::Contract(lbl),
-                            &mut self.call_stack,
                         )
                     }
                     _ => Err(EvalError::InternalError(
@@ -2993,65 +4312,586 @@ impl<R: ImportResolver, C: Cache> VirtualMachine<R, C> {
                     Closure {
                         body:
                             RichTerm {
-                                term: label,
-                                pos: label_pos,
+                                term: label,
+                                pos: label_pos,
+                            },
+                        ..
+                    },
+                    pos3,
+                ) = args.next().unwrap();
+                debug_assert!(args.next().is_none());
+
+                let Term::SealingKey(key) = *key else {
+                    return Err(EvalError::NAryPrimopTypeError {
+                        primop: String::from("insert_type_variable"),
+                        expected: String::from("SealingKey"),
+                        arg_number: 1,
+                        arg_pos: key_pos,
+                        arg_evaluated: RichTerm {
+                            term: key,
+                            pos: pos1,
+                        },
+                    });
+                };
+
+                let Ok(polarity) = Polarity::try_from(polarity.as_ref()) else {
+                    return Err(EvalError::NAryPrimopTypeError {
+                        primop: String::from("insert_type_variable"),
+                        expected: String::from("Polarity"),
+                        arg_number: 2,
+                        arg_pos: polarity_pos,
+                        arg_evaluated: RichTerm {
+                            term: polarity,
+                            pos: pos2,
+                        },
+                    });
+                };
+
+                let Term::Lbl(label) = &*label else {
+                    return Err(EvalError::NAryPrimopTypeError {
+                        primop: String::from("insert_type_variable"),
+                        expected: String::from("Label"),
+                        arg_number: 3,
+                        arg_pos: label_pos,
+                        arg_evaluated: RichTerm {
+                            term: label,
+                            pos: pos3,
+                        },
+                    });
+                };
+
+                let mut new_label = label.clone();
+                new_label
+                    .type_environment
+                    .insert(key, TypeVarData { polarity });
+
+                Ok(Closure::atomic_closure(RichTerm::new(
+                    Term::Lbl(new_label),
+                    pos2.into_inherited(),
+                )))
+            }
+            NAryOp::ArraySlice => {
+                let mut args = args.into_iter();
+
+                let (
+                    Closure {
+                        body:
+                            RichTerm {
+                                term: t1,
+                                pos: pos1,
+                            },
+                        ..
+                    },
+                    fst_pos,
+                ) = args.next().unwrap();
+
+                let (
+                    Closure {
+                        body:
+                            RichTerm {
+                                term: t2,
+                                pos: pos2,
+                            },
+                        ..
+                    },
+                    snd_pos,
+                ) = args.next().unwrap();
+
+                let (
+                    Closure {
+                        body:
+                            RichTerm {
+                                term: t3,
+                                pos: pos3,
+                            },
+                        env: env3,
+                    },
+                    third_pos,
+                ) = args.next().unwrap();
+                debug_assert!(args.next().is_none());
+
+                let Term::Num(ref start) = &*t1 else {
+                    return Err(EvalError::NAryPrimopTypeError {
+                        primop: String::from("array_slice"),
+                        expected: String::from("Number"),
+                        arg_number: 1,
+                        arg_pos: fst_pos,
+                        arg_evaluated: RichTerm {
+                            term: t1,
+                            pos: pos1,
+                        },
+                    });
+                };
+
+                let Term::Num(ref end) = &*t2 else {
+                    return Err(EvalError::NAryPrimopTypeError {
+                        primop: String::from("array_slice"),
+                        expected: String::from("Number"),
+                        arg_number: 2,
+                        arg_pos: snd_pos,
+                        arg_evaluated: RichTerm {
+                            term: t2,
+                            pos: pos2,
+                        },
+                    });
+                };
+
+                let t3_owned = t3.into_owned();
+
+                let Term::Array(mut array, attrs) = t3_owned else {
+                    return Err(EvalError::NAryPrimopTypeError {
+                        primop: String::from("array_slice"),
+                        expected: String::from("Array"),
+                        arg_number: 3,
+                        arg_pos: third_pos,
+                        arg_evaluated: RichTerm::new(t3_owned, pos3),
+                    });
+                };
+
+                let Ok(start_as_usize) = usize::try_from(start) else {
+                    return Err(EvalError::Other(
+                        format!(
+                            "array_slice expects its first argument (start) to be a \
+                            positive integer smaller than {}, got {start}",
+                            usize::MAX
+                        ),
+                        pos_op,
+                    ));
+                };
+
+                let Ok(end_as_usize) = usize::try_from(end) else {
+                    return Err(EvalError::Other(
+                        format!(
+                            "array_slice expects its second argument (end) to be a \
+                            positive integer smaller than {}, got {end}",
+                            usize::MAX
+                        ),
+                        pos_op,
+                    ));
+                };
+
+                let result = array.slice(start_as_usize, end_as_usize);
+
+                if let Err(OutOfBoundError) = result {
+                    return Err(EvalError::Other(
+                        format!(
+                            "array_slice: index out of bounds. Expected `start <= end <= {}`, but \
+                            got `start={start}` and `end={end}`.",
+                            array.len()
+                        ),
+                        pos_op,
+                    ));
+                };
+
+                Ok(Closure {
+                    body: RichTerm::new(Term::Array(array, attrs), pos_op_inh),
+                    env: env3,
+                })
+            }
+            NAryOp::ArrayZipWith => {
+                let mut args = args.into_iter();
+
+                let (Closure { body: f, env: env1 }, _) = args.next().unwrap();
+
+                let (
+                    Closure {
+                        body:
+                            RichTerm {
+                                term: t2,
+                                pos: pos2,
+                            },
+                        env: env2,
+                    },
+                    snd_pos,
+                ) = args.next().unwrap();
+
+                let (
+                    Closure {
+                        body:
+                            RichTerm {
+                                term: t3,
+                                pos: pos3,
+                            },
+                        env: env3,
+                    },
+                    third_pos,
+                ) = args.next().unwrap();
+                debug_assert!(args.next().is_none());
+
+                let Term::Array(ts2, attrs2) = &*t2 else {
+                    return Err(EvalError::NAryPrimopTypeError {
+                        primop: String::from("array/zip_with"),
+                        expected: String::from("Array"),
+                        arg_number: 2,
+                        arg_pos: snd_pos,
+                        arg_evaluated: RichTerm {
+                            term: t2,
+                            pos: pos2,
+                        },
+                    });
+                };
+
+                let Term::Array(ts3, attrs3) = &*t3 else {
+                    return Err(EvalError::NAryPrimopTypeError {
+                        primop: String::from("array/zip_with"),
+                        expected: String::from("Array"),
+                        arg_number: 3,
+                        arg_pos: third_pos,
+                        arg_evaluated: RichTerm {
+                            term: t3,
+                            pos: pos3,
+                        },
+                    });
+                };
+
+                let f_as_var = f.closurize(&mut self.cache, env1);
+
+                // As for `array/zip`, the result is truncated to the shortest array.
+                let ts = ts2
+                    .iter()
+                    .zip(ts3.iter())
+                    .map(|(fst, snd)| {
+                        let fst = RuntimeContract::apply_all(
+                            fst.clone(),
+                            attrs2.pending_contracts.iter().cloned(),
+                            pos2.into_inherited(),
+                        )
+                        .closurize(&mut self.cache, env2.clone());
+                        let snd = RuntimeContract::apply_all(
+                            snd.clone(),
+                            attrs3.pending_contracts.iter().cloned(),
+                            pos3.into_inherited(),
+                        )
+                        .closurize(&mut self.cache, env3.clone());
+
+                        mk_app!(f_as_var.clone(), fst, snd)
+                            .with_pos(pos_op_inh)
+                            .closurize(&mut self.cache, Environment::new())
+                    })
+                    .collect();
+
+                Ok(Closure {
+                    body: RichTerm::new(
+                        Term::Array(ts, ArrayAttrs::new().closurized()),
+                        pos_op_inh,
+                    ),
+                    env: Environment::new(),
+                })
+            }
+            NAryOp::ArrayScan => {
+                let mut args = args.into_iter();
+
+                let (Closure { body: f, env: env1 }, _) = args.next().unwrap();
+                let (
+                    Closure {
+                        body: init,
+                        env: env2,
+                    },
+                    _,
+                ) = args.next().unwrap();
+
+                let (
+                    Closure {
+                        body:
+                            RichTerm {
+                                term: t3,
+                                pos: pos3,
+                            },
+                        env: env3,
+                    },
+                    third_pos,
+                ) = args.next().unwrap();
+                debug_assert!(args.next().is_none());
+
+                let Term::Array(ts, attrs) = &*t3 else {
+                    return Err(EvalError::NAryPrimopTypeError {
+                        primop: String::from("array/scan"),
+                        expected: String::from("Array"),
+                        arg_number: 3,
+                        arg_pos: third_pos,
+                        arg_evaluated: RichTerm {
+                            term: t3,
+                            pos: pos3,
+                        },
+                    });
+                };
+
+                let f_as_var = f.closurize(&mut self.cache, env1);
+                let mut acc = init.closurize(&mut self.cache, env2);
+
+                // Each accumulator is a thunk depending on the previous one, so that the result
+                // is built in linear time without forcing any application of `f`.
+                let ts = ts
+                    .iter()
+                    .map(|elt| {
+                        let elt = RuntimeContract::apply_all(
+                            elt.clone(),
+                            attrs.pending_contracts.iter().cloned(),
+                            pos3.into_inherited(),
+                        )
+                        .closurize(&mut self.cache, env3.clone());
+
+                        acc = mk_app!(f_as_var.clone(), acc.clone(), elt)
+                            .with_pos(pos_op_inh)
+                            .closurize(&mut self.cache, Environment::new());
+
+                        acc.clone()
+                    })
+                    .collect();
+
+                Ok(Closure {
+                    body: RichTerm::new(
+                        Term::Array(ts, ArrayAttrs::new().closurized()),
+                        pos_op_inh,
+                    ),
+                    env: Environment::new(),
+                })
+            }
+            NAryOp::NumberClamp => {
+                let mut nums = Vec::with_capacity(3);
+
+                for (arg_number, (clos, arg_pos)) in args.into_iter().enumerate() {
+                    let Term::Num(n) = clos.body.as_ref() else {
+                        return Err(EvalError::NAryPrimopTypeError {
+                            primop: String::from("number/clamp"),
+                            expected: String::from("Number"),
+                            arg_number: arg_number + 1,
+                            arg_pos,
+                            arg_evaluated: clos.body,
+                        });
+                    };
+
+                    nums.push(n.clone());
+                }
+
+                let [value, lo, hi]: [Number; 3] = nums
+                    .try_into()
+                    .expect("the parser ensures that number/clamp has three arguments");
+
+                if lo > hi {
+                    return Err(EvalError::Other(
+                        format!(
+                            "number/clamp: the lower bound {lo} is greater than the upper bound \
+                            {hi}"
+                        ),
+                        pos_op,
+                    ));
+                }
+
+                Ok(Closure::atomic_closure(RichTerm::new(
+                    Term::Num(value.clamp(lo, hi)),
+                    pos_op_inh,
+                )))
+            }
+            NAryOp::RecordInsertWithMetadata => {
+                let mut args = args.into_iter();
+                let mut next_arg = || args.next().unwrap();
+
+                let (field, field_pos) = next_arg();
+                let (doc, doc_pos) = next_arg();
+                let (priority, priority_pos) = next_arg();
+                let (contracts, contracts_pos) = next_arg();
+                let (record, record_pos) = next_arg();
+
+                let mk_err = |expected: &str, arg_number, arg_pos, arg: &Closure| {
+                    EvalError::NAryPrimopTypeError {
+                        primop: String::from("record/insert_with_metadata"),
+                        expected: String::from(expected),
+                        arg_number,
+                        arg_pos,
+                        arg_evaluated: arg.body.clone(),
+                    }
+                };
+
+                if !matches!(field.body.as_ref(), Term::Str(_)) {
+                    return Err(mk_err("String", 1, field_pos, &field));
+                }
+
+                let doc = match doc.body.as_ref() {
+                    Term::Str(doc) => Some(doc.to_string()),
+                    Term::Null => None,
+                    _ => return Err(mk_err("String", 2, doc_pos, &doc)),
+                };
+
+                let priority = match priority.body.as_ref() {
+                    Term::Num(n) => MergePriority::Numeral(n.clone()),
+                    Term::Enum(tag) if tag.label() == "Default" => MergePriority::Bottom,
+                    Term::Enum(tag) if tag.label() == "Force" => MergePriority::Top,
+                    Term::Null => MergePriority::Neutral,
+                    _ => {
+                        return Err(mk_err(
+                            "Number or [| 'Default, 'Force |]",
+                            3,
+                            priority_pos,
+                            &priority,
+                        ))
+                    }
+                };
+
+                let contracts_array = match contracts.body.as_ref() {
+                    Term::Array(contracts_array, _) => contracts_array.clone(),
+                    Term::Null => Array::default(),
+                    _ => return Err(mk_err("Array", 4, contracts_pos, &contracts)),
+                };
+
+                // The label of the contracts points to the insertion, which is the closest thing
+                // to a definition site that we have.
+                let span = pos_op.into_opt().unwrap_or_else(|| Label::default().span);
+
+                let contracts: Vec<LabeledType> = contracts_array
+                    .iter()
+                    .map(|ctr| {
+                        let ctr = ctr
+                            .clone()
+                            .closurize(&mut self.cache, contracts.env.clone());
+                        LabeledType::new(Type::from(TypeF::Flat(ctr)), span)
+                    })
+                    .collect();
+
+                let pending_contracts = contracts
+                    .iter()
+                    .cloned()
+                    .map(RuntimeContract::try_from)
+                    .collect::<Result<Vec<_>, _>>()?;
+
+                let metadata = FieldMetadata {
+                    doc,
+                    annotation: TypeAnnotation {
+                        typ: None,
+                        contracts,
+                    },
+                    priority,
+                    ..Default::default()
+                };
+
+                // The actual insertion, including the handling of the value given as an
+                // additional argument, is delegated to `record/insert`.
+                let insert = BinaryOp::RecordInsert {
+                    metadata,
+                    pending_contracts,
+                    ext_kind: RecordExtKind::WithValue,
+                    op_kind: RecordOpKind::IgnoreEmptyOpt,
+                };
+
+                if !matches!(record.body.as_ref(), Term::Record(_)) {
+                    return Err(mk_err("Record", 5, record_pos, &record));
+                }
+
+                Ok(Closure {
+                    body: mk_term::op2(
+                        insert,
+                        field.body,
+                        record.body.closurize(&mut self.cache, record.env),
+                    )
+                    .with_pos(pos_op_inh),
+                    env: Environment::new(),
+                })
+            }
+            NAryOp::ArrayFindIndexFrom => {
+                let mut args = args.into_iter();
+
+                let (
+                    Closure {
+                        body: pred,
+                        env: env1,
+                    },
+                    _,
+                ) = args.next().unwrap();
+
+                let (
+                    Closure {
+                        body:
+                            RichTerm {
+                                term: t2,
+                                pos: pos2,
+                            },
+                        env: env2,
+                    },
+                    snd_pos,
+                ) = args.next().unwrap();
+
+                let (
+                    Closure {
+                        body:
+                            RichTerm {
+                                term: t3,
+                                pos: pos3,
                             },
                         ..
                     },
-                    pos3,
+                    third_pos,
                 ) = args.next().unwrap();
                 debug_assert!(args.next().is_none());
 
-                let Term::SealingKey(key) = *key else {
-                    return Err(EvalError::NAryPrimopTypeError {
-                        primop: String::from("insert_type_variable"),
-                        expected: String::from("SealingKey"),
-                        arg_number: 1,
-                        arg_pos: key_pos,
-                        arg_evaluated: RichTerm {
-                            term: key,
-                            pos: pos1,
-                        },
-                    });
-                };
-
-                let Ok(polarity) = Polarity::try_from(polarity.as_ref()) else {
+                let Term::Array(ts, attrs) = &*t2 else {
                     return Err(EvalError::NAryPrimopTypeError {
-                        primop: String::from("insert_type_variable"),
-                        expected: String::from("Polarity"),
+                        primop: String::from("array/find_index_from"),
+                        expected: String::from("Array"),
                         arg_number: 2,
-                        arg_pos: polarity_pos,
+                        arg_pos: snd_pos,
                         arg_evaluated: RichTerm {
-                            term: polarity,
+                            term: t2,
                             pos: pos2,
                         },
                     });
                 };
 
-                let Term::Lbl(label) = &*label else {
+                let Term::Num(start) = &*t3 else {
                     return Err(EvalError::NAryPrimopTypeError {
-                        primop: String::from("insert_type_variable"),
-                        expected: String::from("Label"),
+                        primop: String::from("array/find_index_from"),
+                        expected: String::from("Number"),
                         arg_number: 3,
-                        arg_pos: label_pos,
+                        arg_pos: third_pos,
                         arg_evaluated: RichTerm {
-                            term: label,
+                            term: t3,
                             pos: pos3,
                         },
                     });
                 };
 
-                let mut new_label = label.clone();
-                new_label
-                    .type_environment
-                    .insert(key, TypeVarData { polarity });
+                if ts.is_empty() {
+                    return Ok(Closure::atomic_closure(RichTerm::new(
+                        Term::Enum("None".into()),
+                        pos_op_inh,
+                    )));
+                }
 
-                Ok(Closure::atomic_closure(RichTerm::new(
-                    Term::Lbl(new_label),
+                let pred = pred.closurize(&mut self.cache, env1);
+                let first = RuntimeContract::apply_all(
+                    ts.get(0).unwrap().clone(),
+                    attrs.pending_contracts.iter().cloned(),
                     pos2.into_inherited(),
-                )))
+                )
+                .closurize(&mut self.cache, env2.clone());
+                let rest = RichTerm::new(
+                    Term::Array(ts.clone().advance_by(1), attrs.clone()),
+                    pos2.into_inherited(),
+                )
+                .closurize(&mut self.cache, env2);
+                let found = Term::EnumVariant {
+                    tag: "Some".into(),
+                    arg: RichTerm::new(Term::Num(start.clone()), pos_op_inh),
+                    attrs: EnumVariantAttrs::default(),
+                };
+
+                // As for `array/find_map`, the rest of the array is only searched if the first
+                // element doesn't satisfy the predicate.
+                Ok(Closure {
+                    body: mk_term::if_then_else(
+                        mk_app!(pred.clone(), first),
+                        found,
+                        mk_opn!(
+                            NAryOp::ArrayFindIndexFrom,
+                            pred,
+                            rest,
+                            Term::Num(start.clone() + Number::from(1))
+                        ),
+                    )
+                    .with_pos(pos_op_inh),
+                    env: Environment::new(),
+                })
             }
-            NAryOp::ArraySlice => {
+            NAryOp::RecordRename => {
                 let mut args = args.into_iter();
 
                 let (
@@ -3091,10 +4931,10 @@ impl<R: ImportResolver, C: Cache> VirtualMachine<R, C> {
                 ) = args.next().unwrap();
                 debug_assert!(args.next().is_none());
 
-                let Term::Num(ref start) = &*t1 else {
+                let Term::Str(old) = &*t1 else {
                     return Err(EvalError::NAryPrimopTypeError {
-                        primop: String::from("array_slice"),
-                        expected: String::from("Number"),
+                        primop: String::from("record/rename"),
+                        expected: String::from("String"),
                         arg_number: 1,
                         arg_pos: fst_pos,
                         arg_evaluated: RichTerm {
@@ -3104,10 +4944,10 @@ impl<R: ImportResolver, C: Cache> VirtualMachine<R, C> {
                     });
                 };
 
-                let Term::Num(ref end) = &*t2 else {
+                let Term::Str(new) = &*t2 else {
                     return Err(EvalError::NAryPrimopTypeError {
-                        primop: String::from("array_slice"),
-                        expected: String::from("Number"),
+                        primop: String::from("record/rename"),
+                        expected: String::from("String"),
                         arg_number: 2,
                         arg_pos: snd_pos,
                         arg_evaluated: RichTerm {
@@ -3117,56 +4957,116 @@ impl<R: ImportResolver, C: Cache> VirtualMachine<R, C> {
                     });
                 };
 
-                let t3_owned = t3.into_owned();
+                let renames = vec![(LocIdent::from(old), LocIdent::from(new))];
 
-                let Term::Array(mut array, attrs) = t3_owned else {
-                    return Err(EvalError::NAryPrimopTypeError {
-                        primop: String::from("array_slice"),
-                        expected: String::from("Array"),
+                match_sharedterm!(match (t3) {
+                    Term::Record(record) => Ok(Closure {
+                        body: RichTerm::new(
+                            Term::Record(rename_fields(
+                                record,
+                                renames,
+                                "record/rename",
+                                pos3,
+                                pos_op,
+                            )?),
+                            pos_op_inh,
+                        ),
+                        env: env3,
+                    }),
+                    _ => Err(EvalError::NAryPrimopTypeError {
+                        primop: String::from("record/rename"),
+                        expected: String::from("Record"),
                         arg_number: 3,
                         arg_pos: third_pos,
-                        arg_evaluated: RichTerm::new(t3_owned, pos3),
-                    });
-                };
+                        arg_evaluated: RichTerm {
+                            term: t3,
+                            pos: pos3,
+                        },
+                    }),
+                })
+            }
+            NAryOp::RecordRenameAll => {
+                let mut args = args.into_iter();
 
-                let Ok(start_as_usize) = usize::try_from(start) else {
-                    return Err(EvalError::Other(
-                        format!(
-                            "array_slice expects its first argument (start) to be a \
-                            positive integer smaller than {}, got {start}",
-                            usize::MAX
-                        ),
-                        pos_op,
-                    ));
-                };
+                let mut names = args
+                    .by_ref()
+                    .take(2)
+                    .enumerate()
+                    .map(|(idx, (Closure { body, .. }, arg_pos))| {
+                        let Term::Array(ts, _) = body.as_ref() else {
+                            return Err(EvalError::NAryPrimopTypeError {
+                                primop: String::from("record/rename_all"),
+                                expected: String::from("Array"),
+                                arg_number: idx + 1,
+                                arg_pos,
+                                arg_evaluated: body,
+                            });
+                        };
 
-                let Ok(end_as_usize) = usize::try_from(end) else {
-                    return Err(EvalError::Other(
-                        format!(
-                            "array_slice expects its second argument (end) to be a \
-                            positive integer smaller than {}, got {end}",
-                            usize::MAX
-                        ),
-                        pos_op,
-                    ));
-                };
+                        ts.iter()
+                            .map(|elt| {
+                                let value = match elt.as_ref() {
+                                    Term::Closure(idx) => self.cache.get(idx.clone()).body,
+                                    _ => elt.clone(),
+                                };
+
+                                match value.as_ref() {
+                                    Term::Str(name) => Ok(LocIdent::from(name)),
+                                    _ => Err(EvalError::TypeError(
+                                        String::from("String"),
+                                        String::from("the new field names must be strings"),
+                                        pos_op,
+                                        value,
+                                    )),
+                                }
+                            })
+                            .collect::<Result<Vec<_>, _>>()
+                    })
+                    .collect::<Result<Vec<_>, _>>()?
+                    .into_iter();
 
-                let result = array.slice(start_as_usize, end_as_usize);
+                let olds = names.next().unwrap();
+                let news = names.next().unwrap();
 
-                if let Err(OutOfBoundError) = result {
-                    return Err(EvalError::Other(
-                        format!(
-                            "array_slice: index out of bounds. Expected `start <= end <= {}`, but \
-                            got `start={start}` and `end={end}`.",
-                            array.len()
-                        ),
-                        pos_op,
-                    ));
-                };
+                let (
+                    Closure {
+                        body:
+                            RichTerm {
+                                term: t3,
+                                pos: pos3,
+                            },
+                        env: env3,
+                    },
+                    third_pos,
+                ) = args.next().unwrap();
+                debug_assert!(args.next().is_none());
 
-                Ok(Closure {
-                    body: RichTerm::new(Term::Array(array, attrs), pos_op_inh),
-                    env: env3,
+                let renames = olds.into_iter().zip(news).collect();
+
+                match_sharedterm!(match (t3) {
+                    Term::Record(record) => Ok(Closure {
+                        body: RichTerm::new(
+                            Term::Record(rename_fields(
+                                record,
+                                renames,
+                                "record/rename_keys",
+                                pos3,
+                                pos_op,
+                            )?),
+                            pos_op_inh,
+                        ),
+                        env: env3,
+                    }),
+                    _ => Err(EvalError::NAryPrimopTypeError {
+                        primop: String::from("record/rename_all"),
+                        expected: String::from("Record"),
+                        arg_number: 3,
+                        arg_pos: third_pos,
+                        arg_evaluated: RichTerm {
+                            term: t3,
+                            pos: pos3,
+                        },
+                    }),
                 })
             }
         }
@@ -3208,6 +5108,7 @@ impl RecPriority {
     fn propagate_in_record<C: Cache>(
         &self,
         cache: &mut C,
+        fresh_idents: &mut FreshIdentGen,
         mut record: RecordData,
         env: &Environment,
         pos: TermPos,
@@ -3263,6 +5164,7 @@ impl RecPriority {
                             cache.map_at_index(idx, |cache, inner| match inner.body.as_ref() {
                                 Term::Record(record_data) => self.propagate_in_record(
                                     cache,
+                                    fresh_idents,
                                     record_data.clone(),
                                     &inner.env,
                                     pos,
@@ -3274,7 +5176,7 @@ impl RecPriority {
                                 _ => panic!("rec_priority: expected an evaluated form"),
                             });
 
-                        let fresh_id = LocIdent::fresh();
+                        let fresh_id = fresh_idents.fresh();
                         new_env.insert(fresh_id.ident(), new_idx);
                         RichTerm::new(Term::Var(fresh_id), pos)
                     } else {
@@ -3300,12 +5202,15 @@ impl RecPriority {
     fn propagate_in_term<C: Cache>(
         &self,
         cache: &mut C,
+        fresh_idents: &mut FreshIdentGen,
         st: SharedTerm,
         env: Environment,
         pos: TermPos,
     ) -> Closure {
         match st.into_owned() {
-            Term::Record(record_data) => self.propagate_in_record(cache, record_data, &env, pos),
+            Term::Record(record_data) => {
+                self.propagate_in_record(cache, fresh_idents, record_data, &env, pos)
+            }
             t => Closure {
                 body: RichTerm::new(t, pos),
                 env,
@@ -3314,6 +5219,363 @@ impl RecPriority {
     }
 }
 
+/// The units of durations, from the largest to the smallest, together with their length in
+/// seconds.
+const DURATION_UNITS: [(&str, u32); 4] = [("d", 86400), ("h", 3600), ("m", 60), ("s", 1)];
+
+/// Parse a duration such as `"1h30m"` into a number of seconds. A duration is a non-empty sequence
+/// of components, each made of a non-negative decimal amount immediately followed by a unit. Each
+/// unit can appear at most once, and units must go from the largest to the smallest.
+///
+/// On error, return a description of the problem.
+fn parse_duration(s: &str) -> Result<Number, String> {
+    if s.is_empty() {
+        return Err(String::from("a duration can't be empty"));
+    }
+
+    let mut rest = s;
+    let mut total = Number::ZERO;
+    // The index in `DURATION_UNITS` of the largest unit allowed for the next component.
+    let mut next_unit = 0;
+
+    while !rest.is_empty() {
+        let amount_len = rest
+            .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+            .unwrap_or(rest.len());
+        let (amount, tail) = rest.split_at(amount_len);
+        let unit_len = tail.find(|c: char| !c.is_alphabetic()).unwrap_or(tail.len());
+        let (unit, tail) = tail.split_at(unit_len);
+
+        if amount.is_empty() {
+            return Err(format!("expected an amount at `{rest}`"));
+        }
+
+        if unit.is_empty() {
+            return Err(format!("missing unit after `{amount}` (expected one of d, h, m or s)"));
+        }
+
+        let Some(idx) = DURATION_UNITS.iter().position(|(name, _)| *name == unit) else {
+            return Err(format!("invalid unit `{unit}` (expected one of d, h, m or s)"));
+        };
+
+        let amount =
+            parse_number_sci(amount).map_err(|_| format!("invalid amount `{amount}`"))?;
+
+        if idx < next_unit {
+            return Err(format!(
+                "unexpected unit `{unit}` (each unit can appear only once, from the largest to \
+                the smallest)"
+            ));
+        }
+
+        total += amount * Number::from(DURATION_UNITS[idx].1);
+        next_unit = idx + 1;
+        rest = tail;
+    }
+
+    Ok(total)
+}
+
+/// Format a non-negative number of seconds as a duration, using the largest units first and
+/// omitting the units with a zero amount. Any fractional part goes into the seconds. This is the
+/// inverse of [parse_duration] on durations in this canonical form.
+fn format_duration(secs: &Number) -> String {
+    if secs == &Number::ZERO {
+        return String::from("0s");
+    }
+
+    let mut rest = secs.clone();
+    let mut result = String::new();
+
+    for (unit, len) in DURATION_UNITS {
+        let len = Number::from(len);
+        let amount = if unit == "s" {
+            rest.clone()
+        } else {
+            Number::from(Integer::rounding_from(&rest / &len, RoundingMode::Down).0)
+        };
+
+        if amount != Number::ZERO {
+            result.push_str(&format!("{}{unit}", amount.to_sci()));
+            rest -= amount * len;
+        }
+    }
+
+    result
+}
+
+/// A piece of a template of `%string/format%`.
+enum FormatPiece {
+    Literal(String),
+    /// A `{name}` placeholder, to be replaced with the value of the field `name`.
+    Placeholder(String),
+}
+
+/// Parse a template of `%string/format%` into literal pieces and placeholders. `{{` and `}}`
+/// stand for literal braces.
+///
+/// On error, return a description of the problem.
+fn parse_format_template(template: &str) -> Result<Vec<FormatPiece>, String> {
+    let mut pieces = Vec::new();
+    let mut literal = String::new();
+    let mut chars = template.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+                literal.push('{');
+            }
+            '}' if chars.peek() == Some(&'}') => {
+                chars.next();
+                literal.push('}');
+            }
+            '{' => {
+                let mut name = String::new();
+
+                loop {
+                    match chars.next() {
+                        Some('}') => break,
+                        Some('{') | None => {
+                            return Err(format!(
+                                "unterminated placeholder `{{{name}` (use `{{{{` for a literal \
+                                opening brace)"
+                            ))
+                        }
+                        Some(c) => name.push(c),
+                    }
+                }
+
+                if name.is_empty() {
+                    return Err(String::from("empty placeholder `{}`"));
+                }
+
+                if !literal.is_empty() {
+                    pieces.push(FormatPiece::Literal(std::mem::take(&mut literal)));
+                }
+
+                pieces.push(FormatPiece::Placeholder(name));
+            }
+            '}' => {
+                return Err(String::from(
+                    "unmatched `}` (use `}}` for a literal closing brace)",
+                ))
+            }
+            c => literal.push(c),
+        }
+    }
+
+    if !literal.is_empty() {
+        pieces.push(FormatPiece::Literal(literal));
+    }
+
+    Ok(pieces)
+}
+
+/// The text encodings supported by `%string/to_bytes%` and `%string/from_bytes%`.
+#[derive(Clone, Copy, Debug)]
+enum TextEncoding {
+    Utf8,
+    /// Big-endian UTF-16, without byte order mark.
+    Utf16,
+    Latin1,
+}
+
+impl TextEncoding {
+    /// Get the encoding corresponding to an enum tag, or `None` if the term isn't one of the
+    /// supported encodings.
+    fn from_term(t: &Term) -> Option<Self> {
+        let Term::Enum(id) = t else {
+            return None;
+        };
+
+        match id.label() {
+            "Utf8" => Some(TextEncoding::Utf8),
+            "Utf16" => Some(TextEncoding::Utf16),
+            "Latin1" => Some(TextEncoding::Latin1),
+            _ => None,
+        }
+    }
+
+    /// Encode a string. On error, return the offset, in Unicode scalar values, of the first
+    /// character which can't be represented in this encoding.
+    fn encode(self, s: &str) -> Result<Vec<u8>, usize> {
+        match self {
+            TextEncoding::Utf8 => Ok(s.as_bytes().to_vec()),
+            TextEncoding::Utf16 => Ok(s.encode_utf16().flat_map(u16::to_be_bytes).collect()),
+            TextEncoding::Latin1 => s
+                .chars()
+                .enumerate()
+                .map(|(offset, c)| u8::try_from(c).map_err(|_| offset))
+                .collect(),
+        }
+    }
+
+    /// Decode a byte sequence. On error, return the offset of the first invalid byte.
+    fn decode(self, bytes: &[u8]) -> Result<String, usize> {
+        match self {
+            TextEncoding::Utf8 => std::str::from_utf8(bytes)
+                .map(String::from)
+                .map_err(|err| err.valid_up_to()),
+            TextEncoding::Utf16 => {
+                let units = bytes
+                    .chunks(2)
+                    .enumerate()
+                    .map(|(index, pair)| match pair {
+                        [hi, lo] => Ok(u16::from_be_bytes([*hi, *lo])),
+                        // A trailing odd byte
+                        _ => Err(2 * index),
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+
+                let mut result = String::with_capacity(units.len());
+                let mut offset = 0;
+
+                for c in char::decode_utf16(units.iter().copied()) {
+                    let c = c.map_err(|_| offset)?;
+                    result.push(c);
+                    offset += 2 * c.len_utf16();
+                }
+
+                Ok(result)
+            }
+            TextEncoding::Latin1 => Ok(bytes.iter().map(|b| char::from(*b)).collect()),
+        }
+    }
+}
+
+impl std::fmt::Display for TextEncoding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TextEncoding::Utf8 => write!(f, "UTF-8"),
+            TextEncoding::Utf16 => write!(f, "UTF-16"),
+            TextEncoding::Latin1 => write!(f, "Latin-1"),
+        }
+    }
+}
+
+/// Rename the fields of a record, given a list of pairs of an old name and a new name. All the
+/// fields are removed before being inserted back under their new name, so that the renamings
+/// happen at once: two fields can swap their names, and a field can be renamed to itself. The
+/// renamed fields keep their metadata and their pending contracts.
+///
+/// `operator` is the name of the primop, used for error reporting.
+fn rename_fields(
+    mut record: RecordData,
+    renames: Vec<(LocIdent, LocIdent)>,
+    operator: &str,
+    pos_record: TermPos,
+    pos_op: TermPos,
+) -> Result<RecordData, EvalError> {
+    if let Some((old, _)) = renames
+        .iter()
+        .find(|(old, _)| !record.fields.contains_key(old))
+    {
+        return Err(EvalError::FieldMissing {
+            id: *old,
+            field_names: record.field_names(RecordOpKind::IgnoreEmptyOpt),
+            operator: String::from(operator),
+            pos_record,
+            pos_op,
+        });
+    }
+
+    let moved: Vec<_> = renames
+        .into_iter()
+        .map(|(old, new)| {
+            let field = record.fields.remove(&old).unwrap();
+            (old, new, field)
+        })
+        .collect();
+
+    // The original name of the fields inserted so far, to report collisions between two renamed
+    // fields.
+    let mut renamed_from = HashMap::new();
+
+    for (old, new, field) in moved {
+        if let Some(other) = renamed_from.get(&new) {
+            return Err(EvalError::Other(
+                format!("{operator}: can't rename both `{other}` and `{old}` to `{new}`"),
+                pos_op,
+            ));
+        }
+
+        if record.fields.contains_key(&new) {
+            return Err(EvalError::Other(
+                format!(
+                    "{operator}: can't rename `{old}` to `{new}`, because the record already has \
+                    a field `{new}`"
+                ),
+                pos_op,
+            ));
+        }
+
+        renamed_from.insert(new, old);
+        record.fields.insert(new, field);
+    }
+
+    Ok(record)
+}
+
+/// A hashable representation of a fully evaluated value, used by `%array/dedup%` to find
+/// duplicates in linear time. Two values have the same key if and only if they are equal
+/// according to `==`.
+#[derive(Debug, PartialEq, Eq, Hash)]
+enum DedupKey {
+    Null,
+    Bool(bool),
+    Num(Number),
+    Str(String),
+    Enum(Ident),
+    EnumVariant(Ident, Box<DedupKey>),
+    Array(Vec<DedupKey>),
+    /// The fields are sorted by name, so that the order of definition doesn't matter.
+    Record(Vec<(Ident, DedupKey)>),
+}
+
+impl DedupKey {
+    /// Compute the key of a term, looking up variables and closures in the cache. Return `None`
+    /// if the term isn't fully evaluated or contains a value which can't be compared, such as a
+    /// function.
+    fn new<C: Cache>(cache: &C, rt: &RichTerm, env: &Environment) -> Option<Self> {
+        let from_cache = |idx: &CacheIndex| {
+            let Closure { body, env } = cache.get(idx.clone());
+            Self::new(cache, &body, &env)
+        };
+
+        match rt.as_ref() {
+            Term::Var(id) => from_cache(env.get(&id.ident())?),
+            Term::Closure(idx) => from_cache(idx),
+            Term::Null => Some(DedupKey::Null),
+            Term::Bool(b) => Some(DedupKey::Bool(*b)),
+            Term::Num(n) => Some(DedupKey::Num(n.clone())),
+            Term::Str(s) => Some(DedupKey::Str(String::from(s))),
+            Term::Enum(id) => Some(DedupKey::Enum(id.ident())),
+            Term::EnumVariant { tag, arg, .. } => Some(DedupKey::EnumVariant(
+                tag.ident(),
+                Box::new(Self::new(cache, arg, env)?),
+            )),
+            Term::Array(ts, _) => ts
+                .iter()
+                .map(|t| Self::new(cache, t, env))
+                .collect::<Option<_>>()
+                .map(DedupKey::Array),
+            Term::Record(record) => {
+                let mut fields = record
+                    .fields
+                    .iter()
+                    .filter_map(|(id, field)| Some((id.ident(), field.value.as_ref()?)))
+                    .map(|(id, value)| Some((id, Self::new(cache, value, env)?)))
+                    .collect::<Option<Vec<_>>>()?;
+                fields.sort_by(|(id1, _), (id2, _)| id1.label().cmp(id2.label()));
+
+                Some(DedupKey::Record(fields))
+            }
+            _ => None,
+        }
+    }
+}
+
 /// Compute the equality of two terms, represented as closures.
 ///
 /// # Parameters
@@ -0,0 +1,86 @@
+//! Timing of the evaluation of the fields of a configuration, to find out which parts of a
+//! program are slow. See [super::VirtualMachine::set_profile].
+
+use std::{
+    fmt,
+    time::{Duration, Instant},
+};
+
+use crate::identifier::{FieldPath, LocIdent};
+
+/// The time spent forcing each field of the result of a program, down to a given depth.
+///
+/// Fields are timed while they are forced by [crate::term::UnaryOp::Force], which forces them one
+/// after the other, from the last one to the first one. The time of a field includes the time
+/// spent forcing its subfields. Because of sharing, the time spent evaluating an expression used
+/// by several fields is attributed to the first field forced, which is the last one in the record.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Profile {
+    depth: usize,
+    timings: Vec<(FieldPath, Duration)>,
+    /// The start times of the fields being forced, innermost last.
+    started: Vec<Instant>,
+}
+
+impl Profile {
+    /// Create an empty profile timing the fields up to `depth` levels of nesting. A depth of `1`
+    /// only times the top-level fields.
+    pub fn new(depth: usize) -> Self {
+        Profile {
+            depth,
+            timings: Vec::new(),
+            started: Vec::new(),
+        }
+    }
+
+    /// The maximum nesting level of the fields being timed.
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    /// Start timing a field. The timings of nested fields are started and stopped while the
+    /// timing of their parent is running.
+    pub fn start(&mut self) {
+        self.started.push(Instant::now());
+    }
+
+    /// Stop the timing started last, and record it as the time spent forcing the field at `path`.
+    /// The time spent forcing the root of the result, whose path is empty, isn't recorded.
+    pub fn stop(&mut self, path: Vec<LocIdent>) {
+        let elapsed = self
+            .started
+            .pop()
+            .expect("a timing must have been started before being stopped")
+            .elapsed();
+
+        if !path.is_empty() {
+            self.timings.push((FieldPath(path), elapsed));
+        }
+    }
+
+    /// Discard the timings that have been started but not stopped, when an evaluation has been
+    /// aborted.
+    pub fn reset(&mut self) {
+        self.started.clear();
+    }
+
+    /// The timings recorded so far, in the order in which the fields were forced.
+    pub fn timings(&self) -> &[(FieldPath, Duration)] {
+        &self.timings
+    }
+}
+
+impl fmt::Display for Profile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut timings: Vec<_> = self.timings.iter().collect();
+        timings.sort_by(|(_, t1), (_, t2)| t2.cmp(t1));
+
+        write!(f, "{:>12}  field", "time (ms)")?;
+
+        for (path, time) in timings {
+            write!(f, "\n{:>12.3}  {path}", time.as_secs_f64() * 1000.0)?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,61 @@
+//! The pseudo-random number generator used by the primitive operators which need randomness,
+//! such as `%array/shuffle%`.
+//!
+//! The generator is [SplitMix64](https://prng.di.unimi.it/splitmix64.c). It isn't suitable for
+//! anything security related, but it's fast, its state is a single integer, and a given seed
+//! yields the same sequence of numbers on every platform, which makes evaluation reproducible.
+
+use std::{
+    collections::hash_map::RandomState,
+    hash::{BuildHasher, Hasher},
+};
+
+#[derive(Clone, Debug)]
+pub struct Rng {
+    seed: u64,
+    state: u64,
+}
+
+impl Rng {
+    /// Create a generator from a seed. Two generators created from the same seed produce the
+    /// same sequence of numbers.
+    pub fn new(seed: u64) -> Self {
+        Rng { seed, state: seed }
+    }
+
+    /// Create a generator from a seed which is different for each process.
+    pub fn from_entropy() -> Self {
+        // The standard library randomizes the keys of the default hasher, which is a cheap way of
+        // getting some entropy without an additional dependency.
+        Self::new(RandomState::new().build_hasher().finish())
+    }
+
+    /// Restart the sequence of numbers from the beginning.
+    pub fn reset(&mut self) {
+        self.state = self.seed;
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    }
+
+    /// A number in `0..bound`. `bound` must be positive.
+    pub fn below(&mut self, bound: usize) -> usize {
+        debug_assert!(bound > 0);
+        // Multiply-shift rather than modulo: the bias is negligible for the sizes we deal with,
+        // and it doesn't favor small numbers.
+        ((u128::from(self.next_u64()) * bound as u128) >> 64) as usize
+    }
+
+    /// Shuffle `elts` in place with the Fisher-Yates algorithm.
+    pub fn shuffle<T>(&mut self, elts: &mut [T]) {
+        for i in (1..elts.len()).rev() {
+            let j = self.below(i + 1);
+            elts.swap(i, j);
+        }
+    }
+}
@@ -0,0 +1,34 @@
+//! Statistics about an evaluation, to help understanding the performance of a Nickel program. See
+//! [super::VirtualMachine::set_stats].
+
+use std::fmt;
+
+/// Counters of the costly operations performed during evaluation.
+///
+/// The evaluation cache is consulted each time a variable is accessed: the access is a hit if the
+/// corresponding thunk has already been evaluated, and a miss if it has to be evaluated now. A
+/// lookup which is neither a hit nor a miss is an infinite recursion, which aborts the evaluation.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct EvalStats {
+    /// The number of accesses to the evaluation cache.
+    pub cache_lookups: usize,
+    /// The number of accesses to a thunk which had already been evaluated.
+    pub cache_hits: usize,
+    /// The number of accesses to a thunk which had yet to be evaluated.
+    pub cache_misses: usize,
+    /// The number of merges performed, not counting the merges generated by the application of
+    /// record contracts.
+    pub merges: usize,
+    /// The number of contract applications.
+    pub contracts_applied: usize,
+}
+
+impl fmt::Display for EvalStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "cache lookups:     {}", self.cache_lookups)?;
+        writeln!(f, "cache hits:        {}", self.cache_hits)?;
+        writeln!(f, "cache misses:      {}", self.cache_misses)?;
+        writeln!(f, "merges:            {}", self.merges)?;
+        write!(f, "contracts applied: {}", self.contracts_applied)
+    }
+}
@@ -2,11 +2,11 @@ use super::cache::CacheImpl;
 use super::*;
 use crate::cache::resolvers::{DummyResolver, SimpleResolver};
 use crate::error::ImportError;
-use crate::label::Label;
+use crate::label::{Label, MergeKind};
 use crate::parser::{grammar, lexer, ErrorTolerantParser};
 use crate::term::make as mk_term;
 use crate::term::Number;
-use crate::term::{BinaryOp, StrChunk, UnaryOp};
+use crate::term::{BinaryOp, MergePriority, StrChunk, UnaryOp};
 use crate::transform::import_resolution::strict::resolve_imports;
 use crate::{mk_app, mk_fun, mk_record};
 use assert_matches::assert_matches;
@@ -397,3 +397,870 @@ fn foreign_id() {
     let fid = LocIdent::from(Ident::new("ForeignId"));
     assert_matches!(ty, Term::Enum(f) if f == fid);
 }
+
+#[test]
+fn array_at_bounds() {
+    let at = |array: &str, index: &str| {
+        eval_no_import(parse(&format!("%array/at% {array} ({index})")).unwrap())
+    };
+
+    assert_eq!(at("[1, 2, 3]", "2"), Ok(Term::Num(Number::from(3))));
+    assert_eq!(at("[1, 2, 3]", "0"), Ok(Term::Num(Number::from(1))));
+
+    assert_matches!(
+        at("[1, 2, 3]", "-1"),
+        Err(EvalError::ArrayOutOfBounds { index, length: 3, .. }) if index == Number::from(-1)
+    );
+    assert_matches!(
+        at("[1, 2, 3]", "3"),
+        Err(EvalError::ArrayOutOfBounds { index, length: 3, .. }) if index == Number::from(3)
+    );
+    assert_matches!(
+        at("[]", "0"),
+        Err(EvalError::ArrayOutOfBounds { length: 0, .. })
+    );
+    assert_matches!(at("[1, 2, 3]", "0.5"), Err(EvalError::Other(..)));
+}
+
+#[test]
+fn array_chunk() {
+    let chunk = |size: &str, array: &str, expected: &str| {
+        eval_no_import(parse(&format!("%array/chunk% ({size}) {array} == {expected}")).unwrap())
+    };
+
+    assert_eq!(
+        chunk("2", "[1, 2, 3, 4, 5, 6]", "[[1, 2], [3, 4], [5, 6]]"),
+        Ok(Term::Bool(true))
+    );
+    assert_eq!(
+        chunk("2", "[1, 2, 3, 4, 5]", "[[1, 2], [3, 4], [5]]"),
+        Ok(Term::Bool(true))
+    );
+    assert_eq!(chunk("5", "[1, 2]", "[[1, 2]]"), Ok(Term::Bool(true)));
+    assert_eq!(chunk("3", "[]", "[]"), Ok(Term::Bool(true)));
+
+    assert_matches!(chunk("0", "[1, 2]", "[]"), Err(EvalError::Other(..)));
+    assert_matches!(chunk("-1", "[1, 2]", "[]"), Err(EvalError::Other(..)));
+    assert_matches!(chunk("1.5", "[1, 2]", "[]"), Err(EvalError::Other(..)));
+}
+
+#[test]
+fn array_flatten() {
+    let flatten = |depth: &str, array: &str, expected: &str| {
+        eval_no_import(parse(&format!("%array/flatten% ({depth}) {array} == {expected}")).unwrap())
+    };
+
+    assert_eq!(
+        flatten("1", "[[1, 2], [3], []]", "[1, 2, 3]"),
+        Ok(Term::Bool(true))
+    );
+    assert_eq!(
+        flatten("1", "[[[1], [2]], [[3]]]", "[[1], [2], [3]]"),
+        Ok(Term::Bool(true))
+    );
+    assert_eq!(
+        flatten("100", "[[[1], [2]], [[3]]]", "[1, 2, 3]"),
+        Ok(Term::Bool(true))
+    );
+    // Elements are evaluated as needed to find out whether they are arrays.
+    assert_eq!(
+        flatten("2", "[[[1] @ [2]], [%array/at% [[3]] 0]]", "[1, 2, 3]"),
+        Ok(Term::Bool(true))
+    );
+    assert_eq!(flatten("0", "[[1], 2]", "[[1], 2]"), Ok(Term::Bool(true)));
+    assert_eq!(flatten("3", "[]", "[]"), Ok(Term::Bool(true)));
+
+    assert_matches!(flatten("1", "[[1], 2]", "[]"), Err(EvalError::Other(..)));
+    assert_matches!(flatten("1", "[1, 2]", "[]"), Err(EvalError::Other(..)));
+    assert_matches!(
+        flatten("2", "[[1], [[2]]]", "[]"),
+        Err(EvalError::Other(..))
+    );
+    assert_matches!(flatten("-1", "[[1]]", "[]"), Err(EvalError::Other(..)));
+    assert_matches!(flatten("0.5", "[[1]]", "[]"), Err(EvalError::Other(..)));
+}
+
+#[test]
+fn array_scan() {
+    let scan = |f: &str, init: &str, array: &str, expected: &str| {
+        eval_no_import(parse(&format!("%array/scan% ({f}) {init} {array} == {expected}")).unwrap())
+    };
+
+    assert_eq!(
+        scan("fun acc x => acc + x", "0", "[1, 2, 3, 4]", "[1, 3, 6, 10]"),
+        Ok(Term::Bool(true))
+    );
+    assert_eq!(
+        scan("fun acc x => acc @ [x]", "[]", "[1, 2]", "[[1], [1, 2]]"),
+        Ok(Term::Bool(true))
+    );
+    assert_eq!(
+        scan("fun acc x => acc + x", "0", "[]", "[]"),
+        Ok(Term::Bool(true))
+    );
+    // Accumulators which aren't needed aren't computed.
+    assert_eq!(
+        eval_no_import(
+            parse("%array/length% (%array/scan% (fun acc x => acc + x) 0 [1, \"a\", 2])").unwrap()
+        ),
+        Ok(Term::Num(Number::from(3)))
+    );
+
+    assert_matches!(
+        scan("fun acc x => acc + x", "0", "{}", "[]"),
+        Err(EvalError::NAryPrimopTypeError { .. })
+    );
+}
+
+#[test]
+fn string_bytes() {
+    let eval_str = |s: &str| eval_no_import(parse(s).unwrap());
+
+    assert_eq!(
+        eval_str("%string/to_bytes% 'Utf8 \"Nickel\" == [78, 105, 99, 107, 101, 108]"),
+        Ok(Term::Bool(true))
+    );
+    for encoding in ["'Utf8", "'Utf16", "'Latin1"] {
+        assert_eq!(
+            eval_str(&format!(
+                "%string/from_bytes% {encoding} (%string/to_bytes% {encoding} \"config.ncl\") \
+                == \"config.ncl\""
+            )),
+            Ok(Term::Bool(true))
+        );
+    }
+
+    assert_eq!(
+        eval_str("%string/to_bytes% 'Utf8 \"é€\" == [195, 169, 226, 130, 172]"),
+        Ok(Term::Bool(true))
+    );
+    assert_eq!(
+        eval_str("%string/from_bytes% 'Utf8 [195, 169, 226, 130, 172] == \"é€\""),
+        Ok(Term::Bool(true))
+    );
+    assert_eq!(
+        eval_str("%string/to_bytes% 'Utf16 \"é\" == [0, 233]"),
+        Ok(Term::Bool(true))
+    );
+    assert_eq!(
+        eval_str("%string/to_bytes% 'Latin1 \"é\" == [233]"),
+        Ok(Term::Bool(true))
+    );
+
+    // Elements are evaluated as needed.
+    assert_eq!(
+        eval_str("%string/from_bytes% 'Latin1 [100 + 4, 105] == \"hi\""),
+        Ok(Term::Bool(true))
+    );
+
+    assert_matches!(
+        eval_str("%string/from_bytes% 'Utf8 [104, 105, 195, 40]"),
+        Err(EvalError::Other(msg, _)) if msg.contains("offset 2")
+    );
+    assert_matches!(
+        eval_str("%string/from_bytes% 'Utf16 [0, 104, 0]"),
+        Err(EvalError::Other(msg, _)) if msg.contains("offset 2")
+    );
+    assert_matches!(
+        eval_str("%string/from_bytes% 'Utf8 [104, 256]"),
+        Err(EvalError::Other(msg, _)) if msg.contains("index 1")
+    );
+    assert_matches!(
+        eval_str("%string/to_bytes% 'Latin1 \"a€\""),
+        Err(EvalError::Other(msg, _)) if msg.contains("offset 1")
+    );
+    assert_matches!(
+        eval_str("%string/to_bytes% 'Latin1 \"€\""),
+        Err(EvalError::Other(msg, _)) if msg.contains("offset 0")
+    );
+    assert_matches!(
+        eval_str("%string/to_bytes% 'Ascii \"a\""),
+        Err(EvalError::NAryPrimopTypeError { .. })
+    );
+}
+
+#[test]
+fn enum_variants() {
+    let eval_str = |s: &str| eval_no_import(parse(s).unwrap());
+
+    assert_eq!(
+        eval_str("%enum/variants% [| 'Red, 'Green, 'Blue |] == [\"Red\", \"Green\", \"Blue\"]"),
+        Ok(Term::Bool(true))
+    );
+    assert_eq!(
+        eval_str("%enum/variants% [| 'Port Number, 'Auto |] == [\"Port\", \"Auto\"]"),
+        Ok(Term::Bool(true))
+    );
+
+    assert_matches!(
+        eval_str("%enum/variants% 'Red"),
+        Err(EvalError::UnaryPrimopTypeError { .. })
+    );
+    assert_matches!(
+        eval_str("%enum/variants% Number"),
+        Err(EvalError::Other(msg, _)) if msg.contains("expected an enum contract")
+    );
+}
+
+#[test]
+fn record_rename() {
+    let eval = |src: &str| eval_no_import(parse(src).unwrap());
+
+    assert_eq!(
+        eval(r#"%record/rename% "a" "c" {a = 1, b = 2} == {c = 1, b = 2}"#),
+        Ok(Term::Bool(true))
+    );
+    assert_eq!(
+        eval(r#"%record/rename% "a" "a" {a = 1, b = 2} == {a = 1, b = 2}"#),
+        Ok(Term::Bool(true))
+    );
+    // The renamed field keeps its priority.
+    assert_eq!(
+        eval(r#"(%record/rename% "a" "b" {a | default = 1} & {b = 2}).b == 2"#),
+        Ok(Term::Bool(true))
+    );
+
+    assert_matches!(
+        eval(r#"%record/rename% "a" "b" {a = 1, b = 2}"#),
+        Err(EvalError::Other(..))
+    );
+    assert_matches!(
+        eval(r#"%record/rename% "c" "d" {a = 1, b = 2}"#),
+        Err(EvalError::FieldMissing { .. })
+    );
+}
+
+#[test]
+fn record_rename_keys() {
+    let eval = |src: &str| eval_no_import(parse(src).unwrap());
+
+    assert_eq!(
+        eval(r#"%record/rename_keys% {a = "c", b = "d"} {a = 1, b = 2} == {c = 1, d = 2}"#),
+        Ok(Term::Bool(true))
+    );
+    // The fields are renamed all at once, so they can swap their names.
+    assert_eq!(
+        eval(r#"%record/rename_keys% {a = "b", b = "a"} {a = 1, b = 2} == {a = 2, b = 1}"#),
+        Ok(Term::Bool(true))
+    );
+    assert_eq!(
+        eval(r#"%record/rename_keys% {a = "c" ++ "d"} {a = 1} == {cd = 1}"#),
+        Ok(Term::Bool(true))
+    );
+
+    assert_matches!(
+        eval(r#"%record/rename_keys% {a = "b"} {a = 1, b = 2}"#),
+        Err(EvalError::Other(..))
+    );
+    assert_matches!(
+        eval(r#"%record/rename_keys% {a = "c", b = "c"} {a = 1, b = 2}"#),
+        Err(EvalError::Other(..))
+    );
+}
+
+#[test]
+fn record_map_keys() {
+    let eval = |src: &str| eval_no_import(parse(src).unwrap());
+
+    assert_eq!(
+        eval(
+            r#"%record/map_keys% (fun k => "app_" ++ k) {a = 1, b = 2} == {app_a = 1, app_b = 2}"#
+        ),
+        Ok(Term::Bool(true))
+    );
+    assert_eq!(
+        eval(r#"%record/map_keys% (fun k => k) {a = 1, b = 2} == {a = 1, b = 2}"#),
+        Ok(Term::Bool(true))
+    );
+    // The renamed fields keep their metadata.
+    assert_eq!(
+        eval(r#"(%record/map_keys% (fun k => k ++ "2") {a | default = 1} & {a2 = 2}).a2 == 2"#),
+        Ok(Term::Bool(true))
+    );
+
+    assert_matches!(
+        eval(r#"%record/map_keys% (fun k => "c") {a = 1, b = 2}"#),
+        Err(EvalError::Other(msg, _)) if msg.contains("`a`") && msg.contains("`b`")
+    );
+    assert_matches!(
+        eval(r#"%record/map_keys% (fun k => 1) {a = 1}"#),
+        Err(EvalError::TypeError(..))
+    );
+}
+
+#[test]
+fn array_find_map() {
+    let eval = |src: &str| eval_no_import(parse(src).unwrap());
+
+    assert_eq!(
+        eval("%array/find_map% (fun x => if x > 1 then 'Some x else 'None) [1, 2, 3] == 'Some 2"),
+        Ok(Term::Bool(true))
+    );
+    assert_eq!(
+        eval("%array/find_map% (fun x => 'None) [1, 2, 3] == 'None"),
+        Ok(Term::Bool(true))
+    );
+    assert_eq!(
+        eval("%array/find_map% (fun x => 'Some x) [] == 'None"),
+        Ok(Term::Bool(true))
+    );
+    // The elements after the first match aren't evaluated.
+    assert_matches!(
+        eval(r#"%array/find_map% (fun x => if x == 2 then 'Some x else 'None) [1, 2, 1 + "a"]"#),
+        Ok(Term::EnumVariant { .. })
+    );
+    assert_matches!(
+        eval(r#"%array/find_map% (fun x => if x == 3 then 'Some x else 'None) [1, 2, 1 + "a"]"#),
+        Err(EvalError::NAryPrimopTypeError { .. })
+    );
+}
+
+#[test]
+fn array_find_index() {
+    let eval = |src: &str| eval_no_import(parse(src).unwrap());
+
+    assert_eq!(
+        eval("%array/find_index% (fun x => x > 1) [1, 2, 3] == 'Some 1"),
+        Ok(Term::Bool(true))
+    );
+    assert_eq!(
+        eval("%array/find_index% (fun x => x > 5) [1, 2, 3] == 'None"),
+        Ok(Term::Bool(true))
+    );
+    // The elements after the first match aren't evaluated.
+    assert_eq!(
+        eval(r#"%array/find_index% (fun x => x == 2) [1, 2, 1 + "a"] == 'Some 1"#),
+        Ok(Term::Bool(true))
+    );
+    assert_matches!(
+        eval(r#"%array/find_index% (fun x => x == 3) [1, 2, 1 + "a"]"#),
+        Err(EvalError::NAryPrimopTypeError { .. })
+    );
+}
+
+#[test]
+fn string_format() {
+    let eval = |src: &str| eval_no_import(parse(src).unwrap());
+
+    assert_eq!(
+        eval(r#"%string/format% "{name}:{port}" { name = "web", port = 80 } == "web:80""#),
+        Ok(Term::Bool(true))
+    );
+    assert_eq!(
+        eval(r#"%string/format% "{x} and {x}, {b}" { x = 'a, b = true } == "a and a, true""#),
+        Ok(Term::Bool(true))
+    );
+    assert_matches!(
+        eval(r#"%string/format% "{name} {missing}" { name = "web" }"#),
+        Err(EvalError::FieldMissing { id, .. }) if id.label() == "missing"
+    );
+    assert_eq!(
+        eval(r#"%string/format% "{{{x}}} {{}}" { x = 1 } == "{1} {}""#),
+        Ok(Term::Bool(true))
+    );
+    assert_matches!(
+        eval(r#"%string/format% "{x" { x = 1 }"#),
+        Err(EvalError::Other(..))
+    );
+}
+
+#[test]
+fn string_split_lines() {
+    let eval = |src: &str| eval_no_import(parse(src).unwrap());
+
+    // Mixed line endings
+    assert_eq!(
+        eval(r#"%string/split_lines% "a\nb\r\nc\rd" == ["a", "b", "c", "d"]"#),
+        Ok(Term::Bool(true))
+    );
+    // A final newline doesn't produce an empty line, but empty lines in between are kept.
+    assert_eq!(
+        eval(r#"%string/split_lines% "a\n\nb\n" == ["a", "", "b"]"#),
+        Ok(Term::Bool(true))
+    );
+    assert_eq!(
+        eval(r#"%string/split_lines% "" == []"#),
+        Ok(Term::Bool(true))
+    );
+    // CRLF content, where splitting on `\n` leaves the carriage returns in.
+    assert_eq!(
+        eval(r#"%string/split_lines% "a\r\nb\r\n" == ["a", "b"]"#),
+        Ok(Term::Bool(true))
+    );
+    assert_eq!(
+        eval(r#"%string/split% "a\r\nb\r\n" "\n" == ["a\r", "b\r", ""]"#),
+        Ok(Term::Bool(true))
+    );
+}
+
+#[test]
+fn array_dedup() {
+    let eval = |src: &str| eval_no_import(parse(src).unwrap());
+
+    assert_eq!(
+        eval("%array/dedup% (%force% [3, 1, 3, 2, 1, 2]) == [3, 1, 2]"),
+        Ok(Term::Bool(true))
+    );
+    assert_eq!(
+        eval(r#"%array/dedup% (%force% ["b", "a", "b", "c", "a"]) == ["b", "a", "c"]"#),
+        Ok(Term::Bool(true))
+    );
+    // Records are compared structurally, regardless of the order of their fields.
+    assert_eq!(
+        eval(
+            "%array/dedup% (%force% [{ a = 1, b = [1] }, { b = [1], a = 1 }, { a = 2 }, { a = 1 + 1 }]) \
+            == [{ a = 1, b = [1] }, { a = 2 }]"
+        ),
+        Ok(Term::Bool(true))
+    );
+    assert_eq!(
+        eval("%array/dedup% (%force% ['Foo 1, 'Foo 2, 'Foo 1, 'Foo]) == ['Foo 1, 'Foo 2, 'Foo]"),
+        Ok(Term::Bool(true))
+    );
+    // As with `==`, a function is different from the values which can be compared, but two
+    // functions can't be compared.
+    assert_eq!(
+        eval("%array/length% (%array/dedup% (%force% [1, fun x => x, 1])) == 2"),
+        Ok(Term::Bool(true))
+    );
+    assert_matches!(
+        eval("%array/dedup% (%force% [1, fun x => x, fun x => x])"),
+        Err(EvalError::IncomparableValues { .. })
+    );
+}
+
+#[test]
+fn record_difference_intersection() {
+    let eval = |src: &str| eval_no_import(parse(src).unwrap());
+
+    // Disjoint records
+    assert_eq!(
+        eval("%record/difference% { a = 1, b = 2 } { c = 3 } == { a = 1, b = 2 }"),
+        Ok(Term::Bool(true))
+    );
+    assert_eq!(
+        eval("%record/intersection% { a = 1, b = 2 } { c = 3 } == {}"),
+        Ok(Term::Bool(true))
+    );
+
+    // Overlapping records: the values of the first record are kept.
+    assert_eq!(
+        eval("%record/difference% { a = 1, b = 2 } { b = 3, c = 4 } == { a = 1 }"),
+        Ok(Term::Bool(true))
+    );
+    assert_eq!(
+        eval("%record/intersection% { a = 1, b = 2 } { b = 3, c = 4 } == { b = 2 }"),
+        Ok(Term::Bool(true))
+    );
+
+    // Identical sets of fields
+    assert_eq!(
+        eval("%record/difference% { a = 1, b = 2 } { a = 1, b = 2 } == {}"),
+        Ok(Term::Bool(true))
+    );
+    assert_eq!(
+        eval("%record/intersection% { a = 1, b = 2 } { b = 0, a = 0 } == { a = 1, b = 2 }"),
+        Ok(Term::Bool(true))
+    );
+
+    // Metadata is preserved, and empty optional fields of the second record are ignored.
+    assert_eq!(
+        eval("(%record/intersection% { a | default = 1 } { a = 0 }) & { a = 2 } == { a = 2 }"),
+        Ok(Term::Bool(true))
+    );
+    assert_eq!(
+        eval("%record/difference% { a = 1 } { a | optional } == { a = 1 }"),
+        Ok(Term::Bool(true))
+    );
+    assert_matches!(
+        eval("%record/difference% { a = 1 } 1"),
+        Err(EvalError::NAryPrimopTypeError { arg_number: 2, .. })
+    );
+}
+
+#[test]
+fn enum_record_conversion() {
+    let eval = |src: &str| eval_no_import(parse(src).unwrap());
+
+    assert_eq!(
+        eval(r#"%enum/to_record% ('Foo 1) == { tag = "Foo", arg = 1 }"#),
+        Ok(Term::Bool(true))
+    );
+    assert_eq!(
+        eval(r#"%enum/to_record% 'Bar == { tag = "Bar" }"#),
+        Ok(Term::Bool(true))
+    );
+    assert_eq!(
+        eval(r#"%enum/from_record% { tag = "Foo", arg = { x = 1 } } == 'Foo { x = 1 }"#),
+        Ok(Term::Bool(true))
+    );
+    assert_eq!(
+        eval(r#"%enum/from_record% { tag = "Bar" } == 'Bar"#),
+        Ok(Term::Bool(true))
+    );
+    assert_eq!(
+        eval(r#"let x = 'Foo [1, 2] in %enum/from_record% (%enum/to_record% x) == x"#),
+        Ok(Term::Bool(true))
+    );
+    assert_matches!(
+        eval(r#"%enum/from_record% { arg = 1 }"#),
+        Err(EvalError::FieldMissing { id, .. }) if id.label() == "tag"
+    );
+    assert_matches!(
+        eval(r#"%enum/from_record% { tag = "Foo", extra = 1 }"#),
+        Err(EvalError::Other(..))
+    );
+    assert_matches!(
+        eval(r#"%enum/to_record% "Foo""#),
+        Err(EvalError::UnaryPrimopTypeError { .. })
+    );
+}
+
+#[test]
+fn infinite_recursion_reports_bindings() {
+    #[track_caller]
+    fn cycle(src: &str) -> Vec<String> {
+        match eval_no_import(parse(src).unwrap()) {
+            Err(EvalError::InfiniteRecursion(_, _, bindings)) => bindings
+                .iter()
+                .map(|id| id.label().to_owned())
+                .collect(),
+            other => panic!("expected an infinite recursion error, got {other:?}"),
+        }
+    }
+
+    assert_eq!(cycle("let rec x = x + 1 in x"), ["x"]);
+    assert_eq!(cycle("{x = y + 1, y = x + 1}.x"), ["x", "y"]);
+    assert_eq!(cycle("{x = y, y = z, z = x}.x"), ["x", "y", "z"]);
+}
+
+#[test]
+fn merge_all_reports_element_index() {
+    let merge_all = |array: &str| {
+        let src = format!("%record/merge_all% {array}");
+        let id = Files::new().add("<test>", src.clone());
+        let t = grammar::TermParser::new()
+            .parse_strict(id, lexer::Lexer::new(&src))
+            .unwrap();
+
+        eval_full_no_import(t)
+    };
+
+    assert_matches!(merge_all("[]"), Ok(Term::Record(data)) if data.fields.is_empty());
+    assert_matches!(
+        merge_all("[{a = 1}, {b = 2}, {a = 2}]"),
+        Err(EvalError::MergeIncompatibleArgs { merge_label, .. })
+            if merge_label.kind == MergeKind::ArrayElement { index: 2 }
+    );
+}
+
+#[test]
+fn deserialization_error_location() {
+    let deserialize = |src: &str| {
+        let id = Files::new().add("<test>", String::from(src));
+        let t = grammar::TermParser::new()
+            .parse_strict(id, lexer::Lexer::new(src))
+            .unwrap();
+
+        match eval_full_no_import(t) {
+            Err(EvalError::DeserializationError { location, .. }) => location.unwrap(),
+            result => panic!("expected a deserialization error, got {result:?}"),
+        }
+    };
+
+    let src = r#"%deserialize% 'Json "[1, 2,, 3]""#;
+    let location = deserialize(src);
+    assert_eq!((location.offset, location.line, location.column), (6, 1, 7));
+    let span = location.span.unwrap();
+    assert_eq!(&src[span.start.to_usize()..span.end.to_usize()], ",");
+    assert_eq!(span.start.to_usize(), 27);
+
+    // With an escape sequence, the input can't be mapped back to the source anymore.
+    let location = deserialize(r#"%deserialize% 'Json "[1,\n 2,, 3]""#);
+    assert_eq!((location.offset, location.line, location.column), (7, 2, 4));
+    assert_eq!(location.span, None);
+}
+
+#[test]
+fn record_equality_ignores_order_and_metadata() {
+    let eq = |s: &str| eval_full_no_import(parse(s).unwrap());
+
+    assert_eq!(
+        eq("{a = 1, b = {c = \"x\", d = [1]}} == {b = {d = [1], c = \"x\"}, a = 1}"),
+        Ok(Term::Bool(true))
+    );
+    assert_eq!(
+        eq("{a | doc \"first\" = 1} == {a | doc \"second\" | default = 1}"),
+        Ok(Term::Bool(true))
+    );
+    assert_eq!(eq("{a | doc \"first\" = 1} == {a = 2}"), Ok(Term::Bool(false)));
+
+    // Optional fields without a definition are ignored, but defined ones are compared.
+    assert_eq!(eq("{a | optional, b = 1} == {b = 1}"), Ok(Term::Bool(true)));
+    assert_eq!(eq("{a | optional = 1} == {a = 1}"), Ok(Term::Bool(true)));
+    assert_eq!(eq("{a | optional = 1} == {}"), Ok(Term::Bool(false)));
+}
+
+#[test]
+fn fresh_idents_are_deterministic() {
+    // `%string/is_match%` evaluates to a function whose parameter is a fresh identifier.
+    let matcher = || mk_term::op1(UnaryOp::StringIsMatch, mk_term::string("a+"));
+    let param = |vm: &mut VirtualMachine<DummyResolver, CacheImpl>| match vm
+        .eval(matcher())
+        .map(Term::from)
+        .unwrap()
+    {
+        Term::Fun(param, _) => param,
+        t => panic!("expected a function, got {t:?}"),
+    };
+
+    let mut vm1 = VirtualMachine::<_, CacheImpl>::new(DummyResolver {}, std::io::sink());
+    let mut vm2 = VirtualMachine::<_, CacheImpl>::new(DummyResolver {}, std::io::sink());
+
+    let first = param(&mut vm1);
+    assert_eq!(first, param(&mut vm2));
+
+    // Fresh identifiers are distinct within an evaluation round...
+    assert_ne!(first, param(&mut vm1));
+
+    // ...but the generation starts over once the machine is reset.
+    vm1.reset();
+    assert_eq!(first, param(&mut vm1));
+}
+
+#[test]
+fn record_entries() {
+    let eval = |s: &str| eval_full_no_import(parse(s).unwrap());
+
+    assert_eq!(
+        eval(
+            r#"%record/to_entries% {b = 2, a = 1} == [{field = "a", value = 1}, {field = "b", value = 2}]"#
+        ),
+        Ok(Term::Bool(true))
+    );
+    assert_eq!(
+        eval("%record/from_entries% (%record/to_entries% {b = 2, a = {c = 3}}) == {a = {c = 3}, b = 2}"),
+        Ok(Term::Bool(true))
+    );
+    assert_eq!(eval("%record/from_entries% []"), eval("{}"));
+
+    // Values are not forced by the conversion.
+    assert_eq!(
+        eval(
+            r#"(%record/from_entries% [{field = "a", value = 1}, {field = "b", value = 1 + "x"}]).a"#
+        ),
+        Ok(Term::Num(Number::from(1)))
+    );
+
+    assert_matches!(
+        eval(r#"%record/from_entries% [{field = "a", value = 1}, {field = "a", value = 2}]"#),
+        Err(EvalError::Other(msg, _)) if msg.contains("field a")
+    );
+}
+
+#[test]
+fn string_indent_dedent() {
+    let eval = |s: &str| eval_full_no_import(parse(s).unwrap());
+    let string = |s: &str| Ok(Term::Str(s.into()));
+
+    assert_eq!(
+        eval(r#"%string/indent% "foo:\n  bar: 1\n\nbaz: 2\n" "  ""#),
+        string("  foo:\n    bar: 1\n\n  baz: 2\n")
+    );
+    assert_eq!(
+        eval(r#"%string/indent% "a\r\nb" "- ""#),
+        string("- a\r\n- b")
+    );
+
+    assert_eq!(
+        eval(r#"%string/dedent% "    foo:\n      bar: 1\n\n    baz: 2\n""#),
+        string("foo:\n  bar: 1\n\nbaz: 2\n")
+    );
+    // Blank lines don't count in the common indentation, but are preserved.
+    assert_eq!(
+        eval(r#"%string/dedent% "\t\tfoo\n  \n\t\t\tbar""#),
+        string("foo\n\n\tbar")
+    );
+    assert_eq!(
+        eval(r#"%string/dedent% "  foo\n bar""#),
+        string(" foo\nbar")
+    );
+}
+
+#[test]
+fn record_insert_with_metadata() {
+    let eval = |s: &str| eval_full_no_import(parse(s).unwrap());
+
+    let Ok(Term::Record(data)) =
+        eval(r#"%record/insert_with_metadata% "foo" "The foo" 'Default [Number] {bar = 1} 5"#)
+    else {
+        panic!("expected a record");
+    };
+
+    let field = data.fields.get(&LocIdent::from("foo")).unwrap();
+    assert_eq!(field.metadata.doc.as_deref(), Some("The foo"));
+    assert_eq!(field.metadata.priority, MergePriority::Bottom);
+    assert_eq!(field.metadata.annotation.contracts.len(), 1);
+    assert_eq!(
+        field.value.as_ref().map(|v| v.as_ref()),
+        Some(&Term::Num(Number::from(5)))
+    );
+
+    // Missing metadata can be given as `null`.
+    assert_eq!(
+        eval(r#"(%record/insert_with_metadata% "foo" null null null {} 5).foo"#),
+        Ok(Term::Num(Number::from(5)))
+    );
+
+    // The contracts are enforced on the value.
+    assert_matches!(
+        eval(r#"(%record/insert_with_metadata% "foo" null null [Number] {} "5").foo"#),
+        Err(EvalError::BlameError { .. })
+    );
+
+    assert_matches!(
+        eval(r#"%record/insert_with_metadata% "foo" null 'Sometimes null {} 5"#),
+        Err(EvalError::NAryPrimopTypeError { arg_number: 3, .. })
+    );
+}
+
+#[test]
+fn number_min_max_clamp() {
+    let eval = |s: &str| eval_full_no_import(parse(s).unwrap());
+
+    assert_eq!(eval("%number/min% 3 (-2)"), eval("-2"));
+    assert_eq!(eval("%number/max% 3 (-2)"), eval("3"));
+    assert_eq!(eval("%number/clamp% 42 1 10"), eval("10"));
+    assert_eq!(eval("%number/clamp% (-3) 1 10"), eval("1"));
+    assert_eq!(eval("%number/clamp% 5 1 10"), eval("5"));
+
+    // These numbers are equal once converted to f64, but they are distinct as exact rationals.
+    let (big, bigger) = ("9007199254740993", "9007199254740994");
+    assert_eq!(eval(&format!("%number/min% {bigger} {big}")), eval(big));
+    assert_eq!(eval(&format!("%number/max% {big} {bigger}")), eval(bigger));
+    assert_eq!(eval(&format!("%number/clamp% 1e100 0 {big}")), eval(big));
+
+    let lo = "(1/10 + 1/100000000000000000000)";
+    assert_eq!(eval(&format!("%number/clamp% 0.1 {lo} 1")), eval(lo));
+
+    assert_matches!(
+        eval("%number/clamp% 5 10 1"),
+        Err(EvalError::Other(msg, _)) if msg.contains("lower bound 10")
+    );
+}
+
+#[test]
+fn array_merge_modes() {
+    use super::merge::ArrayMergeMode;
+
+    let eval = |mode, s: &str| {
+        let mut vm = VirtualMachine::<_, CacheImpl>::new(DummyResolver {}, std::io::sink());
+        vm.set_array_merge_mode(mode);
+        vm.eval(parse(s).unwrap()).map(Term::from)
+    };
+    let holds = |mode, s: &str| assert_eq!(eval(mode, s), Ok(Term::Bool(true)), "{s}");
+
+    holds(ArrayMergeMode::Concat, "([1] & [2, 3]) == [1, 2, 3]");
+    holds(ArrayMergeMode::Concat, "([] & []) == []");
+    holds(
+        ArrayMergeMode::Concat,
+        "({ a = [1] } & { a = [2] }).a == [1, 2]",
+    );
+
+    holds(ArrayMergeMode::Zip, "([] & []) == []");
+    holds(
+        ArrayMergeMode::Zip,
+        "([{ a = 1 }, { b = 2 }] & [{ c = 3 }, { b | force = 4 }])
+         == [{ a = 1, c = 3 }, { b = 4 }]",
+    );
+    // Overriding a field of an element updates the fields depending on it.
+    holds(
+        ArrayMergeMode::Zip,
+        "([{ a = 1, b = a + 1 }] & [{ a | force = 10 }]) == [{ a = 10, b = 11 }]",
+    );
+
+    assert_matches!(
+        eval(ArrayMergeMode::Zip, "[1, 2] & [1, 2, 3]"),
+        Err(EvalError::MergeArrayLengthMismatch {
+            left_length: 2,
+            right_length: 3,
+            ..
+        })
+    );
+    assert_matches!(
+        eval(ArrayMergeMode::Zip, "([1, 2] & [1, 3]) == []"),
+        Err(EvalError::MergeIncompatibleArgs { .. })
+    );
+}
+
+#[test]
+fn merge_terms() {
+    let mut vm = VirtualMachine::<_, CacheImpl>::new(DummyResolver {}, std::io::sink());
+
+    let base = vm.eval(parse("{ a = 1, b = a + 1 }").unwrap()).unwrap();
+    let merged = vm
+        .merge_terms(base, parse("{ a | force = 2, c = 3 }").unwrap())
+        .unwrap();
+    assert_matches!(merged.as_ref(), Term::Record(..));
+
+    let field = |vm: &mut VirtualMachine<_, _>, id: &str| {
+        vm.eval(mk_term::op1(
+            UnaryOp::RecordAccess(id.into()),
+            merged.clone(),
+        ))
+        .map(Term::from)
+    };
+    assert_eq!(field(&mut vm, "b"), Ok(Term::Num(Number::from(3))));
+    assert_eq!(field(&mut vm, "c"), Ok(Term::Num(Number::from(3))));
+
+    assert_matches!(
+        vm.merge_terms(mk_term::integer(1), mk_term::integer(2)),
+        Err(EvalError::MergeIncompatibleArgs { .. })
+    );
+}
+
+#[test]
+fn merged_documentation() {
+    use super::merge::DocMergeMode;
+
+    let doc = |mode, s: &str| {
+        let mut vm = VirtualMachine::<_, CacheImpl>::new(DummyResolver {}, std::io::sink());
+        vm.set_doc_merge_mode(mode);
+
+        match vm.eval(parse(s).unwrap()).map(Term::from) {
+            Ok(Term::Record(data)) => data.fields[&LocIdent::from("a")].metadata.doc.clone(),
+            other => panic!("expected a record, got {other:?}"),
+        }
+    };
+
+    let both = r#"{ a | doc "first" = 1 } & { a | doc "second" }"#;
+    assert_eq!(
+        doc(DocMergeMode::Concat, both).as_deref(),
+        Some("first\n\nsecond")
+    );
+    assert_eq!(doc(DocMergeMode::First, both).as_deref(), Some("first"));
+
+    let same = r#"{ a | doc "same" = 1 } & { a | doc "same" }"#;
+    assert_eq!(doc(DocMergeMode::Concat, same).as_deref(), Some("same"));
+
+    let right_only = r#"{ a = 1 } & { a | doc "second" }"#;
+    assert_eq!(
+        doc(DocMergeMode::Concat, right_only).as_deref(),
+        Some("second")
+    );
+    assert_eq!(
+        doc(DocMergeMode::First, right_only).as_deref(),
+        Some("second")
+    );
+
+    // Piecewise definitions are combined by the parser, which keeps the first documentation.
+    let piecewise = r#"{ a | doc "first" = 1, a | doc "second" }"#;
+    assert_eq!(
+        doc(DocMergeMode::First, piecewise).as_deref(),
+        Some("first")
+    );
+    assert_eq!(
+        doc(DocMergeMode::Concat, piecewise).as_deref(),
+        Some("first")
+    );
+}
@@ -0,0 +1,24 @@
+//! The destination of the messages of `std.trace`. See [super::VirtualMachine::set_trace_sink].
+
+use std::io::Write;
+
+use crate::term::{RichTerm, Term};
+
+/// A receiver of the messages traced with `std.trace` during evaluation.
+pub trait TraceSink {
+    /// Called each time `std.trace` is evaluated, in evaluation order. `msg` is the evaluated
+    /// message, which is a string, together with its position.
+    fn trace(&mut self, msg: &RichTerm);
+}
+
+/// The default trace sink, which writes each message on its own line to a stream, such as the
+/// standard error.
+pub struct WriteTraceSink<W>(pub W);
+
+impl<W: Write> TraceSink for WriteTraceSink<W> {
+    fn trace(&mut self, msg: &RichTerm) {
+        if let Term::Str(s) = msg.as_ref() {
+            let _ = writeln!(self.0, "std.trace: {s}");
+        }
+    }
+}
@@ -145,6 +145,40 @@ impl LocIdent {
     }
 }
 
+/// A generator of fresh identifiers, owned by an evaluation context such as the virtual machine.
+///
+/// As opposed to [LocIdent::fresh], which draws from a counter shared by the whole thread, the
+/// names generated here only depend on the number of identifiers generated since the creation or
+/// the last [reset](FreshIdentGen::reset) of the generator. Evaluating the same program twice thus
+/// produces the same intermediate terms, which makes them suitable for snapshot testing.
+///
+/// The generated names use a different prefix than the ones of [LocIdent::fresh], so that the two
+/// can't collide.
+#[derive(Clone, Debug, Default)]
+pub struct FreshIdentGen {
+    next: usize,
+}
+
+impl FreshIdentGen {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Generate a new identifier, distinct from all the identifiers generated since the last
+    /// reset.
+    pub fn fresh(&mut self) -> LocIdent {
+        increment!("FreshIdentGen::fresh");
+        let id = LocIdent::new(format!("{GEN_PREFIX}eval{}", self.next));
+        self.next += 1;
+        id
+    }
+
+    /// Restart the generation from the first identifier.
+    pub fn reset(&mut self) {
+        self.next = 0;
+    }
+}
+
 /// Special character used for generating fresh identifiers. It must be syntactically impossible to
 /// use to write in a standard Nickel program, to avoid name clashes.
 pub const GEN_PREFIX: char = '%';
@@ -226,6 +260,32 @@ impl AsRef<str> for LocIdent {
     }
 }
 
+/// A path of fields, that is a list, locating this field from the root of the configuration.
+#[derive(Clone, Default, PartialEq, Eq, Debug, Hash)]
+pub struct FieldPath(pub Vec<LocIdent>);
+
+impl FieldPath {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl fmt::Display for FieldPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use crate::pretty::ident_quoted;
+
+        write!(
+            f,
+            "{}",
+            self.0
+                .iter()
+                .map(ident_quoted)
+                .collect::<Vec<_>>()
+                .join(".")
+        )
+    }
+}
+
 mod interner {
     use std::collections::HashMap;
     use std::sync::{Mutex, RwLock};
@@ -0,0 +1,329 @@
+//! Generate a [JSON Schema](https://json-schema.org/) from the contracts annotating a
+//! configuration.
+//!
+//! The translation is static and best-effort. Builtin types, record contracts and a few scalar
+//! contracts of the standard library are translated faithfully. Any other contract, such as a
+//! custom contract defined by a function, is translated to the schema `true`, which accepts
+//! anything. The generated schema is thus never stricter than the original contracts: a
+//! configuration which passes its contracts is always valid with respect to the schema.
+use std::collections::HashMap;
+
+use codespan::FileId;
+use serde_json::{json, Map, Value};
+
+use crate::{
+    identifier::Ident,
+    term::{
+        record::{Field, RecordData},
+        MergePriority, RichTerm, Term, TypeAnnotation, UnaryOp,
+    },
+    typ::{EnumRowsIteratorItem, RecordRowsIteratorItem, Type, TypeF},
+};
+
+/// The JSON Schema dialect of the generated schemas.
+pub const SCHEMA_DIALECT: &str = "https://json-schema.org/draft/2020-12/schema";
+
+/// How deeply we're willing to follow variables, imports and nested contracts. Contracts can be
+/// recursive, in which case the schema is cut at this depth.
+const MAX_DEPTH: usize = 32;
+
+/// The JSON schemas of the contracts of the standard library that have a direct equivalent.
+fn stdlib_schema(path: &[&str]) -> Option<Value> {
+    match path {
+        ["number", "Integer"] => Some(json!({ "type": "integer" })),
+        ["number", "Nat"] => Some(json!({ "type": "integer", "minimum": 0 })),
+        ["number", "PosNat"] => Some(json!({ "type": "integer", "minimum": 1 })),
+        ["string", "NonEmpty"] => Some(json!({ "type": "string", "minLength": 1 })),
+        _ => None,
+    }
+}
+
+/// Generate a schema for the contracts annotating the root of `term`, looking through
+/// let-bindings and imports. Imported files are fetched with `resolve_import`. Return `None` if
+/// the root of `term` isn't annotated.
+pub fn root_schema(
+    term: &RichTerm,
+    resolve_import: impl Fn(FileId) -> Option<RichTerm>,
+) -> Option<Value> {
+    let mut gen = SchemaGen {
+        resolve_import: &resolve_import,
+        scope: HashMap::new(),
+    };
+
+    let annot = gen.root_annotation(term, 0)?;
+    let mut schema = match gen.annotation(&annot, 0) {
+        Value::Object(schema) => schema,
+        // The schema `true` is equivalent to the empty schema `{}`.
+        _ => Map::new(),
+    };
+    schema.insert("$schema".to_owned(), Value::from(SCHEMA_DIALECT));
+
+    Some(Value::Object(schema))
+}
+
+struct SchemaGen<'a> {
+    resolve_import: &'a dyn Fn(FileId) -> Option<RichTerm>,
+    /// The let-bound variables seen so far. Scoping is approximated: a binding is visible from
+    /// everywhere once it has been seen.
+    scope: HashMap<Ident, RichTerm>,
+}
+
+impl SchemaGen<'_> {
+    fn root_annotation(&mut self, rt: &RichTerm, depth: usize) -> Option<TypeAnnotation> {
+        if depth > MAX_DEPTH {
+            return None;
+        }
+
+        match rt.as_ref() {
+            Term::Annotated(annot, _) => Some(annot.clone()),
+            Term::Let(id, bound, body, _) => {
+                self.scope.insert(id.ident(), bound.clone());
+                self.root_annotation(body, depth)
+            }
+            Term::ResolvedImport(file_id) => {
+                let imported = (self.resolve_import)(*file_id)?;
+                self.root_annotation(&imported, depth + 1)
+            }
+            _ => None,
+        }
+    }
+
+    /// The schema of a type annotation, which is the conjunction of the schemas of the type and
+    /// of the contracts.
+    fn annotation(&mut self, annot: &TypeAnnotation, depth: usize) -> Value {
+        let mut schemas: Vec<Value> = annot
+            .typ
+            .iter()
+            .chain(annot.contracts.iter())
+            .map(|labeled| self.typ(&labeled.typ, depth))
+            .filter(|schema| schema != &Value::Bool(true))
+            .collect();
+
+        match schemas.len() {
+            0 => Value::Bool(true),
+            1 => schemas.pop().unwrap(),
+            _ => json!({ "allOf": schemas }),
+        }
+    }
+
+    fn typ(&mut self, typ: &Type, depth: usize) -> Value {
+        if depth > MAX_DEPTH {
+            return Value::Bool(true);
+        }
+
+        match &typ.typ {
+            TypeF::Number => json!({ "type": "number" }),
+            TypeF::Bool => json!({ "type": "boolean" }),
+            TypeF::String => json!({ "type": "string" }),
+            TypeF::Array(elt) => json!({ "type": "array", "items": self.typ(elt, depth + 1) }),
+            TypeF::Dict { type_fields, .. } => json!({
+                "type": "object",
+                "additionalProperties": self.typ(type_fields, depth + 1),
+            }),
+            TypeF::Record(rows) => {
+                let mut properties = Map::new();
+                let mut open = false;
+
+                for item in rows.iter() {
+                    match item {
+                        RecordRowsIteratorItem::Row(row) => {
+                            properties.insert(row.id.to_string(), self.typ(row.typ, depth + 1));
+                        }
+                        RecordRowsIteratorItem::TailDyn | RecordRowsIteratorItem::TailVar(_) => {
+                            open = true
+                        }
+                    }
+                }
+
+                let required: Vec<_> = properties.keys().cloned().collect();
+
+                json!({
+                    "type": "object",
+                    "properties": properties,
+                    "required": required,
+                    "additionalProperties": open,
+                })
+            }
+            TypeF::Enum(rows) => {
+                // Only enums made of bare tags, which are serialized as strings, have a JSON
+                // counterpart.
+                let tags: Option<Vec<_>> = rows
+                    .iter()
+                    .map(|item| match item {
+                        EnumRowsIteratorItem::Row(row) if row.typ.is_none() => {
+                            Some(Value::from(row.id.to_string()))
+                        }
+                        _ => None,
+                    })
+                    .collect();
+
+                tags.map(|tags| json!({ "enum": tags }))
+                    .unwrap_or(Value::Bool(true))
+            }
+            TypeF::Flat(rt) => self.contract(rt, depth + 1),
+            _ => Value::Bool(true),
+        }
+    }
+
+    /// The schema of a contract given as an arbitrary term.
+    fn contract(&mut self, rt: &RichTerm, depth: usize) -> Value {
+        if depth > MAX_DEPTH {
+            return Value::Bool(true);
+        }
+
+        if let Some(schema) = static_access_path(rt).and_then(|path| match path.as_slice() {
+            ["std", rest @ ..] if !self.scope.contains_key(&Ident::from("std")) => {
+                stdlib_schema(rest)
+            }
+            _ => None,
+        }) {
+            return schema;
+        }
+
+        match rt.as_ref() {
+            Term::Type(typ) => self.typ(typ, depth),
+            Term::Record(data) | Term::RecRecord(data, ..) => self.record_contract(data, depth),
+            Term::Var(id) => match self.scope.get(&id.ident()).cloned() {
+                Some(bound) => self.contract(&bound, depth + 1),
+                None => Value::Bool(true),
+            },
+            Term::Let(id, bound, body, _) => {
+                self.scope.insert(id.ident(), bound.clone());
+                self.contract(body, depth)
+            }
+            Term::ResolvedImport(file_id) => match (self.resolve_import)(*file_id) {
+                Some(imported) => self.contract(&imported, depth + 1),
+                None => Value::Bool(true),
+            },
+            _ => Value::Bool(true),
+        }
+    }
+
+    fn record_contract(&mut self, data: &RecordData, depth: usize) -> Value {
+        let mut properties = Map::new();
+        let mut required = Vec::new();
+
+        for (id, field) in data.fields.iter() {
+            let name = id.to_string();
+
+            // Fields which aren't exported never appear in the exported configuration.
+            if !field.metadata.opt && !field.metadata.not_exported {
+                required.push(Value::from(name.clone()));
+            }
+
+            properties.insert(name, self.field(field, depth + 1));
+        }
+
+        json!({
+            "type": "object",
+            "properties": properties,
+            "required": required,
+            "additionalProperties": data.attrs.open,
+        })
+    }
+
+    fn field(&mut self, field: &Field, depth: usize) -> Value {
+        let mut schema = match self.annotation(&field.metadata.annotation, depth) {
+            Value::Object(schema) => schema,
+            _ => Map::new(),
+        };
+
+        if let Some(doc) = &field.metadata.doc {
+            schema.insert("description".to_owned(), Value::from(doc.as_str()));
+        }
+
+        // Only constant values can be represented in the schema. A default value is a default,
+        // while any other value is imposed by the contract.
+        let constant = field
+            .value
+            .as_ref()
+            .filter(|value| value.as_ref().is_constant());
+
+        if let Some(value) = constant.and_then(|value| serde_json::to_value(value).ok()) {
+            let key = if field.metadata.priority == MergePriority::Bottom {
+                "default"
+            } else {
+                "const"
+            };
+
+            schema.insert(key.to_owned(), value);
+        }
+
+        if schema.is_empty() {
+            Value::Bool(true)
+        } else {
+            Value::Object(schema)
+        }
+    }
+}
+
+/// If `rt` is a chain of static record accesses on a variable, such as `std.number.Nat`, return
+/// the corresponding path.
+fn static_access_path(rt: &RichTerm) -> Option<Vec<&str>> {
+    match rt.as_ref() {
+        Term::Var(id) => Some(vec![id.label()]),
+        Term::Op1(UnaryOp::RecordAccess(field), record) => {
+            let mut path = static_access_path(record)?;
+            path.push(field.label());
+            Some(path)
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{grammar::TermParser, lexer::Lexer, ErrorTolerantParser};
+    use codespan::Files;
+
+    fn schema(s: &str) -> Option<Value> {
+        let id = Files::new().add("<test>", s);
+        let term = TermParser::new().parse_strict(id, Lexer::new(s)).unwrap();
+
+        root_schema(&term, |_| None)
+    }
+
+    #[test]
+    fn record_contract() {
+        let schema = schema(
+            r#"
+            let Port = std.number.Nat in
+            let Schema = {
+              name | doc "The name of the server" | String,
+              port | Port | default = 80,
+              tags | Array String | optional,
+              token | String | not_exported,
+              ..
+            }
+            in
+            { name = "web" } | Schema
+            "#,
+        );
+
+        assert_eq!(
+            schema,
+            Some(json!({
+                "$schema": SCHEMA_DIALECT,
+                "type": "object",
+                "properties": {
+                    "name": { "type": "string", "description": "The name of the server" },
+                    "port": { "type": "integer", "minimum": 0, "default": 80 },
+                    "tags": { "type": "array", "items": { "type": "string" } },
+                    "token": { "type": "string" },
+                },
+                "required": ["name", "port"],
+                "additionalProperties": true,
+            }))
+        );
+    }
+
+    #[test]
+    fn unannotated_or_custom() {
+        assert_eq!(schema("{ foo = 1 }"), None);
+        assert_eq!(
+            schema("1 | std.contract.from_predicate (fun x => x > 0)"),
+            Some(json!({ "$schema": SCHEMA_DIALECT }))
+        );
+    }
+}
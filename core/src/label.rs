@@ -4,6 +4,8 @@
 //! information about the context of a contract failure.
 use std::{collections::HashMap, rc::Rc};
 
+use serde::{Deserialize, Serialize};
+
 use crate::{
     eval::cache::{Cache as EvalCache, CacheIndex},
     identifier::LocIdent,
@@ -292,6 +294,10 @@ pub struct Label {
     /// The original position of the value being checked. Set at run-time by the interpreter.
     pub arg_pos: TermPos,
 
+    /// The position of the definition of the contract being checked, if it's not a type but a
+    /// user-defined contract, such as a function or a record. Set at run-time by the interpreter.
+    pub contract_pos: TermPos,
+
     /// The polarity, used for higher-order contracts, that specifies if the current contract is
     /// on the environment (ex, the argument of a function) or on the term.
     pub polarity: Polarity,
@@ -307,6 +313,13 @@ pub struct Label {
     /// while first transforming a record as part of the pending contract generation.
     /// Contract applications outside of records will have this field set to `None`.
     pub field_name: Option<LocIdent>,
+
+    /// The path of the records enclosing the field `field_name`. It is set by the parser for
+    /// fields defined with a path, as in `web.port | Number`, and updated by the interpreter when
+    /// merging records: the fields of the records merged as the value of `services.web`, or of a
+    /// record contract attached to `services.web`, get the path `services.web`. See
+    /// [MergeLabel::field_path].
+    pub field_path: Vec<LocIdent>,
 }
 
 /// Data about type variables that is needed for polymorphic contracts to decide which actions to
@@ -386,6 +399,10 @@ pub struct ContractDiagnostic {
     pub message: Option<String>,
     /// Additional notes printed at the end of the message.
     pub notes: Vec<String>,
+    /// Structured data describing the violation, such as the expected and the actual values. It
+    /// isn't shown in the text output, but is included in the serialized diagnostics, so that
+    /// tools can consume it without parsing the message.
+    pub data: Option<serde_json::Value>,
 }
 
 impl ContractDiagnostic {
@@ -407,15 +424,24 @@ impl ContractDiagnostic {
         self
     }
 
+    /// Attach structured data to this diagnostic, and return the updated value. Erase potential
+    /// previous data.
+    pub fn with_data(mut self, data: serde_json::Value) -> Self {
+        self.data = Some(data);
+        self
+    }
+
     /// Append a note to this diagnostic.
     pub fn append_note(&mut self, note: impl Into<String>) {
         self.notes.push(note.into());
     }
 
     /// Return `true` if this diagnostic is empty, that is if `message` is either not set (`None`)
-    /// or is set but empty, AND notes are empty.
+    /// or is set but empty, AND notes are empty, AND there's no data.
     pub fn is_empty(&self) -> bool {
-        self.message.as_ref().map(String::is_empty).unwrap_or(true) && self.notes.is_empty()
+        self.message.as_ref().map(String::is_empty).unwrap_or(true)
+            && self.notes.is_empty()
+            && self.data.is_none()
     }
 }
 
@@ -469,6 +495,21 @@ impl Label {
         self
     }
 
+    /// Set the structured data of the current diagnostic (the last diagnostic of the stack).
+    /// Potentially erase the previous value.
+    ///
+    /// If the diagnostic stack is empty, this method pushes a new diagnostic with the given data.
+    pub fn with_diagnostic_data(mut self, data: serde_json::Value) -> Self {
+        if let Some(current) = self.diagnostics.last_mut() {
+            current.data = Some(data);
+        } else {
+            self.diagnostics
+                .push(ContractDiagnostic::new().with_data(data));
+        };
+
+        self
+    }
+
     /// Append a note to the current diagnostic (the last diagnostic of the stack). Potentially
     /// erase the previous value.
     ///
@@ -503,6 +544,39 @@ impl Label {
         Label { field_name, ..self }
     }
 
+    /// Prepend `prefix` to the path of the enclosing records.
+    pub fn with_field_path_prefix(self, prefix: &[LocIdent]) -> Self {
+        let field_path = prefix
+            .iter()
+            .chain(self.field_path.iter())
+            .copied()
+            .collect();
+        Label { field_path, ..self }
+    }
+
+    /// The full path of the field being checked, from the outermost enclosing record, or an empty
+    /// path if this label isn't attached to a field.
+    pub fn full_field_path(&self) -> Vec<LocIdent> {
+        match self.field_name {
+            Some(name) => self.field_path.iter().copied().chain([name]).collect(),
+            None => Vec::new(),
+        }
+    }
+
+    /// The full path of the field being checked as a dotted string, such as `services.web.port`,
+    /// if this label is attached to a field.
+    pub fn qualified_field_name(&self) -> Option<String> {
+        use crate::pretty::ident_quoted;
+
+        self.field_name.map(|_| {
+            self.full_field_path()
+                .iter()
+                .map(ident_quoted)
+                .collect::<Vec<_>>()
+                .join(".")
+        })
+    }
+
     /// Tests if the contract associated to this label might have polymorphic subcontracts
     /// (equivalently, if the contract is derived from a type which has free type variables). Such
     /// contracts are special, in particular because they aren't idempotent and thus can't be
@@ -531,15 +605,17 @@ impl Default for Label {
             diagnostics: Default::default(),
             arg_idx: Default::default(),
             arg_pos: Default::default(),
+            contract_pos: Default::default(),
             path: Default::default(),
             type_environment: Default::default(),
             field_name: None,
+            field_path: Vec::new(),
         }
     }
 }
 
 /// Possible origins of a merge operation.
-#[derive(Clone, Copy, Eq, PartialEq, Debug, Default)]
+#[derive(Clone, Copy, Eq, PartialEq, Debug, Default, Serialize, Deserialize)]
 pub enum MergeKind {
     /// A standard, user-written merge operation (or a merge operation descending from a
     /// user-written merge operation).
@@ -551,6 +627,9 @@ pub enum MergeKind {
     /// { foo = def1, foo = def2}
     /// ```
     PiecewiseDef,
+    /// A merge generated by `std.record.merge_all`, merging the element at position `index` of
+    /// the array argument with the merge of the previous elements.
+    ArrayElement { index: usize },
 }
 
 /// A merge label.
@@ -565,11 +644,16 @@ pub enum MergeKind {
 /// Additionally, merging arrays currently generates a contract and its associated label for which
 /// we don't necessarily have a defined span at hand. The merge label makes it possible to fallback
 /// to the original position of the merge.
-#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[derive(Clone, Debug, Eq, PartialEq)]
 pub struct MergeLabel {
     /// The span of the original merge (which might then decompose into many others).
     pub span: RawSpan,
     pub kind: MergeKind,
+    /// The path of the merged records, when they are the values of a field of records being
+    /// merged in turn, or of a field checked by a record contract. The labels of the fields of the
+    /// merged records are updated with this path, so that blame errors report the full path of
+    /// nested fields. Empty for the merges written in the source.
+    pub field_path: Vec<LocIdent>,
 }
 
 impl From<Label> for MergeLabel {
@@ -577,6 +661,7 @@ impl From<Label> for MergeLabel {
         MergeLabel {
             span: label.span,
             kind: Default::default(),
+            field_path: label.full_field_path(),
         }
     }
 }
@@ -1,11 +1,15 @@
+pub mod ast_cache;
+pub mod ast_diff;
 pub mod cache;
 pub mod closurize;
 pub mod combine;
+pub mod contract_query;
 pub mod deserialize;
 pub mod environment;
 pub mod error;
 pub mod eval;
 pub mod identifier;
+pub mod json_schema;
 pub mod label;
 #[cfg(feature = "nix-experimental")]
 pub mod nix_ffi;
@@ -13,6 +17,9 @@ pub mod parser;
 pub mod position;
 pub mod pretty;
 pub mod program;
+pub mod proto;
+#[cfg(feature = "remote-imports")]
+pub mod remote_import;
 pub mod repl;
 pub mod serialize;
 pub mod stdlib;
@@ -0,0 +1,161 @@
+//! Attachment of comments to the AST, for tools which need to re-emit the comments of a source
+//! file, such as formatters or codemods.
+//!
+//! Comments aren't part of the AST: the lexer skips them, but it records where they are. Once a
+//! source has been parsed, [attach] associates each comment with the nearest AST node, following
+//! these rules:
+//!
+//! 1. A comment which follows some code on the same line is a trailing comment of the node ending
+//!    right before it. If several nodes end there, the outermost one is picked.
+//! 2. Otherwise, a comment is a leading comment of the node starting right after it, provided that
+//!    only whitespace and other comments separate them. If several nodes start there, a record
+//!    field takes precedence, and the outermost term is picked otherwise.
+//! 3. A comment which can't be attached by the rules above, such as a comment following the last
+//!    field of a record, is a trailing comment of the node ending right before it.
+//!
+//! Record fields are identified by the span of their name, while other nodes are identified by
+//! the span of the corresponding term. Comments are re-emitted for record fields by
+//! [crate::pretty::pretty_with_comments].
+use std::{cmp::Reverse, collections::HashMap, ops::Range};
+
+use codespan::FileId;
+
+use super::{lexer::Lexer, utils::mk_span};
+use crate::{
+    position::RawSpan,
+    term::{RichTerm, Term, Traverse, TraverseControl},
+};
+
+/// A line comment.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Comment {
+    pub span: RawSpan,
+    /// The text of the comment, including the leading `#` but without the trailing whitespace.
+    pub text: String,
+}
+
+/// The comments of a source file, indexed by the span of the AST node they're attached to.
+#[derive(Clone, Debug, Default)]
+pub struct CommentMap {
+    leading: HashMap<RawSpan, Vec<Comment>>,
+    trailing: HashMap<RawSpan, Vec<Comment>>,
+}
+
+impl CommentMap {
+    /// The comments preceding the node with the given span, in source order.
+    pub fn leading(&self, span: &RawSpan) -> &[Comment] {
+        self.leading.get(span).map(Vec::as_slice).unwrap_or_default()
+    }
+
+    /// The comments following the node with the given span, in source order.
+    pub fn trailing(&self, span: &RawSpan) -> &[Comment] {
+        self.trailing.get(span).map(Vec::as_slice).unwrap_or_default()
+    }
+
+    /// Whether any comment is attached to the node with the given span.
+    pub fn has_comments(&self, span: &RawSpan) -> bool {
+        self.leading.contains_key(span) || self.trailing.contains_key(span)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.leading.is_empty() && self.trailing.is_empty()
+    }
+}
+
+/// An AST node to which comments can be attached.
+struct Node {
+    span: RawSpan,
+    is_field: bool,
+}
+
+/// Return the spans of the line comments of a source, in order. If the source can't be lexed
+/// entirely, only the comments before the first lexing error are returned.
+pub fn collect(source: &str) -> Vec<Range<usize>> {
+    let mut lexer = Lexer::new(source);
+
+    for token in lexer.by_ref() {
+        if token.is_err() {
+            break;
+        }
+    }
+
+    lexer.comments
+}
+
+/// Collect the positioned nodes of `rt` belonging to `file_id`.
+fn nodes(file_id: FileId, rt: &RichTerm) -> Vec<Node> {
+    let mut nodes = Vec::new();
+
+    rt.traverse_ref(
+        &mut |rt: &RichTerm, _: &()| {
+            if let Some(span) = rt.pos.as_opt_ref() {
+                nodes.push(Node {
+                    span: *span,
+                    is_field: false,
+                });
+            }
+
+            if let Term::Record(data) | Term::RecRecord(data, ..) = rt.as_ref() {
+                nodes.extend(data.fields.keys().filter_map(|id| {
+                    id.pos.as_opt_ref().map(|span| Node {
+                        span: *span,
+                        is_field: true,
+                    })
+                }));
+            }
+
+            TraverseControl::<(), ()>::Continue
+        },
+        &(),
+    );
+
+    nodes.retain(|node| node.span.src_id == file_id);
+    nodes
+}
+
+/// Whether `text` consists only of whitespace and line comments.
+fn is_trivia(text: &str) -> bool {
+    text.lines().all(|line| {
+        let line = line.trim_start();
+        line.is_empty() || line.starts_with('#')
+    })
+}
+
+/// Attach the comments of `source` to the nodes of `rt`, which must be the result of parsing
+/// `source` as the file `file_id`. See the [module documentation][self] for the attachment rules.
+pub fn attach(file_id: FileId, source: &str, rt: &RichTerm) -> CommentMap {
+    let nodes = nodes(file_id, rt);
+    let mut map = CommentMap::default();
+
+    for range in collect(source) {
+        let comment = Comment {
+            span: mk_span(file_id, range.start, range.end),
+            text: source[range.clone()].trim_end().to_owned(),
+        };
+
+        let preceding = nodes
+            .iter()
+            .filter(|node| node.span.end.to_usize() <= range.start)
+            .max_by_key(|node| (node.span.end, Reverse(node.span.start)));
+
+        let following = nodes
+            .iter()
+            .filter(|node| node.span.start.to_usize() >= range.end)
+            .min_by_key(|node| (node.span.start, !node.is_field, Reverse(node.span.end)));
+
+        let on_same_line = preceding
+            .filter(|node| !source[node.span.end.to_usize()..range.start].contains('\n'));
+        let adjacent = following
+            .filter(|node| is_trivia(&source[range.end..node.span.start.to_usize()]));
+
+        if let Some(node) = on_same_line {
+            map.trailing.entry(node.span).or_default().push(comment);
+        } else if let Some(node) = adjacent {
+            map.leading.entry(node.span).or_default().push(comment);
+        } else if let Some(node) = preceding {
+            map.trailing.entry(node.span).or_default().push(comment);
+        }
+    }
+
+    map
+}
@@ -18,6 +18,8 @@ pub enum LexicalError {
         opening_delimiter: Range<usize>,
         closing_delimiter: Range<usize>,
     },
+    /// A C-style comment `//`. Comments start with `#` in Nickel.
+    CStyleComment(Range<usize>),
     /// Generic lexer error
     Generic(Range<usize>),
 }
@@ -239,10 +239,24 @@ pub enum NormalToken<'input> {
     RecordInsert,
     #[token("%record/insert_with_opts%")]
     RecordInsertWithOpts,
+    #[token("%record/insert_with_metadata%")]
+    RecordInsertWithMetadata,
     #[token("%record/remove%")]
     RecordRemove,
     #[token("%record/remove_with_opts%")]
     RecordRemoveWithOpts,
+    #[token("%record/filter_keys%")]
+    RecordFilterKeys,
+    #[token("%record/rename%")]
+    RecordRename,
+    #[token("%record/rename_keys%")]
+    RecordRenameKeys,
+    #[token("%record/map_keys%")]
+    RecordMapKeys,
+    #[token("%record/difference%")]
+    RecordDifference,
+    #[token("%record/intersection%")]
+    RecordIntersection,
     #[token("%record/empty_with_tail%")]
     RecordEmptyWithTail,
     #[token("%record/seal_tail%")]
@@ -257,6 +271,10 @@ pub enum NormalToken<'input> {
     OpForce,
     #[token("%array/length%")]
     ArrayLength,
+    #[token("%array/shuffle%")]
+    ArrayShuffle,
+    #[token("%array/dedup%")]
+    ArrayDedup,
     #[token("%record/fields%")]
     RecordFields,
     #[token("%record/fields_with_opts%")]
@@ -264,8 +282,20 @@ pub enum NormalToken<'input> {
 
     #[token("%record/values%")]
     RecordValues,
+    #[token("%record/to_entries%")]
+    RecordToEntries,
+    #[token("%record/from_entries%")]
+    RecordFromEntries,
+    #[token("%record/merge_all%")]
+    RecordMergeAll,
     #[token("%pow%")]
     Pow,
+    #[token("%number/min%")]
+    NumberMin,
+    #[token("%number/max%")]
+    NumberMax,
+    #[token("%number/clamp%")]
+    NumberClamp,
     #[token("%trace%")]
     Trace,
 
@@ -277,6 +307,18 @@ pub enum NormalToken<'input> {
     ArrayMap,
     #[token("%array/at%")]
     ArrayAt,
+    #[token("%array/zip%")]
+    ArrayZip,
+    #[token("%array/chunk%")]
+    ArrayChunk,
+    #[token("%array/flatten%")]
+    ArrayFlatten,
+    #[token("%array/find_map%")]
+    ArrayFindMap,
+    #[token("%array/find_index%")]
+    ArrayFindIndex,
+    #[token("%function/compose%")]
+    FunctionCompose,
     #[token("%array/generate%")]
     ArrayGen,
     #[token("%rec_force%")]
@@ -313,14 +355,28 @@ pub enum NormalToken<'input> {
     StringSplit,
     #[token("%string/trim%")]
     StringTrim,
+    #[token("%string/dedent%")]
+    StringDedent,
     #[token("%string/chars%")]
     StringChars,
+    #[token("%string/split_lines%")]
+    StringSplitLines,
     #[token("%string/uppercase%")]
     StringUppercase,
     #[token("%string/lowercase%")]
     StringLowercase,
     #[token("%string/contains%")]
     StringContains,
+    #[token("%string/wrap%")]
+    StringWrap,
+    #[token("%string/indent%")]
+    StringIndent,
+    #[token("%string/format%")]
+    StringFormat,
+    #[token("%string/to_bytes%")]
+    StringToBytes,
+    #[token("%string/from_bytes%")]
+    StringFromBytes,
     #[token("%string/replace%")]
     StringReplace,
     #[token("%string/replace_regex%")]
@@ -335,12 +391,20 @@ pub enum NormalToken<'input> {
     StringLength,
     #[token("%string/substr%")]
     StringSubstr,
+    #[token("%string/pad_start%")]
+    StringPadStart,
+    #[token("%string/pad_end%")]
+    StringPadEnd,
     #[token("%to_string%")]
     ToString,
     #[token("%number/from_string%")]
     NumberFromString,
     #[token("%enum/from_string%")]
     EnumFromString,
+    #[token("%duration/from_string%")]
+    DurationFromString,
+    #[token("%duration/to_string%")]
+    DurationToString,
     #[token("%enum/get_arg%")]
     EnumGetArg,
     #[token("%enum/make_variant%")]
@@ -349,17 +413,29 @@ pub enum NormalToken<'input> {
     EnumIsVariant,
     #[token("%enum/get_tag%")]
     EnumGetTag,
+    #[token("%enum/to_record%")]
+    EnumToRecord,
+    #[token("%enum/from_record%")]
+    EnumFromRecord,
+    #[token("%enum/variants%")]
+    EnumVariants,
 
     #[token("%label/with_message%")]
     LabelWithMessage,
     #[token("%label/with_notes%")]
     LabelWithNotes,
+    #[token("%label/with_error_data%")]
+    LabelWithErrorData,
     #[token("%label/append_note%")]
     LabelAppendNote,
     #[token("%label/push_diag%")]
     LabelPushDiag,
     #[token("%array/slice%")]
     ArraySlice,
+    #[token("%array/zip_with%")]
+    ArrayZipWith,
+    #[token("%array/scan%")]
+    ArrayScan,
     #[token("%eval_nix%")]
     EvalNix,
 
@@ -392,6 +468,10 @@ pub enum NormalToken<'input> {
     EnumClose,
     #[regex("#[^\n]*")]
     LineComment,
+    /// The start of a C-style comment. This isn't valid Nickel, but we lex it in order to report
+    /// a helpful error.
+    #[token("//")]
+    DoubleSlash,
 }
 
 pub const KEYWORDS: &[&str] = &[
@@ -593,6 +673,9 @@ pub struct Lexer<'input> {
     /// previous mode together with its associated state is pushed on this stack. It can be then
     /// restored once the current mode is exited (in the string example, when the string ends).
     pub modes: Vec<Mode>,
+    /// The spans of the line comments encountered so far. Comments aren't emitted as tokens, but
+    /// tools such as formatters need to know where they are. See [super::comments].
+    pub comments: Vec<Range<usize>>,
 }
 
 impl<'input> Lexer<'input> {
@@ -603,6 +686,7 @@ impl<'input> Lexer<'input> {
                 logos_lexer: NormalToken::lexer(s),
             }),
             modes: Vec::new(),
+            comments: Vec::new(),
         }
     }
 
@@ -790,8 +874,14 @@ impl<'input> Lexer<'input> {
                     data.brace_count -= 1;
                 }
             }
-            // Ignore comment
-            NormalToken::LineComment => return self.next(),
+            // Ignore comment, but remember where it was
+            NormalToken::LineComment => {
+                self.comments.push(span);
+                return self.next();
+            }
+            NormalToken::DoubleSlash => {
+                return Some(Err(ParseError::Lexical(LexicalError::CStyleComment(span))))
+            }
             NormalToken::Error => {
                 return Some(Err(ParseError::Lexical(LexicalError::Generic(span))))
             }
@@ -14,8 +14,10 @@ lalrpop_mod!(
 
 use grammar::__ToTriple;
 
+pub mod comments;
 pub mod error;
 pub mod lexer;
+pub mod tokens;
 pub mod uniterm;
 pub mod utils;
 
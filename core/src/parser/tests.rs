@@ -570,3 +570,17 @@ fn import() {
         )
     );
 }
+
+#[test]
+fn c_style_comment() {
+    let span_of = |src: &str| match parse(src) {
+        Err(ParseError::CStyleComment(span)) => (span.start.to_usize(), span.end.to_usize()),
+        other => panic!("expected a C-style comment error, got {other:?}"),
+    };
+
+    assert_eq!(span_of("// some comment\n1"), (0, 2));
+    assert_eq!(span_of("{ foo = 1, // some comment\n bar = 2 }"), (11, 13));
+    assert_eq!(span_of("1 + 1 //no space"), (6, 8));
+    // A `//` inside a string isn't a comment.
+    assert_matches!(parse("\"https://nickel-lang.org\""), Ok(_));
+}
@@ -0,0 +1,213 @@
+//! A flat, classified token stream, for editors and other tools which need a simple tokenization
+//! of Nickel code, such as a syntax highlighting fallback or semantic tokens.
+//!
+//! As opposed to parsing, tokenizing is total: [tokenize] always succeeds, even on ill-formed
+//! input. Invalid characters are reported as [TokenKind::Error] tokens, and the rest of the input
+//! is tokenized as usual.
+use std::ops::Range;
+
+use codespan::FileId;
+
+use super::{
+    error::{LexicalError, ParseError},
+    lexer::{Lexer, MultiStringToken, NormalToken, StringToken, Token},
+};
+use crate::position::RawSpan;
+
+/// The category of a token.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum TokenKind {
+    /// A reserved keyword, such as `let` or `if`, or a metadata keyword, such as `doc`.
+    Keyword,
+    /// A builtin type, such as `Number` or `Dyn`.
+    Type,
+    Identifier,
+    Number,
+    /// A boolean literal or `null`.
+    Constant,
+    /// An enum tag, such as `'Foo`.
+    EnumTag,
+    /// A piece of a string literal, including its delimiters and escape sequences.
+    String,
+    /// The delimiters of an interpolated expression inside a string, that is `%{` and the matching
+    /// `}`.
+    Interpolation,
+    /// A primitive operator, such as `%typeof%`.
+    Builtin,
+    /// An infix or prefix operator, such as `+`, `&` or `|>`.
+    Operator,
+    /// Delimiters and separators, such as braces, commas or `=`.
+    Punctuation,
+    Comment,
+    /// Input which couldn't be tokenized.
+    Error,
+}
+
+/// Tokenize `source`, the content of the file `src_id`, returning the tokens in source order.
+/// Whitespace isn't part of any token.
+pub fn tokenize(src_id: FileId, source: &str) -> Vec<(TokenKind, RawSpan)> {
+    let mut lexer = Lexer::new(source);
+    let mut tokens = Vec::new();
+    // The closing brace of an interpolated expression is lexed in normal mode, as any other
+    // brace. We need to track the interpolations currently open to tell them apart.
+    let mut brace_depths: Vec<usize> = Vec::new();
+
+    for next in lexer.by_ref() {
+        let (kind, range) = match next {
+            Ok((start, token, end)) => {
+                let kind = match &token {
+                    Token::Normal(NormalToken::LBrace) => {
+                        if let Some(depth) = brace_depths.last_mut() {
+                            *depth += 1;
+                        }
+                        TokenKind::Punctuation
+                    }
+                    Token::Normal(NormalToken::RBrace) => match brace_depths.last_mut() {
+                        Some(0) => {
+                            brace_depths.pop();
+                            TokenKind::Interpolation
+                        }
+                        Some(depth) => {
+                            *depth -= 1;
+                            TokenKind::Punctuation
+                        }
+                        None => TokenKind::Punctuation,
+                    },
+                    Token::Str(StringToken::Interpolation)
+                    | Token::MultiStr(MultiStringToken::Interpolation) => {
+                        brace_depths.push(0);
+                        TokenKind::Interpolation
+                    }
+                    Token::Normal(token) => normal_token_kind(token),
+                    Token::Str(_) | Token::MultiStr(_) => TokenKind::String,
+                };
+
+                (kind, start..end)
+            }
+            Err(err) => (TokenKind::Error, error_range(&err, source.len())),
+        };
+
+        tokens.push((kind, RawSpan::from_range(src_id, range)));
+    }
+
+    tokens.extend(
+        lexer
+            .comments
+            .into_iter()
+            .map(|range| (TokenKind::Comment, RawSpan::from_range(src_id, range))),
+    );
+    tokens.sort_by_key(|(_, span)| span.start);
+    tokens
+}
+
+fn normal_token_kind(token: &NormalToken<'_>) -> TokenKind {
+    use NormalToken::*;
+
+    match token {
+        Identifier(_) => TokenKind::Identifier,
+        DecNumLiteral(_) | HexNumLiteral(_) | OctNumLiteral(_) | BinNumLiteral(_) => {
+            TokenKind::Number
+        }
+        RawEnumTag(_) => TokenKind::EnumTag,
+        // The beginning of a quoted enum tag such as `'"foo bar"`. The rest of the tag is lexed
+        // in string mode.
+        StrEnumTagBegin | DoubleQuote | MultiStringStart(_) | SymbolicStringStart(_) => {
+            TokenKind::String
+        }
+        Dyn | Number | Bool | String | Array => TokenKind::Type,
+        If | Then | Else | Forall | In | Let | Rec | Match | Or | Fun | Import | Merge
+        | Default | Doc | Optional | Priority | Force | NotExported => TokenKind::Keyword,
+        Null | True | False => TokenKind::Constant,
+        Plus | Minus | Times | Div | Percent | DoublePlus | DoubleEq | NotEquals | At
+        | DoubleAnd | DoublePipe | Bang | Ampersand | RightPipe | LAngleBracket | LessOrEq
+        | RAngleBracket | GreaterOrEq | SimpleArrow => TokenKind::Operator,
+        QuestionMark | Comma | Semicolon | Colon | Dollar | Equals | Dot | Ellipsis | Pipe
+        | DoubleArrow | Underscore | LBrace | RBrace | LBracket | RBracket | LParen | RParen
+        | EnumOpen | EnumClose => TokenKind::Punctuation,
+        LineComment => TokenKind::Comment,
+        Error => TokenKind::Error,
+        // All the remaining tokens are primitive operators.
+        _ => TokenKind::Builtin,
+    }
+}
+
+/// The range of the input covered by a lexical error.
+fn error_range(err: &ParseError, len: usize) -> Range<usize> {
+    let single_char = |pos: usize| pos..(pos + 1).min(len);
+
+    match err {
+        ParseError::Lexical(
+            LexicalError::Generic(range) | LexicalError::CStyleComment(range),
+        ) => range.clone(),
+        ParseError::Lexical(LexicalError::StringDelimiterMismatch {
+            closing_delimiter, ..
+        }) => closing_delimiter.clone(),
+        ParseError::Lexical(
+            LexicalError::UnmatchedCloseBrace(pos)
+            | LexicalError::InvalidEscapeSequence(pos)
+            | LexicalError::InvalidAsciiEscapeCode(pos),
+        ) => single_char(*pos),
+        // The lexer only ever returns lexical errors.
+        _ => 0..0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use codespan::Files;
+
+    fn tokens(source: &str) -> Vec<(TokenKind, &str)> {
+        let src_id = Files::new().add("<test>", source);
+
+        tokenize(src_id, source)
+            .into_iter()
+            .map(|(kind, span)| (kind, &source[span.start.to_usize()..span.end.to_usize()]))
+            .collect()
+    }
+
+    #[test]
+    fn strings_and_numbers() {
+        use TokenKind::*;
+
+        assert_eq!(
+            tokens("let x = 0x1F in \"a %{x + 1.5} b\" # done"),
+            [
+                (Keyword, "let"),
+                (Identifier, "x"),
+                (Punctuation, "="),
+                (Number, "0x1F"),
+                (Keyword, "in"),
+                (String, "\""),
+                (String, "a "),
+                (Interpolation, "%{"),
+                (Identifier, "x"),
+                (Operator, "+"),
+                (Number, "1.5"),
+                (Interpolation, "}"),
+                (String, " b"),
+                (String, "\""),
+                (Comment, "# done"),
+            ]
+        );
+    }
+
+    #[test]
+    fn invalid_characters() {
+        use TokenKind::*;
+
+        assert_eq!(
+            tokens("{ a = 1 ~ 2 } }"),
+            [
+                (Punctuation, "{"),
+                (Identifier, "a"),
+                (Punctuation, "="),
+                (Number, "1"),
+                (Error, "~"),
+                (Number, "2"),
+                (Punctuation, "}"),
+                (Error, "}"),
+            ]
+        );
+    }
+}
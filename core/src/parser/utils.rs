@@ -11,7 +11,7 @@ use super::error::ParseError;
 use crate::{
     combine::Combine,
     eval::{
-        merge::{merge_doc, split},
+        merge::{merge_doc, split, DocMergeMode},
         operation::RecPriority,
     },
     identifier::LocIdent,
@@ -118,11 +118,23 @@ impl FieldDef {
     ///
     /// # Preconditions
     /// - /!\ path must be **non-empty**, otherwise this function panics
-    pub fn elaborate(self) -> (FieldPathElem, Field) {
+    pub fn elaborate(mut self) -> (FieldPathElem, Field) {
         let last_ident = self.path.last().and_then(|elem| match elem {
             FieldPathElem::Ident(id) => Some(*id),
             FieldPathElem::Expr(_) => None,
         });
+        // The enclosing records are only recorded in the labels when they are all known
+        // statically.
+        let enclosing: Option<Vec<LocIdent>> = self.path[..self.path.len() - 1]
+            .iter()
+            .map(|elem| match elem {
+                FieldPathElem::Ident(id) => Some(*id),
+                FieldPathElem::Expr(_) => None,
+            })
+            .collect();
+        self.field = self
+            .field
+            .with_field_path_prefix(&enclosing.unwrap_or_default());
 
         let mut it = self.path.into_iter();
         let fst = it.next().unwrap();
@@ -296,7 +308,7 @@ impl Combine for FieldMetadata {
         };
 
         FieldMetadata {
-            doc: merge_doc(left.doc, right.doc),
+            doc: merge_doc(left.doc, right.doc, DocMergeMode::First),
             annotation: Combine::combine(left.annotation, right.annotation),
             opt: left.opt || right.opt,
             // The resulting field will be suppressed from serialization if either of the fields to be merged is.
@@ -547,6 +559,7 @@ fn merge_fields(id_span: RawSpan, field1: Field, field2: Field) -> Field {
                 BinaryOp::Merge(MergeLabel {
                     span: id_span,
                     kind: MergeKind::PiecewiseDef,
+                    field_path: Vec::new(),
                 }),
                 RichTerm::new(t1, pos1),
                 RichTerm::new(t2, pos2),
@@ -578,7 +591,13 @@ fn merge_fields(id_span: RawSpan, field1: Field, field2: Field) -> Field {
         // [`FieldMetadata::combine`] produces subtly different behaviour from
         // the runtime merging code, which is what we need to replicate here
         metadata: FieldMetadata {
-            doc: merge_doc(field1.metadata.doc, field2.metadata.doc),
+            // The parser doesn't know the mode set on the virtual machine, so piecewise
+            // definitions keep the first documentation, as they always did (see [DocMergeMode]).
+            doc: merge_doc(
+                field1.metadata.doc,
+                field2.metadata.doc,
+                DocMergeMode::First,
+            ),
             annotation: Combine::combine(field1.metadata.annotation, field2.metadata.annotation),
             opt: field1.metadata.opt && field2.metadata.opt,
             not_exported: field1.metadata.not_exported || field2.metadata.not_exported,
@@ -616,6 +635,7 @@ pub fn mk_merge_label(src_id: FileId, l: usize, r: usize) -> MergeLabel {
     MergeLabel {
         span: mk_span(src_id, l, r),
         kind: Default::default(),
+        field_path: Vec::new(),
     }
 }
 
@@ -1,7 +1,8 @@
 use std::fmt;
 
 use crate::identifier::LocIdent;
-use crate::parser::lexer::KEYWORDS;
+use crate::parser::{comments::CommentMap, lexer::KEYWORDS};
+use crate::position::TermPos;
 use crate::term::{
     pattern::*,
     record::{Field, FieldMetadata, RecordData},
@@ -162,13 +163,108 @@ where
     doc.render_fmt(80, f)
 }
 
+/// Pretty print a term, re-emitting the comments attached to its record fields. See
+/// [crate::parser::comments] for how comments are attached to the AST.
+pub fn pretty_with_comments(rt: &RichTerm, comments: &CommentMap, width: usize) -> String {
+    let allocator = CommentAllocator { comments };
+    let doc: DocBuilder<_, ()> = rt.pretty(&allocator);
+
+    let mut out = String::new();
+    // Rendering to a string can't fail.
+    doc.render_fmt(width, &mut out).unwrap();
+    out
+}
+
 impl<'a, A: Clone + 'a> NickelAllocatorExt<'a, A> for pretty::BoxAllocator {}
 
+/// An allocator which behaves like [pretty::BoxAllocator], but also has access to the comments of
+/// the term being printed.
+struct CommentAllocator<'c> {
+    comments: &'c CommentMap,
+}
+
+impl<'a, 'c, A: 'a> DocAllocator<'a, A> for CommentAllocator<'c> {
+    type Doc = pretty::BoxDoc<'a, A>;
+
+    fn alloc(&'a self, doc: pretty::Doc<'a, Self::Doc, A>) -> Self::Doc {
+        pretty::BoxDoc::new(doc)
+    }
+
+    fn alloc_column_fn(
+        &'a self,
+        f: impl Fn(usize) -> Self::Doc + 'a,
+    ) -> <Self::Doc as pretty::DocPtr<'a, A>>::ColumnFn {
+        Box::new(f)
+    }
+
+    fn alloc_width_fn(
+        &'a self,
+        f: impl Fn(isize) -> Self::Doc + 'a,
+    ) -> <Self::Doc as pretty::DocPtr<'a, A>>::WidthFn {
+        Box::new(f)
+    }
+}
+
+impl<'a, 'c, A: Clone + 'a> NickelAllocatorExt<'a, A> for CommentAllocator<'c> {
+    fn comments(&self) -> Option<&CommentMap> {
+        Some(self.comments)
+    }
+}
+
 trait NickelAllocatorExt<'a, A: 'a>: DocAllocator<'a, A> + Sized
 where
     Self::Doc: Clone,
     A: Clone,
 {
+    /// The comments attached to the term being printed, if they should be re-emitted.
+    fn comments(&self) -> Option<&CommentMap> {
+        None
+    }
+
+    /// The comments preceding the node at `pos`, each followed by a line break.
+    fn leading_comments(&'a self, pos: TermPos) -> DocBuilder<'a, Self, A> {
+        let comments = self
+            .comments()
+            .zip(pos.as_opt_ref())
+            .map(|(comments, span)| comments.leading(span))
+            .unwrap_or_default();
+
+        self.concat(
+            comments
+                .iter()
+                .map(|comment| self.text(comment.text.clone()).append(self.hardline())),
+        )
+    }
+
+    /// The comments following the node at `pos`, on the same line.
+    fn trailing_comments(&'a self, pos: TermPos) -> DocBuilder<'a, Self, A> {
+        let comments = self
+            .comments()
+            .zip(pos.as_opt_ref())
+            .map(|(comments, span)| comments.trailing(span))
+            .unwrap_or_default();
+
+        self.concat(
+            comments
+                .iter()
+                .map(|comment| self.text(" ").append(self.text(comment.text.clone()))),
+        )
+    }
+
+    /// Whether any comment is attached to a field, either to its name or to its value. Such a
+    /// field can't be printed on the same line as its neighbours.
+    fn field_has_comments(&'a self, id: &LocIdent, field: &Field) -> bool {
+        let Some(comments) = self.comments() else {
+            return false;
+        };
+
+        id.pos
+            .as_opt_ref()
+            .into_iter()
+            .chain(field.value.as_ref().and_then(|value| value.pos.as_opt_ref()))
+            .any(|span| comments.has_comments(span))
+    }
+
     /// Escape the special characters in a string, including the newline character, so that it can
     /// be enclosed by double quotes a be a valid Nickel string.
     fn escaped_string(&'a self, s: &str) -> DocBuilder<'a, Self, A> {
@@ -326,15 +422,19 @@ where
                 MergePriority::Neutral => self.nil(),
                 MergePriority::Numeral(p) =>
                     docs![self, self.line(), "| priority ", p.to_sci().to_string()],
+                MergePriority::Relative(p) =>
+                    docs![self, self.line(), "| priority +", p.to_sci().to_string()],
                 MergePriority::Top => docs![self, self.line(), "| force"],
             }
         ]
     }
 
     fn field(&'a self, id: &LocIdent, field: &Field) -> DocBuilder<'a, Self, A> {
-        self.text(ident_quoted(id))
-            .append(self.field_body(field))
-            .group()
+        self.leading_comments(id.pos).append(
+            self.text(ident_quoted(id))
+                .append(self.field_body(field))
+                .group(),
+        )
     }
 
     fn dyn_field(&'a self, id_expr: &RichTerm, field: &Field) -> DocBuilder<'a, Self, A> {
@@ -368,17 +468,22 @@ where
             } else {
                 self.nil()
             },
-            ","
+            ",",
+            self.trailing_comments(field.value.as_ref().map_or(TermPos::None, |value| value.pos))
         ]
         .nest(2)
     }
 
-    fn fields(&'a self, fields: &IndexMap<LocIdent, Field>) -> DocBuilder<'a, Self, A> {
+    fn fields(
+        &'a self,
+        fields: &IndexMap<LocIdent, Field>,
+        separator: DocBuilder<'a, Self, A>,
+    ) -> DocBuilder<'a, Self, A> {
         self.intersperse(
             sorted_map(fields)
                 .iter()
                 .map(|(id, field)| self.field(id, field)),
-            self.line(),
+            separator,
         )
     }
 
@@ -401,23 +506,35 @@ where
             return self.text("{}");
         }
 
+        // A comment extends to the end of the line, so a record with comments must be printed on
+        // several lines.
+        let line = if record_data
+            .fields
+            .iter()
+            .any(|(id, field)| self.field_has_comments(id, field))
+        {
+            self.hardline()
+        } else {
+            self.line()
+        };
+
         docs![
             self,
-            self.line(),
-            self.fields(&record_data.fields),
+            line.clone(),
+            self.fields(&record_data.fields, line.clone()),
             if !dyn_fields.is_empty() {
-                docs![self, self.line(), self.dyn_fields(dyn_fields)]
+                docs![self, line.clone(), self.dyn_fields(dyn_fields)]
             } else {
                 self.nil()
             },
             if record_data.attrs.open {
-                docs![self, self.line(), ".."]
+                docs![self, line.clone(), ".."]
             } else {
                 self.nil()
             }
         ]
         .nest(2)
-        .append(self.line())
+        .append(line)
         .braces()
         .group()
     }
@@ -1633,6 +1750,24 @@ mod tests {
         );
     }
 
+    #[test]
+    fn pretty_with_comments_round_trip() {
+        let source = indoc! {r#"
+            {
+              host = "localhost", # Only local connections.
+              # The port to listen on.
+              port = 80,
+            }"#
+        };
+        let id = Files::new().add("<test>", source);
+        let rt = TermParser::new()
+            .parse_strict(id, Lexer::new(source))
+            .unwrap();
+        let comments = crate::parser::comments::attach(id, source, &rt);
+
+        assert_eq!(pretty_with_comments(&rt, &comments, 80), source);
+    }
+
     #[test]
     fn pretty_let() {
         assert_long_short_term(
@@ -23,38 +23,43 @@
 use crate::{
     cache::*,
     error::{
-        report::{report, ColorOpt, ErrorFormat},
+        report::{report, report_as_str, ColorOpt, ErrorFormat},
         Error, EvalError, IOError, IntoDiagnostics, ParseError,
     },
-    eval::{cache::Cache as EvalCache, Closure, VirtualMachine},
+    eval::{
+        cache::Cache as EvalCache,
+        merge::{ArrayMergeMode, DocMergeMode, MergeMessages},
+        profile::Profile,
+        stats::EvalStats,
+        trace::TraceSink,
+        Closure, VirtualMachine,
+    },
     identifier::LocIdent,
     label::Label,
     metrics::increment,
+    position::RawSpan,
     term::{
-        make as mk_term, make::builder, record::Field, BinaryOp, MergePriority, RichTerm, Term,
+        make as mk_term,
+        make::builder,
+        record::{Field, FieldDeps, FieldMetadata, RecordData, RecordOpKind},
+        BinaryOp, MergePriority, RichTerm, Term,
     },
 };
 
 use codespan::FileId;
-use codespan_reporting::term::termcolor::Ansi;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use std::{
+    collections::HashMap,
     ffi::OsString,
-    fmt,
-    io::{self, Cursor, Read, Write},
+    io::{self, Read, Write},
     result::Result,
+    time::Duration,
 };
 
-/// A path of fields, that is a list, locating this field from the root of the configuration.
-#[derive(Clone, Default, PartialEq, Eq, Debug, Hash)]
-pub struct FieldPath(pub Vec<LocIdent>);
+pub use crate::identifier::FieldPath;
 
 impl FieldPath {
-    pub fn new() -> Self {
-        Self::default()
-    }
-
     /// Parse a string as a query path. A query path is a sequence of dot-separated identifiers.
     /// Identifiers can be enclosed by double quotes when they contain characters that aren't
     /// allowed inside bare identifiers. The accepted grammar is the same as a sequence of record
@@ -95,22 +100,6 @@ impl FieldPath {
     }
 }
 
-impl fmt::Display for FieldPath {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        use crate::pretty::ident_quoted;
-
-        write!(
-            f,
-            "{}",
-            self.0
-                .iter()
-                .map(ident_quoted)
-                .collect::<Vec<_>>()
-                .join(".")
-        )
-    }
-}
-
 /// Several CLI commands accept additional overrides specified directly on the command line. They
 /// are represented by this structure.
 #[derive(Clone)]
@@ -192,6 +181,10 @@ pub struct Program<EC: EvalCache> {
     pub field: FieldPath,
 }
 
+/// The name under which the standard input is registered in the file cache, and which is shown
+/// in error messages.
+pub const STDIN_SOURCE_NAME: &str = "<stdin>";
+
 /// The Possible Input Sources, anything that a Nickel program can be created from
 pub enum Input<T, S> {
     /// A filepath
@@ -203,7 +196,7 @@ pub enum Input<T, S> {
 impl<EC: EvalCache> Program<EC> {
     /// Create a program by reading it from the standard input.
     pub fn new_from_stdin(trace: impl Write + 'static) -> std::io::Result<Self> {
-        Program::new_from_source(io::stdin(), "<stdin>", trace)
+        Program::new_from_source(io::stdin(), STDIN_SOURCE_NAME, trace)
     }
 
     /// Contructor that abstracts over the Input type (file, string, etc.). Used by
@@ -367,6 +360,125 @@ impl<EC: EvalCache> Program<EC> {
         self.vm.import_resolver_mut().add_import_paths(paths);
     }
 
+    /// Restrict imports to files located inside the `root` directory, which must exist. Imports
+    /// escaping this directory, for example through `..` components or symbolic links, fail with
+    /// [crate::error::ImportError::OutsideSandbox]. The main input of the program isn't affected.
+    pub fn set_sandbox_root(&mut self, root: impl AsRef<Path>) -> io::Result<()> {
+        self.vm.import_resolver_mut().set_sandbox_root(root)
+    }
+
+    /// Enable the on-disk cache of parsed terms, stored in `dir`. The program and its imports are
+    /// then looked up in this cache before being parsed. See [crate::ast_cache].
+    pub fn set_ast_cache(&mut self, dir: impl Into<PathBuf>) {
+        self.vm
+            .import_resolver_mut()
+            .set_ast_cache(crate::ast_cache::AstCache::new(dir));
+    }
+
+    /// Override the configuration of remote imports. See [crate::remote_import].
+    #[cfg(feature = "remote-imports")]
+    pub fn set_remote_imports(&mut self, remote_imports: crate::remote_import::RemoteImports) {
+        self.vm
+            .import_resolver_mut()
+            .set_remote_imports(remote_imports)
+    }
+
+    /// Set the maximum wall-clock time that the evaluation of the program may take. Once the limit
+    /// is exceeded, evaluation is aborted with [EvalError::TimeLimitExceeded]. The clock starts
+    /// after the program has been typechecked, right before evaluation. `None` removes the limit.
+    pub fn set_max_eval_time(&mut self, limit: Option<Duration>) {
+        self.vm.set_time_limit(limit);
+    }
+
+    /// Set the provider of the explanations attached to merge errors. See
+    /// [VirtualMachine::set_merge_messages].
+    pub fn set_merge_messages(&mut self, messages: impl MergeMessages + 'static) {
+        self.vm.set_merge_messages(messages);
+    }
+
+    /// Set how arrays are merged during evaluation. See [VirtualMachine::set_array_merge_mode].
+    pub fn set_array_merge_mode(&mut self, mode: ArrayMergeMode) {
+        self.vm.set_array_merge_mode(mode);
+    }
+
+    /// Set how the documentation of merged fields is combined during evaluation. See
+    /// [VirtualMachine::set_doc_merge_mode].
+    pub fn set_doc_merge_mode(&mut self, mode: DocMergeMode) {
+        self.vm.set_doc_merge_mode(mode);
+    }
+
+    /// Set the seed of the pseudo-random number generator used during evaluation. See
+    /// [VirtualMachine::set_seed].
+    pub fn set_seed(&mut self, seed: Option<u64>) {
+        self.vm.set_seed(seed);
+    }
+
+    /// Enable or disable the recording of the fields checked by record contracts during
+    /// evaluation. See [VirtualMachine::set_contract_coverage].
+    pub fn set_contract_coverage(&mut self, enabled: bool) {
+        self.vm.set_contract_coverage(enabled);
+    }
+
+    /// The paths of the fields of record contracts which have been checked against a value since
+    /// contract coverage has been enabled, in the order in which they were first checked. Paths
+    /// are relative to the outermost record contract. Empty if contract coverage is disabled.
+    pub fn contract_coverage(&self) -> Vec<FieldPath> {
+        self.vm
+            .contract_coverage()
+            .map(|coverage| coverage.checked_fields().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// Enable or disable the recording of the definitions merged to compute each field during
+    /// evaluation. See [VirtualMachine::set_merge_provenance].
+    pub fn set_merge_provenance(&mut self, enabled: bool) {
+        self.vm.set_merge_provenance(enabled);
+    }
+
+    /// The spans of the definitions of each field computed by merging since merge provenance has
+    /// been enabled, keyed by the path of the field. Empty if merge provenance is disabled.
+    pub fn merge_provenance(&self) -> HashMap<FieldPath, Vec<RawSpan>> {
+        self.vm
+            .merge_provenance()
+            .map(|provenance| {
+                provenance
+                    .sources()
+                    .map(|(path, spans)| (path.clone(), spans.to_vec()))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Set the receiver of the messages traced with `std.trace`, instead of the stream given when
+    /// creating the program. See [VirtualMachine::set_trace_sink].
+    pub fn set_trace_sink(&mut self, sink: impl TraceSink + 'static) {
+        self.vm.set_trace_sink(sink);
+    }
+
+    /// Enable or disable the collection of evaluation statistics, such as the number of merges
+    /// performed. See [VirtualMachine::set_stats].
+    pub fn set_stats(&mut self, enabled: bool) {
+        self.vm.set_stats(enabled);
+    }
+
+    /// The statistics collected during evaluation since they have been enabled, or `None` if
+    /// they are disabled.
+    pub fn stats(&self) -> Option<&EvalStats> {
+        self.vm.stats()
+    }
+
+    /// Enable profiling, timing the forcing of each field of the result of the program up to
+    /// `depth` levels of nesting, or disable it if `depth` is `None`. Profiling only applies to
+    /// full evaluations. See [VirtualMachine::set_profile].
+    pub fn set_profile(&mut self, depth: Option<usize>) {
+        self.vm.set_profile(depth);
+    }
+
+    /// The timings recorded since profiling has been enabled, or `None` if it's disabled.
+    pub fn profile(&self) -> Option<&Profile> {
+        self.vm.profile()
+    }
+
     /// Only parse the program, don't typecheck or evaluate. returns the [`RichTerm`] AST
     pub fn parse(&mut self) -> Result<RichTerm, Error> {
         self.vm
@@ -433,6 +545,8 @@ impl<EC: EvalCache> Program<EC> {
 
         let prepared = Closure::atomic_closure(prepared_body);
 
+        self.vm.start_time_limit();
+
         let result = if for_query {
             prepared
         } else {
@@ -494,7 +608,120 @@ impl<EC: EvalCache> Program<EC> {
         Ok(self.vm.query_closure(prepared, &self.field)?)
     }
 
+    /// Return the metadata of the field at `path`, such as its documentation, its contracts,
+    /// whether it's optional or whether it has a default value, given by its merge priority.
+    /// `path` is parsed as described in [FieldPath::parse].
+    ///
+    /// As opposed to [Self::query], the value of the field is never forced. The records along the
+    /// path are evaluated to weak head normal form, to find the next field. If a field is missing
+    /// along the way, the error is [EvalError::FieldMissing], naming the first missing segment.
+    pub fn query_metadata(&mut self, path: &str) -> Result<FieldMetadata, Error> {
+        let path = self.parse_field_path(path.to_owned())?;
+        let prepared = self.prepare_query()?;
+
+        self.vm.reset();
+        let (field, _) = self.vm.extract_field_closure(prepared, &path)?;
+
+        Ok(field.metadata)
+    }
+
+    /// Generate a JSON Schema from the contracts annotating the root of the program, or `None` if
+    /// the root isn't annotated. See [crate::json_schema].
+    pub fn root_contract_schema(&mut self) -> Result<Option<serde_json::Value>, Error> {
+        let prepared = self.prepare_query()?;
+        let cache = self.vm.import_resolver();
+
+        Ok(crate::json_schema::root_schema(&prepared.body, |file_id| {
+            cache.get_owned(file_id)
+        }))
+    }
+
+    /// Return the fields that the field at `path` depends on, that is the other fields of the
+    /// same record that are referenced directly from its definition through recursive references.
+    /// Dependencies aren't transitive: if `a` references `b` and `b` references `c`, only `b` is
+    /// a dependency of `a`. Each dependency is returned as a full path from the root of the
+    /// configuration, sorted by name.
+    ///
+    /// Dependencies are intra-record: only references to sibling fields (fields of the record
+    /// directly enclosing the target field) are reported. References to fields of enclosing
+    /// records, to let-bound variables or to imports aren't dependencies in this sense.
+    ///
+    /// The dependencies are the ones computed by the free variables transformation and used by the
+    /// evaluator to revert recursive records. When the path goes through record literals only,
+    /// they are read directly from the program without evaluating anything. Otherwise, the
+    /// records along the path are evaluated to weak head normal form, as for [Self::query], but
+    /// the target field itself is never forced. When the dependencies of a field are unknown (see
+    /// [FieldDeps::Unknown]), all the other fields of the enclosing record are conservatively
+    /// returned.
+    pub fn field_dependencies(&mut self, path: &FieldPath) -> Result<Vec<FieldPath>, Error> {
+        let Some((last, parent)) = path.0.split_last() else {
+            // The root of the configuration isn't part of any record.
+            return Ok(Vec::new());
+        };
+
+        let prepared = self.prepare_query()?;
+
+        let deps = if let Some(deps) = static_field_deps(&prepared.body, &path.0) {
+            deps
+        } else {
+            self.vm.reset();
+
+            let parent_path = FieldPath(parent.to_vec());
+            let parent_value = if parent.is_empty() {
+                self.vm.eval_closure(prepared)?
+            } else {
+                self.vm.extract_field_value_closure(prepared, &parent_path)?
+            };
+
+            match parent_value.body.as_ref() {
+                Term::Record(data) => {
+                    let Some(field) = data.fields.get(last) else {
+                        return Err(EvalError::FieldMissing {
+                            id: *last,
+                            field_names: data.field_names(RecordOpKind::IgnoreEmptyOpt),
+                            operator: String::from("field_dependencies"),
+                            pos_record: parent_value.body.pos,
+                            pos_op: last.pos,
+                        }
+                        .into());
+                    };
+
+                    // Once a recursive record has been evaluated, its fields are closurized and
+                    // their dependencies are stored in the evaluation cache.
+                    let field_deps = match field.value.as_ref().map(|v| v.as_ref()) {
+                        Some(Term::Closure(idx)) => {
+                            self.vm.cache.deps(idx).unwrap_or_else(FieldDeps::empty)
+                        }
+                        _ => FieldDeps::empty(),
+                    };
+
+                    sibling_deps(data, last, field_deps)
+                }
+                _ => {
+                    return Err(EvalError::QueryNonRecord {
+                        pos: parent_value.body.pos,
+                        id: *last,
+                        value: parent_value.body.clone(),
+                    }
+                    .into())
+                }
+            }
+        };
+
+        Ok(deps
+            .into_iter()
+            .map(|dep| {
+                let mut dep_path = parent.to_vec();
+                dep_path.push(dep);
+                FieldPath(dep_path)
+            })
+            .collect())
+    }
+
     /// Load, parse, and typecheck the program and the standard library, if not already done.
+    ///
+    /// Nothing is evaluated: this returns the first type error, if any, without forcing any
+    /// value of the program.
     pub fn typecheck(&mut self) -> Result<(), Error> {
         self.vm
             .import_resolver_mut()
@@ -527,26 +754,13 @@ impl<EC: EvalCache> Program<EC> {
         report(self.vm.import_resolver_mut(), error, format, self.color_opt)
     }
 
-    /// Build an error report as a string and return it.
+    /// Build an error report as a string and return it. See [`report_as_str`] for how the color
+    /// option of the program is taken into account.
     pub fn report_as_str<E>(&mut self, error: E) -> String
     where
         E: IntoDiagnostics<FileId>,
     {
-        let cache = self.vm.import_resolver_mut();
-        let stdlib_ids = cache.get_all_stdlib_modules_file_id();
-        let diagnostics = error.into_diagnostics(cache.files_mut(), stdlib_ids.as_ref());
-        let mut buffer = Ansi::new(Cursor::new(Vec::new()));
-        let config = codespan_reporting::term::Config::default();
-        // write to `buffer`
-        diagnostics
-            .iter()
-            .try_for_each(|d| {
-                codespan_reporting::term::emit(&mut buffer, &config, cache.files_mut(), d)
-            })
-            // safe because writing to a cursor in memory
-            .unwrap();
-        // unwrap(): emit() should only print valid utf8 to the the buffer
-        String::from_utf8(buffer.into_inner().into_inner()).unwrap()
+        report_as_str(self.vm.import_resolver_mut(), error, self.color_opt)
     }
 
     /// Evaluate a program into a record spine, a form suitable for extracting the general
@@ -722,6 +936,61 @@ impl<EC: EvalCache> Program<EC> {
     }
 }
 
+/// Try to find the dependencies of the field at `path` without evaluating anything, by walking
+/// down record literals (possibly annotated) and reading the dependencies computed by the free
+/// variables transformation. Return `None` whenever the path goes through anything else, in
+/// which case the caller has to resort to evaluation.
+fn static_field_deps(term: &RichTerm, path: &[LocIdent]) -> Option<Vec<LocIdent>> {
+    let (last, parent) = path.split_last()?;
+    let mut current = term;
+
+    for id in parent {
+        current = strip_annotations(current);
+
+        match current.as_ref() {
+            Term::RecRecord(data, dyn_fields, _) if dyn_fields.is_empty() => {
+                current = data.fields.get(id)?.value.as_ref()?;
+            }
+            _ => return None,
+        }
+    }
+
+    match strip_annotations(current).as_ref() {
+        Term::RecRecord(data, dyn_fields, Some(deps)) if dyn_fields.is_empty() => {
+            data.fields.get(last)?;
+            let field_deps = deps.stat_fields.get(&last.ident())?.clone();
+            Some(sibling_deps(data, last, field_deps))
+        }
+        _ => None,
+    }
+}
+
+fn strip_annotations(mut term: &RichTerm) -> &RichTerm {
+    while let Term::Annotated(_, inner) = term.as_ref() {
+        term = inner;
+    }
+
+    term
+}
+
+/// Turn the dependencies of the field `id` of `data` into the list of the corresponding sibling
+/// fields, sorted by name. Unknown dependencies are approximated by all the other fields.
+fn sibling_deps(data: &RecordData, id: &LocIdent, deps: FieldDeps) -> Vec<LocIdent> {
+    let mut result: Vec<LocIdent> = data
+        .fields
+        .keys()
+        .filter(|sibling| *sibling != id)
+        .filter(|sibling| match &deps {
+            FieldDeps::Known(known) => known.contains(&sibling.ident()),
+            FieldDeps::Unknown => true,
+        })
+        .copied()
+        .collect();
+
+    result.sort_by(|id1, id2| id1.label().cmp(id2.label()));
+    result
+}
+
 #[cfg(feature = "doc")]
 mod doc {
     use crate::error::{Error, ExportErrorData, IOError};
@@ -1063,4 +1332,360 @@ mod tests {
             Err(Error::ParseErrors(_))
         );
     }
+
+    #[test]
+    fn typecheck_doesnt_evaluate() {
+        let program = |body: &str| format!("let rec loop = fun x => loop x in {body}");
+
+        assert_matches!(typecheck(&program("loop 0")), Ok(()));
+        assert_matches!(
+            typecheck(&program("{ stuck = loop 0, wrong = (1 + \"a\" : Number) }")),
+            Err(Error::TypecheckError(_))
+        );
+    }
+
+    #[test]
+    fn contract_coverage() {
+        let src = r#"
+            let Schema = {
+              name | String,
+              port | Number | default = 80,
+              tls | optional | { cert | String, key | String },
+              log | { level | String, file | optional | String },
+            }
+            in
+            { name = "web", log = { level = "info" } } | Schema
+        "#;
+
+        let mut p: Program<CacheImpl> =
+            Program::new_from_source(Cursor::new(src), "<test>", std::io::sink()).unwrap();
+        p.set_contract_coverage(true);
+        p.eval_full().unwrap();
+
+        let mut checked: Vec<String> = p
+            .contract_coverage()
+            .iter()
+            .map(|path| path.to_string())
+            .collect();
+        checked.sort();
+
+        assert_eq!(checked, ["log", "log.level", "name"]);
+    }
+
+    #[test]
+    fn merge_provenance() {
+        let src = r#"
+            let base = { server = { host = "localhost", port = 80 } } in
+            let prod = { server = { port | force = 443, tls = true } } in
+            base & prod
+        "#;
+
+        let mut p: Program<CacheImpl> =
+            Program::new_from_source(Cursor::new(src), "<test>", std::io::sink()).unwrap();
+        p.set_merge_provenance(true);
+        p.eval_full().unwrap();
+
+        let provenance = p.merge_provenance();
+        let sources = |path: &str| -> Vec<&str> {
+            let path = FieldPath(path.split('.').map(LocIdent::from).collect());
+
+            provenance[&path]
+                .iter()
+                .map(|span| &src[span.start.to_usize()..span.end.to_usize()])
+                .collect()
+        };
+
+        assert_eq!(sources("server.port"), ["80", "443"]);
+        assert_eq!(sources("server.host"), ["\"localhost\""]);
+        assert_eq!(sources("server.tls"), ["true"]);
+        assert_eq!(sources("server").len(), 2);
+    }
+
+    #[test]
+    fn eval_stats() {
+        let src = "{ a | Number = 1, b = 2 } & { c = 3 }";
+
+        let mut p: Program<CacheImpl> =
+            Program::new_from_source(Cursor::new(src), "<test>", std::io::sink()).unwrap();
+        assert!(p.stats().is_none());
+
+        p.set_stats(true);
+        p.eval_full().unwrap();
+
+        let stats = p.stats().unwrap();
+        assert_eq!(stats.merges, 1);
+        assert_eq!(stats.contracts_applied, 1);
+        assert!(stats.cache_misses > 0);
+        assert_eq!(stats.cache_hits + stats.cache_misses, stats.cache_lookups);
+    }
+
+    #[test]
+    fn eval_profile() {
+        let src = "{ a = 1 + 1, b.c = \"x\", b.d = [1, 2], e | optional }";
+
+        let mut p: Program<CacheImpl> =
+            Program::new_from_source(Cursor::new(src), "<test>", std::io::sink()).unwrap();
+        assert!(p.profile().is_none());
+
+        p.set_profile(Some(1));
+        assert_eq!(
+            p.eval_full().unwrap().without_pos(),
+            eval_full("{ a = 2, b = { c = \"x\", d = [1, 2] } }")
+                .unwrap()
+                .without_pos()
+        );
+
+        let paths: Vec<_> = p
+            .profile()
+            .unwrap()
+            .timings()
+            .iter()
+            .map(|(path, _)| path.to_string())
+            .collect();
+        assert_eq!(paths, ["b", "a"]);
+
+        p.set_profile(Some(2));
+        p.eval_full().unwrap();
+
+        let paths: Vec<_> = p
+            .profile()
+            .unwrap()
+            .timings()
+            .iter()
+            .map(|(path, _)| path.to_string())
+            .collect();
+        assert_eq!(paths, ["b.d", "b.c", "b", "a"]);
+    }
+
+    #[test]
+    fn trace_sink() {
+        use std::{cell::RefCell, rc::Rc};
+
+        struct Collect(Rc<RefCell<Vec<String>>>);
+
+        impl TraceSink for Collect {
+            fn trace(&mut self, msg: &RichTerm) {
+                if let Term::Str(s) = msg.as_ref() {
+                    self.0.borrow_mut().push(s.to_string());
+                }
+            }
+        }
+
+        let src = r#"
+            let x = std.trace "first" 1 in
+            let y = std.trace "second" (x + 1) in
+            std.trace "third" (y + 1)
+        "#;
+
+        let traced = Rc::new(RefCell::new(Vec::new()));
+        let mut p: Program<CacheImpl> =
+            Program::new_from_source(Cursor::new(src), "<test>", std::io::sink()).unwrap();
+        p.set_trace_sink(Collect(traced.clone()));
+
+        assert_eq!(p.eval_full().unwrap().as_ref(), &Term::Num(3.into()));
+        assert_eq!(*traced.borrow(), ["third", "second", "first"]);
+    }
+
+    #[test]
+    fn query_metadata() {
+        let src = r#"
+            {
+              server = {
+                port
+                  | doc "The port to listen on"
+                  | Number
+                  | default
+                  = std.fail_with "port isn't forced",
+                host | String | optional,
+              },
+            }
+        "#;
+
+        let mut p: Program<CacheImpl> =
+            Program::new_from_source(Cursor::new(src), "<test>", std::io::sink()).unwrap();
+
+        let port = p.query_metadata("server.port").unwrap();
+        assert_eq!(port.doc.as_deref(), Some("The port to listen on"));
+        assert_eq!(port.annotation.contracts.len(), 1);
+        assert_matches!(port.priority, MergePriority::Bottom);
+        assert!(!port.opt);
+
+        let host = p.query_metadata("server.host").unwrap();
+        assert!(host.doc.is_none());
+        assert!(host.opt);
+
+        assert_matches!(
+            p.query_metadata("server.missing.port"),
+            Err(Error::EvalError(EvalError::FieldMissing { id, .. })) if id.label() == "missing"
+        );
+    }
+
+    fn eval_seeded(src: &str, seed: u64) -> String {
+        let mut p: Program<CacheImpl> =
+            Program::new_from_source(Cursor::new(src), "<test>", std::io::sink()).unwrap();
+        p.set_seed(Some(seed));
+        p.eval_full().unwrap().to_string()
+    }
+
+    #[test]
+    fn seeded_shuffle() {
+        let src = "std.array.shuffle (std.array.range 0 50)";
+
+        assert_eq!(eval_seeded(src, 42), eval_seeded(src, 42));
+        assert_ne!(eval_seeded(src, 42), eval_seeded(src, 43));
+    }
+
+    #[test]
+    fn shuffle_depends_on_evaluation_order() {
+        // The generator is shared by the whole evaluation: forcing another shuffle first changes
+        // the result of the same shuffle with the same seed.
+        let alone = eval_seeded("std.array.shuffle (std.array.range 0 50)", 42);
+        let after_another = eval_seeded(
+            "std.seq (std.array.shuffle [1, 2]) (std.array.shuffle (std.array.range 0 50))",
+            42,
+        );
+
+        assert_ne!(alone, after_another);
+    }
+
+    #[test]
+    fn typecheck_if_branches() {
+        use crate::error::TypecheckError;
+
+        assert_matches!(typecheck("(if true then 1 else 2) : Number"), Ok(()));
+        assert_matches!(
+            typecheck("(fun x => if true then x else x) : forall a. a -> a"),
+            Ok(())
+        );
+
+        let src = "(fun b => if b then 1 else \"x\") : Bool -> _";
+        let err = typecheck(src).unwrap_err();
+        let Error::TypecheckError(TypecheckError::IfBranchesMismatch { pos, .. }) = err else {
+            panic!("expected an if branches mismatch, got {err:?}");
+        };
+        let span = pos.unwrap();
+        assert_eq!(&src[span.start.to_usize()..span.end.to_usize()], "\"x\"");
+    }
+
+    #[test]
+    fn custom_extra_field_notes() {
+        use crate::identifier::LocIdent;
+
+        struct Terse;
+
+        impl MergeMessages for Terse {
+            fn extra_field_notes(&self, fields: &[LocIdent]) -> Vec<String> {
+                vec![format!("{} champ(s) en trop", fields.len())]
+            }
+        }
+
+        let src = Cursor::new("{ foo = 1, bar = 2, baz = 3 } | { foo | Number }");
+        let mut p: Program<CacheImpl> =
+            Program::new_from_source(src, "<test>", std::io::sink()).unwrap();
+        p.set_merge_messages(Terse);
+
+        assert_matches!(
+            p.eval_full(),
+            Err(Error::EvalError(EvalError::BlameError { label, .. }))
+                if label.diagnostics.iter().any(|diag| diag.notes == ["2 champ(s) en trop"])
+        );
+    }
+
+    #[test]
+    fn stdin_error_location() {
+        let source = Cursor::new("{\n  foo = 1,\n  bar = 1 + ,\n}\n");
+        let mut p: Program<CacheImpl> =
+            Program::new_from_source(source, STDIN_SOURCE_NAME, std::io::sink()).unwrap();
+        p.color_opt = clap::ColorChoice::Never.into();
+
+        let error = p.eval_full().unwrap_err();
+        assert_matches!(error, Error::ParseErrors(..));
+        assert!(p.report_as_str(error).contains("<stdin>:3:13"));
+    }
+
+    #[test]
+    fn blame_shows_contract_definition() {
+        let source = "let NonZero = std.contract.from_predicate (fun x => x != 0) in\n\
+            { value | NonZero = 0 }.value";
+        let mut p: Program<CacheImpl> =
+            Program::new_from_source(Cursor::new(source), "<test>", std::io::sink()).unwrap();
+        p.color_opt = clap::ColorChoice::Never.into();
+
+        let error = p.eval_full().unwrap_err();
+        let report = p.report_as_str(error);
+        assert!(report.contains("contract defined here"));
+        assert!(report.contains("fun x => x != 0"));
+    }
+
+    #[test]
+    fn merge_record_with_non_record() {
+        let source = "{ port = 80 } & 8080";
+        let mut p: Program<CacheImpl> =
+            Program::new_from_source(Cursor::new(source), "<test>", std::io::sink()).unwrap();
+        p.color_opt = clap::ColorChoice::Never.into();
+
+        let error = p.eval_full().unwrap_err();
+        assert_matches!(
+            error,
+            Error::EvalError(EvalError::MergeRecordWithNonRecord { .. })
+        );
+        assert!(p
+            .report_as_str(error)
+            .contains("cannot merge a record with a number"));
+    }
+
+    #[test]
+    fn blame_error_data_in_json_report() {
+        use crate::error::report::report_with;
+        use codespan_reporting::term::termcolor::NoColor;
+
+        let source = r#"
+            let Port = std.contract.from_validator (fun value =>
+              if std.is_number value && value <= 65535 then
+                'Ok
+              else
+                'Error { message = "invalid port", data = { expected = "Port", got = value } }
+            )
+            in
+            70000 | Port
+        "#;
+        let mut p: Program<CacheImpl> =
+            Program::new_from_source(Cursor::new(source), "<test>", std::io::sink()).unwrap();
+        let error = p.eval_full().unwrap_err();
+
+        let mut buffer = NoColor::new(Vec::new());
+        let cache = p.vm.import_resolver_mut();
+        let stdlib_ids = cache.get_all_stdlib_modules_file_id();
+        report_with(
+            &mut buffer,
+            cache.files_mut(),
+            stdlib_ids.as_ref(),
+            error,
+            ErrorFormat::Json,
+        );
+
+        let report: serde_json::Value = serde_json::from_slice(&buffer.into_inner()).unwrap();
+        assert_eq!(
+            report["data"],
+            serde_json::json!({ "expected": "Port", "got": 70000 })
+        );
+    }
+
+    #[test]
+    fn report_as_str_color() {
+        let report = |color: clap::ColorChoice| {
+            let mut p: Program<CacheImpl> =
+                Program::new_from_source(Cursor::new("1 + \"a\""), "<test>", std::io::sink())
+                    .unwrap();
+            p.color_opt = color.into();
+            let error = p.eval_full().unwrap_err();
+            p.report_as_str(error)
+        };
+
+        let plain = report(clap::ColorChoice::Never);
+        assert!(plain.contains("error"));
+        assert!(!plain.contains('\x1b'));
+
+        assert!(report(clap::ColorChoice::Always).contains('\x1b'));
+    }
 }
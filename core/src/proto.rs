@@ -0,0 +1,407 @@
+//! Generation of [Protocol Buffers](https://protobuf.dev) schemas from Nickel record contracts.
+//!
+//! [contract_to_proto] turns a record contract, that is a record whose fields are annotated with
+//! types or contracts, into a `proto3` message definition. Record types (such as `{ foo : String
+//! }`) are accepted as well. Each field is mapped according to its annotations:
+//!
+//! | Nickel                                                     | Protobuf                     |
+//! |------------------------------------------------------------|------------------------------|
+//! | `String`                                                   | `string`                     |
+//! | `Bool`                                                     | `bool`                       |
+//! | `Number`                                                   | `double` or `int64`          |
+//! | `std.number.Integer`, `std.number.Nat`, `std.number.PosNat` | `int64`                      |
+//! | `Array T`                                                  | `repeated T`                 |
+//! | `{ _ : T }`, `{ _ \| T }`                                  | `map<string, T>`             |
+//! | record contract or record type                             | nested message               |
+//!
+//! Nickel has only one number type, so the mapping of `Number` is a heuristic: a `Number` field
+//! is mapped to `int64` if it's also annotated with one of the integer contracts of the standard
+//! library or if its default value is an integer, and to `double` otherwise. A field without any
+//! annotation is mapped according to its default value, if it's a literal.
+//!
+//! Optional fields are marked `optional`. Field numbers are assigned in order of definition,
+//! starting from 1. Nested messages are named after the corresponding field, in Pascal case.
+use std::fmt::{self, Write};
+
+use malachite::num::conversion::traits::IsInteger;
+
+use crate::{
+    identifier::LocIdent,
+    position::TermPos,
+    term::{record::Field, RichTerm, Term, UnaryOp},
+    typ::{RecordRows, RecordRowsIteratorItem, Type, TypeF},
+};
+
+/// The name of the top-level message generated by [contract_to_proto].
+pub const ROOT_MESSAGE_NAME: &str = "Root";
+
+/// An error occurring while generating a schema from a contract.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SchemaError {
+    /// The contract isn't a record contract or a record type.
+    NotARecord(TermPos),
+    /// The name of a field isn't a valid identifier in the target schema language.
+    InvalidFieldName { field: String, pos: TermPos },
+    /// A field doesn't have any annotation (or default value) from which to derive its type.
+    MissingType { field: String, pos: TermPos },
+    /// A field is annotated with a type or a contract which can't be mapped to the target schema
+    /// language.
+    UnsupportedType {
+        field: String,
+        typ: String,
+        pos: TermPos,
+    },
+}
+
+impl fmt::Display for SchemaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SchemaError::NotARecord(_) => {
+                write!(f, "expected a record contract or a record type")
+            }
+            SchemaError::InvalidFieldName { field, .. } => {
+                write!(f, "`{field}` isn't a valid field name")
+            }
+            SchemaError::MissingType { field, .. } => {
+                write!(f, "can't determine the type of field `{field}`")
+            }
+            SchemaError::UnsupportedType { field, typ, .. } => {
+                write!(f, "the type `{typ}` of field `{field}` isn't supported")
+            }
+        }
+    }
+}
+
+/// Generate a protobuf schema with a single top-level message, named [ROOT_MESSAGE_NAME], from
+/// a record contract. See the [module documentation][self] for the type mapping.
+pub fn contract_to_proto(contract: &RichTerm) -> Result<String, SchemaError> {
+    let message = Message::from_term(ROOT_MESSAGE_NAME.to_owned(), contract, &[])?;
+
+    let mut out = String::from("syntax = \"proto3\";\n\n");
+    message.render(&mut out, 0);
+
+    Ok(out)
+}
+
+struct Message {
+    name: String,
+    fields: Vec<MessageField>,
+    nested: Vec<Message>,
+}
+
+struct MessageField {
+    label: Option<&'static str>,
+    typ: String,
+    name: String,
+}
+
+/// The integer contracts of the standard library, which make a `Number` field an `int64`.
+const INTEGER_CONTRACTS: &[&str] = &["Integer", "Nat", "PosNat"];
+
+fn is_integer_contract(ty: &Type) -> bool {
+    match &ty.typ {
+        TypeF::Flat(rt) => matches!(
+            rt.as_ref(),
+            Term::Op1(UnaryOp::RecordAccess(id), _) if INTEGER_CONTRACTS.contains(&id.label())
+        ),
+        _ => false,
+    }
+}
+
+fn is_valid_name(name: &str) -> bool {
+    let mut chars = name.chars();
+
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+fn pascal_case(name: &str) -> String {
+    name.split(['_', '-'])
+        .flat_map(|part| {
+            let mut chars = part.chars();
+            chars
+                .next()
+                .map(|first| first.to_ascii_uppercase())
+                .into_iter()
+                .chain(chars)
+        })
+        .collect()
+}
+
+fn field_path(path: &[LocIdent], id: LocIdent) -> Vec<LocIdent> {
+    let mut path = path.to_vec();
+    path.push(id);
+    path
+}
+
+fn path_to_string(path: &[LocIdent]) -> String {
+    path.iter()
+        .map(LocIdent::label)
+        .collect::<Vec<_>>()
+        .join(".")
+}
+
+impl Message {
+    fn new(name: String) -> Self {
+        Message {
+            name,
+            fields: Vec::new(),
+            nested: Vec::new(),
+        }
+    }
+
+    fn from_term(name: String, rt: &RichTerm, path: &[LocIdent]) -> Result<Self, SchemaError> {
+        match rt.as_ref() {
+            Term::Record(data) | Term::RecRecord(data, ..) => {
+                let mut message = Message::new(name);
+
+                for (id, field) in data.fields.iter() {
+                    message.push_field(*id, field, path)?;
+                }
+
+                Ok(message)
+            }
+            Term::Annotated(_, inner) => Message::from_term(name, inner, path),
+            Term::Type(Type {
+                typ: TypeF::Record(rows),
+                ..
+            }) => Message::from_rows(name, rows, path),
+            _ => Err(SchemaError::NotARecord(rt.pos)),
+        }
+    }
+
+    fn from_rows(name: String, rows: &RecordRows, path: &[LocIdent]) -> Result<Self, SchemaError> {
+        let mut message = Message::new(name);
+
+        for item in rows.iter() {
+            if let RecordRowsIteratorItem::Row(row) = item {
+                let path = field_path(path, row.id);
+                let (label, typ) = message.map_type(row.typ, false, &path)?;
+                message.push(row.id, label, typ, &path)?;
+            }
+        }
+
+        Ok(message)
+    }
+
+    fn push(
+        &mut self,
+        id: LocIdent,
+        label: Option<&'static str>,
+        typ: String,
+        path: &[LocIdent],
+    ) -> Result<(), SchemaError> {
+        if !is_valid_name(id.label()) {
+            return Err(SchemaError::InvalidFieldName {
+                field: path_to_string(path),
+                pos: id.pos,
+            });
+        }
+
+        self.fields.push(MessageField {
+            label,
+            typ,
+            name: id.label().to_owned(),
+        });
+
+        Ok(())
+    }
+
+    fn push_field(
+        &mut self,
+        id: LocIdent,
+        field: &Field,
+        path: &[LocIdent],
+    ) -> Result<(), SchemaError> {
+        let path = field_path(path, id);
+        let annotation = &field.metadata.annotation;
+        let annots: Vec<&Type> = annotation
+            .typ
+            .iter()
+            .chain(annotation.contracts.iter())
+            .map(|labeled| &labeled.typ)
+            .collect();
+
+        let default_is_integer = matches!(
+            field.value.as_ref().map(AsRef::as_ref),
+            Some(Term::Num(n)) if n.is_integer()
+        );
+        let int_hint = default_is_integer || annots.iter().any(|ty| is_integer_contract(ty));
+
+        let (label, typ) = match annots.iter().find(|ty| !is_integer_contract(ty)) {
+            Some(ty) => self.map_type(ty, int_hint, &path)?,
+            None if int_hint => (None, String::from("int64")),
+            None => match field.value.as_ref().map(AsRef::as_ref) {
+                Some(Term::Str(_)) => (None, String::from("string")),
+                Some(Term::Bool(_)) => (None, String::from("bool")),
+                Some(Term::Num(_)) => (None, String::from("double")),
+                _ => {
+                    return Err(SchemaError::MissingType {
+                        field: path_to_string(&path),
+                        pos: id.pos,
+                    })
+                }
+            },
+        };
+
+        let label = if field.metadata.opt && label.is_none() {
+            Some("optional")
+        } else {
+            label
+        };
+
+        self.push(id, label, typ, &path)
+    }
+
+    /// Map a Nickel type to a protobuf type, together with an optional label (`repeated`). Record
+    /// types and record contracts are added as nested messages of `self`.
+    fn map_type(
+        &mut self,
+        ty: &Type,
+        int_hint: bool,
+        path: &[LocIdent],
+    ) -> Result<(Option<&'static str>, String), SchemaError> {
+        let unsupported = || SchemaError::UnsupportedType {
+            field: path_to_string(path),
+            typ: ty.to_string(),
+            pos: ty.pos,
+        };
+
+        let nested_name = || pascal_case(path.last().map(LocIdent::label).unwrap_or_default());
+
+        let typ = match &ty.typ {
+            TypeF::Number if int_hint => String::from("int64"),
+            TypeF::Number => String::from("double"),
+            TypeF::String => String::from("string"),
+            TypeF::Bool => String::from("bool"),
+            _ if is_integer_contract(ty) => String::from("int64"),
+            TypeF::Array(elt) => {
+                return match self.map_type(elt, int_hint, path)? {
+                    (None, typ) if !typ.starts_with("map<") => Ok((Some("repeated"), typ)),
+                    _ => Err(unsupported()),
+                };
+            }
+            TypeF::Dict { type_fields, .. } => match self.map_type(type_fields, int_hint, path)? {
+                (None, typ) if !typ.starts_with("map<") => format!("map<string, {typ}>"),
+                _ => return Err(unsupported()),
+            },
+            TypeF::Record(rows) => {
+                let message = Message::from_rows(nested_name(), rows, path)?;
+                self.add_nested(message)
+            }
+            TypeF::Flat(rt) => match rt.as_ref() {
+                Term::Record(_) | Term::RecRecord(..) | Term::Annotated(..) => {
+                    let message = Message::from_term(nested_name(), rt, path)?;
+                    self.add_nested(message)
+                }
+                Term::Type(ty) => return self.map_type(ty, int_hint, path),
+                _ => return Err(unsupported()),
+            },
+            _ => return Err(unsupported()),
+        };
+
+        Ok((None, typ))
+    }
+
+    fn add_nested(&mut self, message: Message) -> String {
+        let name = message.name.clone();
+        self.nested.push(message);
+        name
+    }
+
+    fn render(&self, out: &mut String, indent: usize) {
+        let pad = " ".repeat(indent);
+
+        // Writing to a string can't fail.
+        let _ = writeln!(out, "{pad}message {} {{", self.name);
+
+        for (number, field) in self.fields.iter().enumerate() {
+            let label = field.label.map(|l| format!("{l} ")).unwrap_or_default();
+            let _ = writeln!(
+                out,
+                "{pad}  {label}{} {} = {};",
+                field.typ,
+                field.name,
+                number + 1
+            );
+        }
+
+        for nested in &self.nested {
+            out.push('\n');
+            nested.render(out, indent + 2);
+        }
+
+        let _ = writeln!(out, "{pad}}}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{grammar::TermParser, lexer::Lexer, ErrorTolerantParser};
+    use codespan::Files;
+
+    fn parse(s: &str) -> RichTerm {
+        let id = Files::new().add("<test>", s);
+
+        TermParser::new().parse_strict(id, Lexer::new(s)).unwrap()
+    }
+
+    #[test]
+    fn nested_contract() {
+        let contract = parse(
+            r#"{
+                name | String,
+                port | std.number.Nat,
+                ratio | Number,
+                replicas | Number | default = 3,
+                tags | Array String,
+                labels | { _ | String },
+                debug | Bool | optional,
+                server_config | {
+                    host | String,
+                    aliases | Array String,
+                },
+            }"#,
+        );
+
+        assert_eq!(
+            contract_to_proto(&contract).unwrap(),
+            "syntax = \"proto3\";\n\
+            \n\
+            message Root {\n  \
+              string name = 1;\n  \
+              int64 port = 2;\n  \
+              double ratio = 3;\n  \
+              int64 replicas = 4;\n  \
+              repeated string tags = 5;\n  \
+              map<string, string> labels = 6;\n  \
+              optional bool debug = 7;\n  \
+              ServerConfig server_config = 8;\n\
+            \n  \
+              message ServerConfig {\n    \
+                string host = 1;\n    \
+                repeated string aliases = 2;\n  \
+              }\n\
+            }\n"
+        );
+    }
+
+    #[test]
+    fn unsupported_types() {
+        assert_eq!(
+            contract_to_proto(&parse("1")),
+            Err(SchemaError::NotARecord(parse("1").pos))
+        );
+        assert!(matches!(
+            contract_to_proto(&parse("{ f | Number -> Number }")),
+            Err(SchemaError::UnsupportedType { field, .. }) if field == "f"
+        ));
+        assert!(matches!(
+            contract_to_proto(&parse("{ matrix | Array (Array Number) }")),
+            Err(SchemaError::UnsupportedType { field, .. }) if field == "matrix"
+        ));
+    }
+}
@@ -0,0 +1,339 @@
+//! Imports of remote files, such as `import "https://example.com/lib.ncl"`.
+//!
+//! This module is only available with the `remote-imports` feature. Remote files are downloaded
+//! over HTTP(S), stored in a local cache directory, and then imported as if they were local
+//! files. The format of a remote file is determined by the extension of its URL, as for local
+//! files. Relative imports inside a remote file are resolved against its URL.
+//!
+//! # Cache
+//!
+//! The cache directory is given by the `NICKEL_IMPORT_CACHE` environment variable if it's set, and
+//! is the `nickel/imports` subdirectory of the user's cache directory otherwise (for example
+//! `~/.cache/nickel/imports` on Linux). Each URL has its own entry in the cache, which is a
+//! directory named after the SHA-256 hash of the URL and containing:
+//!
+//! - the downloaded content, in a file named after the hash of its entity tag (the `ETag` header
+//!   sent by the server), or after the hash of the content if the server didn't send any;
+//! - a `meta.json` file recording the URL, the entity tag and the SHA-256 hash of the content.
+//!
+//! When a URL is imported again, the cached content is revalidated with the server through a
+//! conditional request (`If-None-Match`), and is only downloaded again if it has changed.
+//!
+//! # Offline mode
+//!
+//! In offline mode, which is enabled by setting the `NICKEL_OFFLINE` environment variable to a
+//! non-empty value, no request is ever made: the cached content is used if there is one, and the
+//! import fails otherwise.
+//!
+//! # Integrity
+//!
+//! The hash of the cached content is checked against the one recorded in `meta.json` before
+//! being used. A corrupted entry is downloaded again, or makes the import fail in offline mode.
+//! Additionally, an import can pin the expected content by appending a `#sha256=<hex digest>`
+//! fragment to its URL, in which case the import fails if the content doesn't have this hash.
+use std::{
+    fmt, fs,
+    io::{self, Read},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+/// The environment variable overriding the location of the cache directory.
+pub const CACHE_DIR_ENV_VAR: &str = "NICKEL_IMPORT_CACHE";
+
+/// The environment variable enabling the offline mode when set to a non-empty value.
+pub const OFFLINE_ENV_VAR: &str = "NICKEL_OFFLINE";
+
+/// The name of the metadata file of a cache entry.
+const META_FILE: &str = "meta.json";
+
+/// The maximum size of a remote file, in bytes.
+const MAX_SIZE: u64 = 64 * 1024 * 1024;
+
+/// An error occurring while fetching a remote import.
+#[derive(Debug)]
+pub enum RemoteImportError {
+    /// The request failed, or the server responded with an error.
+    Http { url: String, message: String },
+    /// The URL isn't in the cache, and the offline mode forbids fetching it.
+    NotCached { url: String },
+    /// The content doesn't match the hash pinned in the URL.
+    IntegrityMismatch {
+        url: String,
+        expected: String,
+        actual: String,
+    },
+    /// The remote file is larger than the maximum size of a remote import.
+    TooLarge { url: String },
+    /// Reading or writing the cache failed.
+    Io(io::Error),
+}
+
+impl fmt::Display for RemoteImportError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RemoteImportError::Http { url, message } => {
+                write!(f, "could not fetch `{url}`: {message}")
+            }
+            RemoteImportError::NotCached { url } => write!(
+                f,
+                "`{url}` isn't available offline (it hasn't been fetched before, \
+                and {OFFLINE_ENV_VAR} is set)"
+            ),
+            RemoteImportError::IntegrityMismatch {
+                url,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "the content of `{url}` doesn't match its pinned hash \
+                (expected sha256={expected}, got sha256={actual})"
+            ),
+            RemoteImportError::TooLarge { url } => write!(
+                f,
+                "`{url}` is larger than the maximum size of a remote import ({MAX_SIZE} bytes)"
+            ),
+            RemoteImportError::Io(err) => write!(f, "could not access the import cache: {err}"),
+        }
+    }
+}
+
+impl From<io::Error> for RemoteImportError {
+    fn from(err: io::Error) -> Self {
+        RemoteImportError::Io(err)
+    }
+}
+
+/// The content of the metadata file of a cache entry.
+#[derive(Debug, Serialize, Deserialize)]
+struct Meta {
+    url: String,
+    etag: Option<String>,
+    sha256: String,
+    /// The name of the file holding the content, relative to the entry directory.
+    file: String,
+}
+
+/// The configuration of the fetching and caching of remote imports.
+#[derive(Clone, Debug)]
+pub struct RemoteImports {
+    /// The directory where remote files are cached.
+    pub cache_dir: PathBuf,
+    /// Never access the network, and only use cached files.
+    pub offline: bool,
+    /// The timeout of HTTP requests.
+    pub timeout: Duration,
+}
+
+impl RemoteImports {
+    pub fn new(cache_dir: impl Into<PathBuf>) -> Self {
+        RemoteImports {
+            cache_dir: cache_dir.into(),
+            offline: false,
+            timeout: Duration::from_secs(30),
+        }
+    }
+
+    /// The configuration given by the environment variables [CACHE_DIR_ENV_VAR] and
+    /// [OFFLINE_ENV_VAR]. See the [module documentation][self].
+    pub fn from_env() -> Self {
+        let cache_dir = std::env::var_os(CACHE_DIR_ENV_VAR)
+            .map(PathBuf::from)
+            .or_else(|| {
+                directories::BaseDirs::new()
+                    .map(|dirs| dirs.cache_dir().join("nickel").join("imports"))
+            })
+            .unwrap_or_else(|| std::env::temp_dir().join("nickel-imports"));
+
+        RemoteImports {
+            offline: std::env::var_os(OFFLINE_ENV_VAR).is_some_and(|v| !v.is_empty()),
+            ..RemoteImports::new(cache_dir)
+        }
+    }
+
+    /// Return the path of an up-to-date local copy of the file at `url`, fetching it if needed.
+    pub fn fetch(&self, url: &str) -> Result<PathBuf, RemoteImportError> {
+        let (url, pinned_hash) = split_pinned_hash(url);
+        let entry_dir = self.cache_dir.join(sha256_hex(url.as_bytes()));
+        let cached = read_valid_meta(&entry_dir);
+
+        let meta = if self.offline {
+            cached.ok_or_else(|| RemoteImportError::NotCached {
+                url: url.to_owned(),
+            })?
+        } else {
+            self.download(url, &entry_dir, cached)?
+        };
+
+        if let Some(expected) = pinned_hash {
+            if !expected.eq_ignore_ascii_case(&meta.sha256) {
+                return Err(RemoteImportError::IntegrityMismatch {
+                    url: url.to_owned(),
+                    expected: expected.to_owned(),
+                    actual: meta.sha256,
+                });
+            }
+        }
+
+        Ok(entry_dir.join(meta.file))
+    }
+
+    /// Download `url` into the cache entry `entry_dir`, unless the server says that the `cached`
+    /// version is still current.
+    fn download(
+        &self,
+        url: &str,
+        entry_dir: &Path,
+        cached: Option<Meta>,
+    ) -> Result<Meta, RemoteImportError> {
+        let http_error = |message: String| RemoteImportError::Http {
+            url: url.to_owned(),
+            message,
+        };
+
+        let agent = ureq::AgentBuilder::new().timeout(self.timeout).build();
+        let mut request = agent.get(url);
+
+        if let Some(etag) = cached.as_ref().and_then(|meta| meta.etag.as_ref()) {
+            request = request.set("If-None-Match", etag);
+        }
+
+        let response = request.call().map_err(|err| http_error(err.to_string()))?;
+
+        if response.status() == 304 {
+            return cached.ok_or_else(|| {
+                http_error(String::from("unexpected `304 Not Modified` response"))
+            });
+        }
+
+        let etag = response.header("ETag").map(String::from);
+        let mut content = Vec::new();
+        response
+            .into_reader()
+            .take(MAX_SIZE + 1)
+            .read_to_end(&mut content)
+            .map_err(|err| http_error(err.to_string()))?;
+
+        if content.len() as u64 > MAX_SIZE {
+            return Err(RemoteImportError::TooLarge {
+                url: url.to_owned(),
+            });
+        }
+
+        let sha256 = sha256_hex(&content);
+        let version = etag.as_ref().map_or(sha256.clone(), |etag| sha256_hex(etag.as_bytes()));
+        let meta = Meta {
+            url: url.to_owned(),
+            file: format!("{version}{}", extension(url)),
+            etag,
+            sha256,
+        };
+
+        fs::create_dir_all(entry_dir)?;
+        fs::write(entry_dir.join(&meta.file), &content)?;
+        fs::write(
+            entry_dir.join(META_FILE),
+            serde_json::to_vec(&meta).map_err(io::Error::from)?,
+        )?;
+
+        Ok(meta)
+    }
+}
+
+/// Whether an import path is a URL to be fetched remotely.
+pub fn is_url(path: &str) -> bool {
+    path.starts_with("http://") || path.starts_with("https://")
+}
+
+/// Resolve a relative import `relative` of the remote file at `base`.
+pub fn join(base: &str, relative: &str) -> String {
+    let base = base.split(['#', '?']).next().unwrap_or(base);
+    // The end of the scheme and authority, such as `https://example.com`, which `..` can't go
+    // past.
+    let authority_end = base
+        .find("://")
+        .and_then(|idx| base[idx + 3..].find('/').map(|end| idx + 3 + end))
+        .unwrap_or(base.len());
+    let (root, path) = base.split_at(authority_end);
+
+    let mut segments: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
+
+    // Drop the file name of the importing file.
+    if !path.ends_with('/') {
+        segments.pop();
+    }
+
+    for segment in relative.split('/') {
+        match segment {
+            "" | "." => (),
+            ".." => {
+                segments.pop();
+            }
+            segment => segments.push(segment),
+        }
+    }
+
+    format!("{root}/{}", segments.join("/"))
+}
+
+/// Split the hash pinned by a `#sha256=<hex digest>` fragment, if any, from a URL.
+fn split_pinned_hash(url: &str) -> (&str, Option<&str>) {
+    match url.split_once("#sha256=") {
+        Some((url, hash)) => (url, Some(hash)),
+        None => (url, None),
+    }
+}
+
+/// The extension of the file name of a URL, including the leading dot, or an empty string if
+/// there's none.
+fn extension(url: &str) -> &str {
+    let path = url.split(['#', '?']).next().unwrap_or(url);
+    let file_name = path.rsplit('/').next().unwrap_or(path);
+
+    file_name.rfind('.').map_or("", |idx| &file_name[idx..])
+}
+
+fn sha256_hex(data: &[u8]) -> String {
+    Sha256::digest(data)
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+/// Read the metadata of a cache entry, provided that the entry is complete and that its content
+/// matches the recorded hash.
+fn read_valid_meta(entry_dir: &Path) -> Option<Meta> {
+    let meta: Meta = serde_json::from_slice(&fs::read(entry_dir.join(META_FILE)).ok()?).ok()?;
+    let content = fs::read(entry_dir.join(&meta.file)).ok()?;
+
+    (sha256_hex(&content) == meta.sha256).then_some(meta)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn join_relative_imports() {
+        let base = "https://example.com/lib/main.ncl";
+
+        assert_eq!(join(base, "utils.ncl"), "https://example.com/lib/utils.ncl");
+        assert_eq!(join(base, "./a/b.ncl"), "https://example.com/lib/a/b.ncl");
+        assert_eq!(join(base, "../other.ncl"), "https://example.com/other.ncl");
+        assert_eq!(join(base, "../../../x.ncl"), "https://example.com/x.ncl");
+        assert_eq!(
+            join("https://example.com/main.ncl?v=1#sha256=00", "lib.ncl"),
+            "https://example.com/lib.ncl"
+        );
+    }
+
+    #[test]
+    fn url_extension() {
+        assert_eq!(extension("https://example.com/lib.ncl"), ".ncl");
+        assert_eq!(extension("https://example.com/data.json?raw=1"), ".json");
+        assert_eq!(extension("https://example.com/v1.2/lib"), "");
+    }
+}
@@ -13,9 +13,9 @@ use crate::error::{
 };
 use crate::eval::cache::Cache as EvalCache;
 use crate::eval::{Closure, VirtualMachine};
+use crate::identifier::FieldPath;
 use crate::identifier::LocIdent;
 use crate::parser::{grammar, lexer, ErrorTolerantParser, ExtendedTerm};
-use crate::program::FieldPath;
 use crate::term::TraverseOrder;
 use crate::term::{record::Field, RichTerm, Term, Traverse};
 use crate::transform::import_resolution;
@@ -61,6 +61,12 @@ impl From<RichTerm> for EvalResult {
 }
 
 /// Interface of the REPL backend.
+///
+/// A REPL is a session: the toplevel let-bindings (`let x = 1`) and the loaded files are added to
+/// the environment, and are visible from subsequent inputs. Binding a name which is already in
+/// the environment shadows the previous definition for subsequent inputs, as a nested `let`
+/// would: the bindings made before keep referring to the previous value. An input which fails to
+/// parse, to typecheck or to evaluate leaves the environment unchanged.
 pub trait Repl {
     /// Evaluate an expression, which can be either a standard term or a toplevel let-binding.
     fn eval(&mut self, exp: &str) -> Result<EvalResult, Error>;
@@ -113,8 +119,8 @@ impl<EC: EvalCache> ReplImpl<EC> {
     // resolution/typechecking/transformation by ourselves.
     //
     // `id` must be set to `None` for normal expressions and to `Some(id_)` for top-level lets. In
-    // the latter case, we need to update the current type environment before doing program
-    // transformations in the case of a top-level let.
+    // the latter case, we need to update the current type environment once the term has been
+    // typechecked and transformed.
     fn prepare(&mut self, id: Option<LocIdent>, t: RichTerm) -> Result<RichTerm, Error> {
         let import_resolution::strict::ResolveResult {
             transformed_term: t,
@@ -132,21 +138,6 @@ impl<EC: EvalCache> ReplImpl<EC> {
         let wildcards =
             typecheck::type_check(&t, self.env.type_ctxt.clone(), self.vm.import_resolver())?;
 
-        if let Some(id) = id {
-            typecheck::env_add(
-                &mut self.env.type_ctxt.type_env,
-                id,
-                &t,
-                &self.env.type_ctxt.term_env,
-                self.vm.import_resolver(),
-            );
-            self.env
-                .type_ctxt
-                .term_env
-                .0
-                .insert(id.ident(), (t.clone(), self.env.type_ctxt.term_env.clone()));
-        }
-
         for id in &pending {
             self.vm
                 .import_resolver_mut()
@@ -156,7 +147,7 @@ impl<EC: EvalCache> ReplImpl<EC> {
                 })?;
         }
 
-        let t = transform::transform(t, Some(&wildcards))
+        let transformed = transform::transform(t.clone(), Some(&wildcards))
             .map_err(|err| Error::ParseErrors(err.into()))?;
         for id in &pending {
             self.vm
@@ -165,7 +156,24 @@ impl<EC: EvalCache> ReplImpl<EC> {
                 .unwrap_or_else(|_| panic!("repl::eval_(): expected imports to be parsed"));
         }
 
-        Ok(t)
+        // The typing environment is only updated once the input has been successfully processed,
+        // so that a failing top-level let leaves the environment as it was.
+        if let Some(id) = id {
+            typecheck::env_add(
+                &mut self.env.type_ctxt.type_env,
+                id,
+                &t,
+                &self.env.type_ctxt.term_env,
+                self.vm.import_resolver(),
+            );
+            self.env
+                .type_ctxt
+                .term_env
+                .0
+                .insert(id.ident(), (t.clone(), self.env.type_ctxt.term_env.clone()));
+        }
+
+        Ok(transformed)
     }
 
     fn eval_(&mut self, exp: &str, eval_full: bool) -> Result<EvalResult, Error> {
@@ -532,3 +540,59 @@ pub fn print_help(out: &mut impl Write, arg: Option<&str>) -> std::io::Result<()
         writeln!(out, "Available commands: help query load typecheck exit")
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::eval::cache::CacheImpl;
+    use crate::term::make as mk_term;
+    use assert_matches::assert_matches;
+
+    fn eval_full(repl: &mut ReplImpl<CacheImpl>, exp: &str) -> RichTerm {
+        match repl.eval_full(exp).unwrap() {
+            EvalResult::Evaluated(t) => t.without_pos(),
+            EvalResult::Bound(id) => panic!("unexpected binding of `{id}`"),
+        }
+    }
+
+    #[test]
+    fn multi_step_session() {
+        let mut repl = ReplImpl::<CacheImpl>::new(std::io::sink());
+
+        assert_matches!(repl.eval("let x = 1"), Ok(EvalResult::Bound(id)) if id.label() == "x");
+        assert_matches!(repl.eval("let y = x + 1"), Ok(EvalResult::Bound(_)));
+        assert_eq!(eval_full(&mut repl, "y"), mk_term::integer(2));
+        assert_eq!(eval_full(&mut repl, "x + y"), mk_term::integer(3));
+    }
+
+    #[test]
+    fn redefinition_shadows() {
+        let mut repl = ReplImpl::<CacheImpl>::new(std::io::sink());
+
+        repl.eval("let x = 1").unwrap();
+        repl.eval("let y = x").unwrap();
+        repl.eval("let x = x + 1").unwrap();
+
+        assert_eq!(
+            eval_full(&mut repl, "x == 2 && y == 1"),
+            Term::Bool(true).into()
+        );
+    }
+
+    #[test]
+    fn errors_leave_session_intact() {
+        let mut repl = ReplImpl::<CacheImpl>::new(std::io::sink());
+
+        repl.eval("let x : Number = 1").unwrap();
+
+        assert_matches!(repl.eval("let x ="), Err(Error::ParseErrors(_)));
+        assert_matches!(
+            repl.eval("let x : String = 1"),
+            Err(Error::TypecheckError(_))
+        );
+        assert_matches!(repl.eval_full("x + \"a\""), Err(Error::EvalError(_)));
+
+        assert_eq!(eval_full(&mut repl, "x"), mk_term::integer(1));
+        assert_eq!(repl.typecheck("x").unwrap().to_string(), "Number");
+    }
+}
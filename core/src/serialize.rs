@@ -34,6 +34,9 @@ pub enum ExportFormat {
     Json,
     Yaml,
     Toml,
+    /// `KEY=value` lines, as read by shells and container runtimes. Only records of scalar values
+    /// (possibly nested) can be exported to this format. See [to_dotenv].
+    Dotenv,
 }
 
 impl fmt::Display for ExportFormat {
@@ -43,6 +46,7 @@ impl fmt::Display for ExportFormat {
             Self::Json => write!(f, "json"),
             Self::Yaml => write!(f, "yaml"),
             Self::Toml => write!(f, "toml"),
+            Self::Dotenv => write!(f, "dotenv"),
         }
     }
 }
@@ -56,6 +60,24 @@ impl fmt::Display for ParseFormatError {
     }
 }
 
+/// Indentation of the serialized output.
+///
+/// Only JSON export can be configured: the YAML and TOML serializers have a fixed layout, and raw
+/// export outputs a string as is. Those formats only accept the default indentation.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum Indentation {
+    /// Minified output, without any whitespace or newline.
+    Compact,
+    /// Pretty-printed output, where each nesting level is indented by the given number of spaces.
+    Spaces(usize),
+}
+
+impl Default for Indentation {
+    fn default() -> Self {
+        Indentation::Spaces(2)
+    }
+}
+
 /// Implicitly convert numbers to primitive integers when possible, and serialize an exact
 /// representation. Note that `u128` and `i128` aren't supported for common configuration formats in
 /// serde, so we rather pick `i64` and `u64`, even if the former couple theoretically allows for a
@@ -271,7 +293,9 @@ impl fmt::Display for NickelPointer {
 }
 
 /// Check that a term is serializable. Serializable terms are booleans, numbers, strings, enum,
-/// arrays of serializable terms or records of serializable terms.
+/// arrays of serializable terms or records of serializable terms. The dotenv format is more
+/// restrictive: only records are serializable, and their values must be either scalars or records
+/// themselves.
 pub fn validate(format: ExportFormat, t: &RichTerm) -> Result<(), ExportError> {
     use Term::*;
 
@@ -340,6 +364,9 @@ pub fn validate(format: ExportFormat, t: &RichTerm) -> Result<(), ExportError> {
                 })?;
                 Ok(())
             }
+            Array(..) if format == ExportFormat::Dotenv => {
+                Err(ExportErrorData::NotAScalar(format, t.clone()).into())
+            }
             Array(array, _) => {
                 array.iter().enumerate().try_for_each(|(index, t)| {
                     do_validate(format, t)
@@ -357,6 +384,8 @@ pub fn validate(format: ExportFormat, t: &RichTerm) -> Result<(), ExportError> {
         } else {
             Err(ExportErrorData::NotAString(t.clone()).into())
         }
+    } else if format == ExportFormat::Dotenv && !matches!(t.term.as_ref(), Term::Record(_)) {
+        Err(ExportErrorData::NotARecord(format, t.clone()).into())
     } else {
         let mut result = do_validate(format, t);
 
@@ -368,13 +397,42 @@ pub fn validate(format: ExportFormat, t: &RichTerm) -> Result<(), ExportError> {
     }
 }
 
-pub fn to_writer<W>(mut writer: W, format: ExportFormat, rt: &RichTerm) -> Result<(), ExportError>
+pub fn to_writer<W>(writer: W, format: ExportFormat, rt: &RichTerm) -> Result<(), ExportError>
 where
     W: io::Write,
 {
+    to_writer_with_indentation(writer, format, Indentation::default(), rt)
+}
+
+/// Same as [to_writer], but with a custom indentation. Return an error if `indentation` isn't the
+/// default one and `format` doesn't support configuring it (see [Indentation]).
+pub fn to_writer_with_indentation<W>(
+    mut writer: W,
+    format: ExportFormat,
+    indentation: Indentation,
+    rt: &RichTerm,
+) -> Result<(), ExportError>
+where
+    W: io::Write,
+{
+    if format != ExportFormat::Json && indentation != Indentation::default() {
+        return Err(ExportErrorData::Other(format!(
+            "the indentation of the output can only be configured for JSON, not for {format}"
+        ))
+        .into());
+    }
+
     match format {
-        ExportFormat::Json => serde_json::to_writer_pretty(writer, &rt)
-            .map_err(|err| ExportErrorData::Other(err.to_string())),
+        ExportFormat::Json => match indentation {
+            Indentation::Compact => serde_json::to_writer(writer, &rt),
+            Indentation::Spaces(width) => {
+                let indent = vec![b' '; width];
+                let formatter = serde_json::ser::PrettyFormatter::with_indent(&indent);
+                let mut serializer = serde_json::Serializer::with_formatter(writer, formatter);
+                rt.serialize(&mut serializer)
+            }
+        }
+        .map_err(|err| ExportErrorData::Other(err.to_string())),
         ExportFormat::Yaml => serde_yaml::to_writer(writer, &rt)
             .map_err(|err| ExportErrorData::Other(err.to_string())),
         ExportFormat::Toml => toml::to_string_pretty(rt)
@@ -395,18 +453,147 @@ where
                 t.type_of().unwrap()
             ))),
         },
+        ExportFormat::Dotenv => to_dotenv(rt).and_then(|s| {
+            writer
+                .write_all(s.as_bytes())
+                .map_err(|err| ExportErrorData::Other(err.to_string()))
+        }),
     }?;
 
     Ok(())
 }
 
 pub fn to_string(format: ExportFormat, rt: &RichTerm) -> Result<String, ExportError> {
+    to_string_with_indentation(format, Indentation::default(), rt)
+}
+
+/// Same as [to_string], but with a custom indentation. See [to_writer_with_indentation].
+pub fn to_string_with_indentation(
+    format: ExportFormat,
+    indentation: Indentation,
+    rt: &RichTerm,
+) -> Result<String, ExportError> {
     let mut buffer: Vec<u8> = Vec::new();
-    to_writer(&mut buffer, format, rt)?;
+    to_writer_with_indentation(&mut buffer, format, indentation, rt)?;
 
     Ok(String::from_utf8_lossy(&buffer).into_owned())
 }
 
+/// Serialize a record to the dotenv format, with one `KEY=value` line per field.
+///
+/// Nested records are flattened: the name of a variable is the path of the corresponding field,
+/// joined with underscores and converted to uppercase, so that `{db.host = "localhost"}` becomes
+/// `DB_HOST=localhost`. Variables are sorted by name. Numbers and booleans are written as is, while
+/// strings and enum tags are quoted if they contain characters which have a special meaning in a
+/// shell, such as whitespace, quotes or `$`.
+///
+/// The term must have been validated beforehand (see [validate]). This function still returns an
+/// error if a path doesn't give a valid variable name, or if two paths give the same name.
+pub fn to_dotenv(rt: &RichTerm) -> Result<String, ExportErrorData> {
+    fn flatten(
+        prefix: &str,
+        rt: &RichTerm,
+        vars: &mut Vec<(String, String)>,
+    ) -> Result<(), ExportErrorData> {
+        let value = match rt.as_ref() {
+            Term::Record(record) => {
+                let mut entries = record
+                    .iter_serializable()
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(|err| {
+                        ExportErrorData::Other(format!("missing field definition for `{}`", err.id))
+                    })?;
+                entries.sort_by_key(|(id, _)| *id);
+
+                for (id, value) in entries {
+                    let name = id.label().to_uppercase();
+                    let path = if prefix.is_empty() {
+                        name
+                    } else {
+                        format!("{prefix}_{name}")
+                    };
+
+                    flatten(&path, value, vars)?;
+                }
+
+                return Ok(());
+            }
+            Term::Str(s) => quote_dotenv(s),
+            Term::Enum(tag) => quote_dotenv(tag.label()),
+            Term::Bool(_) | Term::Num(_) => {
+                serde_json::to_string(rt).map_err(|err| ExportErrorData::Other(err.to_string()))?
+            }
+            _ => {
+                return Err(ExportErrorData::NotAScalar(
+                    ExportFormat::Dotenv,
+                    rt.clone(),
+                ))
+            }
+        };
+
+        vars.push((prefix.to_owned(), value));
+        Ok(())
+    }
+
+    let mut vars = Vec::new();
+    flatten("", rt, &mut vars)?;
+    vars.sort_by(|(name1, _), (name2, _)| name1.cmp(name2));
+
+    for window in vars.windows(2) {
+        if window[0].0 == window[1].0 {
+            return Err(ExportErrorData::Other(format!(
+                "dotenv export: several fields map to the same variable `{}`",
+                window[0].0
+            )));
+        }
+    }
+
+    let mut output = String::new();
+
+    for (name, value) in vars {
+        let is_valid_name = name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+            && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+
+        if !is_valid_name {
+            return Err(ExportErrorData::Other(format!(
+                "dotenv export: `{name}` isn't a valid variable name"
+            )));
+        }
+
+        output.push_str(&format!("{name}={value}\n"));
+    }
+
+    Ok(output)
+}
+
+/// Quote a string value for the dotenv format, if needed. Quoted values are enclosed in double
+/// quotes, where backslashes, double quotes, `$` and backticks are escaped, as well as newlines.
+fn quote_dotenv(s: &str) -> String {
+    let is_safe = |c: char| c.is_ascii_alphanumeric() || "_-./:@,+%".contains(c);
+
+    if !s.is_empty() && s.chars().all(is_safe) {
+        return s.to_owned();
+    }
+
+    let mut quoted = String::with_capacity(s.len() + 2);
+    quoted.push('"');
+
+    for c in s.chars() {
+        match c {
+            '\\' | '"' | '$' | '`' => {
+                quoted.push('\\');
+                quoted.push(c);
+            }
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            c => quoted.push(c),
+        }
+    }
+
+    quoted.push('"');
+    quoted
+}
+
 /// TOML deserialization wrappers. Depending on the `spanned-deser` feature being
 /// enabled, [::toml::from_str] will either simply call [toml::from_str] or a custom
 /// deserializer that preserves span information.
@@ -735,4 +922,57 @@ mod tests {
         assert_involutory("{val = [\"a\", 3, []]}");
         assert_involutory("{a.foo.bar = \"2\", b = false, c = [{d = \"e\"}, {d = \"f\"}]}");
     }
+
+    #[test]
+    fn indentation() {
+        let rt = eval("{a = 1, b = {c = [true, null]}}");
+        let export = |indentation| {
+            to_string_with_indentation(ExportFormat::Json, indentation, &rt).unwrap()
+        };
+
+        assert_eq!(export(Indentation::Compact), r#"{"a":1,"b":{"c":[true,null]}}"#);
+        assert_eq!(
+            export(Indentation::Spaces(2)),
+            "{\n  \"a\": 1,\n  \"b\": {\n    \"c\": [\n      true,\n      null\n    ]\n  }\n}"
+        );
+        assert_eq!(
+            export(Indentation::Spaces(4)),
+            "{\n    \"a\": 1,\n    \"b\": {\n        \"c\": [\n            true,\n            null\n        ]\n    }\n}"
+        );
+        assert_eq!(
+            export(Indentation::default()),
+            to_string(ExportFormat::Json, &rt).unwrap()
+        );
+        assert!(
+            to_string_with_indentation(ExportFormat::Yaml, Indentation::Compact, &rt).is_err()
+        );
+    }
+
+    #[test]
+    fn dotenv() {
+        let rt = eval(
+            r#"{
+                db = { host = "localhost", port = 5432, password = "p@ss \"word\" $HOME" },
+                debug = true,
+                mode = 'prod,
+                motd = "line 1\nline 2",
+            }"#,
+        );
+
+        validate(ExportFormat::Dotenv, &rt).unwrap();
+        assert_eq!(
+            to_string(ExportFormat::Dotenv, &rt).unwrap(),
+            "DB_HOST=localhost\n\
+            DB_PASSWORD=\"p@ss \\\"word\\\" \\$HOME\"\n\
+            DB_PORT=5432\n\
+            DEBUG=true\n\
+            MODE=prod\n\
+            MOTD=\"line 1\\nline 2\"\n"
+        );
+
+        assert_fail_validation(ExportFormat::Dotenv, "{ports = [80, 443]}");
+        assert_fail_validation(ExportFormat::Dotenv, "{a = null}");
+        assert_fail_validation(ExportFormat::Dotenv, "\"not a record\"");
+        assert!(to_string(ExportFormat::Dotenv, &eval("{a_b = 1, a.b = 2}")).is_err());
+    }
 }
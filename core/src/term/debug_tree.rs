@@ -0,0 +1,279 @@
+//! A compact textual dump of the structure of a term, to inspect and compare the output of the
+//! parser, for example in snapshot tests. See [RichTerm::debug_tree].
+//!
+//! Each node is printed on its own line, indented by its depth, followed by its children. The
+//! output is deterministic: unlike the `Debug` representation, it doesn't contain cache indices,
+//! file ids or label data, and positions are only included on demand. String chunks are printed in
+//! the order in which they're stored, which is the reverse of the source order.
+use std::fmt::Write;
+
+use super::{
+    record::{Field, RecordData},
+    RichTerm, StrChunk, Term, TypeAnnotation, UnaryOp,
+};
+
+impl RichTerm {
+    /// Render the structure of this term as an indented tree, without positions.
+    pub fn debug_tree(&self) -> String {
+        DebugTree::new(false).render(self)
+    }
+
+    /// Same as [Self::debug_tree], but each node is followed by its span in the source, as a range
+    /// of byte offsets.
+    pub fn debug_tree_with_positions(&self) -> String {
+        DebugTree::new(true).render(self)
+    }
+}
+
+struct DebugTree {
+    positions: bool,
+    out: String,
+}
+
+impl DebugTree {
+    fn new(positions: bool) -> Self {
+        DebugTree {
+            positions,
+            out: String::new(),
+        }
+    }
+
+    fn render(mut self, rt: &RichTerm) -> String {
+        self.term(rt, 0);
+        self.out
+    }
+
+    fn line(&mut self, depth: usize, label: &str) {
+        let _ = writeln!(self.out, "{:indent$}{label}", "", indent = 2 * depth);
+    }
+
+    fn term(&mut self, rt: &RichTerm, depth: usize) {
+        let mut label = match rt.as_ref() {
+            Term::Null => String::from("Null"),
+            Term::Bool(b) => format!("Bool {b}"),
+            Term::Num(_) => format!("Num {rt}"),
+            Term::Str(s) => format!("Str {:?}", s.as_str()),
+            Term::StrChunks(_) => String::from("StrChunks"),
+            Term::Fun(id, _) => format!("Fun {id}"),
+            Term::FunPattern(pat, _) => format!("FunPattern {pat}"),
+            Term::Lbl(_) => String::from("Label"),
+            Term::Let(id, _, _, attrs) if attrs.rec => format!("Let rec {id}"),
+            Term::Let(id, _, _, _) => format!("Let {id}"),
+            Term::LetPattern(pat, _, _) => format!("LetPattern {pat}"),
+            Term::App(_, _) => String::from("App"),
+            Term::Var(id) => format!("Var {id}"),
+            Term::Enum(id) => format!("Enum {id}"),
+            Term::EnumVariant { tag, .. } => format!("EnumVariant {tag}"),
+            Term::Record(_) => String::from("Record"),
+            Term::RecRecord(..) => String::from("RecRecord"),
+            Term::Match(_) => String::from("Match"),
+            Term::Array(..) => String::from("Array"),
+            Term::Op1(UnaryOp::RecordAccess(id), _) => format!("Op1 record/access {id}"),
+            Term::Op1(op, _) => format!("Op1 {op}"),
+            Term::Op2(op, _, _) => format!("Op2 {op}"),
+            Term::OpN(op, _) => format!("OpN {op}"),
+            Term::SealingKey(_) => String::from("SealingKey"),
+            Term::Sealed(..) => String::from("Sealed"),
+            Term::Annotated(..) => String::from("Annotated"),
+            Term::Import(path) => format!("Import {:?}", path.to_string_lossy()),
+            Term::ResolvedImport(_) => String::from("ResolvedImport"),
+            Term::Type(typ) => format!("Type {typ}"),
+            Term::CustomContract(_) => String::from("CustomContract"),
+            Term::ParseError(_) => String::from("ParseError"),
+            Term::RuntimeError(_) => String::from("RuntimeError"),
+            Term::Closure(_) => String::from("Closure"),
+            Term::ForeignId(_) => String::from("ForeignId"),
+        };
+
+        if self.positions {
+            if let Some(span) = rt.pos.as_opt_ref() {
+                let _ = write!(
+                    label,
+                    " @{}..{}",
+                    span.start.to_usize(),
+                    span.end.to_usize()
+                );
+            }
+        }
+
+        self.line(depth, &label);
+        let depth = depth + 1;
+
+        match rt.as_ref() {
+            Term::StrChunks(chunks) => {
+                for chunk in chunks {
+                    match chunk {
+                        StrChunk::Literal(s) => self.line(depth, &format!("Literal {s:?}")),
+                        StrChunk::Expr(e, _) => self.term(e, depth),
+                    }
+                }
+            }
+            Term::Fun(_, body)
+            | Term::FunPattern(_, body)
+            | Term::EnumVariant { arg: body, .. }
+            | Term::Op1(_, body)
+            | Term::Sealed(_, body, _) => self.term(body, depth),
+            Term::Let(_, bound, body, _) | Term::LetPattern(_, bound, body) => {
+                self.term(bound, depth);
+                self.term(body, depth);
+            }
+            Term::App(t1, t2) | Term::Op2(_, t1, t2) => {
+                self.term(t1, depth);
+                self.term(t2, depth);
+            }
+            Term::Record(data) => self.record(data, &[], depth),
+            Term::RecRecord(data, dyn_fields, _) => self.record(data, dyn_fields, depth),
+            Term::Match(data) => {
+                for branch in &data.branches {
+                    self.line(depth, &format!("Branch {}", branch.pattern));
+
+                    if let Some(guard) = &branch.guard {
+                        self.line(depth + 1, "Guard");
+                        self.term(guard, depth + 2);
+                    }
+
+                    self.term(&branch.body, depth + 1);
+                }
+            }
+            Term::Array(elts, _) => {
+                for elt in elts.iter() {
+                    self.term(elt, depth);
+                }
+            }
+            Term::OpN(_, args) => {
+                for arg in args {
+                    self.term(arg, depth);
+                }
+            }
+            Term::Annotated(annot, inner) => {
+                self.annotation(annot, depth);
+                self.term(inner, depth);
+            }
+            Term::CustomContract(contract) => {
+                let (kind, inner) = match contract {
+                    super::CustomContract::Predicate(t) => ("Predicate", t),
+                    super::CustomContract::Validator(t) => ("Validator", t),
+                    super::CustomContract::PartialIdentity(t) => ("PartialIdentity", t),
+                };
+
+                self.line(depth, kind);
+                self.term(inner, depth + 1);
+            }
+            _ => (),
+        }
+    }
+
+    fn annotation(&mut self, annot: &TypeAnnotation, depth: usize) {
+        if let Some(labeled_ty) = &annot.typ {
+            self.line(depth, &format!("Type {}", labeled_ty.typ));
+        }
+
+        for labeled_ty in &annot.contracts {
+            self.line(depth, &format!("Contract {}", labeled_ty.typ));
+        }
+    }
+
+    fn record(&mut self, data: &RecordData, dyn_fields: &[(RichTerm, Field)], depth: usize) {
+        for (id, field) in &data.fields {
+            self.field(&format!("Field {id}"), None, field, depth);
+        }
+
+        for (name, field) in dyn_fields {
+            self.field("DynField", Some(name), field, depth);
+        }
+    }
+
+    /// Print a field. The name of dynamic fields is printed first, before the annotations and the
+    /// value.
+    fn field(&mut self, label: &str, name: Option<&RichTerm>, field: &Field, depth: usize) {
+        let metadata = &field.metadata;
+        let mut label = String::from(label);
+
+        if metadata.opt {
+            label.push_str(" optional");
+        }
+
+        if metadata.not_exported {
+            label.push_str(" not_exported");
+        }
+
+        if metadata.priority != Default::default() {
+            let _ = write!(label, " priority={}", metadata.priority);
+        }
+
+        if metadata.doc.is_some() {
+            label.push_str(" doc");
+        }
+
+        self.line(depth, &label);
+
+        if let Some(name) = name {
+            self.term(name, depth + 1);
+        }
+
+        self.annotation(&metadata.annotation, depth + 1);
+
+        if let Some(value) = &field.value {
+            self.term(value, depth + 1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use codespan::Files;
+
+    use crate::parser::{grammar, lexer, ErrorTolerantParser};
+
+    use super::*;
+
+    fn parse(s: &str) -> RichTerm {
+        let id = Files::new().add("<test>", String::from(s));
+
+        grammar::TermParser::new()
+            .parse_strict(id, lexer::Lexer::new(s))
+            .unwrap()
+    }
+
+    #[test]
+    fn debug_tree_snapshot() {
+        let term = parse(
+            "let rec f = fun x => [x, 'Some x] in \
+            { foo | Number | default = f 1, bar.baz = \"a%{foo}\" }",
+        );
+
+        assert_eq!(
+            term.debug_tree(),
+            "\
+Let rec f
+  Fun x
+    Array
+      Var x
+      EnumVariant Some
+        Var x
+  RecRecord
+    Field foo priority=default
+      Contract Number
+      App
+        Var f
+        Num 1
+    Field bar
+      Record
+        Field baz
+          StrChunks
+            Var foo
+            Literal \"a\"
+"
+        );
+    }
+
+    #[test]
+    fn debug_tree_positions() {
+        let term = parse("f null");
+
+        assert_eq!(
+            term.debug_tree_with_positions(),
+            "App @0..6\n  Var f @0..1\n  Null @2..6\n"
+        );
+    }
+}
@@ -10,6 +10,7 @@
 //! It also features types and type annotations, and other typechecking or contracts-related
 //! constructs (label, symbols, etc.).
 pub mod array;
+mod debug_tree;
 pub mod pattern;
 pub mod record;
 pub mod string;
@@ -599,10 +600,48 @@ pub enum MergePriority {
     /// A numeral priority.
     Numeral(Number),
 
+    /// A priority relative to the priority of the value it's merged with, as in `priority +1`.
+    /// See [MergePriority::resolve_relative].
+    ///
+    /// Outside of merging, it's compared to other priorities as the numeral priority with the
+    /// same value.
+    Relative(Number),
+
     /// The priority of values that override everything else and can't be overridden.
     Top,
 }
 
+impl MergePriority {
+    /// Resolve the relative priorities of two values being merged. A relative priority `+n`
+    /// merged with a numeral priority `p` becomes the numeral priority `p + n`. The `default` and
+    /// `force` priorities aren't numbers: relatively to them, or to the neutral priority, `+n`
+    /// becomes the numeral priority `n`.
+    ///
+    /// When both priorities are relative, they are relative to the same unknown priority, and are
+    /// kept as they are: the higher one wins, and the priority of the result of the merge is still
+    /// relative.
+    ///
+    /// As a consequence, merging isn't associative in the presence of relative priorities. For
+    /// example, `+1` is resolved against `5` in `(5 & +1) & 3`, where the value with priority `+1`
+    /// wins, but against `3` in `5 & (+1 & 3)`, where the value with priority `5` wins.
+    pub fn resolve_relative(p1: Self, p2: Self) -> (Self, Self) {
+        let resolve = |base: &MergePriority, offset: Number| match base {
+            MergePriority::Numeral(p) => MergePriority::Numeral(p.clone() + offset),
+            _ => MergePriority::Numeral(offset),
+        };
+
+        match (p1, p2) {
+            (p1 @ MergePriority::Relative(_), p2 @ MergePriority::Relative(_)) => (p1, p2),
+            (MergePriority::Relative(offset), base) => (resolve(&base, offset), base),
+            (base, MergePriority::Relative(offset)) => {
+                let resolved = resolve(&base, offset);
+                (base, resolved)
+            }
+            (p1, p2) => (p1, p2),
+        }
+    }
+}
+
 impl PartialOrd for MergePriority {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
         Some(self.cmp(other))
@@ -615,13 +654,18 @@ impl PartialEq for MergePriority {
             (MergePriority::Bottom, MergePriority::Bottom)
             | (MergePriority::Neutral, MergePriority::Neutral)
             | (MergePriority::Top, MergePriority::Top) => true,
-            (MergePriority::Numeral(p1), MergePriority::Numeral(p2)) => p1 == p2,
-            (MergePriority::Neutral, MergePriority::Numeral(p))
-            | (MergePriority::Numeral(p), MergePriority::Neutral)
-                if p == &Number::ZERO =>
-            {
-                true
-            }
+            (
+                MergePriority::Numeral(p1) | MergePriority::Relative(p1),
+                MergePriority::Numeral(p2) | MergePriority::Relative(p2),
+            ) => p1 == p2,
+            (
+                MergePriority::Neutral,
+                MergePriority::Numeral(p) | MergePriority::Relative(p),
+            )
+            | (
+                MergePriority::Numeral(p) | MergePriority::Relative(p),
+                MergePriority::Neutral,
+            ) if p == &Number::ZERO => true,
             _ => false,
         }
     }
@@ -636,15 +680,22 @@ impl Ord for MergePriority {
             (MergePriority::Bottom, MergePriority::Bottom)
             | (MergePriority::Top, MergePriority::Top)
             | (MergePriority::Neutral, MergePriority::Neutral) => Ordering::Equal,
-            (MergePriority::Numeral(p1), MergePriority::Numeral(p2)) => p1.cmp(p2),
+            (
+                MergePriority::Numeral(p1) | MergePriority::Relative(p1),
+                MergePriority::Numeral(p2) | MergePriority::Relative(p2),
+            ) => p1.cmp(p2),
 
             // Top and bottom.
             (MergePriority::Bottom, _) | (_, MergePriority::Top) => Ordering::Less,
             (MergePriority::Top, _) | (_, MergePriority::Bottom) => Ordering::Greater,
 
             // Neutral and numeral.
-            (MergePriority::Neutral, MergePriority::Numeral(n)) => Number::ZERO.cmp(n),
-            (MergePriority::Numeral(n), MergePriority::Neutral) => n.cmp(&Number::ZERO),
+            (MergePriority::Neutral, MergePriority::Numeral(n) | MergePriority::Relative(n)) => {
+                Number::ZERO.cmp(n)
+            }
+            (MergePriority::Numeral(n) | MergePriority::Relative(n), MergePriority::Neutral) => {
+                n.cmp(&Number::ZERO)
+            }
         }
     }
 }
@@ -661,6 +712,7 @@ impl fmt::Display for MergePriority {
             MergePriority::Bottom => write!(f, "default"),
             MergePriority::Neutral => write!(f, "{}", Number::ZERO),
             MergePriority::Numeral(p) => write!(f, "{p}"),
+            MergePriority::Relative(p) => write!(f, "+{p}"),
             MergePriority::Top => write!(f, "force"),
         }
     }
@@ -714,6 +766,14 @@ impl LabeledType {
             ..self
         }
     }
+
+    /// Prepend `prefix` to the label's `field_path` field.
+    pub fn with_field_path_prefix(self, prefix: &[LocIdent]) -> Self {
+        LabeledType {
+            label: self.label.with_field_path_prefix(prefix),
+            ..self
+        }
+    }
 }
 
 impl Traverse<RichTerm> for LabeledType {
@@ -827,6 +887,19 @@ impl TypeAnnotation {
         }
     }
 
+    /// Prepend `prefix` to the `field_path` attribute of the labels of the type and contracts
+    /// annotations.
+    pub fn with_field_path_prefix(self, prefix: &[LocIdent]) -> Self {
+        TypeAnnotation {
+            typ: self.typ.map(|t| t.with_field_path_prefix(prefix)),
+            contracts: self
+                .contracts
+                .into_iter()
+                .map(|t| t.with_field_path_prefix(prefix))
+                .collect(),
+        }
+    }
+
     /// Return `true` if this annotation is empty, i.e. hold neither a type annotation nor
     /// contracts annotations.
     pub fn is_empty(&self) -> bool {
@@ -1217,7 +1290,7 @@ impl Deref for SharedTerm {
 /// elseBlock`, `if-then-else` can be seen as a unary operator taking a `Bool` argument and
 /// evaluating to either the first projection `fun x y => x` or the second projection `fun x y =>
 /// y`.
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub enum UnaryOp {
     /// If-then-else.
     IfThenElse,
@@ -1334,6 +1407,16 @@ pub enum UnaryOp {
     /// Return the length of an array.
     ArrayLength,
 
+    /// Return the elements of an array in a random order. The randomness comes from the
+    /// pseudo-random number generator of the virtual machine, which can be seeded to get
+    /// reproducible results.
+    ArrayShuffle,
+
+    /// Remove the duplicates of an array, keeping the first occurrence of each element. Elements
+    /// are compared structurally, as with `==`, and must have been fully evaluated beforehand,
+    /// for example with [UnaryOp::Force].
+    ArrayDedup,
+
     /// Generate an array of a given length by mapping a `Num -> Num` function onto `[1,..,n]`.
     ArrayGen,
 
@@ -1349,12 +1432,29 @@ pub enum UnaryOp {
     /// Return the values of the fields of a record as an array.
     RecordValues,
 
+    /// Convert a record to an array of `{ field, value }` entries, sorted by field name.
+    RecordToEntries,
+
+    /// Build a record from an array of `{ field, value }` entries. Field names must be distinct.
+    RecordFromEntries,
+
+    /// Merge all the elements of an array of records, from left to right. Merge errors report the
+    /// index of the element that caused the conflict.
+    RecordMergeAll,
+
     /// Remove heading and trailing spaces from a string.
     StringTrim,
 
+    /// Remove the longest whitespace prefix common to all the non-blank lines of a string.
+    StringDedent,
+
     /// Return the array of characters of a string.
     StringChars,
 
+    /// Split a string into lines, on any of `\n`, `\r\n` or `\r`. See
+    /// [crate::term::string::NickelString::split_lines].
+    StringSplitLines,
+
     /// Transform a string to uppercase.
     StringUppercase,
 
@@ -1373,6 +1473,12 @@ pub enum UnaryOp {
     /// Transform a string to an enum.
     EnumFromString,
 
+    /// Parse a duration such as `"1h30m"` into a number of seconds.
+    DurationFromString,
+
+    /// Format a number of seconds as a duration, such as `"1h30m"`.
+    DurationToString,
+
     /// Test if a regex matches a string.
     /// Like [`UnaryOp::StringFind`], this is a unary operator because we would like a way to share
     /// the same "compiled regex" for many matching calls. This is done by returning functions
@@ -1389,12 +1495,15 @@ pub enum UnaryOp {
     StringFindAll,
 
     /// Version of [`UnaryOp::StringIsMatch`] which remembers the compiled regex.
+    #[serde(skip)]
     StringIsMatchCompiled(CompiledRegex),
 
     /// Version of [`UnaryOp::StringFind`] which remembers the compiled regex.
+    #[serde(skip)]
     StringFindCompiled(CompiledRegex),
 
     /// Version of [`UnaryOp::StringFindAll`] which remembers the compiled regex.
+    #[serde(skip)]
     StringFindAllCompiled(CompiledRegex),
 
     /// Force full evaluation of a term and return it.
@@ -1426,6 +1535,16 @@ pub enum UnaryOp {
     /// is `false`, these fields are evaluated.
     Force { ignore_not_exported: bool },
 
+    /// Time the evaluation of its argument, which is the forcing of a field, and record it under
+    /// the given field path in the profile of the virtual machine. Generated by the evaluation of
+    /// [`UnaryOp::Force`] when profiling is enabled, see
+    /// [crate::eval::VirtualMachine::set_profile].
+    ///
+    /// The fields of a record forced directly under `Profile` are themselves wrapped in `Profile`,
+    /// until the maximum depth of the profile is reached.
+    #[serde(skip)]
+    Profile(Vec<LocIdent>),
+
     /// Recursive default priority operator. Recursively propagates a default priority through a
     /// record, stopping whenever a field isn't a record anymore to then turn into a simple
     /// `default`.
@@ -1495,6 +1614,17 @@ pub enum UnaryOp {
     EnumIsVariant,
     /// Extract the tag from an enum tag or an enum variant.
     EnumGetTag,
+    /// Convert an enum to a record with a string `tag` field and, for an enum variant, an `arg`
+    /// field: `%enum/to_record% ('Foo t) := { tag = "Foo", arg = t }`. For a bare enum tag, the
+    /// `arg` field is absent: `%enum/to_record% 'Foo := { tag = "Foo" }`.
+    EnumToRecord,
+    /// Inverse of [Self::EnumToRecord]: build an enum variant from a record with a string `tag`
+    /// field and an `arg` field, or a bare enum tag if the `arg` field is absent. The record
+    /// can't have any other field.
+    EnumFromRecord,
+    /// Return the tags of an enum type used as a contract, such as `[| 'A, 'B 'Number |]`, as an
+    /// array of strings, in the order in which they are declared.
+    EnumVariants,
 
     /// Take a record representing bindings to be added to the local environment and proceed to
     /// evaluate a pattern branch given as a second argument (which isn't a proper primop argument
@@ -1538,19 +1668,28 @@ impl fmt::Display for UnaryOp {
             Seq => write!(f, "seq"),
             DeepSeq => write!(f, "deep_seq"),
             ArrayLength => write!(f, "array/length"),
+            ArrayShuffle => write!(f, "array/shuffle"),
+            ArrayDedup => write!(f, "array/dedup"),
             ArrayGen => write!(f, "array/generate"),
             ChunksConcat => write!(f, "chunks_concat"),
             RecordFields(RecordOpKind::IgnoreEmptyOpt) => write!(f, "record/fields"),
             RecordFields(RecordOpKind::ConsiderAllFields) => write!(f, "record/fields_with_opts"),
             RecordValues => write!(f, "record/values"),
+            RecordToEntries => write!(f, "record/to_entries"),
+            RecordFromEntries => write!(f, "record/from_entries"),
+            RecordMergeAll => write!(f, "record/merge_all"),
             StringTrim => write!(f, "string/trim"),
+            StringDedent => write!(f, "string/dedent"),
             StringChars => write!(f, "string/chars"),
+            StringSplitLines => write!(f, "string/split_lines"),
             StringUppercase => write!(f, "string/uppercase"),
             StringLowercase => write!(f, "string/lowercase"),
             StringLength => write!(f, "string/length"),
             ToString => write!(f, "to_string"),
             NumberFromString => write!(f, "number/from_string"),
             EnumFromString => write!(f, "enum/from_string"),
+            DurationFromString => write!(f, "duration/from_string"),
+            DurationToString => write!(f, "duration/to_string"),
             StringIsMatch => write!(f, "string/is_match"),
             StringFind => write!(f, "string/find"),
             StringFindAll => write!(f, "string/find_all"),
@@ -1558,6 +1697,7 @@ impl fmt::Display for UnaryOp {
             StringFindCompiled(_) => write!(f, "string/find_compiled"),
             StringFindAllCompiled(_) => write!(f, "string/find_all_compiled"),
             Force { .. } => write!(f, "force"),
+            Profile(_) => write!(f, "profile"),
             RecDefault => write!(f, "rec_default"),
             RecForce => write!(f, "rec_force"),
             RecordEmptyWithTail => write!(f, "record/empty_with_tail"),
@@ -1571,6 +1711,9 @@ impl fmt::Display for UnaryOp {
             EnumMakeVariant => write!(f, "enum/make_variant"),
             EnumIsVariant => write!(f, "enum/is_variant"),
             EnumGetTag => write!(f, "enum/get_tag"),
+            EnumToRecord => write!(f, "enum/to_record"),
+            EnumFromRecord => write!(f, "enum/from_record"),
+            EnumVariants => write!(f, "enum/variants"),
 
             PatternBranch => write!(f, "pattern_branch"),
         }
@@ -1626,7 +1769,7 @@ impl UnaryOp {
 
 /// The kind of a dynamic record extension. Kind indicates if a definition is expected for the
 /// field being inserted, or if the inserted field doesn't have a definition.
-#[derive(Clone, Debug, PartialEq, Eq, Copy)]
+#[derive(Clone, Debug, PartialEq, Eq, Copy, Serialize, Deserialize)]
 pub enum RecordExtKind {
     WithValue,
     WithoutValue,
@@ -1638,7 +1781,7 @@ pub enum RecordExtKind {
 ///
 /// However, it's sometimes useful and even necessary to take them into account. This behavior is
 /// controlled by [RecordOpKind].
-#[derive(Clone, Debug, PartialEq, Eq, Copy, Default)]
+#[derive(Clone, Debug, PartialEq, Eq, Copy, Default, Serialize, Deserialize)]
 pub enum RecordOpKind {
     #[default]
     IgnoreEmptyOpt,
@@ -1646,7 +1789,7 @@ pub enum RecordOpKind {
 }
 
 /// Primitive binary operators
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub enum BinaryOp {
     /// Addition of numerals.
     Plus,
@@ -1666,6 +1809,12 @@ pub enum BinaryOp {
     /// Raise a number to a power.
     Pow,
 
+    /// The smaller of two numbers. The comparison is exact.
+    NumberMin,
+
+    /// The larger of two numbers. The comparison is exact.
+    NumberMax,
+
     /// Concatenation of strings.
     StringConcat,
 
@@ -1713,6 +1862,7 @@ pub enum BinaryOp {
     /// aren't first class values, at least at the time of writing), so for now we attach it
     /// directly to the extend primop. This isn't ideal, and in the future we may want to have a
     /// more principled primop.
+    #[serde(skip)]
     RecordInsert {
         metadata: FieldMetadata,
         pending_contracts: Vec<RuntimeContract>,
@@ -1723,6 +1873,38 @@ pub enum BinaryOp {
     /// Remove a field from a record. The field name is given as an argument.
     RecordRemove(RecordOpKind),
 
+    /// Keep only the fields of a record whose name satisfies a predicate. The first argument is
+    /// the predicate, a function from strings to booleans. The metadata and the order of the
+    /// remaining fields are preserved.
+    RecordFilterKeys,
+
+    /// Internal operation used by [`BinaryOp::RecordFilterKeys`]. Take an array of booleans, one
+    /// for each field of the record given as a second argument, and keep the fields for which the
+    /// corresponding boolean is `true`. The elements of the array must have been evaluated
+    /// beforehand.
+    RecordFilterKeysMasked,
+
+    /// Rename several fields of a record at once. The first argument is a record mapping old
+    /// field names to new ones. See [`NAryOp::RecordRename`] for the handling of metadata and
+    /// collisions.
+    RecordRenameKeys,
+
+    /// Rename all the fields of a record by applying a function from strings to strings to their
+    /// names. As for [`BinaryOp::RecordRenameKeys`], the fields keep their metadata, and two
+    /// fields can't end up with the same name.
+    RecordMapKeys,
+
+    /// Keep only the fields of the first record which aren't fields of the second record. The
+    /// metadata and the order of the remaining fields are preserved. Optional fields without a
+    /// definition in the second record are ignored.
+    RecordDifference,
+
+    /// Keep only the fields of the first record which are also fields of the second record. The
+    /// values, the metadata and the order of the remaining fields are the ones of the first
+    /// record: the values of the second record are ignored. Optional fields without a definition
+    /// in the second record are ignored.
+    RecordIntersection,
+
     /// Dynamically access a field of record. The field name is given as an argument which should
     /// evaluate to a string.
     RecordGet,
@@ -1739,9 +1921,47 @@ pub enum BinaryOp {
     /// Access the n-th element of an array.
     ArrayAt,
 
+    /// Pair up the elements of two arrays, returning an array of records `{_1, _2}`. If the arrays
+    /// have different lengths, the result is truncated to the length of the shortest one.
+    ArrayZip,
+
+    /// Split an array into consecutive chunks of a given size, the last one being shorter if the
+    /// length of the array isn't a multiple of the size. The size must be a positive integer.
+    ArrayChunk,
+
+    /// Flatten nested arrays up to a given depth: the elements of an array of arrays are
+    /// concatenated, then the elements of the result if they are arrays as well, and so on, at
+    /// most `depth` times. A depth of zero leaves the array unchanged. The depth must be a
+    /// non-negative integer.
+    ///
+    /// Each level must be made either of arrays only, which are flattened, or of other values
+    /// only, in which case the result is already flat and flattening stops. Arrays mixing both
+    /// are rejected, as well as a first level without any array.
+    ArrayFlatten {
+        /// Set on the levels below the first one, where flattening stops without an error once
+        /// there's no array left.
+        nested: bool,
+    },
+
+    /// Apply a function returning `'Some x` or `'None` to the elements of an array, in order, and
+    /// return the first `'Some` result, or `'None` if there isn't any. The search stops at the
+    /// first match: the following elements aren't evaluated.
+    ArrayFindMap,
+
+    /// Return `'Some idx`, where `idx` is the index of the first element of an array satisfying a
+    /// predicate, or `'None` if there isn't any. As for [`BinaryOp::ArrayFindMap`], the elements
+    /// after the first match aren't evaluated.
+    ArrayFindIndex,
+
+    /// Compose two functions. `FunctionCompose f g` evaluates both arguments to functions, and
+    /// returns the function `fun x => f (g x)`. The application `g x` is not forced by the
+    /// composition itself, but only if `f` needs its argument.
+    FunctionCompose,
+
     /// The merge operator (see [crate::eval::merge]). `Merge` is parametrized by a
     /// [crate::label::MergeLabel], which carries additional information for error-reporting
     /// purpose.
+    #[serde(skip)]
     Merge(MergeLabel),
 
     /// Hash a string.
@@ -1759,6 +1979,26 @@ pub enum BinaryOp {
     /// Determine if a string is a substring of another one.
     StringContains,
 
+    /// Wrap a string at word boundaries so that lines don't exceed a given width, counted in
+    /// Unicode scalar values.
+    StringWrap,
+
+    /// Prefix each non-blank line of a string with another string.
+    StringIndent,
+
+    /// Substitute the `{name}` placeholders of a template string with the fields of a record,
+    /// converted to strings. `{{` and `}}` stand for literal braces.
+    StringFormat,
+
+    /// Encode a string as an array of bytes. Takes the encoding, which is one of `'Utf8`, `'Utf16`
+    /// (big-endian, without byte order mark) and `'Latin1`, and the string.
+    StringToBytes,
+
+    /// Decode an array of bytes as a string. Takes the encoding, as for
+    /// [`BinaryOp::StringToBytes`], and the array, which must only contain integers between `0`
+    /// and `255`.
+    StringFromBytes,
+
     /// Seal a term with a sealing key (see [`Term::Sealed`]).
     Seal,
 
@@ -1776,6 +2016,10 @@ pub enum BinaryOp {
     /// Set the notes of the current diagnostic of a label.
     LabelWithNotes,
 
+    /// Set the structured data of the current diagnostic of a label. The data must be fully
+    /// evaluated and serializable.
+    LabelWithErrorData,
+
     /// Append a note to the current diagnostic of a label.
     LabelAppendNote,
 
@@ -1805,6 +2049,8 @@ impl fmt::Display for BinaryOp {
             Div => write!(f, "div"),
             Modulo => write!(f, "modulo"),
             Pow => write!(f, "pow"),
+            NumberMin => write!(f, "number/min"),
+            NumberMax => write!(f, "number/max"),
             StringConcat => write!(f, "string/concat"),
             Eq => write!(f, "eq"),
             LessThan => write!(f, "less_than"),
@@ -1824,6 +2070,12 @@ impl fmt::Display for BinaryOp {
             } => write!(f, "record/insert_with_opts"),
             RecordRemove(RecordOpKind::IgnoreEmptyOpt) => write!(f, "record/remove"),
             RecordRemove(RecordOpKind::ConsiderAllFields) => write!(f, "record/remove_with_opts"),
+            RecordFilterKeys => write!(f, "record/filter_keys"),
+            RecordFilterKeysMasked => write!(f, "record/filter_keys_masked"),
+            RecordRenameKeys => write!(f, "record/rename_keys"),
+            RecordMapKeys => write!(f, "record/map_keys"),
+            RecordDifference => write!(f, "record/difference"),
+            RecordIntersection => write!(f, "record/intersection"),
             RecordGet => write!(f, "record/get"),
             RecordHasField(RecordOpKind::IgnoreEmptyOpt) => write!(f, "record/has_field"),
             RecordHasField(RecordOpKind::ConsiderAllFields) => {
@@ -1837,17 +2089,29 @@ impl fmt::Display for BinaryOp {
             }
             ArrayConcat => write!(f, "array/concat"),
             ArrayAt => write!(f, "array/at"),
+            ArrayZip => write!(f, "array/zip"),
+            ArrayChunk => write!(f, "array/chunk"),
+            ArrayFlatten { .. } => write!(f, "array/flatten"),
+            ArrayFindMap => write!(f, "array/find_map"),
+            ArrayFindIndex => write!(f, "array/find_index"),
+            FunctionCompose => write!(f, "function/compose"),
             Merge(_) => write!(f, "merge"),
             Hash => write!(f, "hash"),
             Serialize => write!(f, "serialize"),
             Deserialize => write!(f, "deserialize"),
             StringSplit => write!(f, "string/split"),
             StringContains => write!(f, "string/contains"),
+            StringWrap => write!(f, "string/wrap"),
+            StringIndent => write!(f, "string/indent"),
+            StringFormat => write!(f, "string/format"),
+            StringToBytes => write!(f, "string/to_bytes"),
+            StringFromBytes => write!(f, "string/from_bytes"),
             Seal => write!(f, "seal"),
             ContractArrayLazyApp => write!(f, "contract/array_lazy_apply"),
             ContractRecordLazyApp => write!(f, "contract/record_lazy_apply"),
             LabelWithMessage => write!(f, "label/with_message"),
             LabelWithNotes => write!(f, "label/with_notes"),
+            LabelWithErrorData => write!(f, "label/with_error_data"),
             LabelAppendNote => write!(f, "label/append_note"),
             LabelLookupTypeVar => write!(f, "label/lookup_type_variable"),
         }
@@ -1856,7 +2120,7 @@ impl fmt::Display for BinaryOp {
 
 /// Primitive n-ary operators. Unary and binary operator make up for most of operators and are
 /// hence special cased. `NAryOp` handles strict operations of arity greater than 2.
-#[derive(Clone, Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub enum NAryOp {
     /// Replace a substring by another one in a string.
     StringReplace,
@@ -1867,6 +2131,13 @@ pub enum NAryOp {
     /// Return a substring of an original string.
     StringSubstr,
 
+    /// Pad a string on the left up to a given width, measured in Unicode scalar values. Takes the
+    /// string, the width and the padding, which must be a single character.
+    StringPadStart,
+
+    /// Same as [`NAryOp::StringPadStart`], but pads the string on the right.
+    StringPadEnd,
+
     /// The merge operator in contract mode (see [crate::eval::merge]). The arguments are in order
     /// the contract's label, the value to check, and the contract as a record.
     MergeContract,
@@ -1904,6 +2175,50 @@ pub enum NAryOp {
     /// hood, as long as the array isn't modified later, this operation is constant in time and
     /// memory.
     ArraySlice,
+
+    /// Combine the elements of two arrays pairwise using a function. As for
+    /// [`BinaryOp::ArrayZip`], the result is truncated to the length of the shortest array.
+    ///
+    /// Takes three arguments: the function, the first array and the second array.
+    ArrayZipWith,
+
+    /// Compute the successive accumulators of a left fold over an array. The initial accumulator
+    /// isn't part of the result, which has the same length as the array. The accumulators are
+    /// lazy: each one is only computed when it's needed.
+    ///
+    /// Takes three arguments: the function, the initial accumulator and the array.
+    ArrayScan,
+
+    /// Rename a field of a record. The field keeps its metadata and its pending contracts. It's
+    /// an error if the record already has a field with the new name, unless the new name is the
+    /// same as the old one.
+    ///
+    /// Takes three arguments: the old name, the new name and the record.
+    RecordRename,
+
+    /// Internal operation used by [`BinaryOp::RecordRenameKeys`]. Takes an array of old names, an
+    /// array of new names of the same length, and a record. All the fields are renamed at once, so
+    /// that two fields can swap their names. The elements of the arrays must have been evaluated
+    /// beforehand.
+    RecordRenameAll,
+
+    /// Internal operation used by [`BinaryOp::ArrayFindIndex`]. Takes a predicate, an array and
+    /// the index that the first element of the array had in the original array. Tests the first
+    /// element, and continues with the rest of the array if it doesn't satisfy the predicate.
+    ArrayFindIndexFrom,
+
+    /// Restrict a number to a range. Takes three arguments: the number, the lower bound and the
+    /// upper bound. It's an error if the lower bound is greater than the upper bound.
+    NumberClamp,
+
+    /// Insert a field together with its metadata into a record. Takes five arguments: the name of
+    /// the field, its documentation (a string or `null`), its priority (a number, `'Default`,
+    /// `'Force` or `null`), its contracts (an array or `null`) and the record. As for
+    /// [`BinaryOp::RecordInsert`], the value of the field is then given as an additional argument.
+    ///
+    /// Contrary to a plain `record/insert`, the contracts are both recorded in the metadata of the
+    /// new field, where they can be queried, and enforced on its value.
+    RecordInsertWithMetadata,
 }
 
 impl NAryOp {
@@ -1912,11 +2227,20 @@ impl NAryOp {
             NAryOp::StringReplace
             | NAryOp::StringReplaceRegex
             | NAryOp::StringSubstr
+            | NAryOp::StringPadStart
+            | NAryOp::StringPadEnd
             | NAryOp::MergeContract
             | NAryOp::RecordUnsealTail
             | NAryOp::LabelInsertTypeVar
-            | NAryOp::ArraySlice => 3,
+            | NAryOp::ArraySlice
+            | NAryOp::ArrayZipWith
+            | NAryOp::ArrayScan
+            | NAryOp::RecordRename
+            | NAryOp::RecordRenameAll
+            | NAryOp::ArrayFindIndexFrom
+            | NAryOp::NumberClamp => 3,
             NAryOp::RecordSealTail => 4,
+            NAryOp::RecordInsertWithMetadata => 5,
         }
     }
 }
@@ -1928,11 +2252,20 @@ impl fmt::Display for NAryOp {
             StringReplace => write!(f, "string/replace"),
             StringReplaceRegex => write!(f, "string/replace_regex"),
             StringSubstr => write!(f, "string/substr"),
+            StringPadStart => write!(f, "string/pad_start"),
+            StringPadEnd => write!(f, "string/pad_end"),
             MergeContract => write!(f, "merge_contract"),
             RecordSealTail => write!(f, "record/seal_tail"),
             RecordUnsealTail => write!(f, "record/unseal_tail"),
             LabelInsertTypeVar => write!(f, "label/insert_type_variable"),
             ArraySlice => write!(f, "array/slice"),
+            ArrayZipWith => write!(f, "array/zip_with"),
+            ArrayScan => write!(f, "array/scan"),
+            RecordRename => write!(f, "record/rename"),
+            RecordRenameAll => write!(f, "record/rename_all"),
+            ArrayFindIndexFrom => write!(f, "array/find_index_from"),
+            NumberClamp => write!(f, "number/clamp"),
+            RecordInsertWithMetadata => write!(f, "record/insert_with_metadata"),
         }
     }
 }
@@ -190,6 +190,7 @@ fn update_with_merge(record_id: LocIdent, id: LocIdent, field: Field) -> RichTer
     let merge_label = MergeLabel {
         span,
         kind: MergeKind::Standard,
+        field_path: Vec::new(),
     };
 
     make::op2(
@@ -218,6 +218,44 @@ impl Field {
             ..self
         }
     }
+
+    /// Prepend `prefix` to the path of the enclosing records stored in the labels of both the
+    /// annotations and the pending contracts of this field.
+    pub fn with_field_path_prefix(self, prefix: &[LocIdent]) -> Self {
+        Field {
+            metadata: FieldMetadata {
+                annotation: self.metadata.annotation.with_field_path_prefix(prefix),
+                ..self.metadata
+            },
+            pending_contracts: self
+                .pending_contracts
+                .into_iter()
+                .map(|ctr| RuntimeContract {
+                    label: ctr.label.with_field_path_prefix(prefix),
+                    ..ctr
+                })
+                .collect(),
+            ..self
+        }
+    }
+
+    /// Replace the path of the enclosing records stored in the labels of both the annotations and
+    /// the pending contracts of this field with `path`.
+    pub fn with_field_path(mut self, path: &[LocIdent]) -> Self {
+        let annotation = &mut self.metadata.annotation;
+        let labels = annotation
+            .typ
+            .iter_mut()
+            .chain(annotation.contracts.iter_mut())
+            .map(|labeled_ty| &mut labeled_ty.label)
+            .chain(self.pending_contracts.iter_mut().map(|ctr| &mut ctr.label));
+
+        for label in labels {
+            label.field_path = path.to_vec();
+        }
+
+        self
+    }
 }
 
 impl Traverse<RichTerm> for Field {
@@ -144,6 +144,31 @@ impl NickelString {
         }
     }
 
+    /// Splits the string into lines, returning an [`Array`] of Nickel strings.
+    ///
+    /// Lines are terminated by `\n`, `\r\n` or `\r`, and the terminators aren't part of the
+    /// result. A terminator at the very end of the string doesn't produce an additional empty
+    /// line. As `\r\n` is a single grapheme cluster, this never splits up grapheme clusters.
+    pub fn split_lines(&self) -> Array {
+        let mut result = Vec::new();
+        let mut rest = self.as_str();
+
+        while !rest.is_empty() {
+            let (line, next) = match rest.find(['\n', '\r']) {
+                Some(idx) => {
+                    let next = rest[idx..].strip_prefix("\r\n").unwrap_or(&rest[idx + 1..]);
+                    (&rest[..idx], next)
+                }
+                None => (rest, ""),
+            };
+
+            result.push(Term::Str(line.into()).into());
+            rest = next;
+        }
+
+        Array::from_iter(result)
+    }
+
     /// Returns `true` if `needle` is contained in `self`, and `false` otherwise.
     ///
     /// Note that in contrast to Rust `String`'s `contains` method, this method
@@ -342,12 +367,135 @@ impl NickelString {
         })
     }
 
+    /// Wraps the string at word boundaries, such that no line is longer than `width`. Contrary to
+    /// most other methods, lengths are measured in Unicode scalar values.
+    ///
+    /// Existing newlines are preserved as hard breaks. Within a line, words are separated by a
+    /// single space in the result, and words longer than `width` are broken into chunks of
+    /// `width` scalar values.
+    ///
+    /// `width` must be non-zero. The time complexity of this method is `O(self.len())`.
+    pub fn wrap(&self, width: usize) -> NickelString {
+        debug_assert!(width > 0);
+
+        let mut result = String::with_capacity(self.len());
+
+        for (idx, line) in self.0.split('\n').enumerate() {
+            if idx > 0 {
+                result.push('\n');
+            }
+
+            // The number of scalar values on the current output line.
+            let mut column = 0;
+
+            for word in line.split_whitespace() {
+                let word_len = word.chars().count();
+
+                if column > 0 && column + 1 + word_len <= width {
+                    result.push(' ');
+                    result.push_str(word);
+                    column += 1 + word_len;
+                    continue;
+                }
+
+                if column > 0 {
+                    result.push('\n');
+                    column = 0;
+                }
+
+                for c in word.chars() {
+                    if column == width {
+                        result.push('\n');
+                        column = 0;
+                    }
+
+                    result.push(c);
+                    column += 1;
+                }
+            }
+        }
+
+        result.into()
+    }
+
+    /// Pads the string on the left with `pad` until it is `width` long. As for [Self::wrap],
+    /// lengths are measured in Unicode scalar values. If the string is already at least `width`
+    /// long, it is returned unchanged.
+    pub fn pad_start(&self, width: usize, pad: char) -> NickelString {
+        let padding = width.saturating_sub(self.0.chars().count());
+        std::iter::repeat(pad)
+            .take(padding)
+            .chain(self.0.chars())
+            .collect::<String>()
+            .into()
+    }
+
+    /// Same as [Self::pad_start], but pads the string on the right.
+    pub fn pad_end(&self, width: usize, pad: char) -> NickelString {
+        let padding = width.saturating_sub(self.0.chars().count());
+        self.0
+            .chars()
+            .chain(std::iter::repeat(pad).take(padding))
+            .collect::<String>()
+            .into()
+    }
+
+    /// Prefixes each line with `prefix`. Blank lines, which only contain whitespace, are left
+    /// unchanged, and so are line endings.
+    pub fn indent(&self, prefix: &str) -> NickelString {
+        self.0
+            .split_inclusive('\n')
+            .flat_map(|line| [if is_blank(line) { "" } else { prefix }, line])
+            .collect::<String>()
+            .into()
+    }
+
+    /// Removes the longest prefix of spaces and tabs common to all the non-blank lines. Blank
+    /// lines are kept, stripped of their leading whitespace up to the common prefix. Line endings
+    /// are preserved.
+    pub fn dedent(&self) -> NickelString {
+        let common = self
+            .0
+            .split_inclusive('\n')
+            .filter(|line| !is_blank(line))
+            .map(leading_whitespace)
+            .reduce(|acc, ws| {
+                // Leading whitespace is ASCII, so any byte index is a char boundary.
+                let len = acc
+                    .bytes()
+                    .zip(ws.bytes())
+                    .take_while(|(c1, c2)| c1 == c2)
+                    .count();
+                &acc[..len]
+            })
+            .unwrap_or_default();
+
+        self.0
+            .split_inclusive('\n')
+            .map(|line| {
+                line.strip_prefix(common)
+                    .unwrap_or_else(|| line.trim_start_matches([' ', '\t']))
+            })
+            .collect::<String>()
+            .into()
+    }
+
     /// Consumes `self`, returning the Rust `String`.
     pub fn into_inner(self) -> String {
         self.0
     }
 }
 
+/// Whether a line only contains whitespace.
+fn is_blank(line: &str) -> bool {
+    line.trim().is_empty()
+}
+
+/// The spaces and tabs at the beginning of a line.
+fn leading_whitespace(line: &str) -> &str {
+    &line[..line.len() - line.trim_start_matches([' ', '\t']).len()]
+}
+
 impl Default for NickelString {
     fn default() -> Self {
         Self::new()
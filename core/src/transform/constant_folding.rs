@@ -0,0 +1,54 @@
+//! Constant folding.
+//!
+//! Evaluate ahead of time the arithmetic operations and the string concatenations whose operands
+//! are literals: `1 + 2` is rewritten to `3`, and `"a" ++ "b"` to `"ab"`. The folded literal takes
+//! the position of the original operation, so that diagnostics still point to the same place in
+//! the source.
+//!
+//! Only operations which can't fail are folded. For example, a division by zero is left as it
+//! is, and reported at evaluation time as usual. Because folding only ever applies to literals,
+//! expressions with side effects, such as `trace`, are never touched.
+//!
+//! This transformation isn't part of [super::transform]. It's meant to be applied on demand, for
+//! example to simplify generated configurations.
+use std::convert::Infallible;
+
+use crate::term::{
+    string::NickelString, BinaryOp, Number, RichTerm, Term, Traverse, TraverseOrder,
+};
+
+/// Fold all the constant operations of `rt`, including the ones exposed by folding nested
+/// operations, as in `(1 + 2) * 3`.
+pub fn transform(rt: RichTerm) -> RichTerm {
+    rt.traverse(
+        &mut |rt: RichTerm| -> Result<RichTerm, Infallible> { Ok(transform_one(rt)) },
+        TraverseOrder::BottomUp,
+    )
+    .unwrap()
+}
+
+/// Fold the operation at the root of `rt`, if its operands are literals. As other `transform_one`
+/// variants, this transformation isn't recursive.
+pub fn transform_one(rt: RichTerm) -> RichTerm {
+    let folded = match rt.as_ref() {
+        Term::Op2(op, t1, t2) => match (op, t1.as_ref(), t2.as_ref()) {
+            (BinaryOp::Plus, Term::Num(n1), Term::Num(n2)) => Some(Term::Num(n1 + n2)),
+            (BinaryOp::Sub, Term::Num(n1), Term::Num(n2)) => Some(Term::Num(n1 - n2)),
+            (BinaryOp::Mult, Term::Num(n1), Term::Num(n2)) => Some(Term::Num(n1 * n2)),
+            (BinaryOp::Div, Term::Num(n1), Term::Num(n2)) if n2 != &Number::ZERO => {
+                Some(Term::Num(n1 / n2))
+            }
+            (BinaryOp::StringConcat, Term::Str(s1), Term::Str(s2)) => {
+                let ss: [&str; 2] = [s1, s2];
+                Some(Term::Str(NickelString::from(ss.concat())))
+            }
+            _ => None,
+        },
+        _ => None,
+    };
+
+    match folded {
+        Some(term) => RichTerm::new(term, rt.pos),
+        None => rt,
+    }
+}
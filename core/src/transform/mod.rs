@@ -6,6 +6,7 @@ use crate::{
     typecheck::Wildcards,
 };
 
+pub mod constant_folding;
 pub mod desugar_destructuring;
 pub mod free_vars;
 pub mod gen_pending_contracts;
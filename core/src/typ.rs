@@ -55,6 +55,8 @@ use crate::{
     },
 };
 
+use serde::{Deserialize, Serialize};
+
 use std::{collections::HashSet, convert::Infallible};
 
 /// A record row, mapping an identifier to a type. A record type is a dictionary mapping
@@ -175,7 +177,7 @@ impl From<&VarKind> for VarKindDiscriminant {
 /// blame, etc.).
 ///
 /// Dictionary contracts might get a proper AST node later on.
-#[derive(Clone, Debug, Copy, Eq, PartialEq)]
+#[derive(Clone, Debug, Copy, Eq, PartialEq, Serialize, Deserialize)]
 pub enum DictTypeFlavour {
     /// Dictionary type (`{_ : T}`)
     Type,
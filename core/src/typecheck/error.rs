@@ -278,6 +278,31 @@ impl UnifError {
         self.into_typecheck_err_(state, &mut names, pos_opt)
     }
 
+    /// Convert a failure to unify the types of the two branches of an if-then-else expression to
+    /// a [`crate::error::TypecheckError::IfBranchesMismatch`], the unification error being kept
+    /// as the cause.
+    ///
+    /// # Parameters
+    ///
+    /// - `then_type`, `else_type`: the types of the `then` and the `else` branches.
+    /// - `pos`: the position of the `else` branch.
+    pub fn into_if_branches_err(
+        self,
+        state: &State,
+        then_type: UnifType,
+        else_type: UnifType,
+        pos: TermPos,
+    ) -> TypecheckError {
+        let mut names = reporting::NameReg::new(state.names.clone());
+
+        TypecheckError::IfBranchesMismatch {
+            then_type: then_type.to_type(&mut names, state.table),
+            else_type: else_type.to_type(&mut names, state.table),
+            cause: Box::new(self.into_typecheck_err_(state, &mut names, TermPos::None)),
+            pos,
+        }
+    }
+
     /// Convert a unification error to a typechecking error, given a populated [name
     /// registry][reporting::NameReg]. Actual meat of the implementation of
     /// [`Self::into_typecheck_err`].
@@ -61,7 +61,7 @@ use crate::{
     stdlib as nickel_stdlib,
     term::{
         record::Field, CustomContract, LabeledType, MatchBranch, RichTerm, StrChunk, Term,
-        Traverse, TraverseOrder, TypeAnnotation,
+        Traverse, TraverseOrder, TypeAnnotation, UnaryOp,
     },
     typ::*,
     {mk_uty_arrow, mk_uty_enum, mk_uty_record, mk_uty_record_row},
@@ -2099,21 +2099,25 @@ fn check<V: TypecheckVisitor>(
                 pat_types,
             ) in with_pat_types.iter()
             {
+                // The bindings of a pattern are only in scope in its own guard and body, and must
+                // not leak into the following branches.
+                let mut branch_ctxt = ctxt.clone();
+
                 if let Some(alias) = &pattern.alias {
                     visitor.visit_ident(alias, return_type.clone());
-                    ctxt.type_env.insert(alias.ident(), return_type.clone());
+                    branch_ctxt.type_env.insert(alias.ident(), return_type.clone());
                 }
 
                 for (id, typ) in pat_types.bindings.iter() {
                     visitor.visit_ident(id, typ.clone());
-                    ctxt.type_env.insert(id.ident(), typ.clone());
+                    branch_ctxt.type_env.insert(id.ident(), typ.clone());
                 }
 
                 if let Some(guard) = guard {
-                    check(state, ctxt.clone(), visitor, guard, mk_uniftype::bool())?;
+                    check(state, branch_ctxt.clone(), visitor, guard, mk_uniftype::bool())?;
                 }
 
-                check(state, ctxt.clone(), visitor, body, return_type.clone())?;
+                check(state, branch_ctxt, visitor, body, return_type.clone())?;
             }
 
             let pat_types = with_pat_types.into_iter().map(|(_, pat_types)| pat_types);
@@ -2228,7 +2232,15 @@ fn check<V: TypecheckVisitor>(
         // for some `a`. In other words, the checking rule is not the same depending on the target
         // type: if the target type is a dictionary type, we simply check each field against the
         // element type.
-        Term::RecRecord(record, dynamic, ..) if !dynamic.is_empty() => {
+        //
+        // Dynamic fields whose name is a parse error don't count: they are produced by the
+        // parser's error recovery on a broken field definition, and we don't want them to turn
+        // an otherwise well-typed record into a dictionary.
+        Term::RecRecord(record, dynamic, ..)
+            if dynamic
+                .iter()
+                .any(|(name, _)| !matches!(name.as_ref(), Term::ParseError(_))) =>
+        {
             let ty_dict = state.table.fresh_type_uvar(ctxt.var_level);
             ty.unify(mk_uniftype::dict(ty_dict.clone()), state, &ctxt)
                 .map_err(|err| err.into_typecheck_err(state, rt.pos))?;
@@ -2654,6 +2666,18 @@ fn infer<V: TypecheckVisitor>(
             Ok(ty_res)
         }
         Term::App(e, t) => {
+            if let Some((cond, then_branch, else_branch)) = as_if_then_else(rt) {
+                return infer_if_then_else(
+                    state,
+                    ctxt,
+                    visitor,
+                    rt,
+                    cond,
+                    then_branch,
+                    else_branch,
+                );
+            }
+
             // If we go the full Quick Look route (cf [quick-look] and the Nickel type system
             // specification), we will have a more advanced and specific rule to guess the
             // instantiation of the potentially polymorphic type of the head of the application.
@@ -2692,6 +2716,56 @@ fn infer<V: TypecheckVisitor>(
     }
 }
 
+/// Decompose an if-then-else expression, which is encoded as the application `(%if_then_else%
+/// cond) then_branch else_branch`, into its condition and its two branches.
+fn as_if_then_else(rt: &RichTerm) -> Option<(&RichTerm, &RichTerm, &RichTerm)> {
+    let Term::App(head, else_branch) = rt.as_ref() else {
+        return None;
+    };
+    let Term::App(head, then_branch) = head.as_ref() else {
+        return None;
+    };
+
+    match head.as_ref() {
+        Term::Op1(UnaryOp::IfThenElse, cond) => Some((cond, then_branch, else_branch)),
+        _ => None,
+    }
+}
+
+/// Infer the type of an if-then-else expression.
+///
+/// While the generic rule for applications would work, typing the if-then-else as a function of
+/// type `Bool -> a -> a -> a`, it would report a conflict between the branches as a mismatch
+/// between the `else` branch and some argument type. Instead, we infer the type of each branch
+/// separately, and unify them afterwards, so that a conflict can be reported as such.
+fn infer_if_then_else<V: TypecheckVisitor>(
+    state: &mut State,
+    ctxt: Context,
+    visitor: &mut V,
+    rt: &RichTerm,
+    cond: &RichTerm,
+    then_branch: &RichTerm,
+    else_branch: &RichTerm,
+) -> Result<UnifType, TypecheckError> {
+    let then_type = state.table.fresh_type_uvar(ctxt.var_level);
+    let else_type = state.table.fresh_type_uvar(ctxt.var_level);
+
+    visitor.visit_term(rt, then_type.clone());
+
+    check(state, ctxt.clone(), visitor, cond, mk_uniftype::bool())?;
+    check(state, ctxt.clone(), visitor, then_branch, then_type.clone())?;
+    check(state, ctxt.clone(), visitor, else_branch, else_type.clone())?;
+
+    then_type
+        .clone()
+        .unify(else_type.clone(), state, &ctxt)
+        .map_err(|err| {
+            err.into_if_branches_err(state, then_type.clone(), else_type, else_branch.pos)
+        })?;
+
+    Ok(then_type)
+}
+
 /// Determine the type of a let-bound expression.
 ///
 /// Call [`apparent_type`] to see if the binding is annotated. If it is, return this type as a
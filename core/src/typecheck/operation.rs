@@ -153,6 +153,14 @@ pub fn get_uop_type(
             let ty_elt = state.table.fresh_type_uvar(var_level);
             (mk_uniftype::array(ty_elt), mk_uniftype::num())
         }
+        // forall a. Array a -> Array a
+        UnaryOp::ArrayShuffle | UnaryOp::ArrayDedup => {
+            let ty_elt = state.table.fresh_type_uvar(var_level);
+            (
+                mk_uniftype::array(ty_elt.clone()),
+                mk_uniftype::array(ty_elt),
+            )
+        }
         // This should not happen, as ChunksConcat() is only produced during evaluation.
         UnaryOp::ChunksConcat => panic!("cannot type ChunksConcat()"),
         // forall a. { _: a } -> Array Str
@@ -170,10 +178,38 @@ pub fn get_uop_type(
 
             (mk_uniftype::dict(ty_a.clone()), mk_uniftype::array(ty_a))
         }
+        // forall a. { _: a } -> Array { field: String, value: a }
+        UnaryOp::RecordToEntries => {
+            let ty_a = state.table.fresh_type_uvar(var_level);
+
+            (
+                mk_uniftype::dict(ty_a.clone()),
+                mk_uniftype::array(mk_uty_record!(("field", TypeF::String), ("value", ty_a))),
+            )
+        }
+        // forall a. Array { field: String, value: a } -> { _: a }
+        UnaryOp::RecordFromEntries => {
+            let ty_a = state.table.fresh_type_uvar(var_level);
+
+            (
+                mk_uniftype::array(mk_uty_record!(
+                    ("field", TypeF::String),
+                    ("value", ty_a.clone())
+                )),
+                mk_uniftype::dict(ty_a),
+            )
+        }
+        // Array { _: Dyn } -> { _: Dyn }
+        UnaryOp::RecordMergeAll => (
+            mk_uniftype::array(mk_uniftype::dict(mk_uniftype::dynamic())),
+            mk_uniftype::dict(mk_uniftype::dynamic()),
+        ),
         // Str -> Str
-        UnaryOp::StringTrim => (mk_uniftype::str(), mk_uniftype::str()),
+        UnaryOp::StringTrim | UnaryOp::StringDedent => (mk_uniftype::str(), mk_uniftype::str()),
         // Str -> Array Str
         UnaryOp::StringChars => (mk_uniftype::str(), mk_uniftype::array(mk_uniftype::str())),
+        // Str -> Array Str
+        UnaryOp::StringSplitLines => (mk_uniftype::str(), mk_uniftype::array(mk_uniftype::str())),
         // Str -> Str
         UnaryOp::StringUppercase => (mk_uniftype::str(), mk_uniftype::str()),
         // Str -> Str
@@ -184,6 +220,10 @@ pub fn get_uop_type(
         UnaryOp::ToString => (mk_uniftype::dynamic(), mk_uniftype::str()),
         // Str -> Num
         UnaryOp::NumberFromString => (mk_uniftype::str(), mk_uniftype::num()),
+        // Str -> Num
+        UnaryOp::DurationFromString => (mk_uniftype::str(), mk_uniftype::num()),
+        // Num -> Str
+        UnaryOp::DurationToString => (mk_uniftype::num(), mk_uniftype::str()),
         // Str -> < | a> for a rigid type variable a
         UnaryOp::EnumFromString => (
             mk_uniftype::str(),
@@ -239,6 +279,8 @@ pub fn get_uop_type(
         ),
         // Dyn -> Dyn
         UnaryOp::Force { .. } => (mk_uniftype::dynamic(), mk_uniftype::dynamic()),
+        // This should not happen, as Profile() is only produced during evaluation.
+        UnaryOp::Profile(_) => panic!("cannot type Profile()"),
         // forall a. a -> a
         UnaryOp::RecDefault => {
             let ty = state.table.fresh_type_uvar(var_level);
@@ -276,6 +318,14 @@ pub fn get_uop_type(
         // Same as `EnumGetArg` just above.
         // Dyn -> Dyn
         UnaryOp::EnumGetTag => (mk_uniftype::dynamic(), mk_uniftype::dynamic()),
+        // The argument of an enum variant isn't known statically, and so isn't the type of the
+        // record.
+        // Dyn -> Dyn
+        UnaryOp::EnumToRecord => (mk_uniftype::dynamic(), mk_uniftype::dynamic()),
+        // Dyn -> Dyn
+        UnaryOp::EnumFromRecord => (mk_uniftype::dynamic(), mk_uniftype::dynamic()),
+        // Dyn -> Array Str
+        UnaryOp::EnumVariants => (mk_uniftype::dynamic(), mk_uniftype::array(TypeF::String)),
         // Note that is_variant breaks parametricity, so it can't get a polymorphic type.
         // Dyn -> Bool
         UnaryOp::EnumIsVariant => (mk_uniftype::dynamic(), mk_uniftype::bool()),
@@ -380,6 +430,52 @@ pub fn get_bop_type(
                 mk_uniftype::dict(res),
             )
         }
+        // forall a. (Str -> Bool) -> { _ : a } -> { _ : a }
+        BinaryOp::RecordFilterKeys => {
+            let res = state.table.fresh_type_uvar(var_level);
+            (
+                mk_uty_arrow!(mk_uniftype::str(), mk_uniftype::bool()),
+                mk_uniftype::dict(res.clone()),
+                mk_uniftype::dict(res),
+            )
+        }
+        // forall a. Array Bool -> { _ : a } -> { _ : a }
+        BinaryOp::RecordFilterKeysMasked => {
+            let res = state.table.fresh_type_uvar(var_level);
+            (
+                mk_uniftype::array(mk_uniftype::bool()),
+                mk_uniftype::dict(res.clone()),
+                mk_uniftype::dict(res),
+            )
+        }
+        // forall a. { _ : Str } -> { _ : a } -> { _ : a }
+        BinaryOp::RecordRenameKeys => {
+            let res = state.table.fresh_type_uvar(var_level);
+            (
+                mk_uniftype::dict(mk_uniftype::str()),
+                mk_uniftype::dict(res.clone()),
+                mk_uniftype::dict(res),
+            )
+        }
+        // forall a. (Str -> Str) -> { _ : a } -> { _ : a }
+        BinaryOp::RecordMapKeys => {
+            let res = state.table.fresh_type_uvar(var_level);
+            (
+                mk_uniftype::arrow(mk_uniftype::str(), mk_uniftype::str()),
+                mk_uniftype::dict(res.clone()),
+                mk_uniftype::dict(res),
+            )
+        }
+        // forall a b. { _ : a } -> { _ : b } -> { _ : a }
+        BinaryOp::RecordDifference | BinaryOp::RecordIntersection => {
+            let res = state.table.fresh_type_uvar(var_level);
+            let other = state.table.fresh_type_uvar(var_level);
+            (
+                mk_uniftype::dict(res.clone()),
+                mk_uniftype::dict(other),
+                mk_uniftype::dict(res),
+            )
+        }
         // forall a. Str -> {_: a} -> Bool
         BinaryOp::RecordHasField(_) => {
             let ty_elt = state.table.fresh_type_uvar(var_level);
@@ -413,6 +509,67 @@ pub fn get_bop_type(
                 ty_elt,
             )
         }
+        // forall a b. Array a -> Array b -> Array {_1: a, _2: b}
+        BinaryOp::ArrayZip => {
+            let a = state.table.fresh_type_uvar(var_level);
+            let b = state.table.fresh_type_uvar(var_level);
+
+            (
+                mk_uniftype::array(a.clone()),
+                mk_uniftype::array(b.clone()),
+                mk_uniftype::array(mk_uty_record!(("_1", a), ("_2", b))),
+            )
+        }
+        // forall a. Number -> Array a -> Array (Array a)
+        BinaryOp::ArrayChunk => {
+            let ty_array = mk_uniftype::array(state.table.fresh_type_uvar(var_level));
+
+            (
+                mk_uniftype::num(),
+                ty_array.clone(),
+                mk_uniftype::array(ty_array),
+            )
+        }
+        // Number -> Array Dyn -> Array Dyn
+        BinaryOp::ArrayFlatten { .. } => (
+            mk_uniftype::num(),
+            mk_uniftype::array(mk_uniftype::dynamic()),
+            mk_uniftype::array(mk_uniftype::dynamic()),
+        ),
+        // forall a b. (a -> [| 'Some b, 'None |]) -> Array a -> [| 'Some b, 'None |]
+        BinaryOp::ArrayFindMap => {
+            let a = state.table.fresh_type_uvar(var_level);
+            let b = state.table.fresh_type_uvar(var_level);
+            let res = mk_uty_enum!(("Some", b), "None");
+
+            (
+                mk_uty_arrow!(a.clone(), res.clone()),
+                mk_uniftype::array(a),
+                res,
+            )
+        }
+        // forall a. (a -> Bool) -> Array a -> [| 'Some Number, 'None |]
+        BinaryOp::ArrayFindIndex => {
+            let a = state.table.fresh_type_uvar(var_level);
+
+            (
+                mk_uty_arrow!(a.clone(), mk_uniftype::bool()),
+                mk_uniftype::array(a),
+                mk_uty_enum!(("Some", mk_uniftype::num()), "None"),
+            )
+        }
+        // forall a b c. (b -> c) -> (a -> b) -> a -> c
+        BinaryOp::FunctionCompose => {
+            let a = state.table.fresh_type_uvar(var_level);
+            let b = state.table.fresh_type_uvar(var_level);
+            let c = state.table.fresh_type_uvar(var_level);
+
+            (
+                mk_uty_arrow!(b.clone(), c.clone()),
+                mk_uty_arrow!(a.clone(), b),
+                mk_uty_arrow!(a, c),
+            )
+        }
         // Dyn -> Dyn -> Dyn
         BinaryOp::Merge(_) => (
             mk_uniftype::dynamic(),
@@ -441,9 +598,33 @@ pub fn get_bop_type(
             mk_uniftype::dynamic(),
         ),
         // Num -> Num -> Num
-        BinaryOp::Pow => (mk_uniftype::num(), mk_uniftype::num(), mk_uniftype::num()),
+        BinaryOp::Pow | BinaryOp::NumberMin | BinaryOp::NumberMax => {
+            (mk_uniftype::num(), mk_uniftype::num(), mk_uniftype::num())
+        }
         // Str -> Str -> Bool
         BinaryOp::StringContains => (mk_uniftype::str(), mk_uniftype::str(), mk_uniftype::bool()),
+        // Str -> Num -> Str
+        BinaryOp::StringWrap => (mk_uniftype::str(), mk_uniftype::num(), mk_uniftype::str()),
+        // Str -> Str -> Str
+        BinaryOp::StringIndent => (mk_uniftype::str(), mk_uniftype::str(), mk_uniftype::str()),
+        // Str -> { _ : Dyn } -> Str
+        BinaryOp::StringFormat => (
+            mk_uniftype::str(),
+            mk_uniftype::dict(mk_uniftype::dynamic()),
+            mk_uniftype::str(),
+        ),
+        // <Utf8, Utf16, Latin1> -> Str -> Array Num
+        BinaryOp::StringToBytes => (
+            mk_uty_enum!("Utf8", "Utf16", "Latin1"),
+            mk_uniftype::str(),
+            mk_uniftype::array(TypeF::Number),
+        ),
+        // <Utf8, Utf16, Latin1> -> Array Num -> Str
+        BinaryOp::StringFromBytes => (
+            mk_uty_enum!("Utf8", "Utf16", "Latin1"),
+            mk_uniftype::array(TypeF::Number),
+            mk_uniftype::str(),
+        ),
         // Str -> Str -> Array Str
         BinaryOp::StringSplit => (
             mk_uniftype::str(),
@@ -486,6 +667,13 @@ pub fn get_bop_type(
             mk_uniftype::dynamic(),
             mk_uniftype::dynamic(),
         ),
+        // Morally: Dyn -> Lbl -> Lbl
+        // Actual: Dyn -> Dyn -> Dyn
+        BinaryOp::LabelWithErrorData => (
+            mk_uniftype::dynamic(),
+            mk_uniftype::dynamic(),
+            mk_uniftype::dynamic(),
+        ),
         // Morally: Str -> Lbl -> Lbl
         // Actual: Str -> Dyn -> Dyn
         BinaryOp::LabelAppendNote => (
@@ -514,11 +702,21 @@ pub fn get_nop_type(
             vec![mk_uniftype::str(), mk_uniftype::str(), mk_uniftype::str()],
             mk_uniftype::str(),
         ),
+        // Num -> Num -> Num -> Num
+        NAryOp::NumberClamp => (
+            vec![mk_uniftype::num(), mk_uniftype::num(), mk_uniftype::num()],
+            mk_uniftype::num(),
+        ),
         // Str -> Num -> Num -> Str
         NAryOp::StringSubstr => (
             vec![mk_uniftype::str(), mk_uniftype::num(), mk_uniftype::num()],
             mk_uniftype::str(),
         ),
+        // Str -> Num -> Str -> Str
+        NAryOp::StringPadStart | NAryOp::StringPadEnd => (
+            vec![mk_uniftype::str(), mk_uniftype::num(), mk_uniftype::str()],
+            mk_uniftype::str(),
+        ),
         // Dyn -> Dyn -> Dyn -> Dyn -> Dyn
         NAryOp::RecordSealTail => (
             vec![
@@ -551,6 +749,89 @@ pub fn get_nop_type(
                 mk_uniftype::array(element_type),
             )
         }
+        // forall a b c. (a -> b -> c) -> Array a -> Array b -> Array c
+        NAryOp::ArrayZipWith => {
+            let a = state.table.fresh_type_uvar(var_level);
+            let b = state.table.fresh_type_uvar(var_level);
+            let c = state.table.fresh_type_uvar(var_level);
+
+            (
+                vec![
+                    mk_uty_arrow!(a.clone(), b.clone(), c.clone()),
+                    mk_uniftype::array(a),
+                    mk_uniftype::array(b),
+                ],
+                mk_uniftype::array(c),
+            )
+        }
+        // forall a b. (b -> a -> b) -> b -> Array a -> Array b
+        NAryOp::ArrayScan => {
+            let a = state.table.fresh_type_uvar(var_level);
+            let b = state.table.fresh_type_uvar(var_level);
+
+            (
+                vec![
+                    mk_uty_arrow!(b.clone(), a.clone(), b.clone()),
+                    b.clone(),
+                    mk_uniftype::array(a),
+                ],
+                mk_uniftype::array(b),
+            )
+        }
+        // forall a. Str -> Str -> { _ : a } -> { _ : a }
+        NAryOp::RecordRename => {
+            let res = state.table.fresh_type_uvar(var_level);
+
+            (
+                vec![
+                    mk_uniftype::str(),
+                    mk_uniftype::str(),
+                    mk_uniftype::dict(res.clone()),
+                ],
+                mk_uniftype::dict(res),
+            )
+        }
+        // forall a. Array Str -> Array Str -> { _ : a } -> { _ : a }
+        NAryOp::RecordRenameAll => {
+            let res = state.table.fresh_type_uvar(var_level);
+
+            (
+                vec![
+                    mk_uniftype::array(mk_uniftype::str()),
+                    mk_uniftype::array(mk_uniftype::str()),
+                    mk_uniftype::dict(res.clone()),
+                ],
+                mk_uniftype::dict(res),
+            )
+        }
+        // forall a. Str -> Dyn -> Dyn -> Dyn -> { _ : a } -> a -> { _ : a }
+        NAryOp::RecordInsertWithMetadata => {
+            let res = state.table.fresh_type_uvar(var_level);
+
+            (
+                vec![
+                    mk_uniftype::str(),
+                    mk_uniftype::dynamic(),
+                    mk_uniftype::dynamic(),
+                    mk_uniftype::dynamic(),
+                    mk_uniftype::dict(res.clone()),
+                ],
+                mk_uty_arrow!(res.clone(), mk_uniftype::dict(res)),
+            )
+        }
+        // forall a. (a -> Bool) -> Array a -> Number -> [| 'Some Number, 'None |]
+        NAryOp::ArrayFindIndexFrom => {
+            let a = state.table.fresh_type_uvar(var_level);
+
+            (
+                vec![
+                    mk_uty_arrow!(a.clone(), mk_uniftype::bool()),
+                    mk_uniftype::array(a),
+                    mk_uniftype::num(),
+                ],
+                mk_uty_enum!(("Some", mk_uniftype::num()), "None"),
+            )
+        }
         // This should not happen, as MergeContract() is only produced during evaluation.
         NAryOp::MergeContract => panic!("cannot typecheck MergeContract()"),
         // Morally: Sym -> Polarity -> Lbl -> Lbl
@@ -0,0 +1,48 @@
+use nickel_lang_core::{
+    term::{Number, RichTerm, Term},
+    transform::constant_folding,
+};
+
+use nickel_lang_utils::test_program::parse;
+
+fn fold(s: &str) -> RichTerm {
+    constant_folding::transform(parse(s).unwrap())
+}
+
+#[track_caller]
+fn assert_folds_to(s: &str, expected: &str) {
+    assert_eq!(
+        fold(s).without_pos(),
+        parse(expected).unwrap().without_pos()
+    );
+}
+
+#[test]
+fn folds_literals() {
+    assert_folds_to("1 + 2 * 3", "7");
+    assert_folds_to("(10 - 4) / 4", "1.5");
+    assert_folds_to(r#""a" ++ "b" ++ "c""#, r#""abc""#);
+    assert_folds_to("[1 + 1, { foo = 2 * 2 }]", "[2, { foo = 4 }]");
+}
+
+#[test]
+fn leaves_other_terms_alone() {
+    // Only operations on literals are folded.
+    assert_folds_to("let x = 1 in x + (2 - 1)", "let x = 1 in x + 1");
+    assert_folds_to(r#"1 + "a""#, r#"1 + "a""#);
+    // Operations which fail are left to the evaluator.
+    assert_folds_to("1 / 0", "1 / 0");
+    // So are side effects.
+    assert_folds_to(r#"(%trace% "a" 1) + 1"#, r#"(%trace% "a" 1) + 1"#);
+}
+
+#[test]
+fn keeps_positions() {
+    let rt = parse("(1 + 2) * 3").unwrap();
+    let pos = rt.pos;
+    let folded = constant_folding::transform(rt);
+
+    assert_eq!(folded.as_ref(), &Term::Num(Number::from(9)));
+    assert!(pos.is_def());
+    assert_eq!(folded.pos, pos);
+}
@@ -60,3 +60,36 @@ fn dictionary_contracts_label_path_is_set_correctly() {
         err => panic!("expected blame error, got {err:#?}"),
     }
 }
+
+#[test]
+fn nested_record_contracts_report_full_field_path() {
+    let programs = [
+        "%force% ({services.web.port = \"80\"} | {services | {web | {port | Number}}}) false",
+        "%force% ({services.web.port = \"80\"} | {services.web | {port | Number}}) false",
+        "%force% ({services.web.port = \"80\"} | {services.web.port | Number}) false",
+        "%force% ({services.web.port = \"80\"} | {services = {web = {port | Number}}}) false",
+        "%force% ({services.web.port = \"80\"} | {services | {web = {port | Number}}}) false",
+        "%force% ({services.web.port = \"80\"} & {services = {web = {port | Number}}}) false",
+    ];
+
+    for program in programs {
+        let res = eval(program);
+        match &res {
+            Err(Error::EvalError(EvalError::BlameError {
+                evaluated_arg: _,
+                ref label,
+                call_stack: _,
+            })) => assert_eq!(
+                label.qualified_field_name().as_deref(),
+                Some("services.web.port")
+            ),
+            err => panic!("expected blame error, got {err:#?}"),
+        }
+
+        let mut files = Files::new();
+        let diagnostics = res.unwrap_err().into_diagnostics(&mut files, None);
+        assert!(diagnostics[0]
+            .message
+            .starts_with("contract broken by the value of `services.web.port`"));
+    }
+}
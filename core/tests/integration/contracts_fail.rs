@@ -1,6 +1,9 @@
 use assert_matches::assert_matches;
 use codespan::Files;
-use nickel_lang_core::error::{Error, EvalError, IntoDiagnostics};
+use nickel_lang_core::{
+    error::{Error, EvalError, IntoDiagnostics},
+    term::Term,
+};
 
 use nickel_lang_utils::test_program::eval;
 
@@ -58,3 +61,40 @@ fn dictionary() {
         err => panic!("expected blame error, got {err:#?}"),
     }
 }
+
+#[test]
+fn matching_regex() {
+    let check = |value: &str| eval(format!("{value} | std.string.MatchingRegex \"^[a-z.]+$\""));
+
+    assert_matches!(check("\"example.org\""), Ok(Term::Str(s)) if s.as_str() == "example.org");
+
+    match check("\"Example.org\"") {
+        Err(Error::EvalError(EvalError::BlameError { label, .. })) => assert_eq!(
+            label
+                .current_diagnostic()
+                .and_then(|diag| diag.message.as_deref()),
+            Some("expected a string matching /^[a-z.]+$/, got \"Example.org\"")
+        ),
+        err => panic!("expected blame error, got {err:?}"),
+    }
+}
+
+#[test]
+fn array_merge() {
+    let blamed_contract = |index: usize| {
+        let res = eval(format!(
+            "let NonZero = std.contract.from_predicate (fun x => x != 0) in \
+            let left | Array Number = [1, 0, \"a\"] in \
+            let right | Array NonZero = [1, 0, \"a\"] in \
+            %array/at% (left & right) {index}"
+        ));
+
+        match res {
+            Err(Error::EvalError(EvalError::BlameError { label, .. })) => label.typ.to_string(),
+            err => panic!("expected blame error, got {err:?}"),
+        }
+    };
+
+    assert_eq!(blamed_contract(1), "Array NonZero");
+    assert_eq!(blamed_contract(2), "Array Number");
+}
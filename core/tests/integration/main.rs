@@ -14,11 +14,16 @@ use nickel_lang_utils::{
 use serde::Deserialize;
 use test_generator::test_resources;
 
+mod constant_folding;
 mod contract_label_path;
 mod free_vars;
 mod pretty;
 mod query;
+#[cfg(feature = "remote-imports")]
+mod remote_import;
+mod sandbox;
 mod stdlib_typecheck;
+mod time_limit;
 
 #[test_resources("core/tests/integration/**/*.ncl")]
 fn check_annotated_nickel_file(path: &str) {
@@ -167,10 +172,14 @@ enum ErrorExpectation {
     EvalMissingFieldDef { field: String },
     #[serde(rename = "EvalError::MergeIncompatibleArgs")]
     EvalMergeIncompatibleArgs,
+    #[serde(rename = "EvalError::MergeRecordWithNonRecord")]
+    EvalMergeRecordWithNonRecord,
     #[serde(rename = "EvalError::NonExhaustiveMatch")]
     EvalNonExhaustiveMatch,
     #[serde(rename = "EvalError::NonExhaustiveEnumMatch")]
     EvalNonExhaustiveEnumMatch,
+    #[serde(rename = "EvalError::ArrayOutOfBounds")]
+    EvalArrayOutOfBounds,
     #[serde(rename = "TypecheckError::UnboundIdentifier")]
     TypecheckUnboundIdentifier { identifier: String },
     #[serde(rename = "TypecheckError::UnboundTypeVariable")]
@@ -200,6 +209,11 @@ enum ErrorExpectation {
     TypecheckMissingDynTail,
     #[serde(rename = "TypecheckError::ArrowTypeMismatch")]
     TypecheckArrowTypeMismatch { cause: Box<ErrorExpectation> },
+    #[serde(rename = "TypecheckError::IfBranchesMismatch")]
+    TypecheckIfBranchesMismatch {
+        then_type: String,
+        else_type: String,
+    },
     #[serde(rename = "TypecheckError::FlatTypeInTermPosition")]
     TypecheckFlatTypeInTermPosition,
     #[serde(rename = "TypecheckError::VarLevelMismatch")]
@@ -241,12 +255,17 @@ impl PartialEq<Error> for ErrorExpectation {
                 EvalMergeIncompatibleArgs,
                 Error::EvalError(EvalError::MergeIncompatibleArgs { .. }),
             )
+            | (
+                EvalMergeRecordWithNonRecord,
+                Error::EvalError(EvalError::MergeRecordWithNonRecord { .. }),
+            )
             | (EvalOther, Error::EvalError(EvalError::Other(..)))
             | (EvalNonExhaustiveMatch, Error::EvalError(EvalError::NonExhaustiveMatch { .. }))
             | (
                 EvalNonExhaustiveEnumMatch,
                 Error::EvalError(EvalError::NonExhaustiveEnumMatch { .. }),
             )
+            | (EvalArrayOutOfBounds, Error::EvalError(EvalError::ArrayOutOfBounds { .. }))
             | (
                 TypecheckRecordRowMismatch,
                 Error::TypecheckError(TypecheckError::RecordRowMismatch { .. }),
@@ -368,11 +387,27 @@ impl PartialEq<Error> for ErrorExpectation {
                 TypecheckArrowTypeMismatch { cause },
                 Error::TypecheckError(TypecheckError::ArrowTypeMismatch { cause: cause2, .. }),
             ) => cause.as_ref() == &Error::TypecheckError((**cause2).clone()),
+            (
+                TypecheckIfBranchesMismatch {
+                    then_type,
+                    else_type,
+                },
+                Error::TypecheckError(TypecheckError::IfBranchesMismatch {
+                    then_type: then_type1,
+                    else_type: else_type1,
+                    ..
+                }),
+            ) => then_type == &then_type1.to_string() && else_type == &else_type1.to_string(),
             // If nothing else matched up to this point, we allow the expected error to appear wrapped inside an `ArrowTypeMismatch`
             //
             (error_exp, Error::TypecheckError(TypecheckError::ArrowTypeMismatch { cause, .. })) => {
                 error_exp == &Error::TypecheckError((**cause).clone())
             }
+            // Same for the cause of an `IfBranchesMismatch`
+            (
+                error_exp,
+                Error::TypecheckError(TypecheckError::IfBranchesMismatch { cause, .. }),
+            ) => error_exp == &Error::TypecheckError((**cause).clone()),
             (_, _) => false,
         }
     }
@@ -396,6 +431,7 @@ impl std::fmt::Display for ErrorExpectation {
             EvalIncomparableValues => "EvalError::IncomparableValues".to_owned(),
             EvalOther => "EvalError::Other".to_owned(),
             EvalMergeIncompatibleArgs => "EvalError::MergeIncompatibleArgs".to_owned(),
+            EvalMergeRecordWithNonRecord => "EvalError::MergeRecordWithNonRecord".to_owned(),
             EvalNAryPrimopTypeError => "EvalError::NAryPrimopTypeError".to_owned(),
             EvalUnaryPrimopTypeError => "EvalError::UnaryPrimopTypeError".to_owned(),
             EvalInfiniteRecursion => "EvalError::InfiniteRecursion".to_owned(),
@@ -410,6 +446,7 @@ impl std::fmt::Display for ErrorExpectation {
             }
             EvalNonExhaustiveMatch => "EvalError::NonExhaustiveMatch".to_owned(),
             EvalNonExhaustiveEnumMatch => "EvalError::NonExhaustiveEnumMatch".to_owned(),
+            EvalArrayOutOfBounds => "EvalError::ArrayOutOfBounds".to_owned(),
             TypecheckUnboundIdentifier { identifier } => {
                 format!("TypecheckError::UnboundIdentifier({identifier})")
             }
@@ -444,6 +481,12 @@ impl std::fmt::Display for ErrorExpectation {
             TypecheckArrowTypeMismatch { cause } => {
                 format!("TypecheckError::ArrowTypeMismatch({cause})")
             }
+            TypecheckIfBranchesMismatch {
+                then_type,
+                else_type,
+            } => {
+                format!("TypecheckError::IfBranchesMismatch({then_type}, {else_type})")
+            }
             TypecheckFlatTypeInTermPosition => "TypecheckError::FlatTypeInTermPosition".to_owned(),
             TypecheckVarLevelMismatch { type_var } => {
                 format!("TypecheckError::VarLevelMismatch({type_var})")
@@ -130,3 +130,29 @@ pub fn test_query_with_wildcard() {
         path,
     );
 }
+
+#[test]
+pub fn test_field_dependencies() {
+    #[track_caller]
+    fn assert_deps(source: &str, path: &str, expected: &[&str]) {
+        let mut program =
+            TestProgram::new_from_source(source.as_bytes(), "regr_tests", std::io::stderr())
+                .unwrap();
+        let path = program.parse_field_path(path.to_owned()).unwrap();
+        let deps: Vec<String> = program
+            .field_dependencies(&path)
+            .unwrap()
+            .into_iter()
+            .map(|dep_path| dep_path.to_string())
+            .collect();
+
+        assert_eq!(deps, expected);
+    }
+
+    assert_deps("{a = 1, b = a + 1, c = 2}", "b", &["a"]);
+    assert_deps("{a = 1, b = a + 1, c = 2}", "a", &[]);
+    assert_deps("{a = 1, b = 2, c = a + b}", "c", &["a", "b"]);
+    assert_deps("{foo = {a = 1, b = a + 1}}", "foo.b", &["foo.a"]);
+    // Only reachable through evaluation
+    assert_deps("let r = {a = 1, b = a + 1} in {foo = r}", "foo.b", &["foo.a"]);
+}
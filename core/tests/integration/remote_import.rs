@@ -0,0 +1,194 @@
+use std::{
+    collections::HashMap,
+    io::{BufRead, BufReader, Write},
+    net::TcpListener,
+    path::Path,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    thread,
+};
+
+use assert_matches::assert_matches;
+use nickel_lang_core::{
+    error::{Error, ImportError},
+    remote_import::RemoteImports,
+    term::Term,
+};
+use nickel_lang_utils::test_program::TestProgram;
+
+/// A minimal HTTP server serving fixed files, each with an `ETag`, and honoring `If-None-Match`.
+struct MockServer {
+    port: u16,
+    /// The number of `304 Not Modified` responses sent so far.
+    not_modified: Arc<AtomicUsize>,
+}
+
+impl MockServer {
+    fn start(files: &[(&str, &str)]) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let files: HashMap<String, String> = files
+            .iter()
+            .map(|(path, content)| (path.to_string(), content.to_string()))
+            .collect();
+        let not_modified = Arc::new(AtomicUsize::new(0));
+        let counter = not_modified.clone();
+
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+
+                let mut request_line = String::new();
+                reader.read_line(&mut request_line).unwrap();
+                let path = request_line
+                    .split(' ')
+                    .nth(1)
+                    .unwrap_or_default()
+                    .to_owned();
+
+                let mut if_none_match = None;
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    let line = line.trim_end();
+
+                    if line.is_empty() {
+                        break;
+                    }
+
+                    if let Some((name, value)) = line.split_once(": ") {
+                        if name.eq_ignore_ascii_case("if-none-match") {
+                            if_none_match = Some(value.to_owned());
+                        }
+                    }
+                }
+
+                let etag = files
+                    .get(&path)
+                    .map(|content| format!("\"{}\"", content.len()));
+                let (status, body) = match files.get(&path) {
+                    Some(_) if if_none_match.is_some() && if_none_match == etag => {
+                        counter.fetch_add(1, Ordering::SeqCst);
+                        ("304 Not Modified", "")
+                    }
+                    Some(content) => ("200 OK", content.as_str()),
+                    None => ("404 Not Found", ""),
+                };
+
+                let mut response = format!("HTTP/1.1 {status}\r\nConnection: close\r\n");
+                if let Some(etag) = etag {
+                    response.push_str(&format!("ETag: {etag}\r\n"));
+                }
+                response.push_str(&format!("Content-Length: {}\r\n\r\n{body}", body.len()));
+
+                stream.write_all(response.as_bytes()).unwrap();
+            }
+        });
+
+        MockServer { port, not_modified }
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("http://127.0.0.1:{}/{path}", self.port)
+    }
+}
+
+fn eval_with_cache(source: &str, cache_dir: &Path, offline: bool) -> Result<Term, Error> {
+    let mut prog =
+        TestProgram::new_from_source(source.as_bytes(), "<test>", std::io::stderr()).unwrap();
+    prog.set_remote_imports(RemoteImports {
+        offline,
+        ..RemoteImports::new(cache_dir)
+    });
+    prog.eval_full().map(|rt| rt.as_ref().clone())
+}
+
+fn lib_server() -> MockServer {
+    MockServer::start(&[
+        ("/lib.ncl", r#"{ value = import "utils/answer.ncl" }"#),
+        ("/utils/answer.ncl", "42"),
+    ])
+}
+
+#[test]
+fn remote_import_is_fetched_and_revalidated() {
+    let server = lib_server();
+    let cache_dir = tempfile::tempdir().unwrap();
+    let source = format!("(import \"{}\").value == 42", server.url("lib.ncl"));
+
+    assert_eq!(
+        eval_with_cache(&source, cache_dir.path(), false).unwrap(),
+        Term::Bool(true)
+    );
+    assert_eq!(server.not_modified.load(Ordering::SeqCst), 0);
+
+    // The second time, the cached files are revalidated instead of being downloaded again.
+    assert_eq!(
+        eval_with_cache(&source, cache_dir.path(), false).unwrap(),
+        Term::Bool(true)
+    );
+    assert_eq!(server.not_modified.load(Ordering::SeqCst), 2);
+}
+
+#[test]
+fn offline_remote_import_uses_cache() {
+    let server = lib_server();
+    let cache_dir = tempfile::tempdir().unwrap();
+    let source = format!("(import \"{}\").value", server.url("lib.ncl"));
+
+    assert_matches!(
+        eval_with_cache(&source, cache_dir.path(), true),
+        Err(Error::ImportError(ImportError::IOError(..)))
+    );
+
+    eval_with_cache(&source, cache_dir.path(), false).unwrap();
+
+    assert_matches!(
+        eval_with_cache(&source, cache_dir.path(), true),
+        Ok(Term::Num(_))
+    );
+}
+
+#[test]
+fn remote_import_checks_pinned_hash() {
+    let server = lib_server();
+    let cache_dir = tempfile::tempdir().unwrap();
+    // The SHA-256 digest of `42`.
+    let digest = "73475cb40a568e8da8a045ced110137e159f890ac4da883b6b17dc651b3a8049";
+
+    let pinned = format!(
+        "import \"{}#sha256={digest}\"",
+        server.url("utils/answer.ncl")
+    );
+    assert_matches!(
+        eval_with_cache(&pinned, cache_dir.path(), false),
+        Ok(Term::Num(_))
+    );
+
+    let wrong = format!("import \"{}#sha256={}\"", server.url("lib.ncl"), digest);
+    assert_matches!(
+        eval_with_cache(&wrong, cache_dir.path(), false),
+        Err(Error::ImportError(ImportError::IOError(_, msg, _))) if msg.contains("pinned hash")
+    );
+}
+
+#[test]
+fn remote_import_cannot_import_local_files() {
+    let local_dir = tempfile::tempdir().unwrap();
+    let local = local_dir.path().join("secret.ncl");
+    std::fs::write(&local, "42").unwrap();
+
+    let server =
+        MockServer::start(&[("/lib.ncl", &format!("import {:?}", local.to_str().unwrap()))]);
+    let cache_dir = tempfile::tempdir().unwrap();
+    let source = format!("import \"{}\"", server.url("lib.ncl"));
+
+    assert_matches!(
+        eval_with_cache(&source, cache_dir.path(), false),
+        Err(Error::ImportError(ImportError::IOError(_, msg, _)))
+            if msg.contains("can only import URLs or relative paths")
+    );
+}
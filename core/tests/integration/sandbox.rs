@@ -0,0 +1,46 @@
+use assert_matches::assert_matches;
+use nickel_lang_core::error::{Error, ImportError};
+
+use nickel_lang_utils::{project_root::project_root, test_program::TestProgram};
+
+/// Create a program importing `import`, as if it was located in the sandbox root, which is the
+/// `imports/imported` directory of the integration test inputs.
+fn sandboxed_program(import: &str) -> TestProgram {
+    let root = project_root().join("core/tests/integration/inputs/imports/imported");
+    let source = format!("import \"{import}\"");
+
+    let mut prog = TestProgram::new_from_source(
+        source.as_bytes(),
+        root.join("main.ncl"),
+        std::io::stderr(),
+    )
+    .unwrap();
+    prog.set_sandbox_root(&root).unwrap();
+    prog
+}
+
+#[test]
+fn sandbox_allows_imports_inside_root() {
+    assert_matches!(sandboxed_program("nested.ncl").eval_full(), Ok(_));
+}
+
+#[test]
+fn sandbox_rejects_path_traversal() {
+    assert_matches!(
+        sandboxed_program("../nested.ncl").eval_full(),
+        Err(Error::ImportError(ImportError::OutsideSandbox { path, .. })) if path == "../nested.ncl"
+    );
+    assert_matches!(
+        sandboxed_program("root_path/../../nested.ncl").eval_full(),
+        Err(Error::ImportError(ImportError::OutsideSandbox { .. }))
+    );
+}
+
+#[test]
+fn sandbox_rejects_remote_imports() {
+    assert_matches!(
+        sandboxed_program("https://example.com/lib.ncl").eval_full(),
+        Err(Error::ImportError(ImportError::IOError(path, msg, _)))
+            if path == "https://example.com/lib.ncl" && msg.contains("sandboxed")
+    );
+}
@@ -0,0 +1,31 @@
+use std::time::Duration;
+
+use assert_matches::assert_matches;
+use nickel_lang_core::error::{Error, EvalError};
+
+use nickel_lang_utils::test_program::TestProgram;
+
+fn program(source: &str, limit: Duration) -> TestProgram {
+    let mut prog =
+        TestProgram::new_from_source(source.as_bytes(), "time_limit", std::io::stderr()).unwrap();
+    prog.set_max_eval_time(Some(limit));
+    prog
+}
+
+#[test]
+fn time_limit_exceeded() {
+    // A bounded but expensive computation, which takes way longer than one millisecond.
+    let source = "std.array.range 0 1000000 |> std.array.fold_left (+) 0";
+
+    assert_matches!(
+        program(source, Duration::from_millis(1)).eval_full(),
+        Err(Error::EvalError(EvalError::TimeLimitExceeded(..)))
+    );
+}
+
+#[test]
+fn time_limit_not_exceeded() {
+    let source = "std.array.range 0 10 |> std.array.fold_left (+) 0";
+
+    assert_matches!(program(source, Duration::from_secs(60)).eval_full(), Ok(_));
+}
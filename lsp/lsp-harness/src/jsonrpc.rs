@@ -94,7 +94,26 @@ impl Server {
     ///
     /// The command's stdin and stdout will be overridden to "piped" (because
     /// that's what LSes do).
-    pub fn new(mut cmd: std::process::Command) -> Result<Server> {
+    pub fn new(cmd: std::process::Command) -> Result<Server> {
+        Self::new_with_options(cmd, None)
+    }
+
+    /// Same as [Server::new], but also sends the given initialization options to the language
+    /// server.
+    pub fn new_with_options(
+        cmd: std::process::Command,
+        initialization_options: Option<serde_json::Value>,
+    ) -> Result<Server> {
+        Self::new_in_workspace(cmd, initialization_options, None)
+    }
+
+    /// Same as [Server::new_with_options], but also tells the language server that the root of
+    /// the workspace is `root_uri`.
+    pub fn new_in_workspace(
+        mut cmd: std::process::Command,
+        initialization_options: Option<serde_json::Value>,
+        root_uri: Option<Url>,
+    ) -> Result<Server> {
         let lsp = cmd.stdin(Stdio::piped()).stdout(Stdio::piped()).spawn()?;
 
         let mut lsp = Server {
@@ -104,7 +123,7 @@ impl Server {
             id: 0,
         };
 
-        lsp.initialize()?;
+        lsp.initialize(initialization_options, root_uri)?;
 
         Ok(lsp)
     }
@@ -151,7 +170,11 @@ impl Server {
         self.send_notification::<Exit>(())
     }
 
-    fn initialize(&mut self) -> Result<()> {
+    fn initialize(
+        &mut self,
+        initialization_options: Option<serde_json::Value>,
+        root_uri: Option<Url>,
+    ) -> Result<()> {
         // `root_path` is deprecated, but we need ot initialize the struct
         // somehow. There is no `Default` implementation for `InitilizeParams`
         // in versions of `lsp-types` compatible with `codespan-lsp`
@@ -159,8 +182,8 @@ impl Server {
         self.send_request::<Initialize>(InitializeParams {
             process_id: None,
             root_path: None,
-            root_uri: None,
-            initialization_options: None,
+            root_uri,
+            initialization_options,
             capabilities: ClientCapabilities::default(),
             trace: None,
             workspace_folders: None,
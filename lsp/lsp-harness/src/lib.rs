@@ -9,11 +9,15 @@ use log::error;
 use lsp_types::{
     notification::{Notification, PublishDiagnostics},
     request::{
-        Completion, DocumentSymbolRequest, Formatting, GotoDefinition, HoverRequest, References,
-        Rename, Request as LspRequest,
+        CallHierarchyIncomingCalls, CallHierarchyOutgoingCalls, CallHierarchyPrepare,
+        CodeActionRequest, CodeLensRequest, Completion, DocumentSymbolRequest, Formatting,
+        GotoDefinition, HoverRequest, LinkedEditingRange, OnTypeFormatting, References, Rename,
+        Request as LspRequest,
     },
-    CompletionParams, DocumentFormattingParams, DocumentSymbolParams, GotoDefinitionParams,
-    HoverParams, PublishDiagnosticsParams, ReferenceParams, RenameParams, Url,
+    CallHierarchyIncomingCallsParams, CallHierarchyOutgoingCallsParams, CallHierarchyPrepareParams,
+    CodeActionParams, CodeLensParams, CompletionParams, DocumentFormattingParams,
+    DocumentOnTypeFormattingParams, DocumentSymbolParams, GotoDefinitionParams, HoverParams,
+    LinkedEditingRangeParams, PublishDiagnosticsParams, ReferenceParams, RenameParams, Url,
 };
 pub use output::LspDebug;
 use serde::Deserialize;
@@ -28,6 +32,8 @@ pub struct TestFixture {
     pub files: Vec<TestFile>,
     pub reqs: Vec<Request>,
     pub expected_diags: Vec<Url>,
+    /// The initialization options to send to the language server, if any.
+    pub config: Option<serde_json::Value>,
 }
 
 pub struct TestFile {
@@ -43,9 +49,16 @@ pub enum Request {
     References(ReferenceParams),
     Completion(CompletionParams),
     Formatting(DocumentFormattingParams),
+    OnTypeFormatting(DocumentOnTypeFormattingParams),
     Hover(HoverParams),
     Rename(RenameParams),
     Symbols(DocumentSymbolParams),
+    CodeAction(CodeActionParams),
+    /// Prepare a call hierarchy, and then request the incoming and outgoing calls of each of the
+    /// returned items.
+    CallHierarchy(CallHierarchyPrepareParams),
+    LinkedEditingRange(LinkedEditingRangeParams),
+    CodeLens(CodeLensParams),
 }
 
 #[derive(Deserialize, Debug, Default)]
@@ -55,6 +68,8 @@ pub struct Requests {
     // TODO: once the background output has settled down a little,
     // consider checking diagnostic snapshots for all tests
     diagnostic: Option<Vec<Url>>,
+    // The initialization options of the language server.
+    config: Option<serde_json::Value>,
 }
 
 impl TestFixture {
@@ -98,6 +113,7 @@ impl TestFixture {
                 files,
                 reqs: Vec::new(),
                 expected_diags: Vec::new(),
+                config: None,
             })
         } else {
             // The remaining lines at the end of the file are a toml source
@@ -109,6 +125,7 @@ impl TestFixture {
                 files,
                 reqs: reqs.request.unwrap_or_default(),
                 expected_diags: reqs.diagnostic.unwrap_or_default(),
+                config: reqs.config,
             })
         }
     }
@@ -127,8 +144,22 @@ impl Default for TestHarness {
 
 impl TestHarness {
     pub fn new() -> Self {
+        Self::new_with_options(None)
+    }
+
+    /// Start a language server, sending it the given initialization options.
+    pub fn new_with_options(initialization_options: Option<serde_json::Value>) -> Self {
+        Self::new_in_workspace(initialization_options, None)
+    }
+
+    /// Start a language server whose workspace is rooted at `root_uri`, sending it the given
+    /// initialization options.
+    pub fn new_in_workspace(
+        initialization_options: Option<serde_json::Value>,
+        root_uri: Option<Url>,
+    ) -> Self {
         let cmd = std::process::Command::cargo_bin("nls").unwrap();
-        let srv = Server::new(cmd).unwrap();
+        let srv = Server::new_in_workspace(cmd, initialization_options, root_uri).unwrap();
         Self {
             srv,
             out: Vec::new(),
@@ -144,15 +175,44 @@ impl TestHarness {
         self.out.push(b'\n');
     }
 
+    /// Send a request and return its result, without recording it in the output.
+    pub fn send_request<T: LspRequest>(&mut self, params: T::Params) -> T::Result {
+        self.srv.send_request::<T>(params).unwrap()
+    }
+
     pub fn request_dyn(&mut self, req: Request) {
         match req {
             Request::GotoDefinition(d) => self.request::<GotoDefinition>(d),
             Request::Completion(c) => self.request::<Completion>(c),
             Request::Formatting(f) => self.request::<Formatting>(f),
+            Request::OnTypeFormatting(f) => self.request::<OnTypeFormatting>(f),
             Request::Hover(h) => self.request::<HoverRequest>(h),
             Request::References(r) => self.request::<References>(r),
             Request::Rename(r) => self.request::<Rename>(r),
             Request::Symbols(s) => self.request::<DocumentSymbolRequest>(s),
+            Request::CodeAction(a) => self.request::<CodeActionRequest>(a),
+            Request::CallHierarchy(c) => self.call_hierarchy(c),
+            Request::LinkedEditingRange(l) => self.request::<LinkedEditingRange>(l),
+            Request::CodeLens(c) => self.request::<CodeLensRequest>(c),
+        }
+    }
+
+    fn call_hierarchy(&mut self, params: CallHierarchyPrepareParams) {
+        let items = self.send_request::<CallHierarchyPrepare>(params);
+        items.debug(&mut self.out).unwrap();
+        self.out.push(b'\n');
+
+        for item in items.into_iter().flatten() {
+            self.request::<CallHierarchyIncomingCalls>(CallHierarchyIncomingCallsParams {
+                item: item.clone(),
+                work_done_progress_params: Default::default(),
+                partial_result_params: Default::default(),
+            });
+            self.request::<CallHierarchyOutgoingCalls>(CallHierarchyOutgoingCallsParams {
+                item,
+                work_done_progress_params: Default::default(),
+                partial_result_params: Default::default(),
+            });
         }
     }
 
@@ -232,6 +232,62 @@ impl LspDebug for WorkspaceEdit {
     }
 }
 
+impl LspDebug for lsp_types::CodeActionOrCommand {
+    fn debug(&self, mut w: impl Write) -> std::io::Result<()> {
+        match self {
+            lsp_types::CodeActionOrCommand::Command(cmd) => {
+                write!(w, "{} (command {})", cmd.title, cmd.command)
+            }
+            lsp_types::CodeActionOrCommand::CodeAction(action) => {
+                write!(w, "{}: {}", action.title, action.edit.debug_str())
+            }
+        }
+    }
+}
+
+impl LspDebug for lsp_types::CallHierarchyItem {
+    fn debug(&self, mut w: impl Write) -> std::io::Result<()> {
+        write!(
+            w,
+            "{} ({:?})@{}:{}",
+            self.name,
+            self.kind,
+            self.uri.as_str(),
+            self.selection_range.debug_str()
+        )
+    }
+}
+
+impl LspDebug for lsp_types::CallHierarchyIncomingCall {
+    fn debug(&self, mut w: impl Write) -> std::io::Result<()> {
+        write!(w, "{} from {}", self.from.debug_str(), self.from_ranges.debug_str())
+    }
+}
+
+impl LspDebug for lsp_types::CallHierarchyOutgoingCall {
+    fn debug(&self, mut w: impl Write) -> std::io::Result<()> {
+        write!(w, "{} from {}", self.to.debug_str(), self.from_ranges.debug_str())
+    }
+}
+
+impl LspDebug for lsp_types::LinkedEditingRanges {
+    fn debug(&self, w: impl Write) -> std::io::Result<()> {
+        self.ranges.debug(w)
+    }
+}
+
+impl LspDebug for lsp_types::CodeLens {
+    fn debug(&self, mut w: impl Write) -> std::io::Result<()> {
+        let title = self.command.as_ref().map(|c| c.title.as_str());
+        write!(
+            w,
+            "{}: {}",
+            self.range.debug_str(),
+            title.unwrap_or_default()
+        )
+    }
+}
+
 impl LspDebug for Diagnostic {
     fn debug(&self, mut w: impl Write) -> std::io::Result<()> {
         write!(w, "{}: {}", self.range.debug_str(), self.message)
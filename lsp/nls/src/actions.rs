@@ -1,7 +1,19 @@
+use std::collections::HashMap;
+
+use codespan::FileId;
 use lsp_server::{RequestId, Response, ResponseError};
-use lsp_types::{CodeActionOrCommand, CodeActionParams};
+use lsp_types::{
+    CodeAction, CodeActionKind, CodeActionOrCommand, CodeActionParams, Range, TextEdit,
+    WorkspaceEdit,
+};
+use nickel_lang_core::parser::lexer::{
+    Lexer, MultiStringToken, NormalToken, SpannedToken, StringToken, Token,
+};
 
-use crate::{cache::CacheExt, server::Server};
+use crate::{
+    cache::CacheExt, codespan_lsp::position_to_byte_index, diagnostic::LocationCompat,
+    server::Server, world::World,
+};
 
 pub fn handle_code_action(
     params: CodeActionParams,
@@ -10,19 +22,117 @@ pub fn handle_code_action(
 ) -> Result<(), ResponseError> {
     let mut actions = Vec::new();
 
-    if server
-        .world
-        .cache
-        .file_id(&params.text_document.uri)?
-        .is_some()
-    {
+    if let Some(file_id) = server.world.cache.file_id(&params.text_document.uri)? {
         actions.push(CodeActionOrCommand::Command(lsp_types::Command {
             title: "evaluate term".to_owned(),
             command: "eval".to_owned(),
             arguments: Some(vec![serde_json::to_value(&params.text_document).unwrap()]),
         }));
+
+        actions.extend(missing_braces_actions(&server.world, file_id, &params));
     }
 
     server.reply(Response::new_ok(req, Some(actions)));
     Ok(())
 }
+
+/// Offer to add the missing braces of a nested record.
+///
+/// Writing `foo = bar = 1` instead of `foo = { bar = 1 }` is a common mistake, and the resulting
+/// parse error (an unexpected `=`) doesn't tell much about how to fix it. We look for this shape
+/// directly in the token stream, because the parser gives up on the whole field and there's no
+/// term left to inspect.
+fn missing_braces_actions(
+    world: &World,
+    file_id: FileId,
+    params: &CodeActionParams,
+) -> Vec<CodeActionOrCommand> {
+    let files = world.cache.files();
+    let source = files.source(file_id);
+
+    let (Ok(range_start), Ok(range_end)) = (
+        position_to_byte_index(files, file_id, &params.range.start),
+        position_to_byte_index(files, file_id, &params.range.end),
+    ) else {
+        return Vec::new();
+    };
+
+    // The tokens up to the first lexing error, if any. The pattern we're looking for is purely
+    // local, so a lexing error further down the file shouldn't prevent us from finding it.
+    let tokens: Vec<SpannedToken<'_>> = Lexer::new(source).map_while(Result::ok).collect();
+
+    tokens
+        .windows(4)
+        .enumerate()
+        .filter_map(|(idx, window)| {
+            let [(_, outer, _), (_, eq1, _), (inner_start, inner, _), (_, eq2, _)] = window else {
+                return None;
+            };
+
+            let (
+                Token::Normal(NormalToken::Identifier(_)),
+                Token::Normal(NormalToken::Identifier(inner)),
+            ) = (outer, inner)
+            else {
+                return None;
+            };
+
+            let equals = Token::Normal(NormalToken::Equals);
+
+            if *eq1 != equals || *eq2 != equals {
+                return None;
+            }
+
+            let inner_end = value_end(&tokens[idx + 4..])?;
+
+            if range_end < *inner_start || range_start > inner_end {
+                return None;
+            }
+
+            let insert_at = |offset: usize, text: &str| TextEdit {
+                range: Range::from_codespan(&file_id, &(offset..offset), files),
+                new_text: text.to_owned(),
+            };
+
+            let edits = vec![insert_at(*inner_start, "{ "), insert_at(inner_end, " }")];
+
+            Some(CodeActionOrCommand::CodeAction(CodeAction {
+                title: format!("wrap `{inner}` in a record"),
+                kind: Some(CodeActionKind::QUICKFIX),
+                edit: Some(WorkspaceEdit {
+                    changes: Some(HashMap::from([(params.text_document.uri.clone(), edits)])),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            }))
+        })
+        .collect()
+}
+
+/// Find the end offset of the field value starting at the first token of `tokens`, that is the
+/// end of the last token before a `,`, a closing delimiter or an `in` at nesting depth zero.
+/// Returns `None` if the value is empty.
+fn value_end(tokens: &[SpannedToken<'_>]) -> Option<usize> {
+    let mut depth = 0usize;
+    let mut end = None;
+
+    for (_, token, token_end) in tokens {
+        match token {
+            Token::Normal(NormalToken::LBrace | NormalToken::LBracket | NormalToken::LParen)
+            | Token::Str(StringToken::Interpolation)
+            | Token::MultiStr(MultiStringToken::Interpolation) => depth += 1,
+            Token::Normal(NormalToken::RBrace | NormalToken::RBracket | NormalToken::RParen) => {
+                if depth == 0 {
+                    break;
+                }
+                depth -= 1;
+            }
+            Token::Normal(NormalToken::Comma | NormalToken::In) if depth == 0 => break,
+            _ => (),
+        }
+
+        end = Some(*token_end);
+    }
+
+    end
+}
@@ -1,4 +1,4 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 use codespan::FileId;
 use nickel_lang_core::{
@@ -259,6 +259,10 @@ pub struct AnalysisRegistry {
     // a table of tables requires more lookups than necessary, but it makes
     // it easy to invalidate a whole file.
     pub analysis: HashMap<FileId, Analysis>,
+    /// The files whose analysis was computed from a previous version of the file or of one of its
+    /// imports. Their analysis is kept to answer requests until they're analyzed again, so that
+    /// a file that doesn't typecheck after an edit doesn't lose hover, completion, etc.
+    stale: HashSet<FileId>,
 }
 
 impl AnalysisRegistry {
@@ -269,6 +273,7 @@ impl AnalysisRegistry {
         term: &RichTerm,
         initial_env: &crate::usage::Environment,
     ) {
+        self.stale.remove(&file_id);
         self.analysis
             .insert(file_id, Analysis::new(term, type_lookups, initial_env));
     }
@@ -278,6 +283,7 @@ impl AnalysisRegistry {
     /// This is useful for temporary little pieces of input (like parts extracted from incomplete input)
     /// that need variable resolution but not the full analysis.
     pub fn insert_usage(&mut self, file_id: FileId, term: &RichTerm, initial_env: &Environment) {
+        self.stale.remove(&file_id);
         self.analysis.insert(
             file_id,
             Analysis {
@@ -287,8 +293,17 @@ impl AnalysisRegistry {
         );
     }
 
-    pub fn remove(&mut self, file_id: FileId) {
-        self.analysis.remove(&file_id);
+    /// Marks the analysis of a file as stale. It's still used to answer requests, but it'll be
+    /// replaced the next time the file is analyzed successfully.
+    pub fn invalidate(&mut self, file_id: FileId) {
+        if self.analysis.contains_key(&file_id) {
+            self.stale.insert(file_id);
+        }
+    }
+
+    /// Whether the file has an analysis that is up to date with its current version.
+    pub fn is_up_to_date(&self, file_id: FileId) -> bool {
+        self.analysis.contains_key(&file_id) && !self.stale.contains(&file_id)
     }
 
     pub fn get_def(&self, ident: &LocIdent) -> Option<&Def> {
@@ -1,5 +1,7 @@
 use std::{
-    collections::{HashMap, HashSet},
+    borrow::Cow,
+    collections::{hash_map::DefaultHasher, HashMap, HashSet},
+    hash::{Hash, Hasher},
     path::PathBuf,
     time::{Duration, Instant},
 };
@@ -16,7 +18,8 @@ use nickel_lang_core::{
 use serde::{Deserialize, Serialize};
 
 use crate::{
-    cache::CacheExt as _, diagnostic::SerializableDiagnostic, files::uri_to_path, world::World,
+    cache::CacheExt as _, config::DiagnosticsConfig, diagnostic::SerializableDiagnostic,
+    files::uri_to_path, world::World,
 };
 
 const EVAL_TIMEOUT: Duration = Duration::from_secs(1);
@@ -49,6 +52,8 @@ struct Eval {
     contents: Vec<(Url, String)>,
     /// The url of the file to evaluate.
     eval: Url,
+    /// The configuration of the static checks, which are run before evaluating.
+    diagnostics_config: DiagnosticsConfig,
 }
 
 /// A borrowed version of `Eval`
@@ -56,11 +61,18 @@ struct Eval {
 struct EvalRef<'a> {
     contents: Vec<(&'a Url, &'a str)>,
     eval: &'a Url,
+    diagnostics_config: &'a DiagnosticsConfig,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+/// The diagnostics that the background worker sends back in response to an `Eval`. They include
+/// the errors found while evaluating the file, such as contract violations, which static checks
+/// can't detect. The server publishes them as is for the file at `path`, replacing the diagnostics
+/// of the previous evaluation.
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Diagnostics {
+    /// The path of the evaluated file.
     pub path: PathBuf,
+    /// The diagnostics of all the stages, as computed by [`full_diagnostics`].
     pub diagnostics: Vec<SerializableDiagnostic>,
 }
 
@@ -85,8 +97,11 @@ fn run_with_timeout<T: Send + 'static, F: FnOnce() -> T + Send + 'static>(
 // reads an `Eval` (in bincode) from stdin, performs the evaluation, and
 // writes a `Diagnostics` (in bincode) to stdout.
 pub fn worker_main() -> anyhow::Result<()> {
-    let mut world = World::default();
     let eval: Eval = bincode::deserialize_from(std::io::stdin().lock())?;
+    let mut world = World {
+        diagnostics_config: eval.diagnostics_config,
+        ..World::default()
+    };
     for (uri, text) in eval.contents {
         world.add_file(uri, text)?;
     }
@@ -96,33 +111,10 @@ pub fn worker_main() -> anyhow::Result<()> {
     };
 
     if let Some(file_id) = world.cache.id_of(&SourcePath::Path(path.clone())) {
-        let mut diagnostics = world.parse_and_typecheck(file_id);
-
-        // Evaluation diagnostics (but only if there were no parse/type errors).
-        if diagnostics.is_empty() {
-            // TODO: avoid cloning the cache.
-            let mut vm =
-                VirtualMachine::<_, CacheImpl>::new(world.cache.clone(), std::io::stderr());
-            // We've already checked that parsing and typechecking are successful, so we
-            // don't expect further errors.
-            let rt = vm.prepare_eval(file_id).unwrap();
-            let errors = vm.eval_permissive(rt, RECURSION_LIMIT);
-            diagnostics.extend(
-                errors
-                    .into_iter()
-                    .filter(|e| {
-                        !matches!(
-                            e,
-                            nickel_lang_core::error::EvalError::MissingFieldDef { .. }
-                        )
-                    })
-                    .flat_map(|e| world.lsp_diagnostics(file_id, e)),
-            );
-        }
-
-        diagnostics.sort();
-        diagnostics.dedup();
-        let diagnostics = Diagnostics { path, diagnostics };
+        let diagnostics = Diagnostics {
+            path,
+            diagnostics: full_diagnostics(&mut world, file_id),
+        };
 
         // If this fails, the main process has already exited. No need for a loud error in that case.
         let _ = bincode::serialize_into(std::io::stdout().lock(), &diagnostics);
@@ -131,6 +123,37 @@ pub fn worker_main() -> anyhow::Result<()> {
     Ok(())
 }
 
+/// Parses, typechecks and evaluates a file, returning the diagnostics of all these stages, sorted
+/// and deduplicated. The file is only evaluated if the previous stages didn't report anything.
+pub fn full_diagnostics(world: &mut World, file_id: FileId) -> Vec<SerializableDiagnostic> {
+    let mut diagnostics = world.parse_and_typecheck(file_id);
+
+    // Evaluation diagnostics (but only if there were no parse/type errors).
+    if diagnostics.is_empty() {
+        // TODO: avoid cloning the cache.
+        let mut vm = VirtualMachine::<_, CacheImpl>::new(world.cache.clone(), std::io::stderr());
+        // We've already checked that parsing and typechecking are successful, so we
+        // don't expect further errors.
+        let rt = vm.prepare_eval(file_id).unwrap();
+        let errors = vm.eval_permissive(rt, RECURSION_LIMIT);
+        diagnostics.extend(
+            errors
+                .into_iter()
+                .filter(|e| {
+                    !matches!(
+                        e,
+                        nickel_lang_core::error::EvalError::MissingFieldDef { .. }
+                    )
+                })
+                .flat_map(|e| world.lsp_diagnostics(file_id, e)),
+        );
+    }
+
+    diagnostics.sort();
+    diagnostics.dedup();
+    diagnostics
+}
+
 struct SupervisorState {
     cmd_rx: Receiver<Command>,
     response_tx: Sender<Diagnostics>,
@@ -144,17 +167,36 @@ struct SupervisorState {
     // If evaluating a file causes the worker to time out or crash, we blacklist that file
     // and refuse to evaluate it for `BLACKLIST_DURATION`
     banned_files: HashMap<Url, Instant>,
+
+    // The diagnostics of the last successful evaluation of each file, together with the hash of
+    // the contents of the file and its dependencies at that time. Opening or re-saving a module
+    // that many files import invalidates all of them, but if the contents didn't actually change
+    // we can reuse their previous diagnostics instead of evaluating them again.
+    //
+    // Only the final diagnostics are cached, not the evaluated modules. Each evaluation runs in
+    // its own worker process, so that it can be killed when it times out, and evaluated terms
+    // hold closures that can't be sent from one process to another. A module imported by several
+    // files is thus still evaluated once per importer, when the importers themselves change.
+    eval_cache: HashMap<Url, (u64, Diagnostics)>,
+
+    diagnostics_config: DiagnosticsConfig,
 }
 
 impl SupervisorState {
-    fn new(cmd_rx: Receiver<Command>, response_tx: Sender<Diagnostics>) -> anyhow::Result<Self> {
+    fn new(
+        cmd_rx: Receiver<Command>,
+        response_tx: Sender<Diagnostics>,
+        diagnostics_config: DiagnosticsConfig,
+    ) -> anyhow::Result<Self> {
         Ok(Self {
             cmd_rx,
             response_tx,
             contents: HashMap::new(),
             deps: HashMap::new(),
             banned_files: HashMap::new(),
+            eval_cache: HashMap::new(),
             eval_stack: Vec::new(),
+            diagnostics_config,
         })
     }
 
@@ -165,7 +207,7 @@ impl SupervisorState {
         while let Some(uri) = stack.pop() {
             if let Some(deps) = self.deps.get(uri) {
                 for dep in deps {
-                    if self.contents.contains_key(dep) && ret.insert(dep) {
+                    if ret.insert(dep) {
                         stack.push(dep);
                     }
                 }
@@ -175,6 +217,43 @@ impl SupervisorState {
         ret
     }
 
+    // A hash of the contents of the file with the given uri and of all its dependencies, which
+    // together determine the result of its evaluation.
+    //
+    // The contents of a file open in the editor are the in-memory ones. Dependencies that aren't
+    // open in the editor are read from disk, as the worker does.
+    fn content_hash(&self, uri: &Url) -> u64 {
+        let mut dependencies: Vec<_> = self.dependencies(uri).into_iter().collect();
+        // `dependencies` comes from a hash set, so we need to sort it to get a stable hash.
+        dependencies.sort();
+
+        let mut hasher = DefaultHasher::new();
+        uri.hash(&mut hasher);
+
+        for dep in dependencies {
+            let contents = match self.contents.get(dep) {
+                Some(text) => Some(Cow::Borrowed(text.as_bytes())),
+                None => uri_to_path(dep)
+                    .ok()
+                    .and_then(|path| std::fs::read(path).ok())
+                    .map(Cow::Owned),
+            };
+
+            (dep, contents).hash(&mut hasher);
+        }
+
+        hasher.finish()
+    }
+
+    // The diagnostics of a previous evaluation of `uri`, provided that neither the file nor its
+    // dependencies have changed since.
+    fn cached_eval(&self, uri: &Url, hash: u64) -> Option<&Diagnostics> {
+        self.eval_cache
+            .get(uri)
+            .filter(|(cached_hash, _)| *cached_hash == hash)
+            .map(|(_, diagnostics)| diagnostics)
+    }
+
     // Evaluate the nickel file with the given uri, blocking until it completes or times out.
     //
     // The current implementation uses a background process per invocation, which is not the
@@ -211,6 +290,7 @@ impl SupervisorState {
                 .filter_map(|&dep| self.contents.get(dep).map(|text| (dep, text.as_ref())))
                 .collect(),
             eval: uri,
+            diagnostics_config: &self.diagnostics_config,
         };
         bincode::serialize_into(&mut tx, &eval)?;
 
@@ -264,10 +344,20 @@ impl SupervisorState {
             self.drain_commands();
 
             if let Some(uri) = self.eval_stack.pop() {
+                let hash = self.content_hash(&uri);
+
+                if let Some(diagnostics) = self.cached_eval(&uri, hash) {
+                    if self.response_tx.send(diagnostics.clone()).is_err() {
+                        break;
+                    }
+                    continue;
+                }
+
                 // This blocks until the eval is done. We allow further eval requests to queue up
                 // in the channel while we're working.
                 match self.eval(&uri) {
                     Ok(diagnostics) => {
+                        self.eval_cache.insert(uri, (hash, diagnostics.clone()));
                         if self.response_tx.send(diagnostics).is_err() {
                             break;
                         }
@@ -285,10 +375,10 @@ impl SupervisorState {
 }
 
 impl BackgroundJobs {
-    pub fn new() -> Self {
+    pub fn new(diagnostics_config: DiagnosticsConfig) -> Self {
         let (cmd_tx, cmd_rx) = crossbeam::channel::unbounded();
         let (diag_tx, diag_rx) = crossbeam::channel::unbounded();
-        match SupervisorState::new(cmd_rx, diag_tx) {
+        match SupervisorState::new(cmd_rx, diag_tx, diagnostics_config) {
             Ok(mut sup) => {
                 std::thread::spawn(move || {
                     sup.run();
@@ -305,13 +395,31 @@ impl BackgroundJobs {
         }
     }
 
+    // The transitive imports of a file. The imports which aren't open in the editor are included
+    // as well, so that the supervisor can tell when they change on disk.
     fn deps(&self, file_id: FileId, world: &World) -> Vec<Url> {
-        world
-            .cache
-            .get_imports(file_id)
-            .filter_map(|dep_id| world.file_uris.get(&dep_id))
-            .cloned()
-            .collect()
+        let mut stack = vec![file_id];
+        let mut visited: HashSet<_> = stack.iter().copied().collect();
+        let mut deps = Vec::new();
+
+        while let Some(file_id) = stack.pop() {
+            for dep_id in world.cache.get_imports(file_id) {
+                if !visited.insert(dep_id) {
+                    continue;
+                }
+
+                stack.push(dep_id);
+
+                let uri = world
+                    .file_uris
+                    .get(&dep_id)
+                    .cloned()
+                    .or_else(|| Url::from_file_path(world.cache.name(dep_id)).ok());
+                deps.extend(uri);
+            }
+        }
+
+        deps
     }
 
     pub fn update_file_deps(&mut self, uri: Url, world: &World) {
@@ -342,3 +450,107 @@ impl BackgroundJobs {
         &self.receiver
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn uri(name: &str) -> Url {
+        Url::parse(&format!("file:///{name}")).unwrap()
+    }
+
+    fn supervisor() -> SupervisorState {
+        let (_, cmd_rx) = crossbeam::channel::unbounded();
+        let (response_tx, _) = crossbeam::channel::unbounded();
+        SupervisorState::new(cmd_rx, response_tx, DiagnosticsConfig::default()).unwrap()
+    }
+
+    fn update(sup: &mut SupervisorState, name: &str, text: &str, deps: &[&str]) {
+        sup.handle_command(Command::UpdateFile {
+            uri: uri(name),
+            text: text.to_owned(),
+            deps: deps.iter().map(|dep| uri(dep)).collect(),
+        });
+    }
+
+    #[test]
+    fn eval_cache_reuse() {
+        let mut sup = supervisor();
+        update(&mut sup, "lib.ncl", "{ x = 1 }", &[]);
+        update(&mut sup, "a.ncl", "(import \"lib.ncl\").x", &["lib.ncl"]);
+        update(
+            &mut sup,
+            "b.ncl",
+            "(import \"lib.ncl\").x + 1",
+            &["lib.ncl"],
+        );
+
+        for name in ["a.ncl", "b.ncl"] {
+            let hash = sup.content_hash(&uri(name));
+            let diagnostics = Diagnostics {
+                path: PathBuf::from(format!("/{name}")),
+                diagnostics: Vec::new(),
+            };
+            sup.eval_cache.insert(uri(name), (hash, diagnostics));
+        }
+
+        // Opening the library without changing it keeps the results of both importers.
+        update(&mut sup, "lib.ncl", "{ x = 1 }", &[]);
+        for name in ["a.ncl", "b.ncl"] {
+            let hash = sup.content_hash(&uri(name));
+            assert!(sup.cached_eval(&uri(name), hash).is_some());
+        }
+
+        // Editing an importer only invalidates that importer.
+        update(
+            &mut sup,
+            "a.ncl",
+            "(import \"lib.ncl\").x + 2",
+            &["lib.ncl"],
+        );
+        let hash = sup.content_hash(&uri("a.ncl"));
+        assert!(sup.cached_eval(&uri("a.ncl"), hash).is_none());
+        let hash = sup.content_hash(&uri("b.ncl"));
+        assert!(sup.cached_eval(&uri("b.ncl"), hash).is_some());
+
+        // Editing the library invalidates every importer.
+        update(&mut sup, "lib.ncl", "{ x = 2 }", &[]);
+        for name in ["a.ncl", "b.ncl"] {
+            let hash = sup.content_hash(&uri(name));
+            assert!(sup.cached_eval(&uri(name), hash).is_none());
+        }
+    }
+
+    #[test]
+    fn eval_cache_closed_dependency() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let lib = dir.path().join("lib.ncl");
+        std::fs::write(&lib, "{ x = 1 }").unwrap();
+
+        // The library isn't open in the editor: it's only known as a dependency of `main.ncl`.
+        let mut sup = supervisor();
+        sup.handle_command(Command::UpdateFile {
+            uri: uri("main.ncl"),
+            text: format!("(import \"{}\").x", lib.display()),
+            deps: vec![Url::from_file_path(&lib).unwrap()],
+        });
+
+        let hash = sup.content_hash(&uri("main.ncl"));
+        let diagnostics = Diagnostics {
+            path: PathBuf::from("/main.ncl"),
+            diagnostics: Vec::new(),
+        };
+        sup.eval_cache.insert(uri("main.ncl"), (hash, diagnostics));
+        assert!(sup.cached_eval(&uri("main.ncl"), hash).is_some());
+
+        // Saving the library again without changing it keeps the result of its importer.
+        std::fs::write(&lib, "{ x = 1 }").unwrap();
+        let hash = sup.content_hash(&uri("main.ncl"));
+        assert!(sup.cached_eval(&uri("main.ncl"), hash).is_some());
+
+        // Changing the library on disk invalidates its importer.
+        std::fs::write(&lib, "{ x = 2 }").unwrap();
+        let hash = sup.content_hash(&uri("main.ncl"));
+        assert!(sup.cached_eval(&uri("main.ncl"), hash).is_none());
+    }
+}
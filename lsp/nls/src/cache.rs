@@ -51,16 +51,19 @@ impl CacheExt for Cache {
             // If we have typechecked a file correctly, its imports should be
             // in the `registry`. The imports that are not in `registry`
             // were not typechecked correctly.
-            if !registry.analysis.contains_key(&id) {
+            if !registry.is_up_to_date(id) {
                 typecheck_import_diagnostics.push(id);
             }
         }
 
         // After self.parse(), the cache must be populated
-        let TermEntry { term, state, .. } = self.terms().get(&file_id).unwrap().clone();
+        let TermEntry {
+            term,
+            state,
+            parse_errs,
+        } = self.terms().get(&file_id).unwrap().clone();
 
-        let result = if state > EntryState::Typechecked && registry.analysis.contains_key(&file_id)
-        {
+        let result = if state > EntryState::Typechecked && registry.is_up_to_date(file_id) {
             Ok(CacheOp::Cached(()))
         } else if state >= EntryState::Parsed {
             let mut collector = TypeCollector::default();
@@ -70,7 +73,16 @@ impl CacheExt for Cache {
                 self,
                 &mut collector,
             )
-            .map_err(|err| vec![Error::TypecheckError(err)])?;
+            .map_err(|err| {
+                // A file being edited often has parse errors, and the partial term recovered by
+                // the parser might not typecheck. Rather than leaving the file without any
+                // analysis, we still index the well-formed parts, only without type information.
+                if !parse_errs.no_errors() {
+                    registry.insert(file_id, Default::default(), &term, initial_term_env);
+                }
+
+                vec![Error::TypecheckError(err)]
+            })?;
 
             let type_lookups = collector.complete(type_tables);
             registry.insert(file_id, type_lookups, &term, initial_term_env);
@@ -0,0 +1,76 @@
+//! A one-shot mode which reports the diagnostics of a single file on the standard output instead
+//! of setting up a language server, for use in scripts or pre-commit hooks.
+
+use std::{fs, path::PathBuf, process::ExitCode};
+
+use anyhow::anyhow;
+use lsp_types::{DiagnosticSeverity, Url};
+
+use crate::{
+    background::{full_diagnostics, Diagnostics},
+    world::World,
+};
+
+/// Parses, typechecks and evaluates the file at `path`, and prints the resulting diagnostics,
+/// either as JSON or as one `file:line:column: severity: message` line per diagnostic.
+///
+/// Returns a failure exit code if any of the diagnostics is an error.
+pub fn check_main(path: PathBuf, json: bool) -> anyhow::Result<ExitCode> {
+    let path = path.canonicalize()?;
+    let contents = fs::read_to_string(&path)?;
+    let uri =
+        Url::from_file_path(&path).map_err(|()| anyhow!("invalid path {}", path.display()))?;
+
+    let mut world = World::default();
+    let (file_id, _) = world.add_file(uri, contents)?;
+    let diagnostics = full_diagnostics(&mut world, file_id);
+
+    let failed = diagnostics.iter().any(|diag| is_error(diag.severity));
+
+    if json {
+        println!(
+            "{}",
+            serde_json::to_string(&Diagnostics { path, diagnostics })?
+        );
+    } else {
+        for diag in diagnostics {
+            let start = diag.range.0.start;
+            println!(
+                "{}:{}:{}: {}: {}",
+                path.display(),
+                start.line + 1,
+                start.character + 1,
+                severity_name(diag.severity),
+                diag.message
+            );
+        }
+    }
+
+    Ok(if failed {
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    })
+}
+
+/// Whether a diagnostic counts as an error. As in [severity_name], diagnostics without a known
+/// severity are errors.
+fn is_error(severity: Option<DiagnosticSeverity>) -> bool {
+    !matches!(
+        severity,
+        Some(
+            DiagnosticSeverity::WARNING
+                | DiagnosticSeverity::INFORMATION
+                | DiagnosticSeverity::HINT
+        )
+    )
+}
+
+fn severity_name(severity: Option<DiagnosticSeverity>) -> &'static str {
+    match severity {
+        Some(DiagnosticSeverity::WARNING) => "warning",
+        Some(DiagnosticSeverity::INFORMATION) => "info",
+        Some(DiagnosticSeverity::HINT) => "hint",
+        _ => "error",
+    }
+}
@@ -0,0 +1,116 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+/// The configuration of the language server, which the client can provide through the
+/// `initializationOptions` field of the `initialize` request.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct LspConfig {
+    pub value_preview: ValuePreviewConfig,
+    pub diagnostics: DiagnosticsConfig,
+    pub code_lens: CodeLensConfig,
+    pub workspace: WorkspaceConfig,
+}
+
+/// Configuration of the preview of evaluated values in hover.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct ValuePreviewConfig {
+    /// Whether to show the evaluated value of the hovered term at all.
+    pub enabled: bool,
+    /// The maximum number of AST nodes of a term, before and after evaluation, for its value to
+    /// be shown.
+    pub max_nodes: usize,
+    /// The maximum time spent evaluating a term for its preview, in milliseconds.
+    pub max_eval_time_ms: u64,
+}
+
+impl Default for ValuePreviewConfig {
+    fn default() -> Self {
+        ValuePreviewConfig {
+            enabled: false,
+            max_nodes: 32,
+            max_eval_time_ms: 50,
+        }
+    }
+}
+
+/// Configuration of the code lenses showing the type of top-level fields.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct CodeLensConfig {
+    /// Whether to show code lenses at all. They are off by default, as they take up a line of
+    /// the editor for each field.
+    pub enabled: bool,
+}
+
+/// Configuration of the analysis of the files of the workspace that aren't open in the editor.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct WorkspaceConfig {
+    /// Whether to scan the workspace root for Nickel files on startup, and to analyze them ahead
+    /// of time. This is what makes their symbols available to workspace symbol search before
+    /// they're opened.
+    pub scan: bool,
+    /// The maximum number of files analyzed by the scan. The remaining files are ignored.
+    pub max_files: usize,
+    /// Files larger than this size, in bytes, are skipped by the scan.
+    pub max_file_size: u64,
+}
+
+impl Default for WorkspaceConfig {
+    fn default() -> Self {
+        WorkspaceConfig {
+            scan: false,
+            max_files: 1000,
+            max_file_size: 1024 * 1024,
+        }
+    }
+}
+
+/// The severity of the diagnostics reported by a static check.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CheckSeverity {
+    Error,
+    Warning,
+    Hint,
+    /// Don't run the check at all.
+    Off,
+}
+
+/// The default severity of each check of [crate::lint], by name.
+pub const DEFAULT_SEVERITIES: &[(&str, CheckSeverity)] = &[
+    (crate::lint::LITERAL_CONTRACT, CheckSeverity::Error),
+    (crate::lint::UNUSED_BINDING, CheckSeverity::Warning),
+    (crate::lint::SHADOWING, CheckSeverity::Hint),
+    (crate::lint::CONTRACT_AS_TYPE, CheckSeverity::Warning),
+    (crate::lint::SHADOWED_DEFAULT, CheckSeverity::Hint),
+    (crate::lint::CONTRACT_AS_VALUE, CheckSeverity::Warning),
+];
+
+/// Configuration of the static checks performed on top of typechecking.
+///
+/// The configuration is sent to the background evaluation worker, hence the `Serialize`
+/// implementation.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DiagnosticsConfig {
+    /// Overrides of the default severity of the checks, by check name. For example,
+    /// `{ "shadowing": "off" }` disables the shadowing check.
+    pub severity: HashMap<String, CheckSeverity>,
+}
+
+impl DiagnosticsConfig {
+    /// The severity of the check named `check`, as configured or as given by
+    /// [DEFAULT_SEVERITIES].
+    pub fn severity(&self, check: &str) -> CheckSeverity {
+        self.severity.get(check).copied().unwrap_or_else(|| {
+            DEFAULT_SEVERITIES
+                .iter()
+                .find_map(|(name, severity)| (*name == check).then_some(*severity))
+                .unwrap_or(CheckSeverity::Warning)
+        })
+    }
+}
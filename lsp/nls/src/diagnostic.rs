@@ -17,7 +17,7 @@ use crate::codespan_lsp::byte_span_to_range;
 /// We also support `PartialOrd` and `Ord` through various wrappers. Not because
 /// there's any semantically meaningful ordering, but because it lets us deduplicate
 /// the output.
-#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
 pub struct SerializableDiagnostic {
     pub range: OrdRange,
     pub severity: Option<lsp_types::DiagnosticSeverity>,
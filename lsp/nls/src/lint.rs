@@ -0,0 +1,744 @@
+//! Static checks performed on top of typechecking, reporting errors that would otherwise only show
+//! up when evaluating, as well as suspicious code such as unused bindings.
+//!
+//! Each check has a name, which is used to configure the severity of its diagnostics (see
+//! [crate::config::DiagnosticsConfig]).
+
+use std::collections::HashSet;
+
+use codespan::FileId;
+use codespan_reporting::diagnostic::{Diagnostic, Label, Severity};
+use nickel_lang_core::{
+    identifier::LocIdent,
+    position::RawSpan,
+    term::{
+        record::{Field, RecordData},
+        BinaryOp, LabeledType, MergePriority, RichTerm, Term, Traverse, TraverseControl,
+        TypeAnnotation,
+    },
+    typ::TypeF,
+};
+
+use crate::{
+    config::{CheckSeverity, DiagnosticsConfig},
+    identifier::LocIdent as NlsLocIdent,
+    pattern::Bindings as _,
+    term::RawSpanExt,
+    usage::UsageLookup,
+};
+
+/// The name of the check performed by [literal_contract_mismatches].
+pub const LITERAL_CONTRACT: &str = "literal-contract";
+/// The name of the check performed by [unused_bindings].
+pub const UNUSED_BINDING: &str = "unused-binding";
+/// The name of the check performed by [shadowed_bindings].
+pub const SHADOWING: &str = "shadowing";
+/// The name of the check performed by [contracts_as_types].
+pub const CONTRACT_AS_TYPE: &str = "contract-as-type";
+/// The name of the check performed by [shadowed_defaults].
+pub const SHADOWED_DEFAULT: &str = "shadowed-default";
+/// The name of the check performed by [contracts_as_values].
+pub const CONTRACT_AS_VALUE: &str = "contract-as-value";
+
+/// Run all the checks on `rt`, the term of `file_id`, with the severities given by `config`.
+/// Checks whose severity is [CheckSeverity::Off] aren't run at all. The name of the check is used
+/// as the code of its diagnostics.
+pub fn run_checks(
+    file_id: FileId,
+    rt: &RichTerm,
+    usages: &UsageLookup,
+    config: &DiagnosticsConfig,
+) -> Vec<Diagnostic<FileId>> {
+    let checks: [(&str, &dyn Fn() -> Vec<Diagnostic<FileId>>); 6] = [
+        (LITERAL_CONTRACT, &|| literal_contract_mismatches(file_id, rt)),
+        (UNUSED_BINDING, &|| unused_bindings(file_id, rt, usages)),
+        (SHADOWING, &|| shadowed_bindings(file_id, rt, usages)),
+        (CONTRACT_AS_TYPE, &|| contracts_as_types(file_id, rt, usages)),
+        (SHADOWED_DEFAULT, &|| shadowed_defaults(file_id, rt, usages)),
+        (CONTRACT_AS_VALUE, &|| contracts_as_values(file_id, rt, usages)),
+    ];
+    let mut diags = Vec::new();
+
+    for (check, run) in checks {
+        let severity = match config.severity(check) {
+            CheckSeverity::Error => Severity::Error,
+            CheckSeverity::Warning => Severity::Warning,
+            CheckSeverity::Hint => Severity::Help,
+            CheckSeverity::Off => continue,
+        };
+
+        diags.extend(run().into_iter().map(|diag| Diagnostic {
+            severity,
+            code: Some(check.to_owned()),
+            ..diag
+        }));
+    }
+
+    diags
+}
+
+/// The name of a builtin contract that is checked by [literal_contract_mismatches].
+fn simple_contract_name(ctr: &LabeledType) -> Option<&'static str> {
+    match ctr.typ.typ {
+        TypeF::Number => Some("Number"),
+        TypeF::String => Some("String"),
+        TypeF::Bool => Some("Bool"),
+        _ => None,
+    }
+}
+
+/// A description of the type of a literal, or `None` if the term isn't a literal.
+fn literal_kind(rt: &RichTerm) -> Option<&'static str> {
+    match rt.as_ref() {
+        Term::Num(_) => Some("Number"),
+        Term::Str(_) | Term::StrChunks(_) => Some("String"),
+        Term::Bool(_) => Some("Bool"),
+        Term::Null => Some("null"),
+        Term::Array(..) => Some("Array"),
+        Term::Record(_) | Term::RecRecord(..) => Some("Record"),
+        _ => None,
+    }
+}
+
+/// Check `value` against the simple contracts of `annot`.
+fn check_annotated(
+    file_id: FileId,
+    annot: &TypeAnnotation,
+    value: &RichTerm,
+    diags: &mut Vec<Diagnostic<FileId>>,
+) {
+    let Some(found) = literal_kind(value) else {
+        return;
+    };
+    let Some(value_span) = value.pos.into_opt().filter(|span| span.src_id == file_id) else {
+        return;
+    };
+
+    // Type annotations are already enforced by the typechecker, so we only look at contracts.
+    for ctr in &annot.contracts {
+        let Some(expected) = simple_contract_name(ctr) else {
+            continue;
+        };
+
+        if expected == found {
+            continue;
+        }
+
+        let (_, value_range) = value_span.to_range();
+        let mut labels = vec![Label::primary(file_id, value_range)
+            .with_message(format!("this {found} literal"))];
+
+        if let Some(ctr_span) = ctr.typ.pos.into_opt().filter(|span| span.src_id == file_id) {
+            let (_, ctr_range) = ctr_span.to_range();
+            labels.push(Label::secondary(file_id, ctr_range).with_message("expected type"));
+        }
+
+        diags.push(
+            Diagnostic::error()
+                .with_message(format!(
+                    "contract broken by a literal: expected a {expected}, got a {found}"
+                ))
+                .with_labels(labels),
+        );
+    }
+}
+
+/// Report the values which obviously break one of their simple builtin contracts `Number`,
+/// `String` or `Bool`, that is the literals of another type, as in `port | String = 8080`.
+///
+/// Contracts are only checked at evaluation time, but there's no need to evaluate anything to know
+/// that such a contract will fail. Only the annotations of `file_id` are considered.
+pub fn literal_contract_mismatches(file_id: FileId, rt: &RichTerm) -> Vec<Diagnostic<FileId>> {
+    let mut diags = Vec::new();
+
+    rt.traverse_ref(
+        &mut |rt: &RichTerm, _: &()| {
+            match rt.as_ref() {
+                Term::Annotated(annot, inner) => check_annotated(file_id, annot, inner, &mut diags),
+                Term::Record(data) | Term::RecRecord(data, ..) => {
+                    for field in data.fields.values() {
+                        if let Some(value) = &field.value {
+                            check_annotated(file_id, &field.metadata.annotation, value, &mut diags);
+                        }
+                    }
+                }
+                _ => (),
+            }
+
+            TraverseControl::<(), ()>::Continue
+        },
+        &(),
+    );
+
+    diags
+}
+
+/// The variables bound by a `let` term, or an empty vector for other terms.
+fn let_bindings(rt: &RichTerm) -> Vec<LocIdent> {
+    match rt.as_ref() {
+        Term::Let(id, ..) => vec![*id],
+        Term::LetPattern(pat, ..) => pat.bindings().into_iter().map(|(_, id, _)| id).collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Report the variables bound by a `let` in `file_id` that are never used. Variables whose name
+/// starts with an underscore are assumed to be unused on purpose.
+pub fn unused_bindings(
+    file_id: FileId,
+    rt: &RichTerm,
+    usages: &UsageLookup,
+) -> Vec<Diagnostic<FileId>> {
+    let mut diags = Vec::new();
+
+    rt.traverse_ref(
+        &mut |rt: &RichTerm, _: &()| {
+            for id in let_bindings(rt) {
+                let Some(span) = id.pos.into_opt().filter(|span| span.src_id == file_id) else {
+                    continue;
+                };
+
+                if id.label().starts_with('_') || usages.usages(&span).next().is_some() {
+                    continue;
+                }
+
+                let (_, range) = span.to_range();
+                diags.push(
+                    Diagnostic::warning()
+                        .with_message(format!("unused variable `{id}`"))
+                        .with_labels(vec![Label::primary(file_id, range)
+                            .with_message("this variable is never used")]),
+                );
+            }
+
+            TraverseControl::<(), ()>::Continue
+        },
+        &(),
+    );
+
+    diags
+}
+
+/// Report the variables bound by a `let` in `file_id` that shadow another variable of the same
+/// file. Shadowing a variable of the standard library, such as `std`, isn't reported.
+pub fn shadowed_bindings(
+    file_id: FileId,
+    rt: &RichTerm,
+    usages: &UsageLookup,
+) -> Vec<Diagnostic<FileId>> {
+    let mut diags = Vec::new();
+
+    rt.traverse_ref(
+        &mut |rt: &RichTerm, _: &()| {
+            let Some(env) = usages.env(rt) else {
+                return TraverseControl::<(), ()>::Continue;
+            };
+
+            for id in let_bindings(rt) {
+                let Some(span) = id.pos.into_opt().filter(|span| span.src_id == file_id) else {
+                    continue;
+                };
+                let Some(shadowed_span) = env
+                    .get(&id.ident())
+                    .and_then(|def| def.ident().pos.into_opt())
+                    .filter(|span| span.src_id == file_id)
+                else {
+                    continue;
+                };
+
+                let (_, range) = span.to_range();
+                let (_, shadowed_range) = shadowed_span.to_range();
+                diags.push(
+                    Diagnostic::warning()
+                        .with_message(format!("`{id}` shadows an existing variable"))
+                        .with_labels(vec![
+                            Label::primary(file_id, range).with_message("this variable"),
+                            Label::secondary(file_id, shadowed_range)
+                                .with_message("shadows this one"),
+                        ]),
+                );
+            }
+
+            TraverseControl::Continue
+        },
+        &(),
+    );
+
+    diags
+}
+
+/// Whether `rt` is a record literal that is meant to be used as a contract: an open record, or a
+/// record with a field that has a contract annotation or no definition at all, as in
+/// `{ name | String, port | Number | default = 80 }`.
+fn is_record_contract(rt: &RichTerm) -> bool {
+    match rt.as_ref() {
+        Term::Record(data) | Term::RecRecord(data, ..) => {
+            data.attrs.open
+                || data.fields.values().any(|field| {
+                    field.value.is_none() || !field.metadata.annotation.contracts.is_empty()
+                })
+        }
+        _ => false,
+    }
+}
+
+/// Check whether the type annotation of `annot`, if any, is actually a record contract, either
+/// written inline or bound to a variable.
+fn check_type_annotation(
+    file_id: FileId,
+    annot: &TypeAnnotation,
+    usages: &UsageLookup,
+    diags: &mut Vec<Diagnostic<FileId>>,
+) {
+    let Some(typ) = &annot.typ else {
+        return;
+    };
+    let TypeF::Flat(rt) = &typ.typ.typ else {
+        return;
+    };
+    let Some(span) = typ.typ.pos.into_opt().filter(|span| span.src_id == file_id) else {
+        return;
+    };
+
+    let is_contract = match rt.as_ref() {
+        Term::Var(id) => usages
+            .def(&NlsLocIdent::from(*id))
+            .and_then(|def| def.value())
+            .is_some_and(is_record_contract),
+        _ => is_record_contract(rt),
+    };
+
+    if !is_contract {
+        return;
+    }
+
+    let (_, range) = span.to_range();
+    diags.push(
+        Diagnostic::warning()
+            .with_message("record contract used as a static type")
+            .with_labels(vec![Label::primary(file_id, range)
+                .with_message("this is a record contract, not a type")])
+            .with_notes(vec![
+                format!(
+                    "a type annotation `: {}` asks the typechecker to treat the contract as an \
+                    opaque type, which record literals can't have",
+                    typ.typ
+                ),
+                format!(
+                    "use a contract annotation `| {}` to check the value at runtime instead",
+                    typ.typ
+                ),
+            ]),
+    );
+}
+
+/// Report the type annotations (`:`) of `file_id` whose type is a record contract, as in
+/// `config : Schema` where `Schema = { port | Number }`. Such contracts can only be applied with a
+/// contract annotation (`|`): as a static type, they are opaque.
+///
+/// Genuine record types, such as `{ port : Number }`, aren't reported.
+pub fn contracts_as_types(
+    file_id: FileId,
+    rt: &RichTerm,
+    usages: &UsageLookup,
+) -> Vec<Diagnostic<FileId>> {
+    let mut diags = Vec::new();
+
+    rt.traverse_ref(
+        &mut |rt: &RichTerm, _: &()| {
+            match rt.as_ref() {
+                Term::Annotated(annot, _) => {
+                    check_type_annotation(file_id, annot, usages, &mut diags)
+                }
+                Term::Record(data) | Term::RecRecord(data, ..) => {
+                    for field in data.fields.values() {
+                        check_type_annotation(
+                            file_id,
+                            &field.metadata.annotation,
+                            usages,
+                            &mut diags,
+                        );
+                    }
+                }
+                _ => (),
+            }
+
+            TraverseControl::<(), ()>::Continue
+        },
+        &(),
+    );
+
+    diags
+}
+
+/// How many variables, or levels of nested records, we follow when looking for the fields merged
+/// together, to avoid looping on recursive definitions.
+const MAX_MERGE_DEPTH: usize = 16;
+
+/// Collect the record literals that are merged together by `rt`, such as the three records of
+/// `{ a = 1 } & ({ b = 2 } & base)` if `base` is bound to a record literal and used only there.
+fn merged_records<'a>(
+    rt: &'a RichTerm,
+    usages: &'a UsageLookup,
+    depth: usize,
+    records: &mut Vec<&'a RecordData>,
+) {
+    if depth > MAX_MERGE_DEPTH {
+        return;
+    }
+
+    match rt.as_ref() {
+        Term::Record(data) | Term::RecRecord(data, ..) => records.push(data),
+        Term::Op2(BinaryOp::Merge(_), t1, t2) => {
+            merged_records(t1, usages, depth, records);
+            merged_records(t2, usages, depth, records);
+        }
+        Term::Annotated(_, inner) => merged_records(inner, usages, depth, records),
+        // A variable used elsewhere might be merged with other values there, or not merged at
+        // all, so its default values aren't always overridden. We only follow the variables which
+        // are used exactly once.
+        Term::Var(id) => {
+            let value = usages
+                .def(&NlsLocIdent::from(*id))
+                .filter(|def| def.path().is_empty())
+                .filter(|def| {
+                    def.ident()
+                        .pos
+                        .into_opt()
+                        .is_some_and(|span| usages.usages(&span).count() == 1)
+                })
+                .and_then(|def| def.value());
+
+            if let Some(value) = value {
+                merged_records(value, usages, depth + 1, records);
+            }
+        }
+        _ => (),
+    }
+}
+
+/// Report the default values of the fields of `records`, which are merged together, that are
+/// overridden by a value of higher priority. Fields defined in several of the records are checked
+/// recursively.
+fn check_merged_fields(
+    file_id: FileId,
+    records: &[&RecordData],
+    usages: &UsageLookup,
+    depth: usize,
+    reported: &mut HashSet<RawSpan>,
+    diags: &mut Vec<Diagnostic<FileId>>,
+) {
+    if depth > MAX_MERGE_DEPTH {
+        return;
+    }
+
+    let mut names: Vec<_> = records
+        .iter()
+        .flat_map(|data| data.fields.keys().copied())
+        .collect();
+    names.sort_by(|id1, id2| id1.label().cmp(id2.label()));
+    names.dedup_by(|id1, id2| id1.ident() == id2.ident());
+
+    for name in names {
+        let defs: Vec<&Field> = records
+            .iter()
+            .filter_map(|data| data.fields.get(&name))
+            .filter(|field| field.value.is_some())
+            .collect();
+
+        if defs.len() < 2 {
+            continue;
+        }
+
+        let winner = defs
+            .iter()
+            .copied()
+            .max_by(|f1, f2| f1.metadata.priority.cmp(&f2.metadata.priority))
+            .expect("there are at least two definitions");
+
+        for field in &defs {
+            let Some(value) = &field.value else {
+                continue;
+            };
+
+            // The fields of a default record value are merged with the other value, so they may
+            // still be used.
+            if field.metadata.priority != MergePriority::Bottom
+                || winner.metadata.priority == MergePriority::Bottom
+                || matches!(value.as_ref(), Term::Record(_) | Term::RecRecord(..))
+            {
+                continue;
+            }
+
+            let Some(span) = value.pos.into_opt().filter(|span| span.src_id == file_id) else {
+                continue;
+            };
+
+            if !reported.insert(span) {
+                continue;
+            }
+
+            let (_, range) = span.to_range();
+            let mut labels =
+                vec![Label::primary(file_id, range).with_message("this default value")];
+
+            if let Some(winner_span) = winner
+                .value
+                .as_ref()
+                .and_then(|value| value.pos.into_opt())
+                .filter(|span| span.src_id == file_id)
+            {
+                let (_, winner_range) = winner_span.to_range();
+                labels.push(
+                    Label::secondary(file_id, winner_range)
+                        .with_message("is overridden by this value"),
+                );
+            }
+
+            diags.push(
+                Diagnostic::warning()
+                    .with_message(format!("the default value of `{name}` is never used"))
+                    .with_labels(labels),
+            );
+        }
+
+        let mut nested = Vec::new();
+        for field in &defs {
+            if let Some(value) = &field.value {
+                merged_records(value, usages, 0, &mut nested);
+            }
+        }
+
+        if nested.len() >= 2 {
+            check_merged_fields(file_id, &nested, usages, depth + 1, reported, diags);
+        }
+    }
+}
+
+/// Report the default values of `file_id` that are always overridden, because the field is merged
+/// with a value of higher priority, as in `{ port | default = 80 } & { port = 8080 }`.
+///
+/// Only the merges written in the source are considered, with the variables bound to records and
+/// used only once replaced by their definition: a default can still be overridden by a merge that
+/// happens elsewhere.
+pub fn shadowed_defaults(
+    file_id: FileId,
+    rt: &RichTerm,
+    usages: &UsageLookup,
+) -> Vec<Diagnostic<FileId>> {
+    let mut diags = Vec::new();
+    // A merge can be analyzed several times, e.g. as a part of a larger merge.
+    let mut reported = HashSet::new();
+
+    rt.traverse_ref(
+        &mut |rt: &RichTerm, _: &()| {
+            if let Term::Op2(BinaryOp::Merge(_), ..) = rt.as_ref() {
+                let mut records = Vec::new();
+                merged_records(rt, usages, 0, &mut records);
+                check_merged_fields(file_id, &records, usages, 0, &mut reported, &mut diags);
+            }
+
+            TraverseControl::<(), ()>::Continue
+        },
+        &(),
+    );
+
+    diags
+}
+
+/// Whether `rt` is one of the builtin contracts `Number`, `String`, `Bool` or an array contract,
+/// written inline or bound to a variable.
+fn is_builtin_contract(rt: &RichTerm, usages: &UsageLookup) -> bool {
+    let rt = match rt.as_ref() {
+        Term::Var(id) => {
+            let Some(value) = usages
+                .def(&NlsLocIdent::from(*id))
+                .and_then(|def| def.value())
+            else {
+                return false;
+            };
+
+            value
+        }
+        _ => rt,
+    };
+
+    matches!(
+        rt.as_ref(),
+        Term::Type(ty) if matches!(
+            ty.typ,
+            TypeF::Number | TypeF::String | TypeF::Bool | TypeF::Array(_)
+        )
+    )
+}
+
+/// Report the fields of `file_id` without annotation which are defined as a builtin contract, as
+/// in `port = Number`. This is most likely a typo for the contract annotation `port | Number`: as
+/// it is, `port` is the contract itself and any value is accepted.
+///
+/// Fields whose name starts with an uppercase letter, such as `Port = Number`, follow the naming
+/// convention of contracts and aren't reported.
+pub fn contracts_as_values(
+    file_id: FileId,
+    rt: &RichTerm,
+    usages: &UsageLookup,
+) -> Vec<Diagnostic<FileId>> {
+    let mut diags = Vec::new();
+
+    rt.traverse_ref(
+        &mut |rt: &RichTerm, _: &()| {
+            let (Term::Record(data) | Term::RecRecord(data, ..)) = rt.as_ref() else {
+                return TraverseControl::<(), ()>::Continue;
+            };
+
+            for (id, field) in &data.fields {
+                let Some(value) = &field.value else {
+                    continue;
+                };
+
+                if !field.metadata.annotation.is_empty()
+                    || id.label().starts_with(char::is_uppercase)
+                    || !is_builtin_contract(value, usages)
+                {
+                    continue;
+                }
+
+                let Some(span) = value.pos.into_opt().filter(|span| span.src_id == file_id) else {
+                    continue;
+                };
+
+                let (_, range) = span.to_range();
+                diags.push(
+                    Diagnostic::warning()
+                        .with_message(format!("the field `{id}` is defined as a contract"))
+                        .with_labels(vec![Label::primary(file_id, range)
+                            .with_message("this is a contract, not a value")])
+                        .with_notes(vec![format!(
+                            "to check the value of `{id}` against this contract, use a contract \
+                            annotation `{id} | {value}` instead"
+                        )]),
+                );
+            }
+
+            TraverseControl::Continue
+        },
+        &(),
+    );
+
+    diags
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        position::tests::parse,
+        usage::{Environment, UsageLookup},
+    };
+
+    use super::{contracts_as_types, contracts_as_values, shadowed_defaults};
+
+    fn count(src: &str) -> usize {
+        let (file, rt) = parse(src);
+        let usages = UsageLookup::new(&rt, &Environment::new());
+        contracts_as_types(file, &rt, &usages).len()
+    }
+
+    fn count_shadowed_defaults(src: &str) -> usize {
+        let (file, rt) = parse(src);
+        let usages = UsageLookup::new(&rt, &Environment::new());
+        shadowed_defaults(file, &rt, &usages).len()
+    }
+
+    fn count_contracts_as_values(src: &str) -> usize {
+        let (file, rt) = parse(src);
+        let usages = UsageLookup::new(&rt, &Environment::new());
+        contracts_as_values(file, &rt, &usages).len()
+    }
+
+    #[test]
+    fn record_contract_as_type() {
+        assert_eq!(
+            count("let Schema = { name | String } in { name = \"a\" } : Schema"),
+            1
+        );
+        assert_eq!(count("{ name = \"a\" } : { name | String, .. }"), 1);
+        assert_eq!(
+            count("let C = { a | Number } in { f : C = { a = 1 }, g | C = { a = 2 } }"),
+            1
+        );
+    }
+
+    #[test]
+    fn genuine_types() {
+        assert_eq!(
+            count("{ port : Number = 1, x : { a : Number } = { a = 1 }, y : Array Number = [] }"),
+            0
+        );
+        assert_eq!(count("let T = { a = 1 } in (fun x => x) : T -> T"), 0);
+        assert_eq!(count("let x : { a : String } = { a = \"b\" } in x"), 0);
+    }
+
+    #[test]
+    fn shadowed_defaults_in_merges() {
+        assert_eq!(
+            count_shadowed_defaults("{ a | default = 1 } & { a = 2 } & { a | force = 3 }"),
+            1
+        );
+        assert_eq!(
+            count_shadowed_defaults("{ a = { b | default = 1 } } & ({ a.b = 2 } & { c = 3 })"),
+            1
+        );
+        assert_eq!(
+            count_shadowed_defaults(
+                "let base = { a | default = 1 } in base & { a | priority -1 = 2 }"
+            ),
+            1
+        );
+    }
+
+    #[test]
+    fn used_defaults() {
+        assert_eq!(
+            count_shadowed_defaults("{ a | default = 1 } & { b = 2 }"),
+            0
+        );
+        assert_eq!(
+            count_shadowed_defaults("{ a | default = 1 } & { a | default = 2 }"),
+            0
+        );
+        // The fields of a default record are merged with the other record.
+        assert_eq!(
+            count_shadowed_defaults("{ a | default = { b = 1 } } & { a = { c = 2 } }"),
+            0
+        );
+        // The default value of `base.port` is used by `a`.
+        assert_eq!(
+            count_shadowed_defaults(
+                "let base = { port | default = 80 } in { a = base, b = base & { port = 8080 } }"
+            ),
+            0
+        );
+    }
+
+    #[test]
+    fn contract_as_value() {
+        assert_eq!(count_contracts_as_values("{ port = Number }"), 1);
+        assert_eq!(
+            count_contracts_as_values("{ name = String, tags = Array String, tls = Bool }"),
+            3
+        );
+        assert_eq!(
+            count_contracts_as_values("let Port = Number in { port = Port }"),
+            1
+        );
+    }
+
+    #[test]
+    fn genuine_values() {
+        assert_eq!(count_contracts_as_values("{ port = 8080 }"), 0);
+        assert_eq!(count_contracts_as_values("{ port | Number = 8080 }"), 0);
+        // Capitalized fields are conventionally contracts.
+        assert_eq!(count_contracts_as_values("{ Port = Number }"), 0);
+        assert_eq!(count_contracts_as_values("{ port | Dyn = Number }"), 0);
+    }
+}
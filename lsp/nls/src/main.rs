@@ -1,23 +1,26 @@
-use std::{fs, io, path::PathBuf};
+use std::{fs, io, path::PathBuf, process::ExitCode};
 
 use anyhow::Result;
 
 use git_version::git_version;
-use log::debug;
+use log::{debug, warn};
 use lsp_server::Connection;
 
 mod actions;
 mod analysis;
 mod background;
 mod cache;
+mod check;
 mod codespan_lsp;
 mod command;
+mod config;
 mod diagnostic;
 mod error;
 mod field_walker;
 mod files;
 mod identifier;
 mod incomplete;
+mod lint;
 mod position;
 mod requests;
 mod server;
@@ -27,9 +30,10 @@ mod term;
 mod trace;
 mod usage;
 mod utils;
+mod workspace;
 mod world;
 
-use crate::trace::Trace;
+use crate::{config::LspConfig, trace::Trace};
 
 #[derive(clap::Parser, Debug)]
 /// The language server of the Nickel language.
@@ -56,9 +60,18 @@ struct Options {
     /// If set, this process runs a background evaluation job instead of setting up a language server.
     #[arg(long)]
     background_eval: bool,
+
+    /// Reports the diagnostics of the given file instead of setting up a language server. Exits
+    /// with a nonzero status if there are any errors.
+    #[arg(long, value_name = "FILE", conflicts_with = "background_eval")]
+    check: Option<PathBuf>,
+
+    /// Outputs the diagnostics reported by `--check` as JSON.
+    #[arg(long, requires = "check")]
+    json_errors: bool,
 }
 
-fn main() -> Result<()> {
+fn main() -> Result<ExitCode> {
     use clap::Parser;
 
     env_logger::init();
@@ -66,7 +79,12 @@ fn main() -> Result<()> {
     let options = Options::parse();
 
     if options.background_eval {
-        return background::worker_main();
+        background::worker_main()?;
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    if let Some(file) = options.check {
+        return check::check_main(file, options.json_errors);
     }
 
     if let Some(file) = options.trace {
@@ -83,9 +101,45 @@ fn main() -> Result<()> {
 
     let capabilities = Server::capabilities();
 
-    connection.initialize(serde_json::to_value(capabilities)?)?;
+    let initialize_params = connection.initialize(serde_json::to_value(capabilities)?)?;
+
+    let config = match initialize_params.get("initializationOptions") {
+        Some(options) if !options.is_null() => {
+            serde_json::from_value::<LspConfig>(options.clone()).unwrap_or_else(|err| {
+                warn!("invalid initialization options, using the default configuration: {err}");
+                LspConfig::default()
+            })
+        }
+        _ => LspConfig::default(),
+    };
 
-    let _server = Server::new(connection).run();
+    let mut server = Server::new(connection, config);
+
+    if server.config.workspace.scan {
+        if let Some(root) = workspace_root(&initialize_params) {
+            server.scan_workspace(&root);
+        }
+    }
+
+    let _ = server.run();
+
+    Ok(ExitCode::SUCCESS)
+}
 
-    Ok(())
+/// The root directory of the workspace, as given by the client in the parameters of the
+/// `initialize` request. When there are several workspace folders, only the first one is used.
+fn workspace_root(initialize_params: &serde_json::Value) -> Option<PathBuf> {
+    let params =
+        serde_json::from_value::<lsp_types::InitializeParams>(initialize_params.clone()).ok()?;
+
+    #[allow(deprecated)]
+    // `root_uri` is deprecated in favor of `workspace_folders`, but some clients only send the
+    // former.
+    let uri = params
+        .workspace_folders
+        .and_then(|folders| folders.into_iter().next())
+        .map(|folder| folder.uri)
+        .or(params.root_uri)?;
+
+    uri.to_file_path().ok()
 }
@@ -0,0 +1,416 @@
+//! Call hierarchy: the calls made to a function (incoming calls), and the calls it makes to other
+//! functions (outgoing calls).
+//!
+//! Functions are `let` bindings and record fields whose value is a function. A call is an
+//! application whose head refers to such a function, either through a variable or a field access.
+//! A curried application `f x y` counts as a single call to `f`. Calls are attributed to the
+//! innermost binding containing them, which may not be a function itself (such as a record field
+//! `foo = f 1`), or to the whole file for calls outside of any binding.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use codespan::FileId;
+use lsp_server::{RequestId, Response, ResponseError};
+use lsp_types::{
+    CallHierarchyIncomingCall, CallHierarchyIncomingCallsParams, CallHierarchyItem,
+    CallHierarchyOutgoingCall, CallHierarchyOutgoingCallsParams, CallHierarchyPrepareParams, Range,
+    SymbolKind, TextDocumentIdentifier, TextDocumentPositionParams, Url,
+};
+use nickel_lang_core::{
+    identifier::LocIdent,
+    position::RawSpan,
+    term::{RichTerm, Term, Traverse, TraverseControl, UnaryOp},
+};
+use serde_json::Value;
+
+use crate::{cache::CacheExt as _, diagnostic::LocationCompat, server::Server, world::World};
+
+/// A `let` binding or a record field.
+struct Binding {
+    ident: LocIdent,
+    ident_span: RawSpan,
+    /// The span of the whole binding, including its value.
+    span: RawSpan,
+    is_function: bool,
+}
+
+/// A call to a function.
+struct Call {
+    /// The index of the innermost binding containing the call, if any.
+    caller: Option<usize>,
+    /// The called function, such as `f` in `f x y`.
+    head: RichTerm,
+    /// The span of the identifier of the called function.
+    span: RawSpan,
+}
+
+/// The bindings and the calls of a file.
+#[derive(Default)]
+struct CallGraph {
+    bindings: Vec<Binding>,
+    calls: Vec<Call>,
+}
+
+impl CallGraph {
+    fn new(rt: &RichTerm) -> Self {
+        let mut graph = CallGraph::default();
+        graph.fill(rt, None);
+        graph
+    }
+
+    fn binding(&self, span: RawSpan) -> Option<usize> {
+        self.bindings.iter().position(|b| b.ident_span == span)
+    }
+
+    /// Record the binding of `ident` to `value`, returning its index.
+    fn add_binding(&mut self, ident: LocIdent, value: &RichTerm) -> Option<usize> {
+        let ident_span = ident.pos.into_opt()?;
+        let span = value
+            .pos
+            .into_opt()
+            .and_then(|span| ident_span.fuse(span))
+            .unwrap_or(ident_span);
+
+        self.bindings.push(Binding {
+            ident,
+            ident_span,
+            span,
+            is_function: is_function(value),
+        });
+        Some(self.bindings.len() - 1)
+    }
+
+    fn fill(&mut self, t: &impl Traverse<RichTerm>, caller: Option<usize>) {
+        t.traverse_ref(
+            &mut |rt: &RichTerm, caller: &Option<usize>| -> TraverseControl<Option<usize>, ()> {
+                match rt.as_ref() {
+                    Term::Let(id, value, body, _) => {
+                        let value_caller = self.add_binding(*id, value).or(*caller);
+                        self.fill(value, value_caller);
+                        self.fill(body, *caller);
+                        TraverseControl::SkipBranch
+                    }
+                    Term::Record(data) | Term::RecRecord(data, ..) => {
+                        for (id, field) in &data.fields {
+                            self.fill(&field.metadata.annotation, *caller);
+
+                            if let Some(value) = &field.value {
+                                let value_caller = self.add_binding(*id, value).or(*caller);
+                                self.fill(value, value_caller);
+                            }
+                        }
+
+                        if let Term::RecRecord(_, dyn_fields, _) = rt.as_ref() {
+                            for (name, field) in dyn_fields {
+                                self.fill(name, *caller);
+                                self.fill(&field.metadata.annotation, *caller);
+                                if let Some(value) = &field.value {
+                                    self.fill(value, *caller);
+                                }
+                            }
+                        }
+
+                        TraverseControl::SkipBranch
+                    }
+                    Term::App(..) => {
+                        let mut head = rt;
+                        let mut args = Vec::new();
+
+                        while let Term::App(fun, arg) = head.as_ref() {
+                            args.push(arg);
+                            head = fun;
+                        }
+
+                        if let Some(span) = callee_span(head) {
+                            self.calls.push(Call {
+                                caller: *caller,
+                                head: head.clone(),
+                                span,
+                            });
+                        }
+
+                        self.fill(head, *caller);
+                        for arg in args {
+                            self.fill(arg, *caller);
+                        }
+
+                        TraverseControl::SkipBranch
+                    }
+                    _ => TraverseControl::Continue,
+                }
+            },
+            &caller,
+        );
+    }
+}
+
+fn is_function(rt: &RichTerm) -> bool {
+    match rt.as_ref() {
+        Term::Fun(..) | Term::FunPattern(..) | Term::Match(_) => true,
+        Term::Annotated(_, inner) => is_function(inner),
+        _ => false,
+    }
+}
+
+/// The span of the identifier referring to the function called by an application whose head is
+/// `head`, if the function is named.
+fn callee_span(head: &RichTerm) -> Option<RawSpan> {
+    match head.as_ref() {
+        Term::Var(id) | Term::Op1(UnaryOp::RecordAccess(id), _) => id.pos.into_opt(),
+        _ => None,
+    }
+}
+
+/// The call graphs of the files involved in a request, computed on demand.
+struct CallGraphs<'a> {
+    world: &'a World,
+    graphs: HashMap<FileId, CallGraph>,
+}
+
+impl<'a> CallGraphs<'a> {
+    fn new(world: &'a World) -> Self {
+        CallGraphs {
+            world,
+            graphs: HashMap::new(),
+        }
+    }
+
+    fn get(&mut self, file_id: FileId) -> Option<&CallGraph> {
+        if !self.graphs.contains_key(&file_id) {
+            let graph = CallGraph::new(self.world.cache.get_ref(file_id)?);
+            self.graphs.insert(file_id, graph);
+        }
+
+        self.graphs.get(&file_id)
+    }
+
+    /// The file and the binding that `item` refers to.
+    fn resolve(
+        &mut self,
+        item: &CallHierarchyItem,
+    ) -> Result<Option<(FileId, usize)>, ResponseError> {
+        let pos = self.world.cache.position(&TextDocumentPositionParams {
+            text_document: TextDocumentIdentifier {
+                uri: item.uri.clone(),
+            },
+            position: item.selection_range.start,
+        })?;
+
+        Ok(self.get(pos.src_id).and_then(|graph| {
+            let idx = graph
+                .bindings
+                .iter()
+                .position(|b| b.ident_span.start == pos.index)?;
+            Some((pos.src_id, idx))
+        }))
+    }
+}
+
+/// The item of a binding, or `None` if the binding isn't defined in a file that the client can
+/// open, such as the standard library or a source which only exists in memory.
+fn item(world: &World, binding: &Binding) -> Option<CallHierarchyItem> {
+    let files = world.cache.files();
+    let uri = Url::from_file_path(files.name(binding.span.src_id)).ok()?;
+
+    Some(CallHierarchyItem {
+        name: binding.ident.to_string(),
+        kind: if binding.is_function {
+            SymbolKind::FUNCTION
+        } else {
+            SymbolKind::FIELD
+        },
+        tags: None,
+        detail: None,
+        uri,
+        range: Range::from_span(&binding.span, files),
+        selection_range: Range::from_span(&binding.ident_span, files),
+        data: None,
+    })
+}
+
+/// An item standing for a whole file, for the calls that aren't inside any binding. As for
+/// [item], returns `None` if the client can't open the file.
+fn file_item(world: &World, file_id: FileId) -> Option<CallHierarchyItem> {
+    let files = world.cache.files();
+    let path = std::path::Path::new(files.name(file_id));
+    let uri = Url::from_file_path(path).ok()?;
+    let range = world
+        .cache
+        .get_ref(file_id)
+        .and_then(|rt| rt.pos.into_opt())
+        .map(|span| Range::from_span(&span, files))
+        .unwrap_or_default();
+
+    Some(CallHierarchyItem {
+        name: path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default(),
+        kind: SymbolKind::FILE,
+        tags: None,
+        detail: None,
+        uri,
+        range,
+        selection_range: range,
+        data: None,
+    })
+}
+
+pub fn handle_prepare(
+    params: CallHierarchyPrepareParams,
+    id: RequestId,
+    server: &mut Server,
+) -> Result<(), ResponseError> {
+    let world = &server.world;
+    let pos = world
+        .cache
+        .position(&params.text_document_position_params)?;
+    let ident = world.lookup_ident_by_position(pos)?;
+
+    let mut def_spans = world
+        .lookup_term_by_position(pos)?
+        .map(|term| world.get_defs(term, ident))
+        .unwrap_or_default();
+    // The position may be pointing at the definition itself.
+    def_spans.extend(ident.and_then(|id| id.pos.into_opt()));
+    def_spans.sort_by_key(|span| (span.start, span.end));
+    def_spans.dedup();
+
+    let mut graphs = CallGraphs::new(world);
+    let items: Vec<_> = def_spans
+        .into_iter()
+        .filter_map(|span| {
+            let graph = graphs.get(span.src_id)?;
+            let binding = &graph.bindings[graph.binding(span)?];
+
+            if binding.is_function {
+                item(world, binding)
+            } else {
+                None
+            }
+        })
+        .collect();
+
+    let response = if items.is_empty() {
+        Response::new_ok(id, Value::Null)
+    } else {
+        Response::new_ok(id, items)
+    };
+
+    server.reply(response);
+    Ok(())
+}
+
+pub fn handle_incoming_calls(
+    params: CallHierarchyIncomingCallsParams,
+    id: RequestId,
+    server: &mut Server,
+) -> Result<(), ResponseError> {
+    let world = &server.world;
+    let mut graphs = CallGraphs::new(world);
+    let mut calls = Vec::new();
+
+    if let Some((file_id, idx)) = graphs.resolve(&params.item)? {
+        let def_span = graphs.get(file_id).unwrap().bindings[idx].ident_span;
+        let mut callee_spans: HashSet<RawSpan> = world
+            .analysis
+            .get_usages(&def_span)
+            .filter_map(|id| id.pos.into_opt())
+            .collect();
+        callee_spans.extend(world.get_field_refs(def_span));
+
+        let mut files: Vec<FileId> = callee_spans.iter().map(|span| span.src_id).collect();
+        files.sort();
+        files.dedup();
+
+        for file_id in files {
+            let Some(graph) = graphs.get(file_id) else {
+                continue;
+            };
+
+            let mut by_caller: BTreeMap<Option<usize>, Vec<Range>> = BTreeMap::new();
+            for call in graph.calls.iter() {
+                if callee_spans.contains(&call.span) {
+                    by_caller
+                        .entry(call.caller)
+                        .or_default()
+                        .push(Range::from_span(&call.span, world.cache.files()));
+                }
+            }
+
+            calls.extend(by_caller.into_iter().filter_map(|(caller, from_ranges)| {
+                let from = match caller {
+                    Some(idx) => item(world, &graph.bindings[idx]),
+                    None => file_item(world, file_id),
+                }?;
+
+                Some(CallHierarchyIncomingCall { from, from_ranges })
+            }));
+        }
+    }
+
+    server.reply(Response::new_ok(id, calls));
+    Ok(())
+}
+
+pub fn handle_outgoing_calls(
+    params: CallHierarchyOutgoingCallsParams,
+    id: RequestId,
+    server: &mut Server,
+) -> Result<(), ResponseError> {
+    let world = &server.world;
+    let mut graphs = CallGraphs::new(world);
+    let mut calls: Vec<CallHierarchyOutgoingCall> = Vec::new();
+
+    if let Some((file_id, idx)) = graphs.resolve(&params.item)? {
+        let own_calls: Vec<(RichTerm, RawSpan)> = graphs
+            .get(file_id)
+            .unwrap()
+            .calls
+            .iter()
+            .filter(|call| call.caller == Some(idx))
+            .map(|call| (call.head.clone(), call.span))
+            .collect();
+
+        // The callees, in the order of their first call.
+        let mut callees: Vec<(RawSpan, CallHierarchyOutgoingCall)> = Vec::new();
+
+        for (head, span) in own_calls {
+            let range = Range::from_span(&span, world.cache.files());
+
+            for def_span in world.get_defs(&head, None) {
+                if let Some((_, call)) = callees.iter_mut().find(|(s, _)| *s == def_span) {
+                    call.from_ranges.push(range);
+                    continue;
+                }
+
+                let Some(graph) = graphs.get(def_span.src_id) else {
+                    continue;
+                };
+                let Some(binding) = graph.binding(def_span).map(|idx| &graph.bindings[idx]) else {
+                    continue;
+                };
+
+                if !binding.is_function {
+                    continue;
+                }
+                let Some(to) = item(world, binding) else {
+                    continue;
+                };
+
+                callees.push((
+                    def_span,
+                    CallHierarchyOutgoingCall {
+                        to,
+                        from_ranges: vec![range],
+                    },
+                ));
+            }
+        }
+
+        calls.extend(callees.into_iter().map(|(_, call)| call));
+    }
+
+    server.reply(Response::new_ok(id, calls));
+    Ok(())
+}
@@ -0,0 +1,71 @@
+use lsp_server::{RequestId, Response, ResponseError};
+use lsp_types::{CodeLens, CodeLensParams, Command, Range};
+
+use crate::cache::CacheExt as _;
+use crate::diagnostic::LocationCompat;
+use crate::field_walker::{FieldResolver, Record};
+use crate::server::Server;
+
+/// Return a lens above each top-level field definition of the document, whose title is the
+/// inferred type of the field. Fields without a known type don't get a lens.
+///
+/// Lenses are an always-visible alternative to hover, which can be noisy: they are only computed
+/// when enabled in the configuration.
+pub fn handle_code_lens(
+    params: CodeLensParams,
+    id: RequestId,
+    server: &mut Server,
+) -> Result<(), ResponseError> {
+    if !server.config.code_lens.enabled {
+        server.reply(Response::new_ok(id, Vec::<CodeLens>::new()));
+        return Ok(());
+    }
+
+    let world = &server.world;
+    let file_id = world
+        .cache
+        .file_id(&params.text_document.uri)?
+        .ok_or_else(|| crate::error::Error::FileNotFound(params.text_document.uri.clone()))?;
+
+    let mut lenses: Vec<CodeLens> = world
+        .cache
+        .get_ref(file_id)
+        .map(|rt| FieldResolver::new(world).resolve_path(rt, [].into_iter()))
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(|rec| match rec {
+            Record::RecordTerm(data) => Some(data),
+            Record::RecordType(_) => None,
+        })
+        .flat_map(|data| data.fields.into_iter())
+        .filter_map(|(id, field)| {
+            let span = id.pos.into_opt()?;
+            // Fields coming from other files, e.g. through a merge with an import, aren't defined
+            // in this document.
+            if span.src_id != file_id {
+                return None;
+            }
+
+            let ty = world
+                .analysis
+                .get_type_for_ident(&id)
+                .or_else(|| world.analysis.get_type(field.value.as_ref()?))?;
+
+            Some(CodeLens {
+                range: Range::from_span(&span, world.cache.files()),
+                command: Some(Command {
+                    title: ty.to_string(),
+                    command: String::new(),
+                    arguments: None,
+                }),
+                data: None,
+            })
+        })
+        .collect();
+
+    // Sort so the response is deterministic.
+    lenses.sort_by_key(|lens| lens.range.start);
+
+    server.reply(Response::new_ok(id, lenses));
+    Ok(())
+}
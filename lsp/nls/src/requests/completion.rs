@@ -20,7 +20,6 @@ use crate::{
     identifier::LocIdent,
     incomplete,
     server::Server,
-    usage::Environment,
     world::World,
 };
 
@@ -78,11 +77,13 @@ fn sanitize_record_path_for_completion(
 ) -> Option<RichTerm> {
     if let (Term::ParseError(_), Some(range)) = (term.term.as_ref(), term.pos.as_opt_ref()) {
         let mut range = *range;
+        // Fall back to the initial environment rather than an empty one, so that `std` (and the
+        // documentation of its members) is still available to complete a path like `std.string.`
         let env = world
             .analysis
             .get_env(term)
             .cloned()
-            .unwrap_or_else(Environment::new);
+            .unwrap_or_else(|| world.initial_term_env.clone());
         if cursor.index < range.start || cursor.index > range.end || cursor.src_id != range.src_id {
             return None;
         }
@@ -194,7 +195,11 @@ fn field_completion(rt: &RichTerm, world: &World) -> Vec<CompletionItem> {
 }
 
 fn env_completion(rt: &RichTerm, world: &World) -> Vec<CompletionItem> {
-    let env = world.analysis.get_env(rt).cloned().unwrap_or_default();
+    let env = world
+        .analysis
+        .get_env(rt)
+        .cloned()
+        .unwrap_or_else(|| world.initial_term_env.clone());
     env.iter_elems()
         .map(|(_, def_with_path)| def_with_path.completion_item())
         .collect()
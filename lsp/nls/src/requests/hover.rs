@@ -1,20 +1,31 @@
+use std::time::Duration;
+
 use lsp_server::{RequestId, Response, ResponseError};
 use lsp_types::{Hover, HoverContents, HoverParams, LanguageString, MarkedString, Range};
 use nickel_lang_core::{
+    cache::{Cache, ErrorTolerance},
     combine::Combine,
+    eval::{cache::CacheImpl, VirtualMachine},
     identifier::Ident,
-    position::RawSpan,
-    term::{record::FieldMetadata, LabeledType, RichTerm, Term, UnaryOp},
-    typ::Type,
+    position::{RawPos, RawSpan},
+    term::{
+        pattern::{Pattern, PatternData},
+        record::FieldMetadata,
+        BinaryOp, LabeledType, MatchBranch, RichTerm, Term, Traverse, TraverseControl, UnaryOp,
+    },
+    transform::transform,
+    typ::{EnumRows, EnumRowsF, Type, TypeF},
 };
 use serde_json::Value;
 
 use crate::{
     cache::CacheExt,
+    config::ValuePreviewConfig,
     diagnostic::LocationCompat,
     field_walker::{FieldResolver, Record},
     identifier::LocIdent,
     server::Server,
+    term::{record_openness, RichTermPtr},
     world::World,
 };
 
@@ -24,6 +35,11 @@ struct HoverData {
     metadata: Vec<FieldMetadata>,
     span: Option<RawSpan>,
     ty: Option<Type>,
+    /// When hovering over a match branch, the pattern of this branch together with the type of
+    /// the values it matches.
+    pattern: Option<(Pattern, Type)>,
+    /// When hovering over a builtin operator, the documentation of this operator.
+    operator: Option<&'static OperatorDoc>,
 }
 
 impl Combine for HoverData {
@@ -32,10 +48,176 @@ impl Combine for HoverData {
         left.metadata.append(&mut right.metadata);
         left.ty = left.ty.or(right.ty);
         left.span = left.span.or(right.span);
+        left.pattern = left.pattern.or(right.pattern);
+        left.operator = left.operator.or(right.operator);
         left
     }
 }
 
+/// The documentation of a builtin operator, shown when hovering over one of its applications.
+#[derive(Debug)]
+struct OperatorDoc {
+    /// The operator, as written in the source.
+    symbol: &'static str,
+    /// The type of the operator, seen as a curried function.
+    typ: &'static str,
+    description: &'static str,
+}
+
+const OPERATORS: &[OperatorDoc] = &[
+    OperatorDoc {
+        symbol: "&",
+        typ: "Dyn -> Dyn -> Dyn",
+        description: "Merges two values. Records are merged recursively, field by field, while \
+            other values can only be merged if they are equal or if they have different \
+            priorities.",
+    },
+    OperatorDoc {
+        symbol: "@",
+        typ: "forall a. Array a -> Array a -> Array a",
+        description: "Concatenates two arrays.",
+    },
+    OperatorDoc {
+        symbol: "++",
+        typ: "String -> String -> String",
+        description: "Concatenates two strings.",
+    },
+    OperatorDoc {
+        symbol: "f x",
+        typ: "forall a b. (a -> b) -> a -> b",
+        description: "Applies a function to an argument.",
+    },
+    OperatorDoc {
+        symbol: "|>",
+        typ: "forall a b. a -> (a -> b) -> b",
+        description: "Reverse application: `x |> f` is `f x`. This makes it possible to write \
+            chains of function calls from left to right.",
+    },
+    OperatorDoc {
+        symbol: "+",
+        typ: "Number -> Number -> Number",
+        description: "Adds two numbers.",
+    },
+    OperatorDoc {
+        symbol: "-",
+        typ: "Number -> Number -> Number",
+        description: "Subtracts two numbers. In prefix position, `-x` is the opposite of `x`.",
+    },
+    OperatorDoc {
+        symbol: "*",
+        typ: "Number -> Number -> Number",
+        description: "Multiplies two numbers.",
+    },
+    OperatorDoc {
+        symbol: "/",
+        typ: "Number -> Number -> Number",
+        description: "Divides two numbers.",
+    },
+    OperatorDoc {
+        symbol: "%",
+        typ: "Number -> Number -> Number",
+        description: "The remainder of the division of two numbers, which has the sign of the \
+            dividend.",
+    },
+    OperatorDoc {
+        symbol: "==",
+        typ: "forall a b. a -> b -> Bool",
+        description: "Structural equality. Functions can't be compared.",
+    },
+    OperatorDoc {
+        symbol: "!=",
+        typ: "forall a b. a -> b -> Bool",
+        description: "Structural inequality, the negation of `==`.",
+    },
+    OperatorDoc {
+        symbol: "<",
+        typ: "Number -> Number -> Bool",
+        description: "Strictly less than.",
+    },
+    OperatorDoc {
+        symbol: "<=",
+        typ: "Number -> Number -> Bool",
+        description: "Less than or equal to.",
+    },
+    OperatorDoc {
+        symbol: ">",
+        typ: "Number -> Number -> Bool",
+        description: "Strictly greater than.",
+    },
+    OperatorDoc {
+        symbol: ">=",
+        typ: "Number -> Number -> Bool",
+        description: "Greater than or equal to.",
+    },
+    OperatorDoc {
+        symbol: "&&",
+        typ: "Bool -> Bool -> Bool",
+        description: "Boolean conjunction. The right operand is only evaluated if the left one \
+            is `true`.",
+    },
+    OperatorDoc {
+        symbol: "||",
+        typ: "Bool -> Bool -> Bool",
+        description: "Boolean disjunction. The right operand is only evaluated if the left one \
+            is `false`.",
+    },
+    OperatorDoc {
+        symbol: "!",
+        typ: "Bool -> Bool",
+        description: "Boolean negation.",
+    },
+];
+
+/// The surface syntax of the operator applied by `rt`, provided that `pos` lies on the operator
+/// itself rather than on one of its operands.
+fn operator_symbol(rt: &RichTerm, pos: RawPos) -> Option<&'static str> {
+    let on_operator = |operands: &[&RichTerm]| !operands.iter().any(|t| t.pos.contains(pos));
+
+    let symbol = match rt.as_ref() {
+        Term::Op2(op, t1, t2) if on_operator(&[t1, t2]) => match op {
+            BinaryOp::Merge(_) => "&",
+            BinaryOp::ArrayConcat => "@",
+            BinaryOp::StringConcat => "++",
+            BinaryOp::Plus => "+",
+            BinaryOp::Sub => "-",
+            BinaryOp::Mult => "*",
+            BinaryOp::Div => "/",
+            BinaryOp::Modulo => "%",
+            BinaryOp::Eq => "==",
+            BinaryOp::LessThan => "<",
+            BinaryOp::LessOrEq => "<=",
+            BinaryOp::GreaterThan => ">",
+            BinaryOp::GreaterOrEq => ">=",
+            _ => return None,
+        },
+        Term::Op1(UnaryOp::BoolNot, t) => match t.as_ref() {
+            // `x != y` is parsed as `!(x == y)`, where the equality doesn't have a position.
+            Term::Op2(BinaryOp::Eq, t1, t2) if !t.pos.is_def() && on_operator(&[t1, t2]) => "!=",
+            _ if on_operator(&[t]) => "!",
+            _ => return None,
+        },
+        Term::App(f, arg) => match f.as_ref() {
+            // The lazy boolean operators are parsed as the application of a unary operator.
+            Term::Op1(UnaryOp::BoolAnd, t) if on_operator(&[t, arg]) => "&&",
+            Term::Op1(UnaryOp::BoolOr, t) if on_operator(&[t, arg]) => "||",
+            // `x |> f` is parsed as `f x`, so the argument comes first in the source.
+            _ if on_operator(&[f, arg]) => match (f.pos.into_opt(), arg.pos.into_opt()) {
+                (Some(f_span), Some(arg_span)) if arg_span.end <= f_span.start => "|>",
+                _ => "f x",
+            },
+            _ => return None,
+        },
+        _ => return None,
+    };
+
+    Some(symbol)
+}
+
+fn operator_doc(rt: &RichTerm, pos: RawPos) -> Option<&'static OperatorDoc> {
+    let symbol = operator_symbol(rt, pos)?;
+    OPERATORS.iter().find(|op| op.symbol == symbol)
+}
+
 fn annotated_contracts(rt: &RichTerm) -> &[LabeledType] {
     match rt.as_ref() {
         Term::Annotated(annot, _) => &annot.contracts,
@@ -43,6 +225,23 @@ fn annotated_contracts(rt: &RichTerm) -> &[LabeledType] {
     }
 }
 
+/// The distinct scalar types (`Number`, `String` and `Bool`) among the type annotations and the
+/// contracts of `metadata` and `values`. No value can satisfy two different scalar types, so if
+/// there are several of them, the merged definitions conflict.
+fn scalar_annotations(metadata: &[FieldMetadata], values: &[RichTerm]) -> Vec<String> {
+    let mut scalars: Vec<_> = metadata
+        .iter()
+        .flat_map(|m| m.annotation.typ.iter().chain(&m.annotation.contracts))
+        .chain(values.iter().flat_map(annotated_contracts))
+        .filter(|annot| matches!(annot.typ.typ, TypeF::Number | TypeF::String | TypeF::Bool))
+        .map(|annot| annot.typ.to_string())
+        .collect();
+
+    scalars.sort();
+    scalars.dedup();
+    scalars
+}
+
 fn nickel_string(s: String) -> MarkedString {
     MarkedString::LanguageString(LanguageString {
         language: "nickel".to_owned(),
@@ -73,6 +272,8 @@ fn ident_hover(ident: LocIdent, world: &World) -> Option<HoverData> {
         metadata: Vec::new(),
         span: Some(span),
         ty,
+        pattern: None,
+        operator: None,
     };
 
     if let Some(def) = world.analysis.get_def(&ident) {
@@ -100,29 +301,249 @@ fn ident_hover(ident: LocIdent, world: &World) -> Option<HoverData> {
     Some(ret)
 }
 
-fn term_hover(rt: &RichTerm, world: &World) -> Option<HoverData> {
+/// Collect the enum tags matched by a pattern, or return `false` if the pattern isn't an enum
+/// pattern or an or-pattern of enum patterns.
+fn matched_tags(pat: &Pattern, tags: &mut Vec<Ident>) -> bool {
+    match &pat.data {
+        PatternData::Enum(enum_pat) => {
+            tags.push(enum_pat.tag.ident());
+            true
+        }
+        PatternData::Or(or_pat) => or_pat.patterns.iter().all(|pat| matched_tags(pat, tags)),
+        _ => false,
+    }
+}
+
+/// Only keep the rows of `rows` corresponding to one of `tags`. The result is a closed enum type.
+fn filter_enum_rows(rows: &EnumRows, tags: &[Ident]) -> EnumRows {
+    match &rows.0 {
+        EnumRowsF::Extend { row, tail } => {
+            let tail = filter_enum_rows(tail, tags);
+
+            if tags.contains(&row.id.ident()) {
+                EnumRows(EnumRowsF::Extend {
+                    row: row.clone(),
+                    tail: Box::new(tail),
+                })
+            } else {
+                tail
+            }
+        }
+        EnumRowsF::Empty | EnumRowsF::TailVar(_) => EnumRows(EnumRowsF::Empty),
+    }
+}
+
+/// Narrow down the type of the argument of a match expression to the values matched by a given
+/// pattern. Currently, only enum types are narrowed, to the tags appearing in the pattern.
+fn narrow_to_pattern(ty: Type, pat: &Pattern) -> Type {
+    let mut tags = Vec::new();
+
+    if let TypeF::Enum(rows) = &ty.typ {
+        if matched_tags(pat, &mut tags) {
+            return Type {
+                typ: TypeF::Enum(filter_enum_rows(rows, &tags)),
+                pos: ty.pos,
+            };
+        }
+    }
+
+    ty
+}
+
+/// Hover data for a branch of a match expression, if `rt` is the body of a branch or if it is a
+/// match expression and `pos` lies within the pattern of one of its branches (patterns aren't
+/// terms, so hovering over a pattern gives back the enclosing match expression).
+///
+/// The reported type is the type of the branch's result, falling back to the result type of the
+/// whole match expression.
+fn match_branch_hover(rt: &RichTerm, pos: RawPos, world: &World) -> Option<HoverData> {
+    let (match_rt, branch, span) = match rt.as_ref() {
+        Term::Match(data) => {
+            let branch = data
+                .branches
+                .iter()
+                .find(|branch| branch.pattern.pos.contains(pos))?;
+            (rt.clone(), branch.clone(), branch.pattern.pos.into_opt())
+        }
+        _ => {
+            let parent = world.analysis.get_parent_chain(rt)?.next()?;
+            let Term::Match(data) = parent.as_ref() else {
+                return None;
+            };
+            let branch = data
+                .branches
+                .iter()
+                .find(|branch| RichTermPtr(branch.body.clone()) == RichTermPtr(rt.clone()))?;
+            (parent.clone(), branch.clone(), rt.pos.into_opt())
+        }
+    };
+
+    let MatchBranch { pattern, body, .. } = branch;
+
+    let (arg_ty, ret_ty) = match world.analysis.get_type(&match_rt).map(|ty| &ty.typ) {
+        Some(TypeF::Arrow(arg, ret)) => (Some(arg.as_ref().clone()), Some(ret.as_ref().clone())),
+        _ => (None, None),
+    };
+
+    let ty = world.analysis.get_type(&body).cloned().or(ret_ty);
+    let arg_ty = arg_ty.unwrap_or_else(|| Type::from(TypeF::Dyn));
+
+    Some(HoverData {
+        values: Vec::new(),
+        metadata: Vec::new(),
+        span,
+        ty,
+        pattern: Some((pattern.clone(), narrow_to_pattern(arg_ty, &pattern))),
+        operator: None,
+    })
+}
+
+/// The type of the application `rt` of a function to some of its arguments, computed from the
+/// type of the function: it's the type of the function without the parameters already applied.
+/// The function's type is either inferred by the typechecker or given by an annotation. Return
+/// `None` if this type is unknown or has fewer parameters than the arguments applied.
+fn partial_application_type(rt: &RichTerm, pos: RawPos, world: &World) -> Option<Type> {
+    let mut head = rt;
+    let mut applied = 0;
+
+    while let Term::App(f, _) = head.as_ref() {
+        head = f;
+        applied += 1;
+    }
+
+    let head_hover = term_hover(head, pos, world)?;
+    let head_ty = head_hover
+        .ty
+        .filter(|ty| !matches!(ty.typ, TypeF::Dyn))
+        .or_else(|| {
+            head_hover
+                .metadata
+                .into_iter()
+                .find_map(|m| Some(m.annotation.typ?.typ))
+        })?;
+
+    residual_type(head_ty, applied)
+}
+
+/// The type of the result of applying a function of type `ty` to `applied` arguments. Type
+/// variables are left free, e.g. applying `forall a b. (a -> b) -> Array a -> Array b` to one
+/// argument gives `Array a -> Array b`.
+fn residual_type(ty: Type, applied: usize) -> Option<Type> {
+    fn strip_foralls(mut ty: Type) -> Type {
+        while let TypeF::Forall { body, .. } = ty.typ {
+            ty = *body;
+        }
+        ty
+    }
+
+    (0..applied).try_fold(strip_foralls(ty), |ty, _| match ty.typ {
+        TypeF::Arrow(_, codomain) => Some(strip_foralls(*codomain)),
+        _ => None,
+    })
+}
+
+fn term_hover(rt: &RichTerm, pos: RawPos, world: &World) -> Option<HoverData> {
     let ty = world.analysis.get_type(rt).cloned();
     let span = rt.pos.into_opt();
 
-    match rt.as_ref() {
+    let hover = match rt.as_ref() {
         Term::Op1(UnaryOp::RecordAccess(id), parent) => {
             let resolver = FieldResolver::new(world);
             let parents = resolver.resolve_record(parent);
             let (values, metadata) = values_and_metadata_from_field(parents, id.ident());
-            Some(HoverData {
+            HoverData {
                 values,
                 metadata,
                 span,
                 ty,
-            })
+                pattern: None,
+                operator: None,
+            }
         }
-        _ => Some(HoverData {
+        // Outside of statically typed code, the type of an application is usually unknown. If the
+        // function being applied has a known type, we can still tell what's left to apply.
+        Term::App(..) if ty.as_ref().map_or(true, |ty| matches!(ty.typ, TypeF::Dyn)) => HoverData {
+            values: vec![rt.clone()],
+            metadata: vec![],
+            span,
+            ty: partial_application_type(rt, pos, world).or(ty),
+            pattern: None,
+            operator: operator_doc(rt, pos),
+        },
+        _ => HoverData {
             values: vec![rt.clone()],
             metadata: vec![],
             span,
             ty,
-        }),
+            pattern: None,
+            operator: operator_doc(rt, pos),
+        },
+    };
+
+    // The branch data comes first, so that its type and span take precedence.
+    Some(match match_branch_hover(rt, pos, world) {
+        Some(branch_hover) => Combine::combine(branch_hover, hover),
+        None => hover,
+    })
+}
+
+fn node_count(rt: &RichTerm) -> usize {
+    let mut count = 0;
+    rt.traverse_ref(
+        &mut |_: &RichTerm, _: &()| {
+            count += 1;
+            TraverseControl::Continue::<_, ()>
+        },
+        &(),
+    );
+    count
+}
+
+/// Whether `rt` can be evaluated on its own, that is if it doesn't refer to any variable or
+/// import.
+fn is_closed(rt: &RichTerm) -> bool {
+    rt.traverse_ref(
+        &mut |rt: &RichTerm, _: &()| match rt.as_ref() {
+            Term::Var(_)
+            | Term::Import(_)
+            | Term::ResolvedImport(_)
+            | Term::ParseError(_)
+            | Term::RuntimeError(_) => TraverseControl::Return(()),
+            _ => TraverseControl::Continue,
+        },
+        &(),
+    )
+    .is_none()
+}
+
+/// Evaluate `rt` and render the result, if it's cheap enough. We don't have an evaluation
+/// environment at hand, so only closed terms are considered. The size of the term, both before
+/// and after evaluation, as well as the evaluation time, are bounded by `config`.
+fn value_preview(rt: &RichTerm, config: &ValuePreviewConfig) -> Option<String> {
+    if !config.enabled || node_count(rt) > config.max_nodes || !is_closed(rt) {
+        return None;
+    }
+
+    let rt = transform(rt.clone(), None).ok()?;
+    let mut vm = VirtualMachine::<_, CacheImpl>::new(
+        Cache::new(ErrorTolerance::Tolerant),
+        std::io::sink(),
+    );
+    vm.set_time_limit(Some(Duration::from_millis(config.max_eval_time_ms)));
+    vm.start_time_limit();
+
+    let value = vm.eval_full(rt).ok()?;
+
+    if node_count(&value) > config.max_nodes
+        || matches!(
+            value.as_ref(),
+            Term::Fun(..) | Term::FunPattern(..) | Term::Match(_)
+        )
+    {
+        return None;
     }
+
+    Some(value.to_string())
 }
 
 pub fn handle(
@@ -141,7 +562,7 @@ pub fn handle(
         .and_then(|ident| ident_hover(ident, &server.world));
 
     let term = server.world.lookup_term_by_position(pos)?;
-    let term_hover_data = term.and_then(|rt| term_hover(rt, &server.world));
+    let term_hover_data = term.and_then(|rt| term_hover(rt, pos, &server.world));
 
     // We combine the hover information from the term (which can have better type information)
     // and the ident (which can have better metadata), but only when hovering over a `Var`.
@@ -194,6 +615,21 @@ pub fn handle(
 
         contents.push(nickel_string(ty));
 
+        if let [value] = hover.values.as_slice() {
+            if let Some(preview) = value_preview(value, &server.config.value_preview) {
+                contents.push(nickel_string(format!("= {preview}")));
+            }
+        }
+
+        if let Some((pattern, pattern_ty)) = &hover.pattern {
+            contents.push(nickel_string(format!("{pattern} : {pattern_ty}")));
+        }
+
+        if let Some(op) = hover.operator {
+            contents.push(nickel_string(format!("({}) : {}", op.symbol, op.typ)));
+            contents.push(MarkedString::String(op.description.to_owned()));
+        }
+
         let mut contracts: Vec<_> = hover
             .metadata
             .iter()
@@ -208,6 +644,42 @@ pub fn handle(
 
         contents.extend(contracts.into_iter().map(nickel_string));
 
+        let scalars = scalar_annotations(&hover.metadata, &hover.values);
+        if scalars.len() > 1 {
+            let scalars: Vec<_> = scalars.iter().map(|s| format!("`{s}`")).collect();
+            contents.push(MarkedString::String(format!(
+                "warning: conflicting annotations {}: no value can satisfy all of them",
+                scalars.join(", ")
+            )));
+        }
+
+        // Record contracts can come from the hovered values themselves or from their
+        // annotations. We only say something if they all agree.
+        let contract_openness = hover
+            .metadata
+            .iter()
+            .flat_map(|m| &m.annotation.contracts)
+            .filter_map(|contract| match &contract.typ.typ {
+                TypeF::Flat(rt) => record_openness(rt),
+                _ => None,
+            });
+        let mut openness = hover
+            .values
+            .iter()
+            .filter_map(record_openness)
+            .chain(contract_openness);
+
+        if let Some(open) = openness.next() {
+            if openness.all(|other| other == open) {
+                let marker = if open {
+                    "open record: fields not listed here are allowed"
+                } else {
+                    "closed record: fields not listed here are rejected"
+                };
+                contents.push(MarkedString::String(marker.to_owned()));
+            }
+        }
+
         // Not sure how to do documentation merging yet, so pick the first non-empty one.
         let doc = hover.metadata.iter().find_map(|m| m.doc.as_ref());
         if let Some(doc) = doc {
@@ -0,0 +1,61 @@
+use lsp_server::{RequestId, Response, ResponseError};
+use lsp_types::{LinkedEditingRangeParams, LinkedEditingRanges, Range};
+use serde_json::Value;
+
+use crate::cache::CacheExt as _;
+use crate::diagnostic::LocationCompat;
+use crate::server::Server;
+
+/// Return the ranges of the definition and the uses of the identifier under the cursor, so that
+/// the editor can rename them all at once as the user types.
+///
+/// As opposed to a rename, linked editing only applies to the current file: the occurrences in
+/// other files are left out.
+pub fn handle_linked_editing_range(
+    params: LinkedEditingRangeParams,
+    id: RequestId,
+    server: &mut Server,
+) -> Result<(), ResponseError> {
+    let world = &server.world;
+    let pos = world
+        .cache
+        .position(&params.text_document_position_params)?;
+
+    let ident = world.lookup_ident_by_position(pos)?;
+    let mut def_locs = world
+        .lookup_term_by_position(pos)?
+        .map(|term| world.get_defs(term, ident))
+        .unwrap_or_default();
+    // The position may be pointing at the definition itself.
+    def_locs.extend(ident.and_then(|id| id.pos.into_opt()));
+
+    let mut spans: Vec<_> = def_locs
+        .iter()
+        .flat_map(|def| world.analysis.get_usages(def))
+        .filter_map(|id| id.pos.into_opt())
+        .chain(def_locs.iter().copied())
+        .chain(def_locs.iter().flat_map(|def| world.get_field_refs(*def)))
+        .filter(|span| span.src_id == pos.src_id)
+        .collect();
+
+    spans.sort_by_key(|span| (span.start, span.end));
+    spans.dedup();
+
+    let response = if spans.is_empty() {
+        Response::new_ok(id, Value::Null)
+    } else {
+        Response::new_ok(
+            id,
+            LinkedEditingRanges {
+                ranges: spans
+                    .iter()
+                    .map(|span| Range::from_span(span, world.cache.files()))
+                    .collect(),
+                word_pattern: None,
+            },
+        )
+    };
+
+    server.reply(response);
+    Ok(())
+}
@@ -1,6 +1,10 @@
+pub mod call_hierarchy;
+pub mod code_lens;
 pub mod completion;
 pub mod goto;
 pub mod hover;
+pub mod linked_editing;
+pub mod on_type_formatting;
 pub mod rename;
 pub mod symbols;
 
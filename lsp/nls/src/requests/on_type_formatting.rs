@@ -0,0 +1,206 @@
+use std::ops::Range;
+
+use codespan::ByteIndex;
+use lsp_server::{RequestId, Response, ResponseError};
+use lsp_types::{DocumentOnTypeFormattingParams, FormattingOptions, TextEdit};
+use nickel_lang_core::{
+    parser::lexer::{Lexer, ModalLexer, MultiStringToken, NormalToken, StringToken, Token},
+    position::RawSpan,
+};
+
+use crate::{cache::CacheExt as _, diagnostic::LocationCompat, server::Server};
+
+/// Reindent the current line as the user types a newline or a closing brace: a new line inside a
+/// block is indented one level deeper than the line opening the block, and a closing brace is
+/// aligned with the line opening the block.
+///
+/// Contrary to full formatting, this only looks at the delimiters around the cursor, and thus also
+/// works on documents that don't parse. Nothing is done inside strings, where whitespace is
+/// significant.
+pub fn handle_on_type_formatting(
+    params: DocumentOnTypeFormattingParams,
+    id: RequestId,
+    server: &mut Server,
+) -> Result<(), ResponseError> {
+    let pos = server
+        .world
+        .cache
+        .position(&params.text_document_position)?;
+    let files = server.world.cache.files();
+    let text = files.source(pos.src_id);
+
+    let edits = reindent(
+        text,
+        pos.index.to_usize(),
+        &params.ch,
+        &indent_unit(&params.options),
+    )
+    .map(|(range, new_text)| {
+        let span = RawSpan {
+            src_id: pos.src_id,
+            start: ByteIndex(range.start as u32),
+            end: ByteIndex(range.end as u32),
+        };
+
+        vec![TextEdit {
+            range: lsp_types::Range::from_span(&span, files),
+            new_text,
+        }]
+    });
+
+    server.reply(Response::new_ok(id, edits));
+    Ok(())
+}
+
+fn indent_unit(options: &FormattingOptions) -> String {
+    if options.insert_spaces {
+        " ".repeat(options.tab_size as usize)
+    } else {
+        String::from("\t")
+    }
+}
+
+/// Where the end of a prefix of a document is.
+enum Context {
+    /// Inside a string.
+    String,
+    /// Inside code, nested in the still open delimiters starting at the given offsets.
+    Code(Vec<usize>),
+}
+
+/// Lex `text` to find out where its end is. Return `None` if `text` can't be lexed.
+fn context(text: &str) -> Option<Context> {
+    let mut lexer = Lexer::new(text);
+    let mut openers = Vec::new();
+
+    for token in lexer.by_ref() {
+        match token.ok()? {
+            (
+                start,
+                Token::Normal(NormalToken::LBrace | NormalToken::LBracket | NormalToken::LParen)
+                | Token::Str(StringToken::Interpolation)
+                | Token::MultiStr(MultiStringToken::Interpolation),
+                _,
+            ) => openers.push(start),
+            (
+                _,
+                Token::Normal(NormalToken::RBrace | NormalToken::RBracket | NormalToken::RParen),
+                _,
+            ) => {
+                openers.pop();
+            }
+            _ => (),
+        }
+    }
+
+    match lexer.lexer {
+        Some(ModalLexer::Normal { .. }) => Some(Context::Code(openers)),
+        _ => Some(Context::String),
+    }
+}
+
+/// The leading whitespace of the line containing `offset`.
+fn line_indent(text: &str, offset: usize) -> &str {
+    let start = text[..offset].rfind('\n').map_or(0, |i| i + 1);
+    let line = &text[start..];
+    let end = line
+        .find(|c: char| c != ' ' && c != '\t')
+        .unwrap_or(line.len());
+    &line[..end]
+}
+
+/// Compute the edit to apply after `ch` has been typed at `offset` in `text` (`offset` is just
+/// after `ch`). The edit replaces the leading whitespace of the current line, and is returned as
+/// a byte range together with the new text.
+fn reindent(
+    text: &str,
+    offset: usize,
+    ch: &str,
+    indent_unit: &str,
+) -> Option<(Range<usize>, String)> {
+    let line_start = text[..offset].rfind('\n').map_or(0, |i| i + 1);
+    let indent_end = line_start + line_indent(text, line_start).len();
+    let rest = text[indent_end..].lines().next().unwrap_or_default();
+    let closes_block = rest.starts_with(['}', ']', ')']);
+
+    match ch {
+        "\n" => (),
+        // Only a closing brace starting the line is realigned.
+        "}" if closes_block && indent_end + 1 == offset => (),
+        _ => return None,
+    }
+
+    let Context::Code(openers) = context(&text[..indent_end])? else {
+        return None;
+    };
+    let opener = *openers.last()?;
+    let opener_indent = line_indent(text, opener);
+
+    let new_text = if !closes_block {
+        format!("{opener_indent}{indent_unit}")
+    } else if ch == "\n" && text[opener..line_start].trim_end().len() == 1 {
+        // The newline has been inserted right between the delimiters of an empty block, as in
+        // `{<newline>}`: the block is split over three lines, the middle one being indented.
+        format!("{opener_indent}{indent_unit}\n{opener_indent}")
+    } else {
+        opener_indent.to_owned()
+    };
+
+    (new_text != text[line_start..indent_end]).then_some((line_start..indent_end, new_text))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Apply the edit computed after typing `ch` at the position marked by `|` in `text`.
+    fn typed(text: &str, ch: &str) -> String {
+        let offset = text.find('|').unwrap();
+        let mut text = text.replacen('|', "", 1);
+
+        if let Some((range, new_text)) = reindent(&text, offset, ch, "  ") {
+            text.replace_range(range, &new_text);
+        }
+
+        text
+    }
+
+    #[test]
+    fn newline_in_record() {
+        assert_eq!(
+            typed("{\n  foo = {\n|bar = 1,\n  },\n}", "\n"),
+            "{\n  foo = {\n    bar = 1,\n  },\n}"
+        );
+        assert_eq!(
+            typed("{\n  foo = {\n|},\n}", "\n"),
+            "{\n  foo = {\n    \n  },\n}"
+        );
+        assert_eq!(typed("[\n|", "\n"), "[\n  ");
+        // Already indented lines, and lines outside of any block, are left alone.
+        assert_eq!(typed("{\n  |", "\n"), "{\n  ");
+        assert_eq!(typed("let x = 1 in\n|x", "\n"), "let x = 1 in\nx");
+    }
+
+    #[test]
+    fn closing_brace() {
+        assert_eq!(
+            typed("{\n  foo = {\n    bar = 1,\n    }|\n}", "}"),
+            "{\n  foo = {\n    bar = 1,\n  }\n}"
+        );
+        // Braces which don't start the line are left alone.
+        assert_eq!(typed("{\n  foo = {}|\n}", "}"), "{\n  foo = {}\n}");
+    }
+
+    #[test]
+    fn inside_strings() {
+        assert_eq!(
+            typed("{\n  foo = m%\"\n|bar\n  \"%,\n}", "\n"),
+            "{\n  foo = m%\"\nbar\n  \"%,\n}"
+        );
+        // Interpolated expressions aren't strings.
+        assert_eq!(
+            typed("{\n  foo = m%\"\n    %{\n|1\n    }\n  \"%,\n}", "\n"),
+            "{\n  foo = m%\"\n    %{\n      1\n    }\n  \"%,\n}"
+        );
+    }
+}
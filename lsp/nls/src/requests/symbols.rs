@@ -1,5 +1,8 @@
 use lsp_server::{RequestId, Response, ResponseError};
-use lsp_types::{DocumentSymbol, DocumentSymbolParams, SymbolKind};
+use lsp_types::{
+    DocumentSymbol, DocumentSymbolParams, Location, SymbolInformation, SymbolKind, Url,
+    WorkspaceSymbolParams, WorkspaceSymbolResponse,
+};
 use nickel_lang_core::term::RichTerm;
 use nickel_lang_core::typ::Type;
 
@@ -7,13 +10,21 @@ use crate::analysis::CollectedTypes;
 use crate::cache::CacheExt as _;
 use crate::field_walker::{FieldResolver, Record};
 use crate::server::Server;
-use crate::term::RawSpanExt;
+use crate::term::{record_openness, RawSpanExt};
 use crate::world::World;
 
 // How deeply are we willing to recurse into records when resolving symbols?
 // This needs to be bounded to avoid the stack overflowing for infinitely nested records.
 const MAX_SYMBOL_DEPTH: usize = 32;
 
+fn openness_label(open: bool) -> &'static str {
+    if open {
+        "open"
+    } else {
+        "closed"
+    }
+}
+
 // Returns a hierarchy of "publicly accessible" symbols in a term.
 //
 // Basically, if the term "evaluates" (in the sense of FieldResolver's heuristics) to a record,
@@ -67,6 +78,17 @@ fn symbols(
                 )
                 .ok()?;
 
+                // Record contracts get their openness appended to the detail, as it changes which
+                // fields they accept.
+                let detail = match (
+                    ty.map(Type::to_string),
+                    field.value.as_ref().and_then(record_openness),
+                ) {
+                    (Some(ty), Some(open)) => Some(format!("{ty} ({})", openness_label(open))),
+                    (None, Some(open)) => Some(openness_label(open).to_owned()),
+                    (ty, None) => ty,
+                };
+
                 let children = max_depth
                     .checked_sub(1)
                     .and_then(|depth| field.value.map(|v| symbols(world, type_lookups, &v, depth)));
@@ -75,7 +97,7 @@ fn symbols(
                 // because the `deprecated` field is... wait for it... deprecated.
                 Some(DocumentSymbol {
                     name: id.ident().to_string(),
-                    detail: ty.map(Type::to_string),
+                    detail,
                     kind: SymbolKind::VARIABLE,
                     tags: None,
                     range,
@@ -112,3 +134,75 @@ pub fn handle_document_symbols(
 
     Ok(())
 }
+
+// Flattens a hierarchy of document symbols into the symbols matching `query`, each with the name
+// of its parent symbol as its container.
+fn flatten_matching(
+    uri: &Url,
+    query: &str,
+    container: Option<&str>,
+    syms: Vec<DocumentSymbol>,
+    out: &mut Vec<SymbolInformation>,
+) {
+    for sym in syms {
+        if sym.name.to_lowercase().contains(query) {
+            #[allow(deprecated)]
+            out.push(SymbolInformation {
+                name: sym.name.clone(),
+                kind: sym.kind,
+                tags: None,
+                deprecated: None,
+                location: Location {
+                    uri: uri.clone(),
+                    range: sym.selection_range,
+                },
+                container_name: container.map(ToOwned::to_owned),
+            });
+        }
+
+        if let Some(children) = sym.children {
+            flatten_matching(uri, query, Some(&sym.name), children, out);
+        }
+    }
+}
+
+/// Searches the symbols of all the files known to the server, which includes the files found by
+/// the workspace scan (see [crate::config::WorkspaceConfig]) even if they aren't open. A symbol
+/// matches if its name contains the query, ignoring case.
+pub fn handle_workspace_symbols(
+    params: WorkspaceSymbolParams,
+    id: RequestId,
+    server: &mut Server,
+) -> Result<(), ResponseError> {
+    // The files of the workspace that haven't been analyzed yet would be missing from the results.
+    server.finish_workspace_scan();
+
+    let query = params.query.to_lowercase();
+    let mut matches = Vec::new();
+
+    for (file_id, uri) in &server.world.file_uris {
+        // Files that failed to parse or to typecheck have no analysis, and thus no symbols.
+        let Ok(analysis) = server.world.file_analysis(*file_id) else {
+            continue;
+        };
+        let Some(term) = server.world.cache.get_ref(*file_id) else {
+            continue;
+        };
+
+        let syms = symbols(&server.world, &analysis.type_lookup, term, MAX_SYMBOL_DEPTH);
+        flatten_matching(uri, &query, None, syms, &mut matches);
+    }
+
+    // Sort so the response is deterministic.
+    matches.sort_by(|s1, s2| {
+        (&s1.location.uri, s1.location.range.start)
+            .cmp(&(&s2.location.uri, s2.location.range.start))
+    });
+
+    server.reply(Response::new_ok(
+        id,
+        Some(WorkspaceSymbolResponse::Flat(matches)),
+    ));
+
+    Ok(())
+}
@@ -1,26 +1,39 @@
+use std::path::{Path, PathBuf};
+
 use anyhow::Result;
 use codespan::FileId;
-use crossbeam::select;
+use crossbeam::{
+    channel::{unbounded, Receiver, Sender},
+    select,
+};
 use log::{debug, trace, warn};
 use lsp_server::{Connection, ErrorCode, Message, Notification, RequestId, Response};
 use lsp_types::{
     notification::Notification as _,
     notification::{DidChangeTextDocument, DidOpenTextDocument},
     request::{Request as RequestTrait, *},
-    CodeActionParams, CompletionOptions, CompletionParams, DidChangeTextDocumentParams,
-    DidOpenTextDocumentParams, DocumentFormattingParams, DocumentSymbolParams,
-    ExecuteCommandParams, GotoDefinitionParams, HoverOptions, HoverParams, HoverProviderCapability,
-    OneOf, PublishDiagnosticsParams, ReferenceParams, RenameParams, ServerCapabilities,
+    CallHierarchyIncomingCallsParams, CallHierarchyOutgoingCallsParams, CallHierarchyPrepareParams,
+    CallHierarchyServerCapability, CodeActionParams, CodeLensOptions, CodeLensParams,
+    CompletionOptions, CompletionParams, DidChangeTextDocumentParams, DidOpenTextDocumentParams,
+    DocumentFormattingParams, DocumentOnTypeFormattingOptions, DocumentOnTypeFormattingParams,
+    DocumentSymbolParams, ExecuteCommandParams, GotoDefinitionParams, HoverOptions, HoverParams,
+    HoverProviderCapability, LinkedEditingRangeParams, LinkedEditingRangeServerCapabilities, OneOf,
+    PublishDiagnosticsParams, ReferenceParams, RenameParams, ServerCapabilities,
     TextDocumentSyncCapability, TextDocumentSyncKind, TextDocumentSyncOptions, Url,
-    WorkDoneProgressOptions,
+    WorkDoneProgressOptions, WorkspaceSymbolParams,
 };
 
 use crate::{
     actions,
     background::BackgroundJobs,
     command,
-    requests::{completion, formatting, goto, hover, rename, symbols},
+    config::LspConfig,
+    requests::{
+        call_hierarchy, code_lens, completion, formatting, goto, hover, linked_editing,
+        on_type_formatting, rename, symbols,
+    },
     trace::Trace,
+    workspace,
     world::World,
 };
 
@@ -36,6 +49,10 @@ pub struct Server {
     pub connection: Connection,
     pub world: World,
     pub background_jobs: BackgroundJobs,
+    pub config: LspConfig,
+    /// The files of the workspace that remain to be analyzed. See [Server::scan_workspace].
+    scan_sender: Sender<PathBuf>,
+    scan_receiver: Receiver<PathBuf>,
 }
 
 impl Server {
@@ -62,22 +79,83 @@ impl Server {
                 ..Default::default()
             }),
             document_symbol_provider: Some(OneOf::Left(true)),
+            workspace_symbol_provider: Some(OneOf::Left(true)),
             document_formatting_provider: Some(OneOf::Left(true)),
+            document_on_type_formatting_provider: Some(DocumentOnTypeFormattingOptions {
+                first_trigger_character: String::from("\n"),
+                more_trigger_character: Some(vec![String::from("}")]),
+            }),
             code_action_provider: Some(lsp_types::CodeActionProviderCapability::Simple(true)),
             execute_command_provider: Some(lsp_types::ExecuteCommandOptions {
                 commands: vec!["eval".to_owned()],
                 ..Default::default()
             }),
             rename_provider: Some(OneOf::Left(true)),
+            call_hierarchy_provider: Some(CallHierarchyServerCapability::Simple(true)),
+            linked_editing_range_provider: Some(LinkedEditingRangeServerCapabilities::Simple(true)),
+            code_lens_provider: Some(CodeLensOptions {
+                resolve_provider: Some(false),
+            }),
             ..ServerCapabilities::default()
         }
     }
 
-    pub fn new(connection: Connection) -> Server {
+    pub fn new(connection: Connection, config: LspConfig) -> Server {
+        let world = World {
+            diagnostics_config: config.diagnostics.clone(),
+            ..World::default()
+        };
+        let (scan_sender, scan_receiver) = unbounded();
+
         Server {
             connection,
-            world: World::default(),
-            background_jobs: BackgroundJobs::new(),
+            world,
+            background_jobs: BackgroundJobs::new(config.diagnostics.clone()),
+            config,
+            scan_sender,
+            scan_receiver,
+        }
+    }
+
+    /// Queue the Nickel files under `root`, as bounded by the workspace configuration, to be
+    /// parsed and typechecked so that cross-file requests see them before they're opened. The
+    /// files are analyzed one by one by [Server::run], in between the handling of messages. No
+    /// diagnostics are published for these files: they'll be reported once the files are opened.
+    pub fn scan_workspace(&mut self, root: &Path) {
+        let files = workspace::nickel_files(root, &self.config.workspace);
+        debug!(
+            "analyzing {} files of the workspace {}",
+            files.len(),
+            root.display()
+        );
+
+        for path in files {
+            // The receiver lives as long as the server, so sending can't fail.
+            let _ = self.scan_sender.send(path);
+        }
+    }
+
+    /// Analyze all the files of the workspace that remain to be analyzed, for requests that need
+    /// the whole workspace.
+    pub(crate) fn finish_workspace_scan(&mut self) {
+        while let Ok(path) = self.scan_receiver.try_recv() {
+            self.scan_file(&path);
+        }
+    }
+
+    /// Load a file of the workspace from the filesystem and analyze it. The file is loaded the
+    /// same way as imports are, so that it's read again if it's modified on disk and then
+    /// imported.
+    fn scan_file(&mut self, path: &Path) {
+        let Ok(uri) = Url::from_file_path(path) else {
+            return;
+        };
+
+        match self.world.load_file(uri) {
+            Ok(file_id) => {
+                self.world.parse_and_typecheck(file_id);
+            }
+            Err(err) => warn!("failed to load {}: {err}", path.display()),
         }
     }
 
@@ -135,6 +213,12 @@ impl Server {
                     let diagnostics = diagnostics.into_iter().map(From::from).collect();
                     self.publish_diagnostics(uri, diagnostics);
                 }
+                recv(self.scan_receiver) -> path => {
+                    // The sender lives as long as the server, so receiving can't fail.
+                    if let Ok(path) = path {
+                        self.scan_file(&path);
+                    }
+                }
             }
         }
         while let Ok(msg) = self.connection.receiver.recv() {
@@ -248,12 +332,25 @@ impl Server {
                 symbols::handle_document_symbols(params, req.id.clone(), self)
             }
 
+            WorkspaceSymbolRequest::METHOD => {
+                debug!("handle workspace symbols");
+                let params: WorkspaceSymbolParams = serde_json::from_value(req.params).unwrap();
+                symbols::handle_workspace_symbols(params, req.id.clone(), self)
+            }
+
             Formatting::METHOD => {
                 debug!("handle formatting");
                 let params: DocumentFormattingParams = serde_json::from_value(req.params).unwrap();
                 formatting::handle_format_document(params, req.id.clone(), self)
             }
 
+            OnTypeFormatting::METHOD => {
+                debug!("handle on type formatting");
+                let params: DocumentOnTypeFormattingParams =
+                    serde_json::from_value(req.params).unwrap();
+                on_type_formatting::handle_on_type_formatting(params, req.id.clone(), self)
+            }
+
             CodeActionRequest::METHOD => {
                 debug!("code action");
                 let params: CodeActionParams = serde_json::from_value(req.params).unwrap();
@@ -272,6 +369,39 @@ impl Server {
                 rename::handle_rename(params, req.id.clone(), self)
             }
 
+            CallHierarchyPrepare::METHOD => {
+                debug!("prepare call hierarchy");
+                let params: CallHierarchyPrepareParams =
+                    serde_json::from_value(req.params).unwrap();
+                call_hierarchy::handle_prepare(params, req.id.clone(), self)
+            }
+
+            CallHierarchyIncomingCalls::METHOD => {
+                debug!("incoming calls");
+                let params: CallHierarchyIncomingCallsParams =
+                    serde_json::from_value(req.params).unwrap();
+                call_hierarchy::handle_incoming_calls(params, req.id.clone(), self)
+            }
+
+            CallHierarchyOutgoingCalls::METHOD => {
+                debug!("outgoing calls");
+                let params: CallHierarchyOutgoingCallsParams =
+                    serde_json::from_value(req.params).unwrap();
+                call_hierarchy::handle_outgoing_calls(params, req.id.clone(), self)
+            }
+
+            LinkedEditingRange::METHOD => {
+                debug!("linked editing range");
+                let params: LinkedEditingRangeParams = serde_json::from_value(req.params).unwrap();
+                linked_editing::handle_linked_editing_range(params, req.id.clone(), self)
+            }
+
+            CodeLensRequest::METHOD => {
+                debug!("code lens");
+                let params: CodeLensParams = serde_json::from_value(req.params).unwrap();
+                code_lens::handle_code_lens(params, req.id.clone(), self)
+            }
+
             _ => Ok(()),
         };
 
@@ -4,6 +4,7 @@ use codespan::FileId;
 use nickel_lang_core::{
     position::RawSpan,
     term::{RichTerm, SharedTerm, Term},
+    typ::TypeF,
 };
 
 // A term that uses a pointer to Term to implement Eq and Hash.
@@ -33,3 +34,29 @@ impl RawSpanExt for RawSpan {
         (self.src_id, (self.start.to_usize()..self.end.to_usize()))
     }
 }
+
+/// Whether `rt` is an open record literal (`Some(true)`) or a closed one (`Some(false)`), looking
+/// through annotations and record contracts.
+///
+/// Only records that are meant to be used as contracts are considered closed, that is records
+/// with at least one field without a definition: nearly all records holding plain data are
+/// closed, and saying so isn't informative.
+pub fn record_openness(rt: &RichTerm) -> Option<bool> {
+    match rt.as_ref() {
+        Term::Record(data) | Term::RecRecord(data, ..) => {
+            if data.attrs.open {
+                Some(true)
+            } else if data.fields.values().any(|field| field.value.is_none()) {
+                Some(false)
+            } else {
+                None
+            }
+        }
+        Term::Annotated(_, inner) => record_openness(inner),
+        Term::Type(typ) => match &typ.typ {
+            TypeF::Flat(rt) => record_openness(rt),
+            _ => None,
+        },
+        _ => None,
+    }
+}
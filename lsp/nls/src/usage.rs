@@ -325,6 +325,20 @@ pub(crate) mod tests {
         assert_eq!(baz_def.path(), vec!["foo".into(), "bar".into()]);
     }
 
+    #[test]
+    fn interpolation() {
+        let (file, rt) = parse("let x = 1 in \"a%{x}b\"");
+        let x0 = locced("x", file, 4..5);
+        let x1 = locced("x", file, 17..18);
+        let table = UsageLookup::new(&rt, &Environment::new());
+
+        assert_eq!(
+            table.usages(&x0.pos.unwrap()).cloned().collect::<Vec<_>>(),
+            vec![x1]
+        );
+        assert_eq!(table.def(&x1).unwrap().ident(), x0);
+    }
+
     #[test]
     fn record_bindings() {
         let (file, rt) =
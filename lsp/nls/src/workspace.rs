@@ -0,0 +1,174 @@
+//! Discovery of the Nickel files of a workspace, so that they can be analyzed before the editor
+//! opens them. See [crate::config::WorkspaceConfig].
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use log::warn;
+
+use crate::config::WorkspaceConfig;
+
+/// A pattern of a `.gitignore` file.
+///
+/// Only the common subset of the syntax is supported: `*` and `?` wildcards, a leading `/` to
+/// only match the entries of the directory containing the `.gitignore`, and a trailing `/` to
+/// only match directories. Other patterns are matched against the name of the entries, at any
+/// depth. Negated patterns (`!pattern`) are ignored.
+#[derive(Debug, Clone)]
+struct IgnorePattern {
+    glob: String,
+    anchored: bool,
+    dir_only: bool,
+}
+
+impl IgnorePattern {
+    fn parse(line: &str) -> Option<Self> {
+        let line = line.trim();
+
+        if line.is_empty() || line.starts_with('#') || line.starts_with('!') {
+            return None;
+        }
+
+        let (line, dir_only) = match line.strip_suffix('/') {
+            Some(line) => (line, true),
+            None => (line, false),
+        };
+        let (line, anchored) = match line.strip_prefix('/') {
+            Some(line) => (line, true),
+            None => (line, false),
+        };
+
+        // Patterns with a slash in the middle are relative to the directory of the `.gitignore`.
+        // We only support them as far as they name a direct child.
+        if line.contains('/') {
+            return None;
+        }
+
+        Some(IgnorePattern {
+            glob: line.to_owned(),
+            anchored,
+            dir_only,
+        })
+    }
+
+    /// Whether this pattern matches the entry `name`, at `depth` levels below the directory of
+    /// the `.gitignore` the pattern comes from (`0` for the direct children).
+    fn matches(&self, name: &str, depth: usize, is_dir: bool) -> bool {
+        (!self.anchored || depth == 0)
+            && (!self.dir_only || is_dir)
+            && wildcard_match(self.glob.as_bytes(), name.as_bytes())
+    }
+}
+
+fn wildcard_match(pattern: &[u8], name: &[u8]) -> bool {
+    match (pattern.first(), name.first()) {
+        (None, None) => true,
+        (Some(b'*'), _) => {
+            wildcard_match(&pattern[1..], name)
+                || (!name.is_empty() && wildcard_match(pattern, &name[1..]))
+        }
+        (Some(b'?'), Some(_)) => wildcard_match(&pattern[1..], &name[1..]),
+        (Some(p), Some(n)) if p == n => wildcard_match(&pattern[1..], &name[1..]),
+        _ => false,
+    }
+}
+
+/// The `.gitignore` patterns in scope in a directory, each with the depth of the directory of
+/// the `.gitignore` it comes from.
+type IgnoreStack = Vec<(usize, IgnorePattern)>;
+
+/// Returns the Nickel files under `root`, in a deterministic order, skipping hidden entries,
+/// ignored entries and files that are too large, and stopping at `config.max_files` files.
+pub fn nickel_files(root: &Path, config: &WorkspaceConfig) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    collect(root, 0, &mut IgnoreStack::new(), config, &mut files);
+    files
+}
+
+fn collect(
+    dir: &Path,
+    depth: usize,
+    ignored: &mut IgnoreStack,
+    config: &WorkspaceConfig,
+    files: &mut Vec<PathBuf>,
+) {
+    let scope = ignored.len();
+
+    if let Ok(gitignore) = fs::read_to_string(dir.join(".gitignore")) {
+        ignored.extend(
+            gitignore
+                .lines()
+                .filter_map(IgnorePattern::parse)
+                .map(|pat| (depth, pat)),
+        );
+    }
+
+    let mut entries = match fs::read_dir(dir) {
+        Ok(entries) => entries.filter_map(Result::ok).collect::<Vec<_>>(),
+        Err(err) => {
+            warn!("failed to read directory {}: {err}", dir.display());
+            Vec::new()
+        }
+    };
+    entries.sort_by_key(|entry| entry.file_name());
+
+    for entry in entries {
+        if files.len() >= config.max_files {
+            break;
+        }
+
+        let Ok(file_type) = entry.file_type() else {
+            continue;
+        };
+        let name = entry.file_name();
+        let Some(name) = name.to_str() else {
+            continue;
+        };
+        let is_dir = file_type.is_dir();
+
+        if name.starts_with('.')
+            || ignored
+                .iter()
+                .any(|(pat_depth, pat)| pat.matches(name, depth - pat_depth, is_dir))
+        {
+            continue;
+        }
+
+        let path = entry.path();
+
+        if is_dir {
+            collect(&path, depth + 1, ignored, config, files);
+        } else if file_type.is_file()
+            && path.extension().is_some_and(|ext| ext == "ncl")
+            && entry
+                .metadata()
+                .is_ok_and(|meta| meta.len() <= config.max_file_size)
+        {
+            files.push(path);
+        }
+    }
+
+    ignored.truncate(scope);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gitignore_patterns() {
+        let pat = IgnorePattern::parse("*.gen.ncl").unwrap();
+        assert!(pat.matches("config.gen.ncl", 2, false));
+        assert!(!pat.matches("config.ncl", 0, false));
+
+        let pat = IgnorePattern::parse("/build/").unwrap();
+        assert!(pat.matches("build", 0, true));
+        assert!(!pat.matches("build", 1, true));
+        assert!(!pat.matches("build", 0, false));
+
+        assert!(IgnorePattern::parse("# comment").is_none());
+        assert!(IgnorePattern::parse("!keep.ncl").is_none());
+    }
+}
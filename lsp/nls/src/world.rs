@@ -19,11 +19,13 @@ use nickel_lang_core::{
 use crate::{
     analysis::{Analysis, AnalysisRegistry},
     cache::CacheExt as _,
+    config::DiagnosticsConfig,
     diagnostic::{DiagnosticCompat, SerializableDiagnostic},
     field_walker::{Def, FieldResolver},
     files::uri_to_path,
     identifier::LocIdent,
     pattern::Bindings as _,
+    usage::UsageLookup,
 };
 
 /// All the state associated with the files we know about.
@@ -44,6 +46,9 @@ pub struct World {
     /// files that failed to import, and the values in this map are the file ids that tried
     /// to import it.
     pub failed_imports: HashMap<OsString, HashSet<FileId>>,
+
+    /// The severities of the static checks of [crate::lint].
+    pub diagnostics_config: DiagnosticsConfig,
 }
 
 impl Default for World {
@@ -66,6 +71,7 @@ impl Default for World {
             initial_term_env,
             file_uris: HashMap::default(),
             failed_imports: HashMap::default(),
+            diagnostics_config: DiagnosticsConfig::default(),
         }
     }
 }
@@ -103,8 +109,11 @@ impl World {
             invalid.extend(self.cache.invalidate_cache(f));
         }
 
+        // The analyses are only replaced once the new versions are analyzed successfully, so
+        // that a file which doesn't typecheck anymore can still be navigated in the meantime.
+        self.analysis.invalidate(file_id);
         for rev_dep in &invalid {
-            self.analysis.remove(*rev_dep);
+            self.analysis.invalidate(*rev_dep);
         }
 
         self.file_uris.insert(file_id, uri);
@@ -112,8 +121,26 @@ impl World {
         Ok((file_id, invalid))
     }
 
+    /// Loads a file from the filesystem rather than from the editor.
+    ///
+    /// As opposed to [Self::add_file], the file is stored in the cache along with its
+    /// modification time, as imported files are, so that it's read again when it's imported after
+    /// having been modified on disk.
+    pub fn load_file(&mut self, uri: Url) -> anyhow::Result<FileId> {
+        let path = uri_to_path(&uri)?;
+        let file_id = self.cache.get_or_add_file(path)?.inner();
+
+        self.file_uris.insert(file_id, uri);
+
+        Ok(file_id)
+    }
+
     /// Updates a file's contents.
     ///
+    /// The analysis of the previous version of the file is kept until the new version is analyzed
+    /// successfully, and is then replaced, so that the memory used by a file doesn't depend on how
+    /// many times it has been edited.
+    ///
     /// Returns a list of files that were invalidated by this change.
     pub fn update_file(
         &mut self,
@@ -123,9 +150,10 @@ impl World {
         let path = uri_to_path(&uri)?;
         let file_id = self.cache.replace_string(SourcePath::Path(path), contents);
 
+        self.analysis.invalidate(file_id);
         let invalid = self.cache.invalidate_cache(file_id);
         for f in &invalid {
-            self.analysis.remove(*f);
+            self.analysis.invalidate(*f);
         }
         Ok((file_id, invalid))
     }
@@ -193,12 +221,27 @@ impl World {
         Ok(())
     }
 
+    /// Runs the static checks of [crate::lint] on a parsed file.
+    pub fn lint(&mut self, file_id: FileId) -> Vec<SerializableDiagnostic> {
+        let Some(term) = self.cache.get_ref(file_id) else {
+            return Vec::new();
+        };
+
+        let usages = UsageLookup::new(term, &self.initial_term_env);
+
+        crate::lint::run_checks(file_id, term, &usages, &self.diagnostics_config)
+            .into_iter()
+            .flat_map(|d| SerializableDiagnostic::from_codespan(file_id, d, self.cache.files_mut()))
+            .collect()
+    }
+
     pub fn parse_and_typecheck(&mut self, file_id: FileId) -> Vec<SerializableDiagnostic> {
         match self.parse(file_id) {
             Ok(mut nonfatal) => {
                 if let Err(e) = self.typecheck(file_id) {
                     nonfatal.extend(e);
                 }
+                nonfatal.extend(self.lint(file_id));
                 nonfatal
             }
             Err(fatal) => fatal,
@@ -363,3 +406,73 @@ impl World {
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use codespan::{ByteIndex, FileId};
+    use lsp_types::Url;
+    use nickel_lang_core::position::RawPos;
+
+    use super::World;
+
+    /// The name of the definition of the identifier at `offset`, according to the current
+    /// analysis of the file.
+    fn def_at(world: &World, file_id: FileId, offset: u32) -> Option<String> {
+        let ident = world
+            .lookup_ident_by_position(RawPos::new(file_id, ByteIndex(offset)))
+            .ok()??;
+        let def = world.analysis.get_def(&ident)?;
+        Some(def.ident().ident.label().to_owned())
+    }
+
+    #[test]
+    fn reparse_replaces_analysis() {
+        let mut world = World::default();
+        let uri = Url::parse("file:///main.ncl").unwrap();
+        let (file_id, _) = world
+            .add_file(uri.clone(), "let foo = 0 in foo".to_owned())
+            .unwrap();
+        assert!(world.parse_and_typecheck(file_id).is_empty());
+        assert_eq!(def_at(&world, file_id, 15).as_deref(), Some("foo"));
+        let analyses = world.analysis.analysis.len();
+
+        for i in 1..100 {
+            let (new_id, _) = world
+                .update_file(uri.clone(), format!("let bar = {i} in bar"))
+                .unwrap();
+            assert_eq!(new_id, file_id);
+            assert!(world.parse_and_typecheck(file_id).is_empty());
+            assert_eq!(world.analysis.analysis.len(), analyses);
+        }
+
+        // The definition of the first version is gone, and the one of the new version resolves.
+        assert_eq!(def_at(&world, file_id, 15).as_deref(), Some("bar"));
+        assert!(world.analysis.is_up_to_date(file_id));
+    }
+
+    #[test]
+    fn failed_typecheck_keeps_previous_analysis() {
+        let mut world = World::default();
+        let uri = Url::parse("file:///main.ncl").unwrap();
+        let (file_id, _) = world
+            .add_file(uri.clone(), "let foo = 0 in foo".to_owned())
+            .unwrap();
+        assert!(world.parse_and_typecheck(file_id).is_empty());
+
+        // The new version doesn't typecheck: requests are answered using the previous analysis.
+        world
+            .update_file(uri.clone(), "let bar : String = 0 in bar".to_owned())
+            .unwrap();
+        assert!(!world.parse_and_typecheck(file_id).is_empty());
+        assert!(!world.analysis.is_up_to_date(file_id));
+        assert_eq!(def_at(&world, file_id, 15).as_deref(), Some("foo"));
+
+        // Once the file typechecks again, the stale analysis is replaced.
+        world
+            .update_file(uri, "let baz = 0 in baz".to_owned())
+            .unwrap();
+        assert!(world.parse_and_typecheck(file_id).is_empty());
+        assert!(world.analysis.is_up_to_date(file_id));
+        assert_eq!(def_at(&world, file_id, 15).as_deref(), Some("baz"));
+    }
+}
@@ -11,7 +11,7 @@ fn check_snapshots(path: &str) {
 
     let contents = std::fs::read_to_string(full_path).unwrap();
     let fixture = TestFixture::parse(&contents).unwrap();
-    let mut harness = TestHarness::new();
+    let mut harness = TestHarness::new_with_options(fixture.config.clone());
 
     harness.prepare_files(&fixture);
     for req in fixture.reqs {
@@ -111,3 +111,122 @@ fn reload_broken_imports() {
         }
     }
 }
+
+#[test]
+fn stdlib_member_completion() {
+    use lsp_types::{
+        request::Completion, CompletionContext, CompletionParams, CompletionResponse,
+        CompletionTriggerKind, Documentation, Position, TextDocumentIdentifier,
+        TextDocumentPositionParams,
+    };
+
+    let _ = env_logger::try_init();
+    let mut harness = TestHarness::new();
+
+    let uri = lsp_types::Url::from_file_path("/main.ncl").unwrap();
+    harness.send_file(uri.clone(), "let x = std.string.");
+
+    let response = harness.send_request::<Completion>(CompletionParams {
+        text_document_position: TextDocumentPositionParams {
+            text_document: TextDocumentIdentifier { uri },
+            position: Position {
+                line: 0,
+                character: 19,
+            },
+        },
+        work_done_progress_params: Default::default(),
+        partial_result_params: Default::default(),
+        context: Some(CompletionContext {
+            trigger_kind: CompletionTriggerKind::TRIGGER_CHARACTER,
+            trigger_character: Some(".".to_owned()),
+        }),
+    });
+
+    let Some(CompletionResponse::Array(items)) = response else {
+        panic!("expected a list of completion items, got {response:?}");
+    };
+
+    // We don't snapshot the whole list, which would change every time the stdlib does, but we
+    // check that the members come with their types and documentation.
+    for member in ["join", "split", "to_number", "uppercase"] {
+        assert!(
+            items.iter().any(|item| item.label == member),
+            "missing completion for std.string.{member}"
+        );
+    }
+
+    let to_number = items.iter().find(|item| item.label == "to_number").unwrap();
+    assert!(to_number
+        .detail
+        .as_deref()
+        .is_some_and(|detail| detail.contains("String -> Number")));
+    assert!(matches!(
+        &to_number.documentation,
+        Some(Documentation::MarkupContent(content))
+            if content.value.starts_with("Converts a string that represents a number")
+    ));
+}
+
+#[test]
+fn check_mode_exit_status() {
+    let check = |file: &str| {
+        std::process::Command::new(env!("CARGO_BIN_EXE_nls"))
+            .arg("--check")
+            .arg(project_root().join("lsp/nls/tests/check").join(file))
+            .output()
+            .expect("nls should be runnable")
+    };
+
+    let clean = check("clean.ncl");
+    assert!(clean.status.success());
+    assert!(clean.stdout.is_empty());
+
+    let broken = check("broken.ncl");
+    assert!(!broken.status.success());
+    let stdout = String::from_utf8(broken.stdout).unwrap();
+    assert!(stdout.contains("broken.ncl:1:"));
+    assert!(stdout.contains(": error: "));
+}
+
+#[test]
+fn workspace_symbols_of_unopened_files() {
+    use lsp_types::{
+        request::WorkspaceSymbolRequest, Url, WorkspaceSymbolParams, WorkspaceSymbolResponse,
+    };
+
+    let _ = env_logger::try_init();
+
+    let dir = tempfile::TempDir::new().unwrap();
+    let root = dir.path();
+    std::fs::create_dir_all(root.join("lib")).unwrap();
+    std::fs::create_dir_all(root.join("generated")).unwrap();
+    std::fs::write(root.join(".gitignore"), "generated/\n").unwrap();
+    std::fs::write(
+        root.join("lib/server.ncl"),
+        "{ server = { listen_port = 8080 } }",
+    )
+    .unwrap();
+    std::fs::write(root.join("generated/client.ncl"), "{ client_port = 80 }").unwrap();
+
+    let mut harness = TestHarness::new_in_workspace(
+        Some(serde_json::json!({ "workspace": { "scan": true } })),
+        Some(Url::from_directory_path(root).unwrap()),
+    );
+
+    // None of the files is opened: the symbols come from the workspace scan.
+    let response = harness.send_request::<WorkspaceSymbolRequest>(WorkspaceSymbolParams {
+        query: "PORT".to_owned(),
+        work_done_progress_params: Default::default(),
+        partial_result_params: Default::default(),
+    });
+
+    let Some(WorkspaceSymbolResponse::Flat(symbols)) = response else {
+        panic!("expected a flat list of symbols, got {response:?}");
+    };
+
+    // The ignored file isn't part of the workspace.
+    assert_eq!(symbols.len(), 1);
+    assert_eq!(symbols[0].name, "listen_port");
+    assert_eq!(symbols[0].container_name.as_deref(), Some("server"));
+    assert!(symbols[0].location.uri.path().ends_with("/lib/server.ncl"));
+}